@@ -1,11 +1,10 @@
 // anonify-enclave
-pub const MY_ROSTER_IDX: usize = 0;
-pub const MAX_ROSTER_IDX: usize = 2;
+/// The roster index of the owner who deployed the contract
+pub const OWNER_ROSTER_IDX: u32 = 0;
 pub const TEST_SPID: &str = "2C149BFC94A61D306A96211AED155BE9";
 pub const UNTIL_ROSTER_IDX: usize = 10;
 pub const UNTIL_EPOCH: usize = 30;
 pub const IAS_URL: &str = "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report";
-pub const TEST_SUB_KEY: &str = "77e2533de0624df28dc3be3a5b9e50d9";
 
 // commands
 pub const ENCRYPT_COMMAND_CMD: u32 = 1;
@@ -17,3 +16,5 @@ pub const CALL_HANDSHAKE_CMD: u32 = 6;
 pub const REGISTER_NOTIFICATION_CMD: u32 = 7;
 pub const GET_ENCRYPTING_KEY_CMD: u32 = 8;
 pub const CALL_REGISTER_REPORT_CMD: u32 = 9;
+pub const CALL_REMOVE_MEMBER_CMD: u32 = 10;
+pub const GET_REPORT_CMD: u32 = 11;
@@ -14,10 +14,17 @@ contract Anonify is ReportHandle {
     uint32 private _rosterIdxCounter;
     // Mapping of a sender and roster index
     mapping(address => uint32) private _senderToRosterIdx;
+    // Mapping of a roster index and the verifying key of the member's enclave
+    mapping(uint32 => address) private _rosterIdxToVerifyingKey;
+    // Roster indices which have been removed from the group
+    mapping(uint32 => bool) private _isRemoved;
+    // Whether the first command, which constructs the state, has been stored
+    bool private _isStateConstructed;
 
     event StoreCiphertext(bytes ciphertext);
     event StoreHandshake(bytes handshake);
     event UpdateMrenclaveVer(uint32 newVersion);
+    event RemoveMember(uint32 rosterIdx);
 
     constructor(
         bytes memory _report,
@@ -25,13 +32,15 @@ contract Anonify is ReportHandle {
         bytes memory _handshake,
         uint32 mrenclaveVer
     ) public ReportHandle(_report, _reportSig) {
-        // The offset of roster index is 4.
-        uint32 rosterIdx = BytesUtils.toUint32(_handshake, 4);
+        // The offset of roster index is 4, which is SCALE-encoded in little endian.
+        uint32 rosterIdx = BytesUtils.toUint32LE(_handshake, 4);
         require(rosterIdx == 0, "First roster_idx must be zero");
 
         _owner = msg.sender;
         _mrenclaveVer = mrenclaveVer;
         _senderToRosterIdx[msg.sender] = rosterIdx;
+        // The owner's verifying key has been registered by `ReportHandle`'s constructor.
+        _rosterIdxToVerifyingKey[rosterIdx] = verifyingKeyArray[0];
         _rosterIdxCounter = rosterIdx;
         handshake_wo_sig(_handshake);
     }
@@ -58,14 +67,20 @@ contract Anonify is ReportHandle {
             _senderToRosterIdx[msg.sender] == 0,
             "The msg.sender can join only once"
         );
+        // The offset of roster index is 4, which is SCALE-encoded in little endian.
+        require(
+            BytesUtils.toUint32LE(_handshake, 4) == _rosterIdx,
+            "The handshake must be sent from the allocated roster index"
+        );
 
-        handleReport(_report, _reportSig);
+        address verifyingKey = handleReport(_report, _reportSig, address(0));
         _senderToRosterIdx[msg.sender] = _rosterIdx;
+        _rosterIdxToVerifyingKey[_rosterIdx] = verifyingKey;
         _rosterIdxCounter = _rosterIdx;
         handshake_wo_sig(_handshake);
     }
 
-    // a recovered TEE node registers the report
+    // a recovered or re-attested TEE node registers the report
     function registerReport(
         bytes memory _report,
         bytes memory _reportSig,
@@ -73,9 +88,24 @@ contract Anonify is ReportHandle {
         uint32 _rosterIdx
     ) public {
         require(_mrenclaveVer == _version, "Must be same version");
+        require(!_isRemoved[_rosterIdx], "The roster index has been removed");
+        // Only the sender which has been allocated the roster index can register the report for it.
+        require(
+            _rosterIdx == 0
+                ? msg.sender == _owner
+                : _senderToRosterIdx[msg.sender] == _rosterIdx,
+            "The roster index must be same as the registered one"
+        );
 
-        handleReport(_report, _reportSig);
+        // A node attested again with the same keys only refreshes its report.
+        address registeredKey = _rosterIdxToVerifyingKey[_rosterIdx];
+        address verifyingKey = handleReport(_report, _reportSig, registeredKey);
+        // A restarted node registers a new key, so the old one can no longer store ciphertexts and handshakes.
+        if (registeredKey != address(0) && registeredKey != verifyingKey) {
+            revokeVerifyingKey(registeredKey);
+        }
         _senderToRosterIdx[msg.sender] = _rosterIdx;
+        _rosterIdxToVerifyingKey[_rosterIdx] = verifyingKey;
     }
 
     function updateMrenclave(
@@ -87,8 +117,12 @@ contract Anonify is ReportHandle {
     ) public onlyOwner {
         require(_mrenclaveVer != _newVersion, "Must be new version");
         require(_rosterIdx == 0, "Only owner can update mrenclave");
+        require(
+            BytesUtils.toUint32LE(_handshake, 4) == _rosterIdx,
+            "The handshake must be sent from the roster index"
+        );
 
-        updateMrenclaveInner(_report, _reportSig);
+        _rosterIdxToVerifyingKey[_rosterIdx] = updateMrenclaveInner(_report, _reportSig);
         handshake_wo_sig(_handshake);
         _mrenclaveVer = _newVersion;
         emit UpdateMrenclaveVer(_newVersion);
@@ -106,6 +140,15 @@ contract Anonify is ReportHandle {
             verifyingKeyMapping[verifyingKey] == verifyingKey,
             "Invalid enclave signature."
         );
+        // The first command constructs the state and makes its sender the state's owner,
+        // so only the deployer can store it and it can't be front-run by another member.
+        if (!_isStateConstructed) {
+            require(
+                _owner == msg.sender,
+                "The state must be constructed by the owner"
+            );
+            _isStateConstructed = true;
+        }
 
         emit StoreCiphertext(_newCiphertext);
     }
@@ -119,6 +162,12 @@ contract Anonify is ReportHandle {
             _senderToRosterIdx[msg.sender] == _rosterIdx,
             "The roster index must be same as the registered one"
         );
+        require(!_isRemoved[_rosterIdx], "The roster index has been removed");
+        // The offset of roster index is 4, which is SCALE-encoded in little endian.
+        require(
+            BytesUtils.toUint32LE(_handshake, 4) == _rosterIdx,
+            "The handshake must be sent from the roster index"
+        );
         address verifyingKey = Secp256k1.recover(
             sha256(abi.encodePacked(_handshake, _rosterIdx)),
             _enclaveSig
@@ -127,10 +176,75 @@ contract Anonify is ReportHandle {
             verifyingKeyMapping[verifyingKey] == verifyingKey,
             "Invalid enclave signature."
         );
+        // The enclaves accept a new verifying key for the roster index in the handshake,
+        // so it must be the one registered for the roster index with `registerReport`.
+        require(
+            _rosterIdxToVerifyingKey[_rosterIdx] == verifyingKey,
+            "The handshake must be signed by the verifying key of the roster index"
+        );
 
         emit StoreHandshake(_handshake);
     }
 
+    // The owner evicts a TEE node from the group with a remove handshake which re-keys the group.
+    function removeMember(
+        bytes memory _handshake,
+        bytes memory _enclaveSig,
+        uint32 _rosterIdx,
+        uint32 _removedRosterIdx
+    ) public onlyOwner {
+        require(
+            _senderToRosterIdx[msg.sender] == _rosterIdx,
+            "The roster index must be same as the registered one"
+        );
+        require(_removedRosterIdx != _rosterIdx, "The owner cannot remove itself");
+        require(
+            BytesUtils.toUint32LE(_handshake, 4) == _rosterIdx,
+            "The handshake must be sent from the roster index"
+        );
+        // The handshake is a SCALE-encoded `ExportHandshake`, whose inner handshake follows the prior epoch,
+        // the roster index and the vector's length prefix. The inner handshake consists of the prior epoch (4 bytes),
+        // the roster index (4 bytes), the cipher suite (2 bytes) and the removed roster index as `Option<u32>`.
+        uint256 removedOffset = 8 + BytesUtils.compactLength(_handshake, 8) + 10;
+        require(
+            uint8(_handshake[removedOffset]) == 1 &&
+                BytesUtils.toUint32LE(_handshake, removedOffset + 1) == _removedRosterIdx,
+            "The removed roster index must be same as the handshake's one"
+        );
+        require(
+            _removedRosterIdx <= _rosterIdxCounter,
+            "The removed roster index hasn't joined the group"
+        );
+        require(
+            !_isRemoved[_removedRosterIdx],
+            "The roster index has already been removed"
+        );
+        address verifyingKey = Secp256k1.recover(
+            sha256(abi.encodePacked(_handshake, _rosterIdx, _removedRosterIdx)),
+            _enclaveSig
+        );
+        require(
+            verifyingKeyMapping[verifyingKey] == verifyingKey,
+            "Invalid enclave signature."
+        );
+
+        _isRemoved[_removedRosterIdx] = true;
+        revokeVerifyingKey(_rosterIdxToVerifyingKey[_removedRosterIdx]);
+        emit RemoveMember(_removedRosterIdx);
+        emit StoreHandshake(_handshake);
+    }
+
+    // The roster index which will be allocated to the next TEE node joining the group.
+    function nextRosterIdx() public view returns (uint32) {
+        return _rosterIdxCounter + 1;
+    }
+
+    function rosterIdxOf(address _sender) public view returns (uint32) {
+        return _senderToRosterIdx[_sender];
+    }
+
+    // The handshake isn't verified on-chain, but it embeds the sender enclave's signature
+    // (and the attestation on joining) which receiving enclaves verify before processing it.
     function handshake_wo_sig(bytes memory _handshake) private {
         emit StoreHandshake(_handshake);
     }
@@ -5,6 +5,7 @@ import "./utils/SolRsaVerify.sol";
 import "./utils/Base64.sol";
 import "./utils/BytesUtils.sol";
 import "./utils/Secp256k1.sol";
+import "./utils/DcapQuote.sol";
 
 contract ReportHandle {
     using SafeMath for uint256;
@@ -23,6 +24,8 @@ contract ReportHandle {
     // `openssl x509 -noout -modulus -in AttestationReportSigningCert.pem` and `openssl x509 -in AttestationReportSigningCert.pem -text`.
     bytes constant internal RSA_EXP = hex"0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010001";
     bytes constant internal RSA_MOD = hex"A97A2DE0E66EA6147C9EE745AC0162686C7192099AFC4B3F040FAD6DE093511D74E802F510D716038157DCAF84F4104BD3FED7E6B8F99C8817FD1FF5B9B864296C3D81FA8F1B729E02D21D72FFEE4CED725EFE74BEA68FBC4D4244286FCDD4BF64406A439A15BCB4CF67754489C423972B4A80DF5C2E7C5BC2DBAF2D42BB7B244F7C95BF92C75D3B33FC5410678A89589D1083DA3ACC459F2704CD99598C275E7C1878E00757E5BDB4E840226C11C0A17FF79C80B15C1DDB5AF21CC2417061FBD2A2DA819ED3B72B7EFAA3BFEBE2805C9B8AC19AA346512D484CFC81941E15F55881CC127E8F7AA12300CD5AFB5742FA1D20CB467A5BEB1C666CF76A368978B5";
+    // The raw public key of Intel SGX Root CA, which is `remote_attestation::DCAP_ROOT_CA`
+    bytes constant internal DCAP_ROOT_KEY = hex"0ba9c4c0c0c86193a3fe23d6b02cda10a8bbd4e88e48b4458561a36e705525f567918e2edc88e40d860bd0cc4ee26aacc988e505a953558c453f6b0904ae7394";
     uint constant internal WORD_SIZE = 32;
 
     // Set new mrenclave value and enclave address
@@ -35,14 +38,26 @@ contract ReportHandle {
     }
 
     // Check mrenclave value and report signature and then set new enclave address.
-    function handleReport(bytes memory _report, bytes memory _reportSig) internal {
+    // The report of the enclave attested again with `_registeredKey` only refreshes it, and the keys are kept as they are.
+    function handleReport(bytes memory _report, bytes memory _reportSig, address _registeredKey) internal returns (address) {
         (bytes32 inpMrEnclave, address inpVerifyingKey, bytes memory inpEncryptingKey) = extractFromReport(_report, _reportSig);
         require(mrEnclave == inpMrEnclave, "mrenclave included in the report is not correct.");
 
+        if (_registeredKey != address(0) && inpVerifyingKey == _registeredKey && verifyingKeyMapping[inpVerifyingKey] == inpVerifyingKey) {
+            require(encryptingKeyMapping[inpEncryptingKey].length != 0, "The encrypting key has not been registered.");
+            return inpVerifyingKey;
+        }
         setKeys(inpVerifyingKey, inpEncryptingKey);
+        return inpVerifyingKey;
+    }
+
+    // Revoke the verifying key so that the enclave can no longer store ciphertexts and handshakes.
+    function revokeVerifyingKey(address inpVerifyingKey) internal {
+        delete verifyingKeyMapping[inpVerifyingKey];
     }
 
-    function updateMrenclaveInner(bytes memory _report, bytes memory _reportSig) internal {
+    // Returns the verifying key of the enclave running the new mrenclave.
+    function updateMrenclaveInner(bytes memory _report, bytes memory _reportSig) internal returns (address) {
         (bytes32 inpMrEnclave, address inpVerifyingKey, bytes memory inpEncryptingKey) = extractFromReport(_report, _reportSig);
         require(mrEnclave != inpMrEnclave, "mrenclave must be different one");
 
@@ -56,9 +71,12 @@ contract ReportHandle {
 
         setKeys(inpVerifyingKey, inpEncryptingKey);
         mrEnclave = inpMrEnclave;
+        return inpVerifyingKey;
     }
 
     function setKeys(address inpVerifyingKey, bytes memory inpEncryptingKey) private {
+        require(verifyingKeyMapping[inpVerifyingKey] == address(0), "The verifying key has already been registered.");
+        require(encryptingKeyMapping[inpEncryptingKey].length == 0, "The encrypting key has already been registered.");
         verifyingKeyMapping[inpVerifyingKey] = inpVerifyingKey;
         encryptingKeyMapping[inpEncryptingKey] = inpEncryptingKey;
         verifyingKeyArray.push(inpVerifyingKey);
@@ -71,15 +89,20 @@ contract ReportHandle {
         return encryptingKeyMapping[inpEncryptingKey];
     }
 
+    // The report is either IAS's report signed with `_reportSig`,
+    // or a DCAP quote with its PCK certificate chain in `_reportSig`. See `DcapQuote`.
     function extractFromReport(bytes memory _report, bytes memory _reportSig) internal view returns (bytes32, address, bytes memory) {
-        require(verifyReportSig(_report, _reportSig) == 0, "Invalid report's signature");
-        bytes memory quote = extractQuote(_report);
+        bytes memory quote;
+        if (DcapQuote.isDcapQuote(_report)) {
+            quote = DcapQuote.verify(_report, _reportSig, dcapRootKey());
+        } else {
+            require(verifyReportSig(_report, _reportSig) == 0, "Invalid report's signature");
+            quote = extractQuote(_report);
+        }
         // See https://api.trustedservices.intel.com/documents/sgx-attestation-api-spec.pdf, P.23.
         bytes32 inpMrEnclave = BytesUtils.toBytes32(extractElement(quote, 112, 32), 0);
         address inpVerifyingKey = BytesUtils.toAddress(extractElement(quote, 368, 20), 0);
         bytes memory inpEncryptingKey = extractElement(quote, 388, 33);
-        require(verifyingKeyMapping[inpVerifyingKey] == address(0), "The verifying key has already been registered.");
-        require(encryptingKeyMapping[inpEncryptingKey].length == 0, "The encrypting key has already been registered.");
 
         return (inpMrEnclave, inpVerifyingKey, inpEncryptingKey);
     }
@@ -106,7 +129,17 @@ contract ReportHandle {
     }
 
     function verifyReportSig(bytes memory _report, bytes memory _reportSig) internal view returns(uint256) {
-        return SolRsaVerify.pkcs1Sha256VerifyRaw(_report, _reportSig, RSA_EXP, RSA_MOD);
+        return SolRsaVerify.pkcs1Sha256VerifyRaw(_report, _reportSig, RSA_EXP, reportSigningModulus());
+    }
+
+    // The modulus of the report signing key, which is only overridden by test deployments. See `AnonifyWithMockIas`.
+    function reportSigningModulus() internal pure returns (bytes memory) {
+        return RSA_MOD;
+    }
+
+    // The key of the root CA issuing PCK CAs, which is only overridden by test deployments. See `AnonifyWithMockIas`.
+    function dcapRootKey() internal pure returns (bytes memory) {
+        return DCAP_ROOT_KEY;
     }
 
     function extractElement(bytes memory src, uint offset, uint len) internal pure returns (bytes memory) {
@@ -0,0 +1,30 @@
+pragma solidity ^0.5.0;
+pragma experimental ABIEncoderV2;
+
+import "../Anonify.sol";
+
+// Anonify which accepts the reports signed by the mock IAS server in `tests/mock-ias-server` instead of Intel's ones,
+// and DCAP quotes chaining up to `remote_attestation::TEST_DCAP_ROOT_CA` instead of Intel SGX Root CA.
+// It's only deployed by the tests, whose enclaves are built with the `mock-ias` feature.
+contract AnonifyWithMockIas is Anonify {
+    // The modulus of `tests/mock-ias-server/certs/report-signing-cert.pem`, you can extract it using:
+    // `openssl x509 -noout -modulus -in report-signing-cert.pem`.
+    bytes constant internal MOCK_RSA_MOD = hex"C7336A2ECEB55A5D4085ACF894FFC131011F68171B13D8AFA91FB8A1AAAC263B1E76D531E20CF57B6660D4C255CEB6EB7C3866FE174360C68AC37204A9D9D570B82506431AC377FF58E841A20244AF6F1C9F59DEE62E26B1B57E3B8DE4524426C3B92447E0B5701C6D3CB35C141C2101EADD23DD27E5B1CA6FC12C963001A613D55ECDDA186B5B762BA7D6B1507BC3C4E0AE11901ADA44569FA4C68D31B7D4CC21B6EB2CF01761239AC6BFA1BFBADC4064F3CF971A066FFE2B0519E6E82BAD5C83F29B51E73B2CCFEB80147A794706D09CCB6219B7D90E9E3AD96DDAA5FFFB8AF1348EA812A9ABA5D5DD9D6970D19856B7E2A06963298D346EB8F6A665198D71";
+    // The raw public key of `frame/remote-attestation/certs/test-dcap-root-ca.pem`
+    bytes constant internal MOCK_DCAP_ROOT_KEY = hex"213fbacdc37a869f0eaf618f0cd6579a99feb67215e4e54c0f27bcc54206ba885c1595af9ecc29692998bbe5831b8ff19248c9a1cd867244a8ae083e40de9e75";
+
+    constructor(
+        bytes memory _report,
+        bytes memory _reportSig,
+        bytes memory _handshake,
+        uint32 mrenclaveVer
+    ) public Anonify(_report, _reportSig, _handshake, mrenclaveVer) {}
+
+    function reportSigningModulus() internal pure returns (bytes memory) {
+        return MOCK_RSA_MOD;
+    }
+
+    function dcapRootKey() internal pure returns (bytes memory) {
+        return MOCK_DCAP_ROOT_KEY;
+    }
+}
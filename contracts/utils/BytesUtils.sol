@@ -34,4 +34,47 @@ library BytesUtils {
 
         return tempUint;
     }
+
+    // Read a little-endian uint32 such as a SCALE-encoded one.
+    function toUint32LE(bytes memory _bytes, uint256 _start) internal pure returns (uint32) {
+        require(_bytes.length >= (_start + 4), "Read out of bounds");
+
+        return uint32(uint8(_bytes[_start]))
+            | (uint32(uint8(_bytes[_start + 1])) << 8)
+            | (uint32(uint8(_bytes[_start + 2])) << 16)
+            | (uint32(uint8(_bytes[_start + 3])) << 24);
+    }
+
+    // Read a little-endian uint16 such as the fields of SGX quotes.
+    function toUint16LE(bytes memory _bytes, uint256 _start) internal pure returns (uint16) {
+        require(_bytes.length >= (_start + 2), "Read out of bounds");
+
+        return uint16(uint8(_bytes[_start])) | (uint16(uint8(_bytes[_start + 1])) << 8);
+    }
+
+    // The byte length of a SCALE compact-encoded integer such as the length prefix of a SCALE-encoded vector.
+    function compactLength(bytes memory _bytes, uint256 _start) internal pure returns (uint256) {
+        require(_bytes.length > _start, "Read out of bounds");
+        uint8 prefix = uint8(_bytes[_start]);
+        uint8 mode = prefix & 0x03;
+        if (mode == 0) {
+            return 1;
+        } else if (mode == 1) {
+            return 2;
+        } else if (mode == 2) {
+            return 4;
+        }
+        // In the big-integer mode, the upper six bits are the number of the following bytes minus 4.
+        return 1 + uint256(prefix >> 2) + 4;
+    }
+
+    function slice(bytes memory _bytes, uint256 _start, uint256 _length) internal pure returns (bytes memory) {
+        require(_bytes.length >= (_start + _length), "Read out of bounds");
+        bytes memory tempBytes = new bytes(_length);
+        for (uint256 i = 0; i < _length; i++) {
+            tempBytes[i] = _bytes[_start + i];
+        }
+
+        return tempBytes;
+    }
 }
@@ -0,0 +1,114 @@
+pragma solidity ^0.5.0;
+
+import "./BytesUtils.sol";
+import "./P256.sol";
+
+// Verification of DCAP ECDSA quotes (`sgx_quote3_t`).
+// X.509 certificates are too costly to parse on-chain, so the host passes the PCK certificate chain
+// as the to-be-signed parts of the PCK certificate and its CA's one, their raw signatures
+// and the offsets of their public keys, which are all checked against the root CA's key,
+// i.e. Intel SGX Root CA's one except in test deployments. See `ReportHandle.dcapRootKey`.
+// The TCB status and the revocation of the PCK certificate are only checked by enclaves,
+// which verify the quotes with the collateral.
+library DcapQuote {
+    uint16 constant QUOTE_VERSION = 3;
+    uint16 constant ATT_KEY_TYPE_ECDSA_P256 = 2;
+    // The header and the report body, which is the same layout as IAS's `isvEnclaveQuoteBody`
+    uint constant QUOTE_BODY_SIZE = 432;
+    // Offsets in `sgx_ql_ecdsa_sig_data_t`, which follows the body and its length
+    uint constant SIG_OFFSET = 436;
+    uint constant ATTEST_KEY_OFFSET = 500;
+    uint constant QE_REPORT_OFFSET = 564;
+    uint constant QE_REPORT_SIZE = 384;
+    uint constant QE_REPORT_SIG_OFFSET = 948;
+    uint constant QE_AUTH_DATA_LEN_OFFSET = 1012;
+    uint constant ECDSA_SIZE = 64;
+    // Offsets in the QE's report body
+    uint constant MR_SIGNER_OFFSET = 128;
+    uint constant ISV_PROD_ID_OFFSET = 256;
+    uint constant REPORT_DATA_OFFSET = 320;
+
+    // MRSIGNER and ISVPRODID of Intel's quoting enclave
+    bytes32 constant QE_MR_SIGNER = 0x8c4f5775d796503e96137f77c68a829a0056ac8ded70140b081b094490c57bff;
+    uint16 constant QE_ISV_PROD_ID = 1;
+    // The DER-encoded SubjectPublicKeyInfo of a P-256 key up to its raw `x || y`
+    bytes constant P256_SPKI_PREFIX = hex"3059301306072a8648ce3d020106082a8648ce3d03010703420004";
+
+    // IAS's reports are JSON, so they never begin with the quote version and the attestation key type.
+    function isDcapQuote(bytes memory _report) internal pure returns (bool) {
+        return _report.length > QE_AUTH_DATA_LEN_OFFSET + 2
+            && BytesUtils.toUint16LE(_report, 0) == QUOTE_VERSION
+            && BytesUtils.toUint16LE(_report, 2) == ATT_KEY_TYPE_ECDSA_P256;
+    }
+
+    // Verify the quote with the PCK certificate chain issued by `_rootKey`, and then returns its header and report body.
+    function verify(bytes memory _quote, bytes memory _pckChain, bytes memory _rootKey) internal pure returns (bytes memory) {
+        bytes memory pckKey = verifyPckChain(_pckChain, _rootKey);
+
+        // The PCK key signs the report of Intel's quoting enclave.
+        bytes memory qeReport = BytesUtils.slice(_quote, QE_REPORT_OFFSET, QE_REPORT_SIZE);
+        require(
+            BytesUtils.toBytes32(qeReport, MR_SIGNER_OFFSET) == QE_MR_SIGNER
+                && BytesUtils.toUint16LE(qeReport, ISV_PROD_ID_OFFSET) == QE_ISV_PROD_ID,
+            "The quote isn't generated by Intel's quoting enclave"
+        );
+        require(
+            P256.verify(sha256(qeReport), BytesUtils.slice(_quote, QE_REPORT_SIG_OFFSET, ECDSA_SIZE), pckKey),
+            "Invalid QE report's signature"
+        );
+
+        // The QE binds the attestation key to its report.
+        bytes memory attestKey = BytesUtils.slice(_quote, ATTEST_KEY_OFFSET, ECDSA_SIZE);
+        uint256 qeAuthDataLen = BytesUtils.toUint16LE(_quote, QE_AUTH_DATA_LEN_OFFSET);
+        bytes memory qeAuthData = BytesUtils.slice(_quote, QE_AUTH_DATA_LEN_OFFSET + 2, qeAuthDataLen);
+        require(
+            sha256(abi.encodePacked(attestKey, qeAuthData)) == BytesUtils.toBytes32(qeReport, REPORT_DATA_OFFSET)
+                && BytesUtils.toBytes32(qeReport, REPORT_DATA_OFFSET + 32) == bytes32(0),
+            "The attestation key isn't bound to the QE's report"
+        );
+
+        // The attestation key signs the header and the enclave's report body.
+        bytes memory quoteBody = BytesUtils.slice(_quote, 0, QUOTE_BODY_SIZE);
+        require(
+            P256.verify(sha256(quoteBody), BytesUtils.slice(_quote, SIG_OFFSET, ECDSA_SIZE), attestKey),
+            "Invalid quote's signature"
+        );
+
+        return quoteBody;
+    }
+
+    // Verify the PCK certificate chain up to the root CA of `_rootKey`, and then returns the PCK key.
+    // The chain is ABI-encoded as `(pckTbs, pckSig, pckKeyOffset, caTbs, caSig, caKeyOffset)`.
+    function verifyPckChain(bytes memory _pckChain, bytes memory _rootKey) internal pure returns (bytes memory) {
+        (
+            bytes memory pckTbs,
+            bytes memory pckSig,
+            uint256 pckKeyOffset,
+            bytes memory caTbs,
+            bytes memory caSig,
+            uint256 caKeyOffset
+        ) = abi.decode(_pckChain, (bytes, bytes, uint256, bytes, bytes, uint256));
+
+        require(
+            P256.verify(sha256(caTbs), caSig, _rootKey),
+            "The PCK CA's certificate isn't issued by the root CA"
+        );
+        require(
+            P256.verify(sha256(pckTbs), pckSig, extractPubkey(caTbs, caKeyOffset)),
+            "The PCK certificate isn't issued by the PCK CA"
+        );
+
+        return extractPubkey(pckTbs, pckKeyOffset);
+    }
+
+    function extractPubkey(bytes memory _tbs, uint256 _offset) private pure returns (bytes memory) {
+        require(_offset >= P256_SPKI_PREFIX.length, "Invalid offset of the public key");
+        require(
+            keccak256(BytesUtils.slice(_tbs, _offset - P256_SPKI_PREFIX.length, P256_SPKI_PREFIX.length))
+                == keccak256(P256_SPKI_PREFIX),
+            "Not found a P-256 public key at the offset"
+        );
+
+        return BytesUtils.slice(_tbs, _offset, ECDSA_SIZE);
+    }
+}
@@ -0,0 +1,158 @@
+pragma solidity ^0.5.0;
+
+import "./BytesUtils.sol";
+
+// ECDSA verification over NIST P-256, which signs DCAP quotes and Intel's PCK certificates.
+// Points are in Jacobian coordinates, and the point at infinity has z = 0.
+// ref: https://github.com/witnet/elliptic-curve-solidity/blob/master/contracts/EllipticCurve.sol
+// ref: https://hyperelliptic.org/EFD/g1p/auto-shortw-jacobian-3.html
+library P256 {
+    // the modulus
+    uint256 constant PP = 0xFFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFF;
+    // a = -3 (mod PP)
+    uint256 constant AA = 0xFFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFC;
+    uint256 constant BB = 0x5AC635D8AA3A93E7B3EBBD55769886BC651D06B0CC53B0F63BCE3C3E27D2604B;
+    uint256 constant GX = 0x6B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296;
+    uint256 constant GY = 0x4FE342E2FE1A7F9B8EE7EB4A7C0F9E162BCE33576B315ECECBB6406837BF51F5;
+    // the order of the base point
+    uint256 constant NN = 0xFFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551;
+
+    /// @dev Verifies an ECDSA signature.
+    /// @param _hash the SHA-256 hash of the signed message
+    /// @param _sig the raw signature, `r || s`
+    /// @param _pubkey the raw public key, `x || y`
+    /// @return whether the signature is valid
+    function verify(bytes32 _hash, bytes memory _sig, bytes memory _pubkey) internal pure returns (bool) {
+        if (_sig.length != 64 || _pubkey.length != 64) {
+            return false;
+        }
+        uint256 r = uint256(BytesUtils.toBytes32(_sig, 0));
+        uint256 s = uint256(BytesUtils.toBytes32(_sig, 32));
+        if (r == 0 || r >= NN || s == 0 || s >= NN) {
+            return false;
+        }
+        uint256 qx = uint256(BytesUtils.toBytes32(_pubkey, 0));
+        uint256 qy = uint256(BytesUtils.toBytes32(_pubkey, 32));
+        if (!isOnCurve(qx, qy)) {
+            return false;
+        }
+
+        uint256 w = expMod(s, NN - 2, NN);
+        uint256[3] memory point = shamirMul(mulmod(uint256(_hash), w, NN), mulmod(r, w, NN), qx, qy);
+        if (point[2] == 0) {
+            return false;
+        }
+        uint256 zInv = expMod(point[2], PP - 2, PP);
+        uint256 x = mulmod(point[0], mulmod(zInv, zInv, PP), PP);
+
+        return x % NN == r;
+    }
+
+    function isOnCurve(uint256 _x, uint256 _y) internal pure returns (bool) {
+        if (_x >= PP || _y >= PP) {
+            return false;
+        }
+        // y^2 = x^3 + ax + b
+        uint256 lhs = mulmod(_y, _y, PP);
+        uint256 rhs = addmod(mulmod(_x, mulmod(_x, _x, PP), PP), addmod(mulmod(_x, AA, PP), BB, PP), PP);
+
+        return lhs == rhs;
+    }
+
+    /// @dev Computes u1 * G + u2 * Q at once with Shamir's trick.
+    function shamirMul(uint256 _u1, uint256 _u2, uint256 _qx, uint256 _qy) private pure returns (uint256[3] memory acc) {
+        uint256[3] memory g = [GX, GY, uint256(1)];
+        uint256[3] memory q = [_qx, _qy, uint256(1)];
+        uint256[3] memory gq = [GX, GY, uint256(1)];
+        jacobianAdd(gq, q);
+
+        for (uint256 i = 256; i > 0; i--) {
+            jacobianDouble(acc);
+            bool bit1 = ((_u1 >> (i - 1)) & 1) == 1;
+            bool bit2 = ((_u2 >> (i - 1)) & 1) == 1;
+            if (bit1 && bit2) {
+                jacobianAdd(acc, gq);
+            } else if (bit1) {
+                jacobianAdd(acc, g);
+            } else if (bit2) {
+                jacobianAdd(acc, q);
+            }
+        }
+    }
+
+    /// @dev Doubles the point in place, where a = -3.
+    function jacobianDouble(uint256[3] memory _p) private pure {
+        if (_p[2] == 0) {
+            return;
+        }
+        uint256 zz = mulmod(_p[2], _p[2], PP);
+        // m = 3 * (x - z^2) * (x + z^2)
+        uint256 m = mulmod(3, mulmod(addmod(_p[0], PP - zz, PP), addmod(_p[0], zz, PP), PP), PP);
+        uint256 yy = mulmod(_p[1], _p[1], PP);
+        // s = 4 * x * y^2
+        uint256 s = mulmod(4, mulmod(_p[0], yy, PP), PP);
+        uint256 x = addmod(mulmod(m, m, PP), PP - mulmod(2, s, PP), PP);
+
+        _p[2] = mulmod(2, mulmod(_p[1], _p[2], PP), PP);
+        _p[1] = addmod(mulmod(m, addmod(s, PP - x, PP), PP), PP - mulmod(8, mulmod(yy, yy, PP), PP), PP);
+        _p[0] = x;
+    }
+
+    /// @dev Adds the second point to the first one in place.
+    function jacobianAdd(uint256[3] memory _p1, uint256[3] memory _p2) private pure {
+        if (_p2[2] == 0) {
+            return;
+        }
+        if (_p1[2] == 0) {
+            _p1[0] = _p2[0];
+            _p1[1] = _p2[1];
+            _p1[2] = _p2[2];
+            return;
+        }
+
+        uint256 u1;
+        uint256 s1;
+        uint256 h;
+        uint256 r;
+        {
+            uint256 z1z1 = mulmod(_p1[2], _p1[2], PP);
+            uint256 z2z2 = mulmod(_p2[2], _p2[2], PP);
+            u1 = mulmod(_p1[0], z2z2, PP);
+            s1 = mulmod(_p1[1], mulmod(_p2[2], z2z2, PP), PP);
+            uint256 u2 = mulmod(_p2[0], z1z1, PP);
+            uint256 s2 = mulmod(_p2[1], mulmod(_p1[2], z1z1, PP), PP);
+            if (u1 == u2) {
+                if (s1 == s2) {
+                    jacobianDouble(_p1);
+                } else {
+                    _p1[2] = 0;
+                }
+                return;
+            }
+            h = addmod(u2, PP - u1, PP);
+            r = addmod(s2, PP - s1, PP);
+        }
+
+        uint256 hh = mulmod(h, h, PP);
+        uint256 hhh = mulmod(h, hh, PP);
+        uint256 v = mulmod(u1, hh, PP);
+        // x3 = r^2 - h^3 - 2 * u1 * h^2
+        uint256 x = addmod(addmod(mulmod(r, r, PP), PP - hhh, PP), PP - mulmod(2, v, PP), PP);
+
+        _p1[1] = addmod(mulmod(r, addmod(v, PP - x, PP), PP), PP - mulmod(s1, hhh, PP), PP);
+        _p1[2] = mulmod(mulmod(_p1[2], _p2[2], PP), h, PP);
+        _p1[0] = x;
+    }
+
+    /// @dev Modular exponentiation, b^e % m.
+    function expMod(uint256 _base, uint256 _exp, uint256 _mod) private pure returns (uint256 result) {
+        result = 1;
+        _base = _base % _mod;
+        for (; _exp > 0; _exp >>= 1) {
+            if ((_exp & 1) == 1) {
+                result = mulmod(result, _base, _mod);
+            }
+            _base = mulmod(_base, _base, _mod);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use attestation_verifier::AttestationBundle;
 use ed25519_dalek::{
     Keypair, PublicKey, Signature, SignatureError, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH,
 };
@@ -89,6 +90,7 @@ pub mod init_state {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -121,6 +123,15 @@ pub mod encrypting_key {
     }
 }
 
+pub mod attestation {
+    pub mod get {
+        use super::super::*;
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response(pub AttestationBundle);
+    }
+}
+
 pub mod transfer {
     pub mod post {
         use super::super::*;
@@ -156,6 +167,7 @@ pub mod transfer {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -211,6 +223,7 @@ pub mod approve {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -266,6 +279,7 @@ pub mod transfer_from {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -321,6 +335,7 @@ pub mod mint {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -376,6 +391,7 @@ pub mod burn {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -408,6 +424,26 @@ pub mod key_rotation {
     }
 }
 
+pub mod remove_member {
+    pub mod post {
+        use super::super::*;
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Request {
+            pub roster_idx: u32,
+        }
+
+        impl Request {
+            pub fn new(roster_idx: u32) -> Self {
+                Request { roster_idx }
+            }
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response(pub H256);
+    }
+}
+
 pub mod allowance {
     pub mod get {
         use super::super::*;
@@ -419,7 +455,8 @@ pub mod allowance {
             pub sig: [u8; SIGNATURE_LENGTH],
             pub pubkey: [u8; PUBLIC_KEY_LENGTH],
             pub challenge: [u8; 32],
-            pub spender: AccountId,
+            /// Hex, checksummed or base64-encoded address of the spender
+            pub spender: String,
         }
 
         impl Request {
@@ -432,13 +469,14 @@ pub mod allowance {
                     sig: sig.to_bytes(),
                     pubkey: keypair.public.to_bytes(),
                     challenge,
-                    spender,
+                    spender: spender.base64_encode(),
                 }
             }
 
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -491,6 +529,7 @@ pub mod state {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -559,6 +598,7 @@ pub mod register_notification {
             pub fn into_access_right(&self) -> Result<Ed25519ChallengeResponse, SignatureError> {
                 let sig = Signature::from_bytes(&self.sig)?;
                 let pubkey = PublicKey::from_bytes(&self.pubkey)?;
+                pubkey.verify(&self.challenge, &sig)?;
 
                 Ok(Ed25519ChallengeResponse::new(sig, pubkey, self.challenge))
             }
@@ -5,8 +5,11 @@ use crate::{
 };
 use anonify_wallet::{DirOperations, KeyFile, KeystoreDirectory, WalletDirectory};
 use anyhow::anyhow;
+use attestation_verifier::{
+    parse_mrenclaves, EnclaveVerifier, VerifiedEnclave, DCAP_NOT_SUPPORTED,
+};
 use bip39::{Language, Mnemonic, MnemonicType, Seed};
-use codec::Encode;
+use codec::{Decode, Encode};
 use ed25519_dalek::Keypair;
 use erc20_state_transition::{approve, burn, construct, mint, transfer, transfer_from};
 use frame_common::crypto::AccountId;
@@ -14,7 +17,7 @@ use frame_runtime::primitives::U64;
 use frame_treekem::{DhPubKey, EciesCiphertext};
 use rand::Rng;
 use reqwest::Client;
-use std::path::PathBuf;
+use std::{env, fs, path::PathBuf};
 
 pub(crate) fn deploy(anonify_url: String) -> Result<()> {
     let res = Client::new()
@@ -62,12 +65,45 @@ pub(crate) fn update_mrenclave(anonify_url: String, contract_addr: String) -> Re
     Ok(())
 }
 
-pub(crate) fn get_encrypting_key(anonify_url: String) -> Result<DhPubKey> {
-    Client::new()
-        .get(&format!("{}/api/v1/encrypting_key", &anonify_url))
+pub(crate) fn verify_attestation(anonify_url: String) -> Result<()> {
+    let verified = get_verified_enclave(&anonify_url)?;
+
+    println!("MRENCLAVE: {}", hex::encode(verified.mr_enclave));
+    println!(
+        "Verifying address: 0x{}",
+        hex::encode(verified.verifying_address)
+    );
+    println!("Quote status: {}", verified.quote_status);
+    Ok(())
+}
+
+/// The encrypting key bound to the report of an allowed enclave.
+pub(crate) fn get_verified_encrypting_key(anonify_url: &str) -> Result<DhPubKey> {
+    let verified = get_verified_enclave(anonify_url)?;
+    DhPubKey::decode(&mut &verified.encrypting_key[..])
+        .map_err(|e| anyhow!("Invalid encrypting key: {:?}", e).into())
+}
+
+/// Fetch the enclave's attestation bundle and verify it against the MRENCLAVEs in `ALLOWED_MRENCLAVES`.
+/// The report is checked with Intel's report signing CA unless `IAS_REPORT_CA` is set to the path of another one.
+/// Nodes attested with DCAP aren't supported, because their reports aren't signed by the attestation service.
+fn get_verified_enclave(anonify_url: &str) -> Result<VerifiedEnclave> {
+    let res: erc20_api::attestation::get::Response = Client::new()
+        .get(&format!("{}/api/v1/attestation", anonify_url))
         .send()?
-        .json()
-        .map_err(Into::into)
+        .json()?;
+    if res.0.is_dcap() {
+        return Err(anyhow!("{}: {}", DCAP_NOT_SUPPORTED, anonify_url).into());
+    }
+
+    let allowed_mrenclaves =
+        env::var("ALLOWED_MRENCLAVES").map_err(|_| anyhow!("ALLOWED_MRENCLAVES is not set"))?;
+    let mut verifier = EnclaveVerifier::new(parse_mrenclaves(&allowed_mrenclaves)?)?;
+    if let Ok(ca_path) = env::var("IAS_REPORT_CA") {
+        verifier = verifier.with_trusted_ca(&fs::read_to_string(ca_path)?)?;
+    }
+
+    verifier.verify(&res.0).map_err(Into::into)
 }
 
 pub(crate) fn init_state<R: Rng>(
@@ -261,6 +297,19 @@ pub(crate) fn key_rotation(anonify_url: String) -> Result<()> {
     Ok(())
 }
 
+pub(crate) fn remove_member(anonify_url: String, roster_idx: u32) -> Result<()> {
+    let req = erc20_api::remove_member::post::Request::new(roster_idx);
+    let res = Client::new()
+        .post(&format!("{}/api/v1/remove_member", &anonify_url))
+        .json(&req)
+        .send()?
+        .text()?;
+
+    println!("Transaction hash: {:?}", res);
+
+    Ok(())
+}
+
 pub(crate) fn allowance<R: Rng>(
     term: &mut Term,
     root_dir: PathBuf,
@@ -313,6 +362,15 @@ pub(crate) fn start_sync_bc(anonify_url: String) -> Result<()> {
     Ok(())
 }
 
+pub(crate) fn start_reattestation(anonify_url: String) -> Result<()> {
+    Client::new()
+        .get(&format!("{}/api/v1/start_reattestation", &anonify_url))
+        .send()?
+        .text()?;
+
+    Ok(())
+}
+
 pub(crate) fn set_contract_addr(anonify_url: String, contract_addr: String) -> Result<()> {
     let req = erc20_api::contract_addr::post::Request::new(contract_addr);
     Client::new()
@@ -36,19 +36,11 @@ fn main() {
 
     let contract_addr = env::var("CONTRACT_ADDR").unwrap_or_else(|_| String::default());
     let anonify_url = env::var("ANONIFY_URL").expect("ANONIFY_URL is not set");
-    let encrypting_key =
-        commands::get_encrypting_key(anonify_url.clone()).expect("Failed getting encrypting key");
 
     match matches.subcommand() {
-        (ANONIFY_COMMAND, Some(matches)) => subcommand_anonify(
-            term,
-            root_dir,
-            contract_addr,
-            &encrypting_key,
-            anonify_url,
-            matches,
-            rng,
-        ),
+        (ANONIFY_COMMAND, Some(matches)) => {
+            subcommand_anonify(term, root_dir, contract_addr, anonify_url, matches, rng)
+        }
         (WALLET_COMMAND, Some(matches)) => subcommand_wallet(term, root_dir, matches, rng),
         _ => {
             term.error(matches.usage()).unwrap();
@@ -71,7 +63,6 @@ fn subcommand_anonify<R: Rng>(
     mut term: Term,
     root_dir: PathBuf,
     default_contract_addr: String,
-    encrypting_key: &DhPubKey,
     anonify_url: String,
     matches: &ArgMatches,
     rng: &mut R,
@@ -118,13 +109,14 @@ fn subcommand_anonify<R: Rng>(
                 .parse()
                 .expect("Failed to parse total_supply");
 
+            let encrypting_key = verified_encrypting_key(&anonify_url);
             commands::init_state(
                 &mut term,
                 root_dir,
                 anonify_url,
                 keyfile_index,
                 total_supply,
-                encrypting_key,
+                &encrypting_key,
                 rng,
             )
             .expect("Failed to init_state command");
@@ -141,8 +133,11 @@ fn subcommand_anonify<R: Rng>(
                 .parse()
                 .expect("Failed to parse amount");
             let target: &str = matches.value_of("target").expect("Not found target");
-            let target_addr = AccountId::base64_decode(target);
+            let target_addr = target
+                .parse::<AccountId>()
+                .expect("Failed to parse target address");
 
+            let encrypting_key = verified_encrypting_key(&anonify_url);
             commands::transfer(
                 &mut term,
                 root_dir,
@@ -150,7 +145,7 @@ fn subcommand_anonify<R: Rng>(
                 keyfile_index,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key,
                 rng,
             )
             .expect("Failed to transfer command");
@@ -167,8 +162,11 @@ fn subcommand_anonify<R: Rng>(
                 .parse()
                 .expect("Failed to parse amount");
             let target: &str = matches.value_of("target").expect("Not found target");
-            let target_addr = AccountId::base64_decode(target);
+            let target_addr = target
+                .parse::<AccountId>()
+                .expect("Failed to parse target address");
 
+            let encrypting_key = verified_encrypting_key(&anonify_url);
             commands::approve(
                 &mut term,
                 root_dir,
@@ -176,7 +174,7 @@ fn subcommand_anonify<R: Rng>(
                 keyfile_index,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key,
                 rng,
             )
             .expect("Failed to approve command");
@@ -193,10 +191,15 @@ fn subcommand_anonify<R: Rng>(
                 .parse()
                 .expect("Failed to parse amount");
             let owner: &str = matches.value_of("owner").expect("Not found owner");
-            let owner_addr = AccountId::base64_decode(owner);
+            let owner_addr = owner
+                .parse::<AccountId>()
+                .expect("Failed to parse owner address");
             let target: &str = matches.value_of("target").expect("Not found target");
-            let target_addr = AccountId::base64_decode(target);
+            let target_addr = target
+                .parse::<AccountId>()
+                .expect("Failed to parse target address");
 
+            let encrypting_key = verified_encrypting_key(&anonify_url);
             commands::transfer_from(
                 &mut term,
                 root_dir,
@@ -205,7 +208,7 @@ fn subcommand_anonify<R: Rng>(
                 owner_addr,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key,
                 rng,
             )
             .expect("Failed to transfer_from command");
@@ -222,8 +225,11 @@ fn subcommand_anonify<R: Rng>(
                 .parse()
                 .expect("Failed to parse amount");
             let target: &str = matches.value_of("target").expect("Not found target");
-            let target_addr = AccountId::base64_decode(target);
+            let target_addr = target
+                .parse::<AccountId>()
+                .expect("Failed to parse target address");
 
+            let encrypting_key = verified_encrypting_key(&anonify_url);
             commands::mint(
                 &mut term,
                 root_dir,
@@ -231,7 +237,7 @@ fn subcommand_anonify<R: Rng>(
                 keyfile_index,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key,
                 rng,
             )
             .expect("Failed to mint command");
@@ -248,20 +254,34 @@ fn subcommand_anonify<R: Rng>(
                 .parse()
                 .expect("Failed to parse amount");
 
+            let encrypting_key = verified_encrypting_key(&anonify_url);
             commands::burn(
                 &mut term,
                 root_dir,
                 anonify_url,
                 keyfile_index,
                 amount,
-                encrypting_key,
+                &encrypting_key,
                 rng,
             )
             .expect("Failed to burn command");
         }
+        ("verify_attestation", Some(_)) => {
+            commands::verify_attestation(anonify_url)
+                .expect("Failed to verify_attestation command");
+        }
         ("key_rotation", Some(_)) => {
             commands::key_rotation(anonify_url).expect("Failed to key_rotation command");
         }
+        ("remove_member", Some(matches)) => {
+            let roster_idx: u32 = matches
+                .value_of("roster-idx")
+                .expect("Not found roster-idx.")
+                .parse()
+                .expect("Failed to parse roster-idx");
+            commands::remove_member(anonify_url, roster_idx)
+                .expect("Failed to remove_member command");
+        }
         ("allowance", Some(matches)) => {
             let keyfile_index: usize = matches
                 .value_of("keyfile-index")
@@ -269,7 +289,9 @@ fn subcommand_anonify<R: Rng>(
                 .parse()
                 .expect("Failed to parse keyfile-index");
             let spender = matches.value_of("spender").expect("Not found spender");
-            let spender_addr = AccountId::base64_decode(spender);
+            let spender_addr = spender
+                .parse::<AccountId>()
+                .expect("Failed to parse spender address");
 
             commands::allowance(
                 &mut term,
@@ -294,6 +316,10 @@ fn subcommand_anonify<R: Rng>(
         ("start_sync_bc", Some(_)) => {
             commands::start_sync_bc(anonify_url).expect("Failed to start_sync_bc command");
         }
+        ("start_reattestation", Some(_)) => {
+            commands::start_reattestation(anonify_url)
+                .expect("Failed to start_reattestation command");
+        }
         ("set_contract_addr", Some(matches)) => {
             let contract_addr = match matches.value_of("contract-addr") {
                 Some(addr) => addr.to_string(),
@@ -310,6 +336,12 @@ fn subcommand_anonify<R: Rng>(
     };
 }
 
+/// Commands are encrypted only after the enclave's attestation is verified.
+fn verified_encrypting_key(anonify_url: &str) -> DhPubKey {
+    commands::get_verified_encrypting_key(anonify_url)
+        .expect("Failed to verify the enclave's attestation")
+}
+
 fn anonify_commands_definition<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name(ANONIFY_COMMAND)
         .about("Anonify operations")
@@ -473,10 +505,24 @@ fn anonify_commands_definition<'a, 'b>() -> App<'a, 'b> {
                         .default_value(DEFAULT_AMOUNT),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("verify_attestation")
+                .about("Verify the enclave's attestation report against ALLOWED_MRENCLAVES. Nodes attested with DCAP aren't supported."),
+        )
         .subcommand(
             SubCommand::with_name("key_rotation")
                 .about("handshake with other group members to rotate key"),
         )
+        .subcommand(
+            SubCommand::with_name("remove_member")
+                .about("Remove a member from the group. Only the owner can remove members.")
+                .arg(
+                    Arg::with_name("roster-idx")
+                        .short("r")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("allowance")
                 .about("Get approved balance of the spender address from anonify services.")
@@ -508,6 +554,10 @@ fn anonify_commands_definition<'a, 'b>() -> App<'a, 'b> {
         .subcommand(
             SubCommand::with_name("start_sync_bc").about("Get state from anonify services."),
         )
+        .subcommand(
+            SubCommand::with_name("start_reattestation")
+                .about("Re-attest the enclave and register the report before it expires."),
+        )
         .subcommand(
             SubCommand::with_name("set_contract_addr")
                 .about("Get state from anonify services.")
@@ -1,6 +1,5 @@
 use crate::ENCLAVE_CONTEXT;
 use anonify_enclave::{context::EnclaveContext, workflow::*};
-use anyhow::anyhow;
 use codec::{Decode, Encode};
 use config::constants::*;
 use erc20_state_transition::{Runtime, MAX_MEM_SIZE};
@@ -31,4 +30,7 @@ register_ecall!(
     ),
     (GET_ENCRYPTING_KEY_CMD, EncryptingKeyGetter),
     (CALL_REGISTER_REPORT_CMD, ReportRegistration),
+    (CALL_REMOVE_MEMBER_CMD, RemoveMemberSender),
+    // Get the cached attestation report, which is refreshed before it expires.
+    (GET_REPORT_CMD, ReportGetter),
 );
@@ -1,13 +1,20 @@
 #![crate_name = "anonifyenclave"]
 #![crate_type = "staticlib"]
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+#[cfg(feature = "sgx")]
 #[macro_use]
 extern crate sgx_tstd as std;
+#[cfg(feature = "sgx")]
+extern crate anyhow_sgx as anyhow;
+#[cfg(feature = "std")]
+extern crate anyhow_std as anyhow;
 #[macro_use]
 extern crate lazy_static;
 
 mod ecalls;
 
+pub use crate::ecalls::ecall_handler;
+
 use anonify_enclave::context::EnclaveContext;
 
 lazy_static! {
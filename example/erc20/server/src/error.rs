@@ -1,9 +1,14 @@
+use actix_web::http::StatusCode;
+use anonify_eth_driver::HostError;
+use frame_common::EcallErrorKind;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, ServerError>;
 
 #[derive(Error, Debug)]
 pub enum ServerError {
+    #[error("Bad request: {0}")]
+    BadRequest(String),
     #[error("{0}")]
     ModuleError(#[from] anonify_eth_driver::HostError),
     #[error("{0}")]
@@ -12,4 +17,26 @@ pub enum ServerError {
     AnyhowError(#[from] anyhow::Error),
 }
 
-impl actix_web::error::ResponseError for ServerError {}
+impl actix_web::error::ResponseError for ServerError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ServerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServerError::ModuleError(HostError::InvalidInput(_)) => StatusCode::BAD_REQUEST,
+            ServerError::ModuleError(HostError::PolicyViolation(_)) => StatusCode::FORBIDDEN,
+            ServerError::ModuleError(HostError::NotFound(_)) => StatusCode::NOT_FOUND,
+            ServerError::FrameError(frame_host::Error::Ecall { error, .. }) => {
+                ecall_error_status(error.kind())
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+fn ecall_error_status(kind: EcallErrorKind) -> StatusCode {
+    match kind {
+        EcallErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        EcallErrorKind::PolicyViolation => StatusCode::FORBIDDEN,
+        EcallErrorKind::NotFound => StatusCode::NOT_FOUND,
+        EcallErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -2,11 +2,17 @@ use crate::error::{Result, ServerError};
 use crate::Server;
 use actix_web::{web, HttpResponse};
 use anonify_eth_driver::traits::*;
-use anyhow::anyhow;
+use attestation_verifier::AttestationBundle;
+use codec::Encode;
 use erc20_state_transition::CallName;
+use frame_common::crypto::AccountId;
 use frame_runtime::primitives::{Approved, U64};
 use log::{debug, error, info};
-use std::{sync::Arc, time};
+use std::{
+    str::FromStr,
+    sync::{atomic::Ordering, Arc},
+    time,
+};
 
 const DEFAULT_GAS: u64 = 5_000_000;
 
@@ -129,7 +135,7 @@ where
         .map_err(|e| ServerError::from(e))?;
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     let encrypted_total_supply = req.encrypted_total_supply.clone();
 
     let tx_hash = server
@@ -163,7 +169,7 @@ where
         .map_err(|e| ServerError::from(e))?;
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     let encrypted_transfer_cmd = req.encrypted_transfer_cmd.clone();
 
     let tx_hash = server
@@ -197,7 +203,7 @@ where
         .map_err(|e| ServerError::from(e))?;
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     let encrypted_approve_cmd = req.encrypted_approve_cmd.clone();
 
     let tx_hash = server
@@ -231,7 +237,7 @@ where
         .map_err(|e| ServerError::from(e))?;
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     let encrypted_mint_cmd = req.encrypted_mint_cmd.clone();
 
     let tx_hash = server
@@ -265,7 +271,7 @@ where
         .map_err(|e| ServerError::from(e))?;
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     let encrypted_burn_cmd = req.encrypted_burn_cmd.clone();
 
     let tx_hash = server
@@ -299,7 +305,7 @@ where
         .map_err(|e| ServerError::from(e))?;
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     let encrypted_transfer_from_cmd = req.encrypted_transfer_from_cmd.clone();
 
     let tx_hash = server
@@ -343,6 +349,34 @@ where
     Ok(HttpResponse::Ok().json(erc20_api::key_rotation::post::Response(tx_hash)))
 }
 
+/// Remove a member from the group. Only the owner of the contract can remove members.
+pub async fn handle_remove_member<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    req: web::Json<erc20_api::remove_member::post::Request>,
+) -> Result<HttpResponse>
+where
+    D: Deployer,
+    S: Sender,
+    W: Watcher,
+{
+    let sender_address = server
+        .dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    let (tx_hash, export_path_secret) = server
+        .dispatcher
+        .remove_member(sender_address, DEFAULT_GAS, req.roster_idx)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    server
+        .store_path_secrets
+        .save_to_local_filesystem(&export_path_secret)
+        .map_err(|e| ServerError::from(e))?;
+
+    Ok(HttpResponse::Ok().json(erc20_api::remove_member::post::Response(tx_hash)))
+}
+
 /// Fetch events from blockchain nodes manually, and then get the balance of the address approved by the owner from enclave.
 pub async fn handle_allowance<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
@@ -361,17 +395,19 @@ where
 
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
+    let spender = AccountId::from_str(&req.spender)
+        .map_err(|e| ServerError::BadRequest(format!("invalid spender: {}", e)))?;
     let owner_approved = server
         .dispatcher
         .get_state::<Approved, _, CallName>(access_right, "approved")
         .map_err(|e| ServerError::from(e))?;
-    let approved_amount = owner_approved.allowance(&req.spender).unwrap();
-    // TODO: stop using unwrap when switching from failure to anyhow.
+    let approved_amount = owner_approved
+        .allowance(&spender)
+        .map(|e| e.as_raw())
+        .unwrap_or_default();
 
-    Ok(HttpResponse::Ok().json(erc20_api::allowance::get::Response(
-        (*approved_amount).as_raw(),
-    )))
+    Ok(HttpResponse::Ok().json(erc20_api::allowance::get::Response(approved_amount)))
 }
 
 /// Fetch events from blockchain nodes manually, and then get balance of the address from enclave.
@@ -392,7 +428,7 @@ where
 
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     let state = server
         .dispatcher
         .get_state::<U64, _, CallName>(access_right, "balance_of")
@@ -417,6 +453,35 @@ where
     Ok(HttpResponse::Ok().json(erc20_api::encrypting_key::get::Response(pub_key)))
 }
 
+/// Returns the attestation bundle binding the enclave's encrypting key,
+/// which clients verify before encrypting commands.
+pub async fn handle_attestation<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+) -> Result<HttpResponse>
+where
+    D: Deployer,
+    S: Sender,
+    W: Watcher,
+{
+    let report = server
+        .dispatcher
+        .get_report()
+        .map_err(|e| ServerError::from(e))?;
+    let pub_key = server
+        .dispatcher
+        .get_encrypting_key()
+        .map_err(|e| ServerError::from(e))?;
+
+    let bundle = AttestationBundle {
+        report: report.report().to_vec(),
+        report_sig: report.report_sig().to_vec(),
+        signing_cert: report.signing_cert().to_vec(),
+        encrypting_key: pub_key.encode(),
+    };
+
+    Ok(HttpResponse::Ok().json(erc20_api::attestation::get::Response(bundle)))
+}
+
 pub async fn handle_start_sync_bc<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
 ) -> Result<HttpResponse>
@@ -441,6 +506,51 @@ where
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Start re-attesting the enclave before its attestation report expires,
+/// and registering the refreshed report to the contract set in advance.
+/// Only one loop is started even if this is requested more than once.
+pub async fn handle_start_reattestation<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+) -> Result<HttpResponse>
+where
+    D: Deployer + Send + Sync + 'static,
+    S: Sender + Send + Sync + 'static,
+    W: Watcher + Send + Sync + 'static,
+{
+    if server
+        .is_reattesting
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        debug!("The enclave is already re-attested periodically");
+        return Ok(HttpResponse::Ok().finish());
+    }
+
+    let sender_address = match server
+        .dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+    {
+        Ok(sender_address) => sender_address,
+        Err(e) => {
+            server.is_reattesting.store(false, Ordering::SeqCst);
+            return Err(ServerError::from(e));
+        }
+    };
+
+    // it spawns a new OS thread, and hosts an event loop.
+    actix_rt::Arbiter::new().exec_fn(move || {
+        actix_rt::spawn(async move {
+            server
+                .dispatcher
+                .reattest_periodically(sender_address, DEFAULT_GAS)
+                .await;
+        });
+    });
+
+    Ok(HttpResponse::Ok().finish())
+}
+
 pub async fn handle_set_contract_addr<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
     req: web::Json<erc20_api::contract_addr::post::Request>,
@@ -472,7 +582,7 @@ where
 {
     let access_right = req
         .into_access_right()
-        .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
+        .map_err(|e| ServerError::BadRequest(format!("{:?}", e)))?;
     server
         .dispatcher
         .register_notification(access_right)
@@ -3,7 +3,10 @@ use anonify_eth_driver::{eth::*, traits::*, Dispatcher, EventCache};
 use frame_host::{EnclaveDir, StorePathSecrets};
 use handlers::*;
 use sgx_types::sgx_enclave_id_t;
-use std::{env, io, sync::Arc};
+use std::{
+    env, io,
+    sync::{atomic::AtomicBool, Arc},
+};
 
 mod error;
 mod handlers;
@@ -22,6 +25,8 @@ pub struct Server<D: Deployer, S: Sender, W: Watcher> {
     pub sync_time: u64,
     pub store_path_secrets: StorePathSecrets,
     pub dispatcher: Dispatcher<D, S, W>,
+    /// Whether the loop re-attesting the enclave has been started
+    pub is_reattesting: AtomicBool,
 }
 
 impl<D, S, W> Server<D, S, W>
@@ -63,6 +68,7 @@ where
             password,
             store_path_secrets,
             dispatcher,
+            is_reattesting: AtomicBool::new(false),
         }
     }
 }
@@ -110,6 +116,10 @@ async fn main() -> io::Result<()> {
                 "/api/v1/key_rotation",
                 web::post().to(handle_key_rotation::<EthDeployer, EthSender, EventWatcher>),
             )
+            .route(
+                "/api/v1/remove_member",
+                web::post().to(handle_remove_member::<EthDeployer, EthSender, EventWatcher>),
+            )
             .route(
                 "/api/v1/approve",
                 web::post().to(handle_approve::<EthDeployer, EthSender, EventWatcher>),
@@ -138,6 +148,10 @@ async fn main() -> io::Result<()> {
                 "/api/v1/start_sync_bc",
                 web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
             )
+            .route(
+                "/api/v1/start_reattestation",
+                web::get().to(handle_start_reattestation::<EthDeployer, EthSender, EventWatcher>),
+            )
             .route(
                 "/api/v1/set_contract_addr",
                 web::get().to(handle_set_contract_addr::<EthDeployer, EthSender, EventWatcher>),
@@ -151,6 +165,10 @@ async fn main() -> io::Result<()> {
                 "/api/v1/encrypting_key",
                 web::get().to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
             )
+            .route(
+                "/api/v1/attestation",
+                web::get().to(handle_attestation::<EthDeployer, EthSender, EventWatcher>),
+            )
             .route(
                 "/api/v1/register_report",
                 web::post().to(handle_register_report::<EthDeployer, EthSender, EventWatcher>),
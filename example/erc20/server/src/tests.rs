@@ -1,5 +1,6 @@
 use crate::*;
 use actix_web::{test, web, App};
+use attestation_verifier::EnclaveVerifier;
 use codec::{Decode, Encode};
 use erc20_state_transition::{construct, transfer};
 use ethabi::Contract as ContractABI;
@@ -42,6 +43,62 @@ async fn test_deploy_post() {
     println!("contract address: {:?}", contract_addr);
 }
 
+#[actix_rt::test]
+async fn test_attestation_bundle() {
+    set_env_vars();
+    set_server_env_vars();
+
+    let enclave = EnclaveDir::new()
+        .init_enclave(true)
+        .expect("Failed to initialize enclave.");
+    let eid = enclave.geteid();
+    let server = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(eid));
+    let mut app = test::init_service(
+        App::new()
+            .data(server.clone())
+            .route(
+                "/api/v1/attestation",
+                web::get().to(handle_attestation::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/encrypting_key",
+                web::get().to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
+            ),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/attestation")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+    let bundle: erc20_api::attestation::get::Response = test::read_body_json(resp).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/encrypting_key")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+    let enc_key_resp: erc20_api::encrypting_key::get::Response = test::read_body_json(resp).await;
+
+    // The enclave is built with the `mock-ias` feature, so its reports are signed by the mock IAS server.
+    let verifier = |mr_enclave| {
+        EnclaveVerifier::new(vec![mr_enclave])
+            .and_then(|v| v.with_trusted_ca(mock_ias_server::TEST_CA_PEM))
+            .unwrap()
+    };
+    assert!(
+        verifier([0u8; 32]).verify(&bundle.0).is_err(),
+        "The report of an enclave not in the allowlist must be rejected"
+    );
+
+    let verified = verifier(report_mr_enclave(&bundle.0.report))
+        .verify(&bundle.0)
+        .unwrap();
+    let enc_key = DhPubKey::decode(&mut &verified.encrypting_key[..]).unwrap();
+    assert_eq!(enc_key, enc_key_resp.0);
+}
+
 #[actix_rt::test]
 async fn test_multiple_messages() {
     set_env_vars();
@@ -621,26 +678,32 @@ async fn test_join_group_then_handshake() {
     assert_eq!(balance.0.as_raw(), 90);
 }
 
+/// Extract MRENCLAVE from the quote body in the report.
+fn report_mr_enclave(report: &[u8]) -> [u8; 32] {
+    let report: serde_json::Value = serde_json::from_slice(report).unwrap();
+    let quote_body = base64::decode(report["isvEnclaveQuoteBody"].as_str().unwrap()).unwrap();
+    let mut mr_enclave = [0u8; 32];
+    mr_enclave.copy_from_slice(&quote_body[112..144]);
+    mr_enclave
+}
+
 fn set_server_env_vars() {
     env::set_var("ETH_URL", "http://172.28.0.2:8545");
     env::set_var("ABI_PATH", "../../../contract-build/Anonify.abi");
-    env::set_var("BIN_PATH", "../../../contract-build/Anonify.bin");
+    // Accepts the reports signed by the mock IAS server instead of Intel's ones
+    env::set_var("BIN_PATH", "../../../contract-build/AnonifyWithMockIas.bin");
     env::set_var("CONFIRMATIONS", "0");
     env::set_var("ACCOUNT_INDEX", "0");
     env::set_var("PASSWORD", "anonify0101");
 }
 
 fn my_turn() {
-    env::remove_var("MY_ROSTER_IDX");
     env::remove_var("ACCOUNT_INDEX");
-    env::set_var("MY_ROSTER_IDX", "0");
     env::set_var("ACCOUNT_INDEX", "0");
 }
 
 fn other_turn() {
-    env::remove_var("MY_ROSTER_IDX");
     env::remove_var("ACCOUNT_INDEX");
-    env::set_var("MY_ROSTER_IDX", "1");
     env::set_var("ACCOUNT_INDEX", "1");
 }
 
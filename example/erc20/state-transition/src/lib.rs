@@ -22,9 +22,14 @@ impl_runtime! {
         sender: AccountId,
         total_supply: U64
     ) {
-        let owner_account_id = update!(*OWNER_ACCOUNT_ID, "Owner", sender);
+        // The contract only accepts the first command from its deployer,
+        // so the owner can't be taken by a construct command front-running the deployer's one.
+        let owner = self.get_map::<AccountId>(GLOBAL_ACCOUNT_ID, "Owner")?;
+        ensure!(owner == AccountId::default(), "the owner has already been set");
+
+        let owner_account_id = update!(GLOBAL_ACCOUNT_ID, "Owner", sender);
         let sender_balance = update!(sender, "Balance", total_supply);
-        let total_supply = update!(*OWNER_ACCOUNT_ID, "TotalSupply", total_supply);
+        let total_supply = update!(GLOBAL_ACCOUNT_ID, "TotalSupply", total_supply);
 
         return_update![owner_account_id, sender_balance, total_supply]
     }
@@ -107,14 +112,14 @@ impl_runtime! {
         recipient: AccountId,
         amount: U64
     ) {
-        let owner_account_id = self.get_map::<AccountId>(*OWNER_ACCOUNT_ID, "Owner")?;
+        let owner_account_id = self.get_map::<AccountId>(GLOBAL_ACCOUNT_ID, "Owner")?;
         ensure!(executer == owner_account_id, "only owner can mint");
 
         let recipient_balance = self.get_map::<U64>(recipient, "Balance")?;
         let recipient_balance_update = update!(recipient, "Balance", recipient_balance + amount);
 
-        let total_supply = self.get_map::<U64>(*OWNER_ACCOUNT_ID, "TotalSupply")?;
-        let total_supply_update = update!(*OWNER_ACCOUNT_ID, "TotalSupply", total_supply + amount);
+        let total_supply = self.get_map::<U64>(GLOBAL_ACCOUNT_ID, "TotalSupply")?;
+        let total_supply_update = update!(GLOBAL_ACCOUNT_ID, "TotalSupply", total_supply + amount);
 
         return_update![recipient_balance_update, total_supply_update]
     }
@@ -129,8 +134,8 @@ impl_runtime! {
         ensure!(balance >= amount, "not enough balance to burn");
         let balance_update = update!(sender, "Balance", balance - amount);
 
-        let total_supply = self.get_map::<U64>(*OWNER_ACCOUNT_ID, "TotalSupply")?;
-        let total_supply_update = update!(*OWNER_ACCOUNT_ID, "TotalSupply", total_supply - amount);
+        let total_supply = self.get_map::<U64>(GLOBAL_ACCOUNT_ID, "TotalSupply")?;
+        let total_supply_update = update!(GLOBAL_ACCOUNT_ID, "TotalSupply", total_supply - amount);
 
         return_update![balance_update, total_supply_update]
     }
@@ -158,7 +163,7 @@ impl_runtime! {
         self,
         caller: AccountId
     ) {
-        let total_supply = self.get_map::<U64>(*OWNER_ACCOUNT_ID, "TotalSupply")?;
+        let total_supply = self.get_map::<U64>(GLOBAL_ACCOUNT_ID, "TotalSupply")?;
         get_state![total_supply]
     }
 
@@ -167,7 +172,7 @@ impl_runtime! {
         self,
         caller: AccountId
     ) {
-        let owner = self.get_map::<AccountId>(*OWNER_ACCOUNT_ID, "Owner")?;
+        let owner = self.get_map::<AccountId>(GLOBAL_ACCOUNT_ID, "Owner")?;
         get_state![owner]
     }
 }
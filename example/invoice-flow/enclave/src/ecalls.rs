@@ -1,6 +1,5 @@
 use crate::ENCLAVE_CONTEXT;
 use anonify_enclave::{context::EnclaveContext, workflow::*};
-use anyhow::anyhow;
 use codec::{Decode, Encode};
 use config::constants::*;
 use frame_common::{
@@ -96,7 +96,8 @@ where
 {
     let access_policy = create_access_policy(keypair);
     let signer = server.dispatcher.get_account(0)?;
-    let recipient = AccountId::base64_decode(DEFAULT_RECIPIENT_ADDRESS);
+    let recipient = AccountId::base64_decode(DEFAULT_RECIPIENT_ADDRESS)
+        .expect("DEFAULT_RECIPIENT_ADDRESS must be a valid account id");
 
     let invoice = Bytes::new(invoice.clone().into());
     let invoice = Bytes::from(invoice);
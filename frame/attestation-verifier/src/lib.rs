@@ -0,0 +1,442 @@
+//! Client-side verification of an enclave's attestation.
+//! Clients verify the bundle served by anonify nodes before encrypting commands to the enclave's encrypting key,
+//! so that a compromised host can't substitute its own key or a modified enclave.
+//! Only EPID reports signed by IAS are verifiable, because DCAP reports aren't signed by the attestation service.
+//! Bundles of nodes attested with DCAP are rejected with `DCAP_NOT_SUPPORTED` before anything else is checked,
+//! so clients of such nodes have to trust the node's registration in the contract instead.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, SystemTime};
+
+/// Intel's CA which issues the report signing certificate of IAS
+pub const IAS_REPORT_CA: &str = include_str!("../certs/ias-report-ca.pem");
+/// The same as `remote_attestation::DEFAULT_MAX_REPORT_AGE`
+pub const DEFAULT_MAX_REPORT_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+pub const MR_ENCLAVE_SIZE: usize = 32;
+pub const VERIFYING_ADDRESS_SIZE: usize = 20;
+pub const ENCRYPTING_KEY_SIZE: usize = 33;
+/// The same as `remote_attestation::dcap::ATTESTATION_TYPE_DCAP`
+pub const ATTESTATION_TYPE_DCAP: &str = "DCAP";
+pub const DCAP_NOT_SUPPORTED: &str =
+    "The node is attested with DCAP, whose reports aren't verifiable by clients. Only EPID (IAS) nodes are supported";
+
+/// Offsets in the `sgx_quote_t` structure, the same as `remote_attestation::AttestationReport`.
+const MR_ENCLAVE_OFFSET: usize = 112;
+const REPORT_DATA_OFFSET: usize = 368;
+const REPORT_DATA_SIZE: usize = 64;
+
+type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
+static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// An enclave's attestation served to clients.
+/// The report's REPORTDATA binds the enclave's identity key in the layout of `EnclaveIdentityKey::report_data`:
+/// - 20 bytes: the address of the verifying key
+/// - 33 bytes: the encrypting key
+/// - 11 bytes: zero padding
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct AttestationBundle {
+    /// The attestation verification report returned by IAS
+    pub report: Vec<u8>,
+    /// The decoded `X-IASReport-Signature`
+    pub report_sig: Vec<u8>,
+    /// The DER-encoded report signing certificate, which chains up to the trusted CA
+    pub signing_cert: Vec<u8>,
+    /// The SCALE-encoded `DhPubKey` of the enclave
+    pub encrypting_key: Vec<u8>,
+}
+
+impl AttestationBundle {
+    /// Whether the report is DCAP's one, which isn't signed by the attestation service.
+    pub fn is_dcap(&self) -> bool {
+        serde_json::from_slice::<Value>(&self.report)
+            .map(|report| report["attestationType"].as_str() == Some(ATTESTATION_TYPE_DCAP))
+            .unwrap_or(false)
+    }
+}
+
+/// The enclave whose attestation is verified
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedEnclave {
+    pub mr_enclave: [u8; MR_ENCLAVE_SIZE],
+    /// The address of the verifying key, which is registered in the contract
+    pub verifying_address: [u8; VERIFYING_ADDRESS_SIZE],
+    /// The SCALE-encoded `DhPubKey` bound to the report
+    pub encrypting_key: Vec<u8>,
+    pub quote_status: String,
+}
+
+/// Verifies attestation bundles against the trusted CA and the allowlist of MRENCLAVEs.
+#[derive(Debug, Clone)]
+pub struct EnclaveVerifier {
+    trusted_ca: Vec<u8>,
+    allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>,
+    allowed_quote_statuses: Vec<String>,
+    allowed_advisory_ids: Vec<String>,
+    max_report_age: Option<Duration>,
+}
+
+impl EnclaveVerifier {
+    /// A verifier trusting Intel's CA, which accepts only the allowed MRENCLAVEs
+    /// and reports of `OK` status issued within the default max age.
+    pub fn new(allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>) -> Result<Self> {
+        Ok(EnclaveVerifier {
+            trusted_ca: pem_to_der(IAS_REPORT_CA)?,
+            allowed_mrenclaves,
+            allowed_quote_statuses: vec!["OK".to_string()],
+            allowed_advisory_ids: vec![],
+            max_report_age: Some(DEFAULT_MAX_REPORT_AGE),
+        })
+    }
+
+    /// Trust the given CA instead of Intel's, e.g. the test CA of the mock IAS server.
+    pub fn with_trusted_ca(mut self, ca_pem: &str) -> Result<Self> {
+        self.trusted_ca = pem_to_der(ca_pem)?;
+        Ok(self)
+    }
+
+    /// Accept the quote statuses, e.g. `GROUP_OUT_OF_DATE`, if all of the report's advisories are also accepted.
+    pub fn with_quote_statuses(
+        mut self,
+        allowed_quote_statuses: Vec<String>,
+        allowed_advisory_ids: Vec<String>,
+    ) -> Self {
+        self.allowed_quote_statuses = allowed_quote_statuses;
+        self.allowed_advisory_ids = allowed_advisory_ids;
+        self
+    }
+
+    /// `None` accepts reports of any age.
+    pub fn with_max_report_age(mut self, max_report_age: Option<Duration>) -> Self {
+        self.max_report_age = max_report_age;
+        self
+    }
+
+    /// Verify that
+    /// 0. the report isn't DCAP's one, which fails with `DCAP_NOT_SUPPORTED`,
+    /// 1. the report is signed by IAS whose certificate chains up to the trusted CA,
+    /// 2. the report is fresh and its quote status is accepted,
+    /// 3. the enclave's MRENCLAVE is allowed,
+    /// 4. the REPORTDATA binds the bundled encrypting key.
+    pub fn verify(&self, bundle: &AttestationBundle) -> Result<VerifiedEnclave> {
+        ensure!(
+            !self.allowed_mrenclaves.is_empty(),
+            "At least one MRENCLAVE must be allowed"
+        );
+        // DCAP's report has neither a signature nor a signing certificate,
+        // so it must be rejected before they are verified to fail with the clear reason.
+        ensure!(!bundle.is_dcap(), DCAP_NOT_SUPPORTED);
+        self.verify_signature(bundle)?;
+
+        let report: Value = serde_json::from_slice(&bundle.report)?;
+        self.verify_timestamp(json_str(&report, "timestamp")?)?;
+        let quote_status = json_str(&report, "isvEnclaveQuoteStatus")?;
+        self.verify_quote_status(quote_status, &advisory_ids(&report)?)?;
+
+        let quote_body = base64::decode(json_str(&report, "isvEnclaveQuoteBody")?)?;
+        ensure!(
+            quote_body.len() >= REPORT_DATA_OFFSET + REPORT_DATA_SIZE,
+            "Invalid length of isvEnclaveQuoteBody: {}",
+            quote_body.len()
+        );
+
+        let mut mr_enclave = [0u8; MR_ENCLAVE_SIZE];
+        mr_enclave
+            .copy_from_slice(&quote_body[MR_ENCLAVE_OFFSET..MR_ENCLAVE_OFFSET + MR_ENCLAVE_SIZE]);
+        ensure!(
+            self.allowed_mrenclaves.contains(&mr_enclave),
+            "MRENCLAVE {} is not allowed",
+            hex::encode(mr_enclave)
+        );
+
+        let report_data = &quote_body[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + REPORT_DATA_SIZE];
+        let (verifying_address, rest) = report_data.split_at(VERIFYING_ADDRESS_SIZE);
+        let (encrypting_key, padding) = rest.split_at(ENCRYPTING_KEY_SIZE);
+        ensure!(
+            encrypting_key == &bundle.encrypting_key[..],
+            "The encrypting key isn't bound to the attestation report"
+        );
+        ensure!(
+            padding.iter().all(|b| *b == 0),
+            "Invalid padding of REPORTDATA"
+        );
+
+        let mut address = [0u8; VERIFYING_ADDRESS_SIZE];
+        address.copy_from_slice(verifying_address);
+        Ok(VerifiedEnclave {
+            mr_enclave,
+            verifying_address: address,
+            encrypting_key: encrypting_key.to_vec(),
+            quote_status: quote_status.to_string(),
+        })
+    }
+
+    fn verify_signature(&self, bundle: &AttestationBundle) -> Result<()> {
+        let trust_anchors =
+            vec![
+                webpki::trust_anchor_util::cert_der_as_trust_anchor(&self.trusted_ca)
+                    .map_err(|e| anyhow!("Invalid trusted CA: {:?}", e))?,
+            ];
+        let now = webpki::Time::try_from(SystemTime::now()).map_err(|e| anyhow!("{:?}", e))?;
+
+        let signing_cert = webpki::EndEntityCert::from(&bundle.signing_cert)
+            .map_err(|e| anyhow!("Invalid report signing certificate: {:?}", e))?;
+        signing_cert
+            .verify_is_valid_tls_server_cert(
+                SUPPORTED_SIG_ALGS,
+                &webpki::TLSServerTrustAnchors(&trust_anchors),
+                &[&self.trusted_ca[..]],
+                now,
+            )
+            .map_err(|e| anyhow!("The report signing certificate isn't trusted: {:?}", e))?;
+        signing_cert
+            .verify_signature(
+                &webpki::RSA_PKCS1_2048_8192_SHA256,
+                &bundle.report,
+                &bundle.report_sig,
+            )
+            .map_err(|e| anyhow!("Invalid signature of the attestation report: {:?}", e))
+    }
+
+    fn verify_timestamp(&self, timestamp: &str) -> Result<()> {
+        let max_report_age = match self.max_report_age {
+            Some(age) => age,
+            None => return Ok(()),
+        };
+        let issued_at =
+            NaiveDateTime::parse_from_str(timestamp.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S%.f")
+                .map_err(|e| anyhow!("Invalid timestamp {}: {}", timestamp, e))?;
+        let age = Utc::now().naive_utc() - issued_at;
+        ensure!(
+            age.num_seconds() <= max_report_age.as_secs() as i64,
+            "The attestation report is too old: {}",
+            timestamp
+        );
+
+        Ok(())
+    }
+
+    fn verify_quote_status(&self, quote_status: &str, advisory_ids: &[String]) -> Result<()> {
+        ensure!(
+            self.allowed_quote_statuses
+                .iter()
+                .any(|s| s == quote_status),
+            "Invalid Enclave Quote Status: {}",
+            quote_status
+        );
+        if let Some(id) = advisory_ids
+            .iter()
+            .find(|id| !self.allowed_advisory_ids.contains(id))
+        {
+            bail!(
+                "Advisory {} isn't accepted (Enclave Quote Status: {})",
+                id,
+                quote_status
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse comma-separated hex-encoded MRENCLAVEs.
+pub fn parse_mrenclaves(s: &str) -> Result<Vec<[u8; MR_ENCLAVE_SIZE]>> {
+    s.split(',')
+        .map(|e| e.trim())
+        .filter(|e| !e.is_empty())
+        .map(|e| {
+            let v = hex::decode(e)?;
+            ensure!(
+                v.len() == MR_ENCLAVE_SIZE,
+                "MRENCLAVE must be {} bytes",
+                MR_ENCLAVE_SIZE
+            );
+            let mut res = [0u8; MR_ENCLAVE_SIZE];
+            res.copy_from_slice(&v);
+            Ok(res)
+        })
+        .collect()
+}
+
+fn json_str<'a>(report: &'a Value, key: &str) -> Result<&'a str> {
+    report[key]
+        .as_str()
+        .ok_or_else(|| anyhow!("Failed to fetch {} from attestation report", key))
+}
+
+fn advisory_ids(report: &Value) -> Result<Vec<String>> {
+    match &report["advisoryIDs"] {
+        Value::Array(ids) => ids
+            .iter()
+            .map(|id| {
+                id.as_str()
+                    .map(ToString::to_string)
+                    .ok_or_else(|| anyhow!("Invalid advisoryIDs: {}", id))
+            })
+            .collect(),
+        Value::Null => Ok(vec![]),
+        ids => bail!("Invalid advisoryIDs: {}", ids),
+    }
+}
+
+fn pem_to_der(pem: &str) -> Result<Vec<u8>> {
+    let b64: String = pem
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.starts_with("-----"))
+        .collect();
+    base64::decode(&b64).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_ias_server::{MockIasServer, SIGNING_CERT_PEM, TEST_CA_PEM};
+
+    const QUOTE_BODY_SIZE: usize = 432;
+    const MR_ENCLAVE: [u8; MR_ENCLAVE_SIZE] = [3u8; MR_ENCLAVE_SIZE];
+    const VERIFYING_ADDRESS: [u8; VERIFYING_ADDRESS_SIZE] = [5u8; VERIFYING_ADDRESS_SIZE];
+    const ENCRYPTING_KEY: [u8; ENCRYPTING_KEY_SIZE] = [2u8; ENCRYPTING_KEY_SIZE];
+
+    fn fake_quote() -> Vec<u8> {
+        let mut quote = vec![0u8; QUOTE_BODY_SIZE];
+        quote[MR_ENCLAVE_OFFSET..MR_ENCLAVE_OFFSET + MR_ENCLAVE_SIZE].copy_from_slice(&MR_ENCLAVE);
+        let report_data = REPORT_DATA_OFFSET;
+        quote[report_data..report_data + VERIFYING_ADDRESS_SIZE]
+            .copy_from_slice(&VERIFYING_ADDRESS);
+        let encrypting_key = report_data + VERIFYING_ADDRESS_SIZE;
+        quote[encrypting_key..encrypting_key + ENCRYPTING_KEY_SIZE]
+            .copy_from_slice(&ENCRYPTING_KEY);
+        quote
+    }
+
+    fn bundle(server: MockIasServer) -> AttestationBundle {
+        let (report, report_sig) = server.attest(&fake_quote(), None).unwrap();
+        AttestationBundle {
+            report,
+            report_sig,
+            signing_cert: pem_to_der(SIGNING_CERT_PEM).unwrap(),
+            encrypting_key: ENCRYPTING_KEY.to_vec(),
+        }
+    }
+
+    fn verifier() -> EnclaveVerifier {
+        EnclaveVerifier::new(vec![MR_ENCLAVE])
+            .unwrap()
+            .with_trusted_ca(TEST_CA_PEM)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_bundle() {
+        let verified = verifier()
+            .verify(&bundle(MockIasServer::new().unwrap()))
+            .unwrap();
+
+        assert_eq!(verified.mr_enclave, MR_ENCLAVE);
+        assert_eq!(verified.verifying_address, VERIFYING_ADDRESS);
+        assert_eq!(verified.encrypting_key, ENCRYPTING_KEY.to_vec());
+        assert_eq!(verified.quote_status, "OK");
+    }
+
+    #[test]
+    fn test_untrusted_ca() {
+        let verifier = EnclaveVerifier::new(vec![MR_ENCLAVE]).unwrap();
+        assert!(verifier
+            .verify(&bundle(MockIasServer::new().unwrap()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_mrenclave_not_allowed() {
+        let verifier = EnclaveVerifier::new(vec![[4u8; MR_ENCLAVE_SIZE]])
+            .unwrap()
+            .with_trusted_ca(TEST_CA_PEM)
+            .unwrap();
+        assert!(verifier
+            .verify(&bundle(MockIasServer::new().unwrap()))
+            .is_err());
+
+        let verifier = EnclaveVerifier::new(vec![])
+            .unwrap()
+            .with_trusted_ca(TEST_CA_PEM)
+            .unwrap();
+        assert!(verifier
+            .verify(&bundle(MockIasServer::new().unwrap()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_substituted_encrypting_key() {
+        let mut bundle = bundle(MockIasServer::new().unwrap());
+        bundle.encrypting_key = [9u8; ENCRYPTING_KEY_SIZE].to_vec();
+
+        assert!(verifier().verify(&bundle).is_err());
+    }
+
+    #[test]
+    fn test_tampered_report() {
+        let mut tampered = bundle(MockIasServer::new().unwrap());
+        let last = tampered.report.len() - 2;
+        tampered.report[last] ^= 1;
+        assert!(verifier().verify(&tampered).is_err());
+
+        let corrupted = bundle(MockIasServer::new().unwrap().with_corrupted_signature());
+        assert!(verifier().verify(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_quote_status() {
+        let server = MockIasServer::new()
+            .unwrap()
+            .with_quote_status("GROUP_OUT_OF_DATE", vec!["INTEL-SA-00334".to_string()]);
+        let bundle = bundle(server);
+        assert!(verifier().verify(&bundle).is_err());
+
+        let statuses = vec!["OK".to_string(), "GROUP_OUT_OF_DATE".to_string()];
+        let verifier = verifier().with_quote_statuses(statuses.clone(), vec![]);
+        assert!(verifier.verify(&bundle).is_err());
+
+        let verifier = verifier.with_quote_statuses(statuses, vec!["INTEL-SA-00334".to_string()]);
+        assert!(verifier.verify(&bundle).is_ok());
+    }
+
+    #[test]
+    fn test_reject_dcap() {
+        let report = serde_json::json!({
+            "attestationType": ATTESTATION_TYPE_DCAP,
+            "timestamp": "2020-01-01T00:00:00.000000",
+            "isvEnclaveQuoteStatus": "UpToDate",
+            "isvEnclaveQuoteBody": base64::encode(&fake_quote()),
+        });
+        let bundle = AttestationBundle {
+            report: serde_json::to_vec(&report).unwrap(),
+            encrypting_key: ENCRYPTING_KEY.to_vec(),
+            ..Default::default()
+        };
+        assert!(bundle.is_dcap());
+
+        let err = verifier().verify(&bundle).unwrap_err();
+        assert_eq!(err.to_string(), DCAP_NOT_SUPPORTED);
+        assert!(!self::bundle(MockIasServer::new().unwrap()).is_dcap());
+    }
+
+    #[test]
+    fn test_parse_mrenclaves() {
+        let mrenclaves = parse_mrenclaves(&format!(
+            " {}, ,{}",
+            hex::encode(MR_ENCLAVE),
+            hex::encode([4u8; MR_ENCLAVE_SIZE])
+        ))
+        .unwrap();
+        assert_eq!(mrenclaves, vec![MR_ENCLAVE, [4u8; MR_ENCLAVE_SIZE]]);
+        assert!(parse_mrenclaves("0102").is_err());
+    }
+}
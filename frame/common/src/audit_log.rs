@@ -0,0 +1,125 @@
+//! Entries of the enclave's audit log of state transitions.
+//! Each entry contains the hash of the previous one and is signed with the enclave's identity key,
+//! so entries removed, reordered or tampered with by the host are detected by `verify_chain`.
+//! When the enclave is launched again, the new chain is anchored to the last entry of the previous one given by the host,
+//! so the chains of all launches are linked in the log.
+//! The host can give an earlier entry as the anchor to drop entries from the end of a chain,
+//! so the entries should also be compared with the ciphertexts stored in the contract.
+//! The entries only contain hashes of the commands and the updated states, not their values.
+
+use crate::crypto::Sha256;
+use crate::local_anyhow::{anyhow, ensure, Result};
+use crate::localstd::vec::Vec;
+use crate::traits::{Hash256, Keccak256};
+use codec::{Decode, Encode};
+
+pub const AUDIT_HASH_SIZE: usize = 32;
+/// The size of the address-formatted verifying key of the enclave's identity key
+pub const SIGNER_ADDRESS_SIZE: usize = 20;
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Starts from 0 when the enclave is launched
+    pub seq: u64,
+    /// The hash of the previous entry. The first entry of a chain has the hash of the previous chain's last entry,
+    /// or zeros if the log is empty.
+    pub prev_hash: [u8; AUDIT_HASH_SIZE],
+    /// Seconds since the unix epoch, which is the host's time
+    pub timestamp: u64,
+    /// The hash of the command's ciphertext, which identifies the transaction sending it
+    pub ciphertext_hash: [u8; AUDIT_HASH_SIZE],
+    pub call_id: u32,
+    pub updated_states_hash: [u8; AUDIT_HASH_SIZE],
+}
+
+impl AuditEntry {
+    pub fn hash(&self) -> [u8; AUDIT_HASH_SIZE] {
+        Sha256::hash(&self.encode()).as_array()
+    }
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct SignedAuditEntry {
+    pub entry: AuditEntry,
+    /// The recoverable secp256k1 signature of the entry's hash
+    pub sig: Vec<u8>,
+    pub recovery_id: u8,
+}
+
+impl SignedAuditEntry {
+    pub fn new(entry: AuditEntry, sig: Vec<u8>, recovery_id: u8) -> Self {
+        SignedAuditEntry {
+            entry,
+            sig,
+            recovery_id,
+        }
+    }
+
+    /// Recover the verifying address of the enclave which signed the entry.
+    pub fn signer(&self) -> Result<[u8; SIGNER_ADDRESS_SIZE]> {
+        ensure!(self.sig.len() == 64, "Signature must be 64 bytes");
+        let mut sig = [0u8; 64];
+        sig.copy_from_slice(&self.sig);
+
+        let msg = secp256k1::Message::parse(&self.entry.hash());
+        let sig = secp256k1::Signature::parse(&sig);
+        let recovery_id =
+            secp256k1::RecoveryId::parse(self.recovery_id).map_err(|e| anyhow!("{:?}", e))?;
+        let pubkey =
+            secp256k1::recover(&msg, &sig, &recovery_id).map_err(|e| anyhow!("{:?}", e))?;
+
+        let hash: [u8; 32] = pubkey.serialize()[..].keccak256();
+        let mut address = [0u8; SIGNER_ADDRESS_SIZE];
+        address.copy_from_slice(&hash[12..]);
+        Ok(address)
+    }
+}
+
+/// Verify that the entries are chained and signed by the same enclave from sequence 0,
+/// and returns the verifying addresses of the signers, which should be compared with the registered ones.
+/// The chain starts over when the enclave is launched again, and must be anchored to the previous chain's last entry.
+/// Entries dropped from the end of the log or of a chain before the next launch's anchor cannot be detected by the chain.
+pub fn verify_chain(entries: &[SignedAuditEntry]) -> Result<Vec<[u8; SIGNER_ADDRESS_SIZE]>> {
+    let mut signers = Vec::new();
+    let mut prev: Option<(&AuditEntry, [u8; SIGNER_ADDRESS_SIZE])> = None;
+
+    for signed in entries {
+        let entry = &signed.entry;
+        let signer = signed.signer()?;
+        if entry.seq == 0 {
+            let anchor = prev.map_or([0u8; AUDIT_HASH_SIZE], |(prev_entry, _)| prev_entry.hash());
+            ensure!(
+                entry.prev_hash == anchor,
+                "The chain isn't anchored to the previous chain's last entry: chain {}",
+                signers.len()
+            );
+            signers.push(signer);
+        } else {
+            let (prev_entry, prev_signer) = prev.ok_or_else(|| {
+                anyhow!(
+                    "The audit log starts in the middle of a chain: seq {}",
+                    entry.seq
+                )
+            })?;
+            ensure!(
+                entry.seq == prev_entry.seq + 1,
+                "The entries are missing or reordered: seq {} follows {}",
+                entry.seq,
+                prev_entry.seq
+            );
+            ensure!(
+                entry.prev_hash == prev_entry.hash(),
+                "The previous hash doesn't match: seq {}",
+                entry.seq
+            );
+            ensure!(
+                signer == prev_signer,
+                "The entry is signed by another enclave: seq {}",
+                entry.seq
+            );
+        }
+        prev = Some((entry, signer));
+    }
+
+    Ok(signers)
+}
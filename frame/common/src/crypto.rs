@@ -1,17 +1,21 @@
 use crate::local_anyhow::{anyhow, Error};
+#[cfg(feature = "sgx")]
+use crate::localstd::untrusted::time::SystemTimeEx;
 use crate::localstd::{
     cmp::Ordering,
+    convert::TryFrom,
     io::{self, Read, Write},
+    str::FromStr,
     string::String,
+    time::{SystemTime, UNIX_EPOCH},
     vec::Vec,
 };
 use crate::serde::{Deserialize, Serialize};
-use crate::traits::{AccessPolicy, Hash256, IntoVec, StateDecoder};
+use crate::traits::{AccessPolicy, Hash256, IntoVec, Keccak256, StateDecoder};
 use codec::{self, Decode, Encode, Input};
-use ed25519_dalek::{
-    Keypair, PublicKey, SecretKey, Signature, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH,
-    SIGNATURE_LENGTH,
-};
+use ed25519_dalek::{Keypair, PublicKey, Signature, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+#[cfg(feature = "sgx")]
+use ed25519_dalek::{SecretKey, SECRET_KEY_LENGTH};
 #[cfg(feature = "std")]
 use rand::Rng;
 #[cfg(feature = "std")]
@@ -21,31 +25,9 @@ use rand_os::OsRng;
 use sha2::Digest;
 
 const ACCOUNT_ID_SIZE: usize = 20;
-pub const COMMON_SECRET: [u8; SECRET_KEY_LENGTH] = [
-    182, 93, 72, 157, 114, 225, 213, 95, 237, 176, 179, 23, 11, 100, 177, 16, 129, 8, 41, 4, 158,
-    209, 227, 21, 89, 47, 118, 0, 232, 162, 217, 203,
-];
-pub const COMMON_CHALLENGE: [u8; CHALLENGE_SIZE] = [
-    39, 79, 228, 49, 240, 219, 135, 53, 169, 47, 65, 111, 236, 125, 2, 195, 214, 154, 18, 77, 254,
-    135, 35, 77, 36, 45, 164, 254, 64, 8, 169, 238,
-];
-
-lazy_static! {
-    pub static ref COMMON_ACCESS_POLICY: Ed25519ChallengeResponse = {
-        let secret = SecretKey::from_bytes(&COMMON_SECRET).unwrap();
-        let pubkey = PublicKey::from(&secret);
-        let keypair = Keypair {
-            secret,
-            public: pubkey,
-        };
-
-        let sig = keypair.sign(&COMMON_CHALLENGE);
-
-        assert!(keypair.verify(&COMMON_CHALLENGE, &sig).is_ok());
-        Ed25519ChallengeResponse::new(sig, keypair.public, COMMON_CHALLENGE)
-    };
-    pub static ref OWNER_ACCOUNT_ID: AccountId = COMMON_ACCESS_POLICY.account_id();
-}
+/// A reserved account id used as the key of application-wide state such as the owner or the total supply.
+/// No key pair can derive this account id, so it's never used to authenticate anyone.
+pub const GLOBAL_ACCOUNT_ID: AccountId = AccountId([0u8; ACCOUNT_ID_SIZE]);
 
 /// User account_id represents last 20 bytes of digest of user's public key.
 /// A signature verification must return true to generate a user account_id.
@@ -83,21 +65,38 @@ impl From<&AccountId> for web3::types::Address {
     }
 }
 
-impl From<&str> for AccountId {
-    fn from(s: &str) -> Self {
-        let mut res = [0u8; ACCOUNT_ID_SIZE];
-        res.copy_from_slice(&s.as_bytes()[..ACCOUNT_ID_SIZE]);
+/// Parse an account id from either of a hex string, an EIP-55 checksummed address
+/// or a base64-encoded string.
+/// A base64-encoded account id can also start with `0x`, so only a 42-character string is parsed as a prefixed hex.
+impl FromStr for AccountId {
+    type Err = Error;
 
-        Self::from_array(res)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() == ACCOUNT_ID_SIZE * 2 + 2 && (s.starts_with("0x") || s.starts_with("0X")) {
+            return Self::from_checksum_address(&s[2..]);
+        }
+        if s.len() == ACCOUNT_ID_SIZE * 2 && s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Self::from_checksum_address(s);
+        }
+
+        Self::decode_base64_str(s)
     }
 }
 
-impl From<String> for AccountId {
-    fn from(s: String) -> Self {
-        let mut res = [0u8; ACCOUNT_ID_SIZE];
-        res.copy_from_slice(&s.as_bytes()[..ACCOUNT_ID_SIZE]);
+impl TryFrom<&str> for AccountId {
+    type Error = Error;
 
-        Self::from_array(res)
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::from_str(s)
+    }
+}
+
+impl TryFrom<String> for AccountId {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::from_str(&s)
     }
 }
 
@@ -149,6 +148,16 @@ impl AccountId {
         AccountId(res)
     }
 
+    /// Derive an ethereum address from a secp256k1 public key,
+    /// which is the last 20 bytes of the keccak256 hash of the uncompressed key.
+    pub fn from_secp256k1_pubkey(pubkey: &secp256k1::PublicKey) -> Self {
+        let hash = pubkey.serialize()[1..].keccak256();
+        let mut res = [0u8; ACCOUNT_ID_SIZE];
+        res.copy_from_slice(&hash[12..]);
+
+        AccountId(res)
+    }
+
     pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         writer.write_all(&self.0)?;
         Ok(())
@@ -166,14 +175,84 @@ impl AccountId {
     }
 
     #[cfg(feature = "std")]
-    pub fn base64_decode(encoded_str: &str) -> Self {
-        let decoded_vec = base64::decode(encoded_str).expect("Failed to decode base64.");
-        assert_eq!(decoded_vec.len(), ACCOUNT_ID_SIZE);
+    pub fn base64_decode(encoded_str: &str) -> Result<Self, Error> {
+        let decoded_vec = base64::decode(encoded_str)
+            .map_err(|e| anyhow!("Failed to decode base64 account id: {}", e))?;
+        if decoded_vec.len() != ACCOUNT_ID_SIZE {
+            return Err(anyhow!(
+                "account id must be {} bytes, but got {} bytes",
+                ACCOUNT_ID_SIZE,
+                decoded_vec.len()
+            ));
+        }
 
         let mut arr = [0u8; ACCOUNT_ID_SIZE];
         arr.copy_from_slice(&decoded_vec[..]);
 
-        AccountId::from_array(arr)
+        Ok(AccountId::from_array(arr))
+    }
+
+    #[cfg(feature = "std")]
+    fn decode_base64_str(s: &str) -> Result<Self, Error> {
+        Self::base64_decode(s)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn decode_base64_str(s: &str) -> Result<Self, Error> {
+        Err(anyhow!("base64-encoded account id is not supported: {}", s))
+    }
+
+    /// Parse 40 hex characters without `0x` prefix.
+    /// If the characters are mixed-case, the EIP-55 checksum must be valid.
+    pub fn from_checksum_address(hex: &str) -> Result<Self, Error> {
+        if hex.len() != ACCOUNT_ID_SIZE * 2 {
+            return Err(anyhow!(
+                "hex account id must be {} characters, but got {}",
+                ACCOUNT_ID_SIZE * 2,
+                hex.len()
+            ));
+        }
+
+        let mut arr = [0u8; ACCOUNT_ID_SIZE];
+        for (i, chunk) in hex.as_bytes().chunks(2).enumerate() {
+            let hi = hex_value(chunk[0])?;
+            let lo = hex_value(chunk[1])?;
+            arr[i] = (hi << 4) | lo;
+        }
+        let account_id = AccountId::from_array(arr);
+
+        let is_mixed_case = hex.bytes().any(|b| b.is_ascii_lowercase())
+            && hex.bytes().any(|b| b.is_ascii_uppercase());
+        if is_mixed_case && account_id.to_checksum_address()[2..] != *hex {
+            return Err(anyhow!("Invalid EIP-55 checksum: {}", hex));
+        }
+
+        Ok(account_id)
+    }
+
+    /// Encode to an EIP-55 checksummed address with `0x` prefix.
+    pub fn to_checksum_address(&self) -> String {
+        const HEX_CHARS: &[u8] = b"0123456789abcdef";
+
+        let mut lower = Vec::with_capacity(ACCOUNT_ID_SIZE * 2);
+        for b in self.as_bytes() {
+            lower.push(HEX_CHARS[(b >> 4) as usize]);
+            lower.push(HEX_CHARS[(b & 0x0f) as usize]);
+        }
+        let hash = lower[..].keccak256();
+
+        let mut res = String::with_capacity(ACCOUNT_ID_SIZE * 2 + 2);
+        res.push_str("0x");
+        for (i, c) in lower.iter().enumerate() {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                res.push(c.to_ascii_uppercase() as char);
+            } else {
+                res.push(*c as char);
+            }
+        }
+
+        res
     }
 
     pub fn as_bytes(&self) -> &[u8] {
@@ -204,6 +283,15 @@ pub fn rand_assign(rand: &mut [u8]) -> Result<(), Error> {
     Ok(())
 }
 
+fn hex_value(c: u8) -> Result<u8, Error> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(anyhow!("Invalid hex character: {}", c as char)),
+    }
+}
+
 /// Hash digest of sha256 hash function
 #[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
 pub struct Sha256([u8; 32]);
@@ -409,6 +497,501 @@ impl Ed25519ChallengeResponse {
     }
 }
 
+/// An m-of-n access policy which bundles several `Ed25519ChallengeResponse`s over the same challenge.
+/// The account id is derived from the threshold and the sorted key set,
+/// so it doesn't depend on which members actually signed.
+#[derive(Debug, Clone)]
+pub struct MultiSigChallengeResponse {
+    threshold: u32,
+    pubkeys: Vec<PublicKey>,
+    responses: Vec<Ed25519ChallengeResponse>,
+}
+
+impl AccessPolicy for MultiSigChallengeResponse {
+    fn verify(&self) -> Result<(), Error> {
+        self.verify_sigs()
+    }
+
+    fn into_account_id(&self) -> AccountId {
+        Self::account_id_from_keys(self.threshold, &self.pubkeys)
+    }
+}
+
+impl Encode for MultiSigChallengeResponse {
+    fn encode(&self) -> Vec<u8> {
+        let pubkeys: Vec<[u8; PUBLIC_KEY_LENGTH]> =
+            self.pubkeys.iter().map(|e| e.to_bytes()).collect();
+
+        let mut acc = vec![];
+        acc.extend_from_slice(&self.threshold.encode());
+        acc.extend_from_slice(&pubkeys.encode());
+        acc.extend_from_slice(&self.responses.encode());
+
+        acc
+    }
+}
+
+impl Decode for MultiSigChallengeResponse {
+    fn decode<I: Input>(value: &mut I) -> Result<Self, codec::Error> {
+        let threshold = u32::decode(value)?;
+        let pubkeys = Vec::<[u8; PUBLIC_KEY_LENGTH]>::decode(value)?
+            .iter()
+            .map(|e| PublicKey::from_bytes(&e[..]))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "Failed to decode pubkeys of MultiSigChallengeResponse")?;
+        let responses = Vec::<Ed25519ChallengeResponse>::decode(value)?;
+
+        Ok(MultiSigChallengeResponse {
+            threshold,
+            pubkeys,
+            responses,
+        })
+    }
+}
+
+impl MultiSigChallengeResponse {
+    pub fn new(
+        threshold: u32,
+        pubkeys: Vec<PublicKey>,
+        responses: Vec<Ed25519ChallengeResponse>,
+    ) -> Result<Self, Error> {
+        let pubkeys = Self::sort_keys(pubkeys);
+        if threshold == 0 || threshold as usize > pubkeys.len() {
+            return Err(anyhow!(
+                "Invalid threshold {} for {} keys",
+                threshold,
+                pubkeys.len()
+            ));
+        }
+
+        Ok(MultiSigChallengeResponse {
+            threshold,
+            pubkeys,
+            responses,
+        })
+    }
+
+    /// Verify that at least `threshold` distinct members of the key set signed the same challenge.
+    pub fn verify_sigs(&self) -> Result<(), Error> {
+        if self.threshold == 0 || self.threshold as usize > self.pubkeys.len() {
+            return Err(anyhow!("Invalid threshold: {}", self.threshold));
+        }
+        if self.pubkeys.windows(2).any(|w| w[0].as_bytes() >= w[1].as_bytes()) {
+            return Err(anyhow!("Key set must be sorted and must not contain duplicates"));
+        }
+
+        let challenge = self
+            .responses
+            .first()
+            .ok_or_else(|| anyhow!("No responses in MultiSigChallengeResponse"))?
+            .challenge();
+
+        let mut signers: Vec<&PublicKey> = Vec::with_capacity(self.responses.len());
+        for resp in &self.responses {
+            if resp.challenge() != challenge {
+                return Err(anyhow!("All responses must be signed over the same challenge"));
+            }
+            if !self.pubkeys.contains(resp.pubkey()) {
+                return Err(anyhow!("Signer {:?} is not in the key set", resp.pubkey()));
+            }
+            if signers.contains(&resp.pubkey()) {
+                return Err(anyhow!("Duplicated signer: {:?}", resp.pubkey()));
+            }
+            resp.verify_sig()?;
+            signers.push(resp.pubkey());
+        }
+
+        if signers.len() < self.threshold as usize {
+            return Err(anyhow!(
+                "Not enough signatures: {} of {}",
+                signers.len(),
+                self.threshold
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The account id is the last 20 bytes of sha256(threshold || sorted pubkeys).
+    pub fn account_id_from_keys(threshold: u32, pubkeys: &[PublicKey]) -> AccountId {
+        let mut inp = Vec::with_capacity(pubkeys.len() * PUBLIC_KEY_LENGTH);
+        for pubkey in Self::sort_keys(pubkeys.to_vec()) {
+            inp.extend_from_slice(pubkey.as_bytes());
+        }
+        let hash = Sha256::hash_with_u32(&inp, threshold);
+
+        let mut res = [0u8; ACCOUNT_ID_SIZE];
+        res.copy_from_slice(&hash.as_bytes()[12..]);
+        AccountId(res)
+    }
+
+    pub fn account_id(&self) -> AccountId {
+        self.into_account_id()
+    }
+
+    pub fn verified_account_id(&self) -> Result<AccountId, Error> {
+        self.verify_sigs()?;
+        Ok(self.into_account_id())
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn pubkeys(&self) -> &[PublicKey] {
+        &self.pubkeys[..]
+    }
+
+    pub fn responses(&self) -> &[Ed25519ChallengeResponse] {
+        &self.responses[..]
+    }
+
+    fn sort_keys(mut pubkeys: Vec<PublicKey>) -> Vec<PublicKey> {
+        pubkeys.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        pubkeys.dedup();
+        pubkeys
+    }
+}
+
+const DELEGATION_DOMAIN: &[u8] = b"anonify-delegation";
+
+/// A certificate by which a master key delegates access to a short-lived session key.
+/// The delegation is limited to `allowed_call_ids` until `expiry` (unix time in seconds).
+#[derive(Debug, Clone)]
+pub struct DelegationCertificate {
+    master_pubkey: PublicKey,
+    session_pubkey: PublicKey,
+    allowed_call_ids: Vec<u32>,
+    expiry: u64,
+    sig: Signature,
+}
+
+impl Encode for DelegationCertificate {
+    fn encode(&self) -> Vec<u8> {
+        let mut acc = vec![];
+        acc.extend_from_slice(self.master_pubkey.as_bytes());
+        acc.extend_from_slice(self.session_pubkey.as_bytes());
+        acc.extend_from_slice(&self.allowed_call_ids.encode());
+        acc.extend_from_slice(&self.expiry.encode());
+        acc.extend_from_slice(&self.sig.to_bytes());
+
+        acc
+    }
+}
+
+impl Decode for DelegationCertificate {
+    fn decode<I: Input>(value: &mut I) -> Result<Self, codec::Error> {
+        let mut master_buf = [0u8; PUBLIC_KEY_LENGTH];
+        let mut session_buf = [0u8; PUBLIC_KEY_LENGTH];
+        let mut sig_buf = [0u8; SIGNATURE_LENGTH];
+
+        value.read(&mut master_buf)?;
+        value.read(&mut session_buf)?;
+        let allowed_call_ids = Vec::<u32>::decode(value)?;
+        let expiry = u64::decode(value)?;
+        value.read(&mut sig_buf)?;
+
+        let master_pubkey = PublicKey::from_bytes(&master_buf)
+            .map_err(|_| "Failed to decode master pubkey of DelegationCertificate")?;
+        let session_pubkey = PublicKey::from_bytes(&session_buf)
+            .map_err(|_| "Failed to decode session pubkey of DelegationCertificate")?;
+        let sig = Signature::from_bytes(&sig_buf)
+            .map_err(|_| "Failed to decode signature of DelegationCertificate")?;
+
+        Ok(DelegationCertificate {
+            master_pubkey,
+            session_pubkey,
+            allowed_call_ids,
+            expiry,
+            sig,
+        })
+    }
+}
+
+impl DelegationCertificate {
+    /// Issue a certificate signed by the master key pair.
+    pub fn issue(
+        master: &Keypair,
+        session_pubkey: PublicKey,
+        allowed_call_ids: Vec<u32>,
+        expiry: u64,
+    ) -> Self {
+        let msg = Self::signing_msg(&session_pubkey, &allowed_call_ids, expiry);
+        let sig = master.sign(&msg);
+
+        DelegationCertificate {
+            master_pubkey: master.public,
+            session_pubkey,
+            allowed_call_ids,
+            expiry,
+            sig,
+        }
+    }
+
+    pub fn verify_sig(&self) -> Result<(), Error> {
+        let msg = Self::signing_msg(&self.session_pubkey, &self.allowed_call_ids, self.expiry);
+        self.master_pubkey
+            .verify(&msg, &self.sig)
+            .map_err(|e| anyhow!("{:?}", e))?;
+
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expiry
+    }
+
+    pub fn allows(&self, call_id: u32) -> bool {
+        self.allowed_call_ids.contains(&call_id)
+    }
+
+    pub fn master_pubkey(&self) -> &PublicKey {
+        &self.master_pubkey
+    }
+
+    pub fn session_pubkey(&self) -> &PublicKey {
+        &self.session_pubkey
+    }
+
+    pub fn allowed_call_ids(&self) -> &[u32] {
+        &self.allowed_call_ids[..]
+    }
+
+    pub fn expiry(&self) -> u64 {
+        self.expiry
+    }
+
+    fn signing_msg(session_pubkey: &PublicKey, allowed_call_ids: &[u32], expiry: u64) -> Vec<u8> {
+        let mut msg = vec![];
+        msg.extend_from_slice(DELEGATION_DOMAIN);
+        msg.extend_from_slice(session_pubkey.as_bytes());
+        msg.extend_from_slice(&allowed_call_ids.to_vec().encode());
+        msg.extend_from_slice(&expiry.to_be_bytes());
+        msg
+    }
+}
+
+/// An access policy authenticated by a session key on behalf of the master key.
+/// It's treated as the master's account once the delegation chain is verified.
+///
+/// The expiry is checked against the host's clock, because the enclave has no trusted time source.
+/// A malicious host can set its clock back and keep using an expired certificate,
+/// so the expiry only limits honest hosts. The master key has to be rotated to revoke a leaked session key.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DelegatedAccessPolicy {
+    cert: DelegationCertificate,
+    session: Ed25519ChallengeResponse,
+}
+
+impl AccessPolicy for DelegatedAccessPolicy {
+    /// The expiry is checked against the host's time. See the trust assumption above.
+    fn verify(&self) -> Result<(), Error> {
+        self.verify_chain(unix_time_now()?)
+    }
+
+    fn into_account_id(&self) -> AccountId {
+        AccountId::from_pubkey(self.cert.master_pubkey())
+    }
+
+    fn verify_call_id(&self, call_id: u32) -> Result<(), Error> {
+        if !self.cert.allows(call_id) {
+            return Err(anyhow!("call id {} is out of the delegated scope", call_id));
+        }
+
+        Ok(())
+    }
+}
+
+impl DelegatedAccessPolicy {
+    pub fn new(cert: DelegationCertificate, session: Ed25519ChallengeResponse) -> Self {
+        DelegatedAccessPolicy { cert, session }
+    }
+
+    /// Verify the master's certificate, the session's response and the expiry at `now`.
+    pub fn verify_chain(&self, now: u64) -> Result<(), Error> {
+        self.cert.verify_sig()?;
+        if self.cert.is_expired(now) {
+            return Err(anyhow!(
+                "Delegation certificate has expired at {}",
+                self.cert.expiry()
+            ));
+        }
+        if self.session.pubkey() != self.cert.session_pubkey() {
+            return Err(anyhow!(
+                "Session key does not match the delegation certificate"
+            ));
+        }
+        self.session.verify_sig()
+    }
+
+    pub fn cert(&self) -> &DelegationCertificate {
+        &self.cert
+    }
+
+    pub fn session(&self) -> &Ed25519ChallengeResponse {
+        &self.session
+    }
+}
+
+/// Current unix time in seconds.
+/// Note that this comes from the untrusted host when called in the enclave,
+/// so it must not be relied on for security by the enclave.
+fn unix_time_now() -> Result<u64, Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| anyhow!("{:?}", e))?;
+    Ok(now.as_secs())
+}
+
+const SECP256K1_SIGNATURE_SIZE: usize = 64;
+/// EIP-191 prefix for personal_sign. The challenge is always 32 bytes long.
+const ETH_SIGNED_MSG_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n32";
+
+/// A challenge and response authentication parameter signed by an ethereum account.
+/// The challenge is signed according to EIP-191 (`personal_sign`), so that
+/// existing ethereum wallets can be used to access anonify's enclave mem db.
+#[derive(Debug, Clone)]
+pub struct Secp256k1ChallengeResponse {
+    sig: [u8; SECP256K1_SIGNATURE_SIZE],
+    recovery_id: u8,
+    address: AccountId,
+    challenge: [u8; CHALLENGE_SIZE],
+}
+
+impl AccessPolicy for Secp256k1ChallengeResponse {
+    fn verify(&self) -> Result<(), Error> {
+        self.verify_sig()
+    }
+
+    fn into_account_id(&self) -> AccountId {
+        self.address
+    }
+}
+
+impl Encode for Secp256k1ChallengeResponse {
+    fn encode(&self) -> Vec<u8> {
+        let mut acc = vec![];
+        acc.extend_from_slice(&self.sig[..]);
+        acc.push(self.recovery_id);
+        acc.extend_from_slice(self.address.as_bytes());
+        acc.extend_from_slice(&self.challenge[..]);
+
+        acc
+    }
+}
+
+impl Decode for Secp256k1ChallengeResponse {
+    fn decode<I: Input>(value: &mut I) -> Result<Self, codec::Error> {
+        let mut sig = [0u8; SECP256K1_SIGNATURE_SIZE];
+        let mut address_buf = [0u8; ACCOUNT_ID_SIZE];
+        let mut challenge = [0u8; CHALLENGE_SIZE];
+
+        value.read(&mut sig)?;
+        let recovery_id = value.read_byte()?;
+        value.read(&mut address_buf)?;
+        value.read(&mut challenge)?;
+
+        Ok(Secp256k1ChallengeResponse {
+            sig,
+            recovery_id,
+            address: AccountId::from_array(address_buf),
+            challenge,
+        })
+    }
+}
+
+impl Secp256k1ChallengeResponse {
+    /// `recovery_id` accepts both of raw (0 or 1) and ethereum style (27 or 28) `v` values.
+    pub fn new(
+        sig: [u8; SECP256K1_SIGNATURE_SIZE],
+        recovery_id: u8,
+        address: AccountId,
+        challenge: [u8; CHALLENGE_SIZE],
+    ) -> Self {
+        Secp256k1ChallengeResponse {
+            sig,
+            recovery_id,
+            address,
+            challenge,
+        }
+    }
+
+    /// Sign the challenge in the same way as `personal_sign` of ethereum wallets.
+    pub fn sign(
+        secret: &secp256k1::SecretKey,
+        challenge: [u8; CHALLENGE_SIZE],
+    ) -> Result<Self, Error> {
+        let msg = secp256k1::Message::parse(&Self::eth_signed_msg_hash(&challenge));
+        let (sig, recovery_id) = secp256k1::sign(&msg, secret).map_err(|e| anyhow!("{:?}", e))?;
+        let pubkey = secp256k1::PublicKey::from_secret_key(secret);
+
+        Ok(Self::new(
+            sig.serialize(),
+            recovery_id.serialize() + 27,
+            AccountId::from_secp256k1_pubkey(&pubkey),
+            challenge,
+        ))
+    }
+
+    /// keccak256("\x19Ethereum Signed Message:\n32" || challenge)
+    pub fn eth_signed_msg_hash(challenge: &[u8; CHALLENGE_SIZE]) -> [u8; 32] {
+        let mut msg = Vec::with_capacity(ETH_SIGNED_MSG_PREFIX.len() + CHALLENGE_SIZE);
+        msg.extend_from_slice(ETH_SIGNED_MSG_PREFIX);
+        msg.extend_from_slice(&challenge[..]);
+        msg.keccak256()
+    }
+
+    /// Recover the signer's public key and check that it corresponds to the claimed address.
+    pub fn verify_sig(&self) -> Result<(), Error> {
+        let recovered = self.recover_address()?;
+        if recovered != self.address {
+            return Err(anyhow!(
+                "Recovered address {:?} does not match the claimed address {:?}",
+                recovered,
+                self.address
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn recover_address(&self) -> Result<AccountId, Error> {
+        let v = match self.recovery_id {
+            0 | 1 => self.recovery_id,
+            27 | 28 => self.recovery_id - 27,
+            v => return Err(anyhow!("Invalid recovery id: {}", v)),
+        };
+        let recovery_id = secp256k1::RecoveryId::parse(v).map_err(|e| anyhow!("{:?}", e))?;
+        let sig = secp256k1::Signature::parse(&self.sig);
+        let msg = secp256k1::Message::parse(&Self::eth_signed_msg_hash(&self.challenge));
+        let pubkey =
+            secp256k1::recover(&msg, &sig, &recovery_id).map_err(|e| anyhow!("{:?}", e))?;
+
+        Ok(AccountId::from_secp256k1_pubkey(&pubkey))
+    }
+
+    pub fn account_id(&self) -> AccountId {
+        self.address
+    }
+
+    pub fn verified_account_id(&self) -> Result<AccountId, Error> {
+        self.verify_sig()?;
+        Ok(self.address)
+    }
+
+    pub fn sig(&self) -> &[u8] {
+        &self.sig[..]
+    }
+
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+
+    pub fn challenge(&self) -> &[u8] {
+        &self.challenge
+    }
+}
+
 impl<T: IntoVec> IntoVec for Vec<T> {
     fn into_vec(&self) -> Vec<u8> {
         self.iter().fold(vec![], |mut acc, x| {
@@ -433,6 +1016,8 @@ pub struct Ciphertext {
     generation: u32,
     epoch: u32,
     roster_idx: u32,
+    /// The identifier of the TreeKEM cipher suite which encrypted the state
+    cipher_suite: u16,
     encrypted_state: Vec<u8>,
 }
 
@@ -467,11 +1052,18 @@ impl PartialOrd for Ciphertext {
 }
 
 impl Ciphertext {
-    pub fn new(generation: u32, epoch: u32, roster_idx: u32, encrypted_state: Vec<u8>) -> Self {
+    pub fn new(
+        generation: u32,
+        epoch: u32,
+        roster_idx: u32,
+        cipher_suite: u16,
+        encrypted_state: Vec<u8>,
+    ) -> Self {
         Ciphertext {
             generation,
             epoch,
             roster_idx,
+            cipher_suite,
             encrypted_state,
         }
     }
@@ -497,6 +1089,10 @@ impl Ciphertext {
         self.roster_idx
     }
 
+    pub fn cipher_suite(&self) -> u16 {
+        self.cipher_suite
+    }
+
     pub fn encrypted_state_ref(&self) -> &[u8] {
         &self.encrypted_state
     }
@@ -548,12 +1144,19 @@ impl ExportPathSecret {
     }
 }
 
-/// Handshake parameter for exporting outside enclave
+/// Handshake parameter for exporting outside enclave.
+/// It is signed by the sender enclave's identity key so that receiving enclaves can check who produced it.
 #[derive(Clone, Debug, Encode, Decode, Hash, Default)]
 pub struct ExportHandshake {
     prior_epoch: u32,
     roster_idx: u32,
     handshake: Vec<u8>,
+    /// The signature over sha256 of `signing_message()`
+    sig: Vec<u8>,
+    recovery_id: u8,
+    /// The encoded attestation binding the sender's verifying key to its enclave.
+    /// It is empty unless the sender's verifying key hasn't been announced to the group yet.
+    attestation: Vec<u8>,
 }
 
 impl ExportHandshake {
@@ -562,9 +1165,35 @@ impl ExportHandshake {
             prior_epoch,
             roster_idx,
             handshake,
+            sig: vec![],
+            recovery_id: 0,
+            attestation: vec![],
         }
     }
 
+    pub fn set_sig(&mut self, sig: Vec<u8>, recovery_id: u8, attestation: Vec<u8>) {
+        self.sig = sig;
+        self.recovery_id = recovery_id;
+        self.attestation = attestation;
+    }
+
+    /// The message signed by the sender, which doesn't contain the signature itself.
+    pub fn signing_message(&self) -> Vec<u8> {
+        (self.prior_epoch, self.roster_idx, &self.handshake).encode()
+    }
+
+    pub fn sig(&self) -> &[u8] {
+        &self.sig[..]
+    }
+
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+
+    pub fn attestation(&self) -> &[u8] {
+        &self.attestation[..]
+    }
+
     pub fn prior_epoch(&self) -> u32 {
         self.prior_epoch
     }
@@ -577,3 +1206,278 @@ impl ExportHandshake {
         &self.handshake[..]
     }
 }
+
+/// The verified attestation report of an enclave's identity key, which is cached in the enclave
+/// and served to peers and clients.
+/// `refresh_at` and `expires_at` are seconds since the UNIX epoch.
+#[derive(Clone, Debug, Encode, Decode, Default)]
+pub struct AttestedReport {
+    report: Vec<u8>,
+    report_sig: Vec<u8>,
+    signing_cert: Vec<u8>,
+    /// The enclave is attested again after this time.
+    refresh_at: u64,
+    /// The report becomes too old for the attestation policy after this time.
+    expires_at: u64,
+}
+
+impl AttestedReport {
+    pub fn new(
+        report: Vec<u8>,
+        report_sig: Vec<u8>,
+        signing_cert: Vec<u8>,
+        refresh_at: u64,
+        expires_at: u64,
+    ) -> Self {
+        AttestedReport {
+            report,
+            report_sig,
+            signing_cert,
+            refresh_at,
+            expires_at,
+        }
+    }
+
+    pub fn report(&self) -> &[u8] {
+        &self.report[..]
+    }
+
+    pub fn report_sig(&self) -> &[u8] {
+        &self.report_sig[..]
+    }
+
+    pub fn signing_cert(&self) -> &[u8] {
+        &self.signing_cert[..]
+    }
+
+    pub fn refresh_at(&self) -> u64 {
+        self.refresh_at
+    }
+
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    pub fn into_parts(self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        (self.report, self.report_sig, self.signing_cert)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    // The test vector in EIP-55
+    const CHECKSUM_ADDRESS: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+    #[test]
+    fn test_checksum_address_round_trip() {
+        let account_id = AccountId::from_str(CHECKSUM_ADDRESS).unwrap();
+        assert_eq!(account_id.to_checksum_address(), CHECKSUM_ADDRESS);
+
+        let lower = CHECKSUM_ADDRESS.to_lowercase();
+        assert_eq!(AccountId::from_str(&lower).unwrap(), account_id);
+        assert_eq!(AccountId::from_str(&lower[2..]).unwrap(), account_id);
+        assert_eq!(
+            AccountId::try_from(CHECKSUM_ADDRESS.to_string()).unwrap(),
+            account_id
+        );
+    }
+
+    #[test]
+    fn test_bad_checksum_address() {
+        let bad_checksum = CHECKSUM_ADDRESS.replacen("aA", "aa", 1);
+        assert!(AccountId::from_str(&bad_checksum).is_err());
+        assert!(AccountId::from_str(&CHECKSUM_ADDRESS[..41]).is_err());
+        assert!(AccountId::from_str("0x5gAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_err());
+    }
+
+    #[test]
+    fn test_base64_round_trip() {
+        let account_id = AccountId::from_array([7u8; ACCOUNT_ID_SIZE]);
+        let encoded = account_id.base64_encode();
+
+        assert_eq!(AccountId::base64_decode(&encoded).unwrap(), account_id);
+        assert_eq!(AccountId::from_str(&encoded).unwrap(), account_id);
+    }
+
+    #[test]
+    fn test_base64_starting_with_0x() {
+        let mut bytes = [7u8; ACCOUNT_ID_SIZE];
+        bytes[0] = 0xd3;
+        bytes[1] = 0x10;
+        let account_id = AccountId::from_array(bytes);
+        let encoded = account_id.base64_encode();
+        assert!(encoded.starts_with("0x"));
+
+        assert_eq!(AccountId::from_str(&encoded).unwrap(), account_id);
+    }
+
+    #[test]
+    fn test_bad_base64() {
+        assert!(AccountId::from_str("not base64!").is_err());
+        // Valid base64, but not 20 bytes
+        assert!(AccountId::from_str(&base64::encode([7u8; 16])).is_err());
+        assert!(AccountId::base64_decode("").is_err());
+    }
+
+    const DELEGATION_EXPIRY: u64 = 1_000;
+
+    fn gen_keypair() -> Keypair {
+        let mut csprng = OsRng::new().unwrap();
+        Keypair::generate(&mut csprng)
+    }
+
+    fn session_response(session: &Keypair) -> Ed25519ChallengeResponse {
+        let challenge = [3u8; CHALLENGE_SIZE];
+        Ed25519ChallengeResponse::new(session.sign(&challenge), session.public, challenge)
+    }
+
+    #[test]
+    fn test_delegated_access_policy() {
+        let master = gen_keypair();
+        let session = gen_keypair();
+        let cert =
+            DelegationCertificate::issue(&master, session.public, vec![1], DELEGATION_EXPIRY);
+        let policy = DelegatedAccessPolicy::new(cert, session_response(&session));
+
+        assert!(policy.verify_chain(DELEGATION_EXPIRY - 1).is_ok());
+        assert!(policy.verify_call_id(1).is_ok());
+        assert_eq!(
+            policy.into_account_id(),
+            AccountId::from_pubkey(&master.public)
+        );
+    }
+
+    #[test]
+    fn test_delegation_expired() {
+        let master = gen_keypair();
+        let session = gen_keypair();
+        let cert =
+            DelegationCertificate::issue(&master, session.public, vec![1], DELEGATION_EXPIRY);
+        let policy = DelegatedAccessPolicy::new(cert, session_response(&session));
+
+        assert!(policy.verify_chain(DELEGATION_EXPIRY).is_err());
+        assert!(policy.verify_chain(DELEGATION_EXPIRY + 1).is_err());
+    }
+
+    #[test]
+    fn test_delegation_out_of_scope() {
+        let master = gen_keypair();
+        let session = gen_keypair();
+        let cert =
+            DelegationCertificate::issue(&master, session.public, vec![1], DELEGATION_EXPIRY);
+        let policy = DelegatedAccessPolicy::new(cert, session_response(&session));
+
+        assert!(policy.verify_call_id(2).is_err());
+
+        // The scope can't be widened after the master signed the certificate.
+        let mut widened = policy.cert().clone();
+        widened.allowed_call_ids.push(2);
+        let policy = DelegatedAccessPolicy::new(widened, session_response(&session));
+        assert!(policy.verify_chain(DELEGATION_EXPIRY - 1).is_err());
+    }
+
+    #[test]
+    fn test_delegation_other_session_key() {
+        let master = gen_keypair();
+        let session = gen_keypair();
+        let other_session = gen_keypair();
+        let cert =
+            DelegationCertificate::issue(&master, session.public, vec![1], DELEGATION_EXPIRY);
+        let policy = DelegatedAccessPolicy::new(cert, session_response(&other_session));
+
+        assert!(policy.verify_chain(DELEGATION_EXPIRY - 1).is_err());
+    }
+
+    #[test]
+    fn test_delegation_forged_master_sig() {
+        let master = gen_keypair();
+        let attacker = gen_keypair();
+        let session = gen_keypair();
+        // A certificate signed by the attacker's key which claims to be issued by the master
+        let mut forged =
+            DelegationCertificate::issue(&attacker, session.public, vec![1], DELEGATION_EXPIRY);
+        forged.master_pubkey = master.public;
+        let policy = DelegatedAccessPolicy::new(forged, session_response(&session));
+
+        assert_eq!(
+            policy.into_account_id(),
+            AccountId::from_pubkey(&master.public)
+        );
+        assert!(policy.verify_chain(DELEGATION_EXPIRY - 1).is_err());
+    }
+
+    // The address of the secret key 1
+    const SECRET_KEY_ONE_ADDRESS: &str = "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf";
+
+    fn secp256k1_secret(last_byte: u8) -> secp256k1::SecretKey {
+        let mut secret = [0u8; 32];
+        secret[31] = last_byte;
+        secp256k1::SecretKey::parse(&secret).unwrap()
+    }
+
+    #[test]
+    fn test_secp256k1_challenge_response() {
+        let challenge = [5u8; CHALLENGE_SIZE];
+        let policy = Secp256k1ChallengeResponse::sign(&secp256k1_secret(1), challenge).unwrap();
+        let expected = AccountId::from_str(SECRET_KEY_ONE_ADDRESS).unwrap();
+
+        assert!(policy.verify().is_ok());
+        assert_eq!(policy.into_account_id(), expected);
+        assert_eq!(policy.verified_account_id().unwrap(), expected);
+
+        // A raw recovery id is accepted as well as an ethereum style `v`
+        let raw = Secp256k1ChallengeResponse::new(
+            policy.sig,
+            policy.recovery_id() - 27,
+            expected,
+            challenge,
+        );
+        assert!(raw.verify().is_ok());
+
+        let decoded = Secp256k1ChallengeResponse::decode(&mut &policy.encode()[..]).unwrap();
+        assert_eq!(decoded.verified_account_id().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_secp256k1_challenge_response_wrong_key() {
+        let challenge = [5u8; CHALLENGE_SIZE];
+        let other = Secp256k1ChallengeResponse::sign(&secp256k1_secret(2), challenge).unwrap();
+        assert_ne!(
+            other.into_account_id(),
+            AccountId::from_str(SECRET_KEY_ONE_ADDRESS).unwrap()
+        );
+
+        // Signed by another key, but claiming the address of the secret key 1
+        let policy = Secp256k1ChallengeResponse::new(
+            other.sig,
+            other.recovery_id(),
+            AccountId::from_str(SECRET_KEY_ONE_ADDRESS).unwrap(),
+            challenge,
+        );
+        assert!(policy.verify().is_err());
+        assert!(policy.verified_account_id().is_err());
+    }
+
+    #[test]
+    fn test_secp256k1_challenge_response_wrong_challenge() {
+        let signed =
+            Secp256k1ChallengeResponse::sign(&secp256k1_secret(1), [5u8; CHALLENGE_SIZE]).unwrap();
+        let policy = Secp256k1ChallengeResponse::new(
+            signed.sig,
+            signed.recovery_id(),
+            signed.into_account_id(),
+            [6u8; CHALLENGE_SIZE],
+        );
+        assert!(policy.verify().is_err());
+
+        let bad_recovery_id = Secp256k1ChallengeResponse::new(
+            signed.sig,
+            29,
+            signed.into_account_id(),
+            [5u8; CHALLENGE_SIZE],
+        );
+        assert!(bad_recovery_id.verify().is_err());
+    }
+}
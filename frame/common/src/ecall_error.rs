@@ -0,0 +1,109 @@
+//! Errors returned from the enclave to the host through the ecall's output buffer.
+
+use crate::local_anyhow;
+use crate::localstd::{
+    fmt,
+    string::{String, ToString},
+};
+use codec::{Decode, Encode};
+
+/// The message of the errors not classified by the enclave
+pub const INTERNAL_ERROR_MESSAGE: &str = "Internal error in the enclave";
+
+/// Errors not classified by the enclave are `Internal`.
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcallErrorKind {
+    /// The input can't be decoded or is malformed.
+    InvalidInput,
+    /// The input is rejected by the engine's policy, e.g. the access policy's signature is invalid.
+    PolicyViolation,
+    /// The command or the requested resource doesn't exist in the enclave.
+    NotFound,
+    Internal,
+}
+
+/// Framework-defined codes of `EcallError`. Applications can define other codes from `APP_CODE_BASE`.
+pub mod codes {
+    pub const UNSPECIFIED: u32 = 0;
+    pub const DECODE_INPUT: u32 = 1;
+    pub const UNREGISTERED_COMMAND: u32 = 2;
+    pub const NO_PENDING_OUTPUT: u32 = 3;
+    pub const TOO_MANY_PENDING_OUTPUTS: u32 = 4;
+    pub const APP_CODE_BASE: u32 = 1000;
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct EcallError {
+    kind: EcallErrorKind,
+    /// Identifies the error within its kind, e.g. for clients to handle it.
+    code: u32,
+    message: String,
+}
+
+impl EcallError {
+    pub fn new(kind: EcallErrorKind, message: impl ToString) -> Self {
+        EcallError {
+            kind,
+            code: codes::UNSPECIFIED,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn with_code(mut self, code: u32) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// The `EcallError` in the error's chain, or an `Internal` error.
+    /// The message of an `Internal` error doesn't contain the error's chain,
+    /// which may reveal the enclave's internal details to the host.
+    pub fn from_anyhow(err: &local_anyhow::Error) -> Self {
+        Self::find(err)
+            .cloned()
+            .unwrap_or_else(|| EcallError::new(EcallErrorKind::Internal, INTERNAL_ERROR_MESSAGE))
+    }
+
+    /// The `EcallError` attached to the error as its context or in the error's chain.
+    pub fn find(err: &local_anyhow::Error) -> Option<&EcallError> {
+        err.downcast_ref::<EcallError>()
+            .or_else(|| err.chain().find_map(|e| e.downcast_ref::<EcallError>()))
+    }
+
+    /// Truncate the message so that the encoded error fits in `max_len` bytes.
+    /// Returns `None` if it doesn't fit even with the empty message.
+    pub fn truncated(mut self, max_len: usize) -> Option<Self> {
+        while self.encode().len() > max_len {
+            if self.message.is_empty() {
+                return None;
+            }
+            let excess = self.encode().len() - max_len;
+            let mut new_len = self.message.len().saturating_sub(excess);
+            while !self.message.is_char_boundary(new_len) {
+                new_len -= 1;
+            }
+            self.message.truncate(new_len);
+        }
+
+        Some(self)
+    }
+
+    pub fn kind(&self) -> EcallErrorKind {
+        self.kind
+    }
+
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for EcallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} (code: {}): {}", self.kind, self.code, self.message)
+    }
+}
+
+impl crate::localstd::error::Error for EcallError {}
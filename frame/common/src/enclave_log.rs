@@ -0,0 +1,35 @@
+//! Log records sent from the enclave to the host's logger through `ocall_log`.
+
+use crate::localstd::{fmt, string::String, vec::Vec};
+use codec::{Decode, Encode};
+
+/// The same levels as the `log` crate's, which isn't shared by the enclave and the host.
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    /// The module path in the enclave
+    pub target: String,
+    pub message: String,
+    /// Key-value pairs appended to the message. Sensitive values are redacted before they leave the enclave.
+    pub fields: Vec<(String, String)>,
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for (key, value) in &self.fields {
+            write!(f, " {}={}", key, value)?;
+        }
+
+        Ok(())
+    }
+}
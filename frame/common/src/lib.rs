@@ -14,11 +14,13 @@ use serde_sgx as serde;
 use serde_std as serde;
 #[cfg(feature = "sgx")]
 use sgx_anyhow as local_anyhow;
-#[macro_use]
-extern crate lazy_static;
 
+pub mod audit_log;
 pub mod crypto;
+pub mod ecall_error;
+pub mod enclave_log;
 pub mod state_types;
 pub mod traits;
 
+pub use ecall_error::{EcallError, EcallErrorKind};
 pub use traits::*;
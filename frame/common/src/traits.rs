@@ -11,6 +11,12 @@ pub trait AccessPolicy: Encode + Decode + Clone + Debug {
     fn verify(&self) -> Result<()>;
 
     fn into_account_id(&self) -> AccountId;
+
+    /// Check whether this policy is permitted to invoke the given call.
+    /// Most policies are not scoped, so every call is allowed by default.
+    fn verify_call_id(&self, _call_id: u32) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait EcallInput {}
@@ -1,11 +1,53 @@
-#![no_std]
+#![cfg_attr(feature = "sgx", no_std)]
+#[cfg(feature = "sgx")]
 #[macro_use]
 extern crate sgx_tstd as std;
+#[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "sgx")]
+extern crate anyhow_sgx as anyhow;
+#[cfg(feature = "std")]
+extern crate anyhow_std as anyhow;
+// Re-exported for the macros expanded in enclave crates
+#[cfg(feature = "sgx")]
+pub extern crate log_sgx as log;
+#[cfg(feature = "std")]
+pub extern crate log_std as log;
+#[cfg(feature = "std")]
+extern crate sgx_types_std as sgx_types;
+#[cfg(feature = "sgx")]
+extern crate thiserror_sgx as thiserror;
+#[cfg(feature = "std")]
+extern crate thiserror_std as thiserror;
+
+#[cfg(all(feature = "std", not(debug_assertions)))]
+compile_error!("The in-process enclave must not be built in release builds");
+
+#[cfg(all(feature = "log-unredacted", not(debug_assertions)))]
+compile_error!("Sensitive fields of the enclave's logs must not be logged in release builds");
 
 pub mod engine;
 mod error;
+pub mod logger;
 pub mod ocalls;
+pub mod pending_output;
 mod register;
+pub mod report;
 
 pub use crate::engine::*;
 pub use crate::error::FrameEnclaveError as Error;
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use std::prelude::v1::*;
+    use test_utils::*;
+
+    pub fn run_tests() -> bool {
+        check_all_passed!(
+            logger::tests::run_tests(),
+            pending_output::tests::run_tests(),
+        )
+    }
+}
@@ -0,0 +1,196 @@
+//! A logger forwarding the enclave's records to the host's `log` crate through `ocall_log`.
+//! Both the `log` crate's records and the structured ones built with `StructuredRecord` are forwarded.
+//! It's configured by
+//! - `ENCLAVE_LOG_LEVEL`: the max level of the forwarded records (default: `info`)
+//!
+//! Sensitive fields are redacted unless the enclave is built with the `log-unredacted` feature, which is only allowed in debug builds.
+//! It isn't configured at runtime, because the enclave's environment variables are given by the untrusted host.
+
+use crate::ocalls::ocall_log;
+use codec::Encode;
+use frame_common::enclave_log::{LogLevel, LogRecord};
+use frame_types::UntrustedStatus;
+use log::Level;
+#[cfg(feature = "sgx")]
+use log::{LevelFilter, Log, Metadata, Record};
+use sgx_types::sgx_status_t;
+#[cfg(feature = "sgx")]
+use std::{env, str::FromStr};
+use std::{
+    fmt::Display,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Replaces the values of sensitive fields
+pub const REDACTED: &str = "[REDACTED]";
+const REDACT: bool = !cfg!(feature = "log-unredacted");
+#[cfg(feature = "sgx")]
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+#[cfg(feature = "sgx")]
+static LOGGER: EnclaveLogger = EnclaveLogger;
+
+#[cfg(feature = "sgx")]
+lazy_static! {
+    static ref MAX_LEVEL: LevelFilter = env::var("ENCLAVE_LOG_LEVEL")
+        .ok()
+        .and_then(|level| LevelFilter::from_str(&level).ok())
+        .unwrap_or(DEFAULT_LEVEL);
+}
+
+/// Set the enclave's logger. It's called on every ecall, and only the first call sets it.
+#[cfg(feature = "sgx")]
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(*MAX_LEVEL);
+    }
+}
+
+/// In-process, the enclave shares the `log` crate with the host, whose logger is kept as it is.
+#[cfg(feature = "std")]
+pub fn init() {}
+
+#[cfg(feature = "sgx")]
+struct EnclaveLogger;
+
+#[cfg(feature = "sgx")]
+impl Log for EnclaveLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            send(&LogRecord {
+                level: log_level(record.level()),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+                fields: vec![],
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// A record with key-value fields, e.g.
+/// `StructuredRecord::new(Level::Info, module_path!(), "Processed a handshake").field("epoch", epoch).log()`
+#[derive(Debug, Clone)]
+pub struct StructuredRecord {
+    level: Level,
+    target: String,
+    message: String,
+    /// The keys, the values and whether they're sensitive
+    fields: Vec<(String, String, bool)>,
+}
+
+impl StructuredRecord {
+    pub fn new(level: Level, target: &str, message: impl ToString) -> Self {
+        StructuredRecord {
+            level,
+            target: target.to_string(),
+            message: message.to_string(),
+            fields: vec![],
+        }
+    }
+
+    pub fn field(mut self, key: &str, value: impl Display) -> Self {
+        self.fields
+            .push((key.to_string(), value.to_string(), false));
+        self
+    }
+
+    /// The value is redacted unless the enclave is built with the `log-unredacted` feature.
+    pub fn sensitive(mut self, key: &str, value: impl Display) -> Self {
+        self.fields.push((key.to_string(), value.to_string(), true));
+        self
+    }
+
+    pub fn log(self) {
+        if self.level <= log::max_level() {
+            send(&self.into_log_record(REDACT));
+        }
+    }
+
+    fn into_log_record(self, redact: bool) -> LogRecord {
+        let fields = self
+            .fields
+            .into_iter()
+            .map(|(key, value, sensitive)| {
+                if sensitive && redact {
+                    (key, REDACTED.to_string())
+                } else {
+                    (key, value)
+                }
+            })
+            .collect();
+
+        LogRecord {
+            level: log_level(self.level),
+            target: self.target,
+            message: self.message,
+            fields,
+        }
+    }
+}
+
+fn log_level(level: Level) -> LogLevel {
+    match level {
+        Level::Error => LogLevel::Error,
+        Level::Warn => LogLevel::Warn,
+        Level::Info => LogLevel::Info,
+        Level::Debug => LogLevel::Debug,
+        Level::Trace => LogLevel::Trace,
+    }
+}
+
+fn send(record: &LogRecord) {
+    let mut rt = UntrustedStatus::default();
+    let record = record.encode();
+
+    let status = unsafe {
+        ocall_log(
+            &mut rt as *mut UntrustedStatus,
+            record.as_ptr(),
+            record.len(),
+        )
+    };
+    // The failure can't be logged through the host's logger.
+    if status != sgx_status_t::SGX_SUCCESS || rt.is_err() {
+        println!(
+            "Failed to send the log record to the host: status: {:?}, untrusted status: {:?}",
+            status, rt
+        );
+    }
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_redact_sensitive_fields,)
+    }
+
+    fn test_redact_sensitive_fields() {
+        let record = StructuredRecord::new(Level::Warn, "test", "Processed a handshake")
+            .field("epoch", 3)
+            .sensitive("path_secret_id", "0a1b");
+
+        let redacted = record.clone().into_log_record(true);
+        assert_eq!(redacted.level, LogLevel::Warn);
+        assert_eq!(
+            redacted.to_string(),
+            format!("Processed a handshake epoch=3 path_secret_id={}", REDACTED)
+        );
+
+        let unredacted = record.into_log_record(false);
+        assert_eq!(
+            unredacted.to_string(),
+            "Processed a handshake epoch=3 path_secret_id=0a1b"
+        );
+    }
+}
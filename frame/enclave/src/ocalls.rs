@@ -1,11 +1,22 @@
-use crate::error::{FrameEnclaveError, Result};
+use crate::{
+    error::{FrameEnclaveError, Result},
+    report::verify_report,
+};
 use anyhow::anyhow;
-use codec::Decode;
-use frame_common::crypto::{ExportPathSecret, EXPORT_ID_SIZE, EXPORT_PATH_SECRET_SIZE};
+use codec::{Decode, Encode};
+use frame_common::{
+    audit_log::SignedAuditEntry,
+    crypto::{rand_assign, ExportPathSecret, EXPORT_ID_SIZE, EXPORT_PATH_SECRET_SIZE},
+};
 use frame_types::UntrustedStatus;
 use sgx_types::*;
+use sha2::{Digest, Sha256};
 use std::vec::Vec;
 
+#[cfg(feature = "std")]
+pub use self::in_process::*;
+
+#[cfg(feature = "sgx")]
 extern "C" {
     pub fn ocall_import_path_secret(
         retval: *mut UntrustedStatus,
@@ -15,6 +26,32 @@ extern "C" {
         id_len: usize,
     ) -> sgx_status_t;
 }
+#[cfg(feature = "sgx")]
+extern "C" {
+    pub fn ocall_log(
+        retval: *mut UntrustedStatus,
+        record: *const u8,
+        record_len: usize,
+    ) -> sgx_status_t;
+}
+#[cfg(feature = "sgx")]
+extern "C" {
+    pub fn ocall_append_audit_log(
+        retval: *mut UntrustedStatus,
+        entry: *const u8,
+        entry_len: usize,
+    ) -> sgx_status_t;
+}
+#[cfg(feature = "sgx")]
+extern "C" {
+    pub fn ocall_last_audit_log_entry(
+        retval: *mut UntrustedStatus,
+        entry: *mut u8,
+        max_len: usize,
+        entry_len: *mut usize,
+    ) -> sgx_status_t;
+}
+#[cfg(feature = "sgx")]
 extern "C" {
     pub fn ocall_sgx_init_quote(
         retval: *mut UntrustedStatus,
@@ -22,6 +59,7 @@ extern "C" {
         ret_gid: *mut sgx_epid_group_id_t,
     ) -> sgx_status_t;
 }
+#[cfg(feature = "sgx")]
 extern "C" {
     pub fn ocall_get_quote(
         retval: *mut UntrustedStatus,
@@ -37,12 +75,31 @@ extern "C" {
         p_quote_len: *mut u32,
     ) -> sgx_status_t;
 }
+#[cfg(feature = "sgx")]
+extern "C" {
+    pub fn ocall_qe_get_target_info(
+        retval: *mut UntrustedStatus,
+        ret_ti: *mut sgx_target_info_t,
+    ) -> sgx_status_t;
+}
+#[cfg(feature = "sgx")]
+extern "C" {
+    pub fn ocall_qe_get_quote(
+        retval: *mut UntrustedStatus,
+        report: *const sgx_report_t,
+        p_quote: *mut u8,
+        maxlen: u32,
+        p_quote_len: *mut u32,
+    ) -> sgx_status_t;
+}
+#[cfg(feature = "sgx")]
 extern "C" {
     pub fn ocall_get_ias_socket(
         retval: *mut UntrustedStatus,
         ret_fd: *mut ::std::os::raw::c_int,
     ) -> sgx_status_t;
 }
+#[cfg(feature = "sgx")]
 extern "C" {
     pub fn ocall_get_update_info(
         retval: *mut UntrustedStatus,
@@ -52,6 +109,129 @@ extern "C" {
     ) -> sgx_status_t;
 }
 
+/// In-process, the host's ocalls are called directly instead of through the edge routines.
+#[cfg(feature = "std")]
+mod in_process {
+    use frame_host::ocalls as untrusted;
+    use frame_types::UntrustedStatus;
+    use sgx_types::*;
+
+    pub unsafe fn ocall_import_path_secret(
+        retval: *mut UntrustedStatus,
+        path_secret: *mut u8,
+        ps_len: usize,
+        id: *const u8,
+        id_len: usize,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_import_path_secret(path_secret, ps_len, id, id_len);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    pub unsafe fn ocall_log(
+        retval: *mut UntrustedStatus,
+        record: *const u8,
+        record_len: usize,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_log(record, record_len);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    pub unsafe fn ocall_append_audit_log(
+        retval: *mut UntrustedStatus,
+        entry: *const u8,
+        entry_len: usize,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_append_audit_log(entry, entry_len);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    pub unsafe fn ocall_last_audit_log_entry(
+        retval: *mut UntrustedStatus,
+        entry: *mut u8,
+        max_len: usize,
+        entry_len: *mut usize,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_last_audit_log_entry(entry, max_len, entry_len);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    pub unsafe fn ocall_sgx_init_quote(
+        retval: *mut UntrustedStatus,
+        ret_ti: *mut sgx_target_info_t,
+        ret_gid: *mut sgx_epid_group_id_t,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_sgx_init_quote(ret_ti, ret_gid);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn ocall_get_quote(
+        retval: *mut UntrustedStatus,
+        p_sigrl: *const u8,
+        sigrl_len: u32,
+        report: *const sgx_report_t,
+        quote_type: sgx_quote_sign_type_t,
+        p_spid: *const sgx_spid_t,
+        p_nonce: *const sgx_quote_nonce_t,
+        p_qe_report: *mut sgx_report_t,
+        p_quote: *mut sgx_quote_t,
+        maxlen: u32,
+        p_quote_len: *mut u32,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_get_quote(
+            p_sigrl,
+            sigrl_len,
+            report,
+            quote_type,
+            p_spid,
+            p_nonce,
+            p_qe_report,
+            p_quote as *mut u8,
+            maxlen,
+            p_quote_len,
+        );
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    pub unsafe fn ocall_qe_get_target_info(
+        retval: *mut UntrustedStatus,
+        ret_ti: *mut sgx_target_info_t,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_qe_get_target_info(ret_ti);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    pub unsafe fn ocall_qe_get_quote(
+        retval: *mut UntrustedStatus,
+        report: *const sgx_report_t,
+        p_quote: *mut u8,
+        maxlen: u32,
+        p_quote_len: *mut u32,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_qe_get_quote(report, p_quote, maxlen, p_quote_len);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    pub unsafe fn ocall_get_ias_socket(
+        retval: *mut UntrustedStatus,
+        ret_fd: *mut ::std::os::raw::c_int,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_get_ias_socket(ret_fd);
+        sgx_status_t::SGX_SUCCESS
+    }
+
+    #[allow(non_snake_case)]
+    pub unsafe fn ocall_get_update_info(
+        retval: *mut UntrustedStatus,
+        platformBlob: *mut sgx_platform_info_t,
+        enclaveTrusted: i32,
+        update_info: *mut sgx_update_info_bit_t,
+    ) -> sgx_status_t {
+        *retval = untrusted::ocall_get_update_info(platformBlob, enclaveTrusted, update_info);
+        sgx_status_t::SGX_SUCCESS
+    }
+}
+
 pub fn import_path_secret(id: &[u8]) -> anyhow::Result<ExportPathSecret> {
     let mut id_arr = [0u8; EXPORT_ID_SIZE];
     id_arr.copy_from_slice(&id);
@@ -91,6 +271,65 @@ fn inner_import_path_secret(id: [u8; EXPORT_ID_SIZE]) -> Result<ExportPathSecret
     Ok(exported_path_secret)
 }
 
+/// Append the signed entry to the audit log kept by the host.
+pub fn append_audit_log(entry: &SignedAuditEntry) -> Result<()> {
+    let mut rt = UntrustedStatus::default();
+    let entry = entry.encode();
+
+    let status = unsafe {
+        ocall_append_audit_log(&mut rt as *mut UntrustedStatus, entry.as_ptr(), entry.len())
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(FrameEnclaveError::SgxError { err: status });
+    }
+    if rt.is_err() {
+        return Err(FrameEnclaveError::UntrustedError {
+            status: rt,
+            function: "ocall_append_audit_log",
+        });
+    }
+
+    Ok(())
+}
+
+/// Get the last entry of the audit log kept by the host, or `None` if the log is empty.
+pub fn last_audit_log_entry() -> Result<Option<SignedAuditEntry>> {
+    const ENTRY_BUF_LEN: usize = 256;
+    let mut rt = UntrustedStatus::default();
+    let mut buf = [0u8; ENTRY_BUF_LEN];
+    let mut entry_len = 0;
+
+    let status = unsafe {
+        ocall_last_audit_log_entry(
+            &mut rt as *mut UntrustedStatus,
+            buf.as_mut_ptr(),
+            ENTRY_BUF_LEN,
+            &mut entry_len as *mut usize,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(FrameEnclaveError::SgxError { err: status });
+    }
+    if rt.is_err() {
+        return Err(FrameEnclaveError::UntrustedError {
+            status: rt,
+            function: "ocall_last_audit_log_entry",
+        });
+    }
+    if entry_len > ENTRY_BUF_LEN {
+        return Err(anyhow!("The audit log entry is too large: {}", entry_len).into());
+    }
+    if entry_len == 0 {
+        return Ok(None);
+    }
+
+    SignedAuditEntry::decode(&mut &buf[..entry_len])
+        .map(Some)
+        .map_err(FrameEnclaveError::CodecError)
+}
+
 pub fn get_ias_socket() -> Result<i32> {
     let mut rt = UntrustedStatus::default();
     let mut ias_sock: i32 = 0;
@@ -137,11 +376,15 @@ pub fn sgx_init_quote() -> Result<sgx_target_info_t> {
     Ok(target_info)
 }
 
+/// Get an EPID quote, which is bound to the request by the quoting enclave's report.
 pub fn get_quote(report: sgx_report_t, spid: &sgx_spid_t) -> Result<Vec<u8>> {
     const RET_QUOTE_BUF_LEN: u32 = 2048;
     let mut quote_len: u32 = 0;
     let mut rt = UntrustedStatus::default();
     let mut quote = vec![0u8; RET_QUOTE_BUF_LEN as usize];
+    let mut nonce = sgx_quote_nonce_t::default();
+    rand_assign(&mut nonce.rand)?;
+    let mut qe_report = sgx_report_t::default();
 
     let status = unsafe {
         ocall_get_quote(
@@ -151,8 +394,8 @@ pub fn get_quote(report: sgx_report_t, spid: &sgx_spid_t) -> Result<Vec<u8>> {
             &report as *const sgx_report_t,
             sgx_quote_sign_type_t::SGX_UNLINKABLE_SIGNATURE, // quote_type
             spid as *const sgx_spid_t,                       // p_spid
-            std::ptr::null(),                                // p_nonce
-            std::ptr::null_mut(),                            // p_qe_report
+            &nonce as *const sgx_quote_nonce_t,              // p_nonce
+            &mut qe_report as *mut sgx_report_t,             // p_qe_report
             quote.as_mut_ptr() as *mut sgx_quote_t,
             RET_QUOTE_BUF_LEN, // maxlen
             &mut quote_len as *mut u32,
@@ -168,6 +411,89 @@ pub fn get_quote(report: sgx_report_t, spid: &sgx_spid_t) -> Result<Vec<u8>> {
             function: "ocall_get_quote",
         });
     }
+    if quote_len > RET_QUOTE_BUF_LEN {
+        return Err(anyhow!("The quote is too large: {}", quote_len).into());
+    }
+
+    let _ = quote.split_off(quote_len as usize);
+    verify_qe_report(&qe_report, &nonce, &quote)?;
+    Ok(quote)
+}
+
+/// The quoting enclave's report must be targeted to this enclave,
+/// and its REPORTDATA must be SHA256(nonce || quote) followed by zeros, so that the host can't replace the quote.
+fn verify_qe_report(
+    qe_report: &sgx_report_t,
+    nonce: &sgx_quote_nonce_t,
+    quote: &[u8],
+) -> Result<()> {
+    verify_report(qe_report)?;
+
+    let mut hasher = Sha256::new();
+    hasher.input(&nonce.rand[..]);
+    hasher.input(quote);
+    let report_data = &qe_report.body.report_data.d;
+    if report_data[..32] != hasher.result()[..] || report_data[32..].iter().any(|b| *b != 0) {
+        return Err(anyhow!("The quoting enclave's report doesn't match the quote").into());
+    }
+
+    Ok(())
+}
+
+/// Get the target info of DCAP's quoting enclave.
+pub fn qe_get_target_info() -> Result<sgx_target_info_t> {
+    let mut rt = UntrustedStatus::default();
+    let mut target_info = sgx_target_info_t::default();
+
+    let status = unsafe {
+        ocall_qe_get_target_info(
+            &mut rt as *mut UntrustedStatus,
+            &mut target_info as *mut sgx_target_info_t,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(FrameEnclaveError::SgxError { err: status });
+    }
+    if rt.is_err() {
+        return Err(FrameEnclaveError::UntrustedError {
+            status: rt,
+            function: "ocall_qe_get_target_info",
+        });
+    }
+
+    Ok(target_info)
+}
+
+/// Get a DCAP ECDSA quote, which includes the PCK certificate chain.
+pub fn qe_get_quote(report: sgx_report_t) -> Result<Vec<u8>> {
+    const RET_QUOTE_BUF_LEN: u32 = 8192;
+    let mut quote_len: u32 = 0;
+    let mut rt = UntrustedStatus::default();
+    let mut quote = vec![0u8; RET_QUOTE_BUF_LEN as usize];
+
+    let status = unsafe {
+        ocall_qe_get_quote(
+            &mut rt as *mut UntrustedStatus,
+            &report as *const sgx_report_t,
+            quote.as_mut_ptr(),
+            RET_QUOTE_BUF_LEN,
+            &mut quote_len as *mut u32,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(FrameEnclaveError::SgxError { err: status });
+    }
+    if rt.is_err() {
+        return Err(FrameEnclaveError::UntrustedError {
+            status: rt,
+            function: "ocall_qe_get_quote",
+        });
+    }
+    if quote_len > RET_QUOTE_BUF_LEN {
+        return Err(anyhow!("The quote is too large: {}", quote_len).into());
+    }
 
     let _ = quote.split_off(quote_len as usize);
     Ok(quote)
@@ -0,0 +1,111 @@
+//! Ecall outputs which don't fit in the host's buffer.
+//! They are kept until the host fetches them by `FETCH_OUTPUT_CMD` with a buffer of the required length,
+//! so that the ecall isn't executed twice.
+
+use anyhow::Result;
+use frame_common::{
+    crypto::rand_assign,
+    ecall_error::{codes, EcallError, EcallErrorKind},
+};
+use frame_types::OUTPUT_TICKET_SIZE;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "sgx")]
+use std::sync::SgxMutex as Mutex;
+use std::{collections::BTreeMap, vec::Vec};
+
+/// No more outputs are kept once this number of outputs are pending, e.g. because the host stopped fetching them.
+/// They are never dropped, because a dropped output may be another ecall's result.
+const MAX_PENDING_OUTPUTS: usize = 64;
+
+lazy_static! {
+    static ref PENDING_OUTPUTS: Mutex<BTreeMap<[u8; OUTPUT_TICKET_SIZE], Vec<u8>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// Keep the output and returns the ticket to fetch it.
+/// The ticket is random so that it can't be guessed to take the output of another ecall.
+pub fn store(output: Vec<u8>) -> Result<[u8; OUTPUT_TICKET_SIZE]> {
+    let mut outputs = PENDING_OUTPUTS.lock().unwrap();
+    if outputs.len() >= MAX_PENDING_OUTPUTS {
+        return Err(EcallError::new(
+            EcallErrorKind::Internal,
+            format!("Too many pending outputs: {}", outputs.len()),
+        )
+        .with_code(codes::TOO_MANY_PENDING_OUTPUTS)
+        .into());
+    }
+
+    let mut ticket = [0u8; OUTPUT_TICKET_SIZE];
+    loop {
+        rand_assign(&mut ticket)?;
+        if !outputs.contains_key(&ticket) {
+            break;
+        }
+    }
+    outputs.insert(ticket, output);
+
+    Ok(ticket)
+}
+
+/// Take the output by the ticket given as the input of `FETCH_OUTPUT_CMD`.
+pub fn take(input: &[u8]) -> Result<Vec<u8>> {
+    if input.len() != OUTPUT_TICKET_SIZE {
+        return Err(EcallError::new(
+            EcallErrorKind::InvalidInput,
+            format!("Invalid length of the output ticket: {}", input.len()),
+        )
+        .with_code(codes::DECODE_INPUT)
+        .into());
+    }
+    let mut ticket = [0u8; OUTPUT_TICKET_SIZE];
+    ticket.copy_from_slice(input);
+
+    PENDING_OUTPUTS
+        .lock()
+        .unwrap()
+        .remove(&ticket)
+        .ok_or_else(|| {
+            EcallError::new(
+                EcallErrorKind::NotFound,
+                format!("No pending output of the ticket: {:?}", ticket),
+            )
+            .with_code(codes::NO_PENDING_OUTPUT)
+            .into()
+        })
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use std::prelude::v1::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_take_stored_output, test_refuse_too_many_outputs,)
+    }
+
+    fn test_take_stored_output() {
+        let ticket = store(vec![1, 2, 3]).unwrap();
+
+        assert!(take(&ticket[..4]).is_err());
+        assert_eq!(take(&ticket).unwrap(), vec![1, 2, 3]);
+        // An output is taken only once.
+        assert!(take(&ticket).is_err());
+    }
+
+    fn test_refuse_too_many_outputs() {
+        let tickets: Vec<_> = (0..MAX_PENDING_OUTPUTS)
+            .map(|i| store(vec![i as u8]).unwrap())
+            .collect();
+        assert!(store(vec![0]).is_err());
+
+        // None of the pending outputs is dropped.
+        for (i, ticket) in tickets.iter().enumerate() {
+            assert_eq!(take(ticket).unwrap(), vec![i as u8]);
+        }
+        let ticket = store(vec![1]).unwrap();
+        assert_eq!(take(&ticket).unwrap(), vec![1]);
+    }
+}
@@ -6,12 +6,19 @@ macro_rules! register_ecall {
         $ctx_ops: ty,
         $( ($cmd: path, $handler: ty), )*
     ) => {
-        fn ecall_handler(cmd: u32, input: &mut [u8]) -> anyhow::Result<Vec<u8>> {
+        /// Dispatches the ecall to its handler.
+        /// It's public so that the enclave built with `std` is run by `frame_host::InProcessEnclave`.
+        pub fn ecall_handler(cmd: u32, input: &mut [u8]) -> anyhow::Result<Vec<u8>> {
             match cmd {
                 $(
                     $cmd => inner_ecall_handler::<$handler>(input),
                 )*
-                _ => anyhow::bail!("Not registered the ecall command"),
+                _ => Err(frame_common::EcallError::new(
+                    frame_common::EcallErrorKind::NotFound,
+                    format!("Not registered the ecall command: {}", cmd),
+                )
+                .with_code(frame_common::ecall_error::codes::UNREGISTERED_COMMAND)
+                .into()),
             }
         }
 
@@ -21,9 +28,25 @@ macro_rules! register_ecall {
             EE::EI: EcallInput + codec::Decode,
             EE::EO: EcallOutput + codec::Encode,
         {
-            let input = EE::EI::decode(&mut &input_payload[..])
-                .map_err(|e| anyhow!("{:?}", e))?;
-            EE::eval_policy(&input)?;
+            let input = EE::EI::decode(&mut &input_payload[..]).map_err(|e| {
+                frame_common::EcallError::new(
+                    frame_common::EcallErrorKind::InvalidInput,
+                    format!("{:?}", e),
+                )
+                .with_code(frame_common::ecall_error::codes::DECODE_INPUT)
+            })?;
+            // An error classified in the policy is passed through, and the others are policy violations.
+            // Only the outermost message is returned to the host, and the chain is kept to be logged.
+            EE::eval_policy(&input).map_err(|e| {
+                if frame_common::EcallError::find(&e).is_some() {
+                    return e;
+                }
+                let message = e.to_string();
+                e.context(frame_common::EcallError::new(
+                    frame_common::EcallErrorKind::PolicyViolation,
+                    message,
+                ))
+            })?;
             let res = EE::handle::<$runtime_exec, $ctx_ops>(input, $ctx, $max_mem)?;
 
             Ok(res.encode())
@@ -38,22 +61,60 @@ macro_rules! register_ecall {
             output_max_len: usize,
             output_len: &mut usize,
         ) -> frame_types::EnclaveStatus {
+            $crate::logger::init();
             let input = unsafe { std::slice::from_raw_parts_mut(input_buf, input_len) };
-            let res = unsafe {
-                match ecall_handler(command, input) {
-                    Ok(out) => out,
-                    Err(e) => {
-                        println!("Error in enclave (ecall_entry_point): command: {:?}, error: {:?}", command, e);
-                        return frame_types::EnclaveStatus::error();
-                    }
+            let res = if command == frame_types::FETCH_OUTPUT_CMD {
+                $crate::pending_output::take(input)
+            } else {
+                ecall_handler(command, input)
+            };
+            // An output which doesn't fit in the host's buffer is kept, and the ticket to fetch it is returned instead.
+            let res = res.and_then(|out| {
+                if out.len() > output_max_len && output_max_len >= frame_types::OUTPUT_TICKET_SIZE {
+                    let out_len = out.len();
+                    $crate::pending_output::store(out).map(|ticket| (ticket.to_vec(), Some(out_len)))
+                } else {
+                    Ok((out, None))
+                }
+            });
+            let (res, pending_len) = match res {
+                Ok(res) => res,
+                Err(e) => {
+                    $crate::logger::StructuredRecord::new($crate::log::Level::Error, module_path!(), "Error in enclave (ecall_entry_point)")
+                        .field("command", command)
+                        .sensitive("error", format!("{:?}", e))
+                        .log();
+                    // The error is returned through the output buffer if it fits.
+                    *output_len = match frame_common::EcallError::from_anyhow(&e).truncated(output_max_len) {
+                        Some(err) => {
+                            let encoded = codec::Encode::encode(&err);
+                            unsafe {
+                                ptr::copy_nonoverlapping(encoded.as_ptr(), output_buf, encoded.len());
+                            }
+                            encoded.len()
+                        }
+                        None => 0,
+                    };
+                    return frame_types::EnclaveStatus::error();
                 }
             };
 
+            if let Some(pending_len) = pending_len {
+                // The host fetches the result with a buffer of `output_len` by the ticket.
+                *output_len = pending_len;
+                unsafe {
+                    ptr::copy_nonoverlapping(res.as_ptr(), output_buf, res.len());
+                }
+                return frame_types::EnclaveStatus::output_too_large();
+            }
+
             let res_len = res.len();
             *output_len = res_len;
 
             if res_len > output_max_len {
-                println!("Result buffer length is over output_max: output_max={}, res_len={}", output_max_len, res_len);
+                $crate::logger::StructuredRecord::new($crate::log::Level::Error, module_path!(), "Output buffer is too small for the ticket")
+                    .field("output_max", output_max_len)
+                    .log();
                 return frame_types::EnclaveStatus::error();
             }
             unsafe {
@@ -0,0 +1,57 @@
+//! Reports of this enclave and the verification of reports targeted to it.
+//! In-process, there is no key to MAC reports with, so they are neither MACed nor verified,
+//! and MRENCLAVE is `IN_PROCESS_MR_ENCLAVE`.
+
+#[cfg(feature = "sgx")]
+use crate::error::FrameEnclaveError;
+use crate::error::Result;
+use sgx_types::{sgx_report_data_t, sgx_report_t, sgx_target_info_t};
+
+/// The in-process enclave isn't measured.
+#[cfg(feature = "std")]
+pub const IN_PROCESS_MR_ENCLAVE: [u8; 32] = [0u8; 32];
+
+/// Create a report of this enclave targeted to the enclave of the target info.
+#[cfg(feature = "sgx")]
+pub fn create_report(
+    target_info: &sgx_target_info_t,
+    report_data: &sgx_report_data_t,
+) -> Result<sgx_report_t> {
+    sgx_tse::rsgx_create_report(target_info, report_data)
+        .map_err(|err| FrameEnclaveError::SgxError { err })
+}
+
+#[cfg(feature = "std")]
+pub fn create_report(
+    _target_info: &sgx_target_info_t,
+    report_data: &sgx_report_data_t,
+) -> Result<sgx_report_t> {
+    let mut report = self_report();
+    report.body.report_data = *report_data;
+
+    Ok(report)
+}
+
+#[cfg(feature = "sgx")]
+pub fn self_report() -> sgx_report_t {
+    sgx_tse::rsgx_self_report()
+}
+
+#[cfg(feature = "std")]
+pub fn self_report() -> sgx_report_t {
+    let mut report = sgx_report_t::default();
+    report.body.mr_enclave.m = IN_PROCESS_MR_ENCLAVE;
+
+    report
+}
+
+/// Verify that the report is created by another enclave on this platform and targeted to this enclave.
+#[cfg(feature = "sgx")]
+pub fn verify_report(report: &sgx_report_t) -> Result<()> {
+    sgx_tse::rsgx_verify_report(report).map_err(|err| FrameEnclaveError::SgxError { err })
+}
+
+#[cfg(feature = "std")]
+pub fn verify_report(_report: &sgx_report_t) -> Result<()> {
+    Ok(())
+}
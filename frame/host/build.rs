@@ -1,11 +1,17 @@
 use std::env;
 
 fn main() {
+    // The in-process enclave needs neither the SGX libraries nor the untrusted bridge generated from EDL.
+    if env::var("CARGO_FEATURE_IN_PROCESS").is_ok() {
+        return;
+    }
+
     let sdk_dir = env::var("SGX_SDK").unwrap_or_else(|_| "/opt/intel/sgxsdk".to_string());
     let rust_sgx_sdk = env::var("SGX_SDK_RUST")
         .unwrap_or_else(|_| format!("{}/sgx", dirs::home_dir().unwrap().display()));
     let is_sim = env::var("SGX_MODE").unwrap_or_else(|_| "HW".to_string());
     let build_dir = env::var("BUILD_DIR_FROM_HOST").unwrap_or_else(|_| "../../build".to_string());
+    let is_dcap = env::var("SGX_DCAP").map(|v| v == "1").unwrap_or(false);
 
     println!("cargo:rustc-link-search=native={}/lib64", sdk_dir);
     match is_sim.as_ref() {
@@ -17,8 +23,14 @@ fn main() {
             // Treat both HW and undefined as HW
             println!("cargo:rustc-link-lib=dylib=sgx_urts");
             println!("cargo:rustc-link-lib=dylib=sgx_uae_service");
+            // DCAP's quote generation library is only available on the platforms supporting FLC.
+            if is_dcap {
+                println!("cargo:rustc-link-lib=dylib=sgx_dcap_ql");
+                println!("cargo:rustc-cfg=dcap");
+            }
         }
     }
+    println!("cargo:rerun-if-env-changed=SGX_DCAP");
 
     let edl = format!("{}/edl", rust_sgx_sdk);
     let test_u_c_path = format!("{}/Anonify_test_u.c", build_dir);
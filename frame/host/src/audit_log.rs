@@ -0,0 +1,165 @@
+use crate::error::Result;
+use crate::PJ_ROOT_DIR;
+use anyhow::anyhow;
+use codec::{Decode, Encode};
+use frame_common::audit_log::{verify_chain, SignedAuditEntry, SIGNER_ADDRESS_SIZE};
+use log::{debug, warn};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const AUDIT_LOG_FILE: &str = ".anonify/audit.log";
+
+lazy_static! {
+    /// Serializes the appends from concurrent ocalls.
+    static ref APPEND_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// The enclave's audit log of state transitions.
+/// Each line is a hex-encoded `SignedAuditEntry`, appended in the order of its sequence number.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    file_path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::with_path((*PJ_ROOT_DIR).to_path_buf().join(AUDIT_LOG_FILE))
+    }
+
+    fn with_path(file_path: PathBuf) -> Self {
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir).expect("Failed to create the audit log's directory");
+        }
+        AuditLog { file_path }
+    }
+
+    pub fn append(&self, entry: &SignedAuditEntry) -> Result<()> {
+        let _lock = APPEND_LOCK.lock().unwrap();
+        debug!(
+            "Appending an audit log entry: seq: {}, call_id: {}",
+            entry.entry.seq, entry.entry.call_id
+        );
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.file_path)?;
+        // A line half-written when the host stopped is terminated, so that the new entry is on its own line.
+        if !ends_with_newline(&mut file)? {
+            writeln!(file)?;
+        }
+        writeln!(file, "{}", hex::encode(entry.encode()))?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+
+    pub fn read_all(&self) -> Result<Vec<SignedAuditEntry>> {
+        let file = match fs::File::open(&self.file_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        BufReader::new(file)
+            .lines()
+            .map(|line| decode_line(&line?))
+            .collect()
+    }
+
+    /// The last entry, which the enclave anchors its new chain to when it's launched.
+    /// Unparsable lines at the end, e.g. a line half-written when the host stopped, are skipped,
+    /// and they make the whole log fail to be verified.
+    pub fn last(&self) -> Result<Option<SignedAuditEntry>> {
+        let file = match fs::File::open(&self.file_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<std::io::Result<Vec<_>>>()?;
+        for line in lines.iter().rev() {
+            match decode_line(line) {
+                Ok(entry) => return Ok(Some(entry)),
+                Err(e) => warn!("Skipped an unparsable audit log entry: {:?}", e),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Verify the whole audit log, and returns the verifying addresses of the enclaves which signed it.
+    /// See `frame_common::audit_log::verify_chain`.
+    pub fn verify(&self) -> Result<Vec<[u8; SIGNER_ADDRESS_SIZE]>> {
+        let entries = self.read_all()?;
+        verify_chain(&entries).map_err(Into::into)
+    }
+}
+
+fn ends_with_newline(file: &mut fs::File) -> Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(true);
+    }
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+fn decode_line(line: &str) -> Result<SignedAuditEntry> {
+    let bytes = hex::decode(line.trim()).map_err(|e| anyhow!("{}", e))?;
+    SignedAuditEntry::decode(&mut &bytes[..]).map_err(Into::into)
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_common::audit_log::{AuditEntry, AUDIT_HASH_SIZE};
+
+    fn entry(seq: u64) -> SignedAuditEntry {
+        let entry = AuditEntry {
+            seq,
+            prev_hash: [0u8; AUDIT_HASH_SIZE],
+            timestamp: 0,
+            ciphertext_hash: [seq as u8; AUDIT_HASH_SIZE],
+            call_id: 1,
+            updated_states_hash: [0u8; AUDIT_HASH_SIZE],
+        };
+        SignedAuditEntry::new(entry, vec![0u8; 64], 0)
+    }
+
+    #[test]
+    fn test_skip_unparsable_tail() {
+        let file_path = std::env::temp_dir()
+            .join(format!("anonify-audit-log-test-{}", std::process::id()))
+            .join("audit.log");
+        let _ = fs::remove_file(&file_path);
+        let audit_log = AuditLog::with_path(file_path.clone());
+        assert_eq!(audit_log.last().unwrap(), None);
+
+        audit_log.append(&entry(0)).unwrap();
+        audit_log.append(&entry(1)).unwrap();
+        // A line half-written when the host stopped
+        let mut file = OpenOptions::new().append(true).open(&file_path).unwrap();
+        write!(file, "{}", &hex::encode(entry(2).encode())[..10]).unwrap();
+
+        assert_eq!(audit_log.last().unwrap(), Some(entry(1)));
+        assert!(audit_log.read_all().is_err());
+
+        // The entry appended afterwards is parsed.
+        audit_log.append(&entry(2)).unwrap();
+        assert_eq!(audit_log.last().unwrap(), Some(entry(2)));
+
+        fs::remove_dir_all(file_path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,21 +1,9 @@
-use crate::error::{FrameHostError, Result};
+use crate::error::Result;
+#[cfg(feature = "in-process")]
+use crate::in_process as backend;
 use codec::{Decode, Encode};
 use frame_common::{EcallInput, EcallOutput};
-use frame_types::EnclaveStatus;
-use sgx_types::{sgx_enclave_id_t, sgx_status_t};
-
-extern "C" {
-    fn ecall_entry_point(
-        eid: sgx_enclave_id_t,
-        retval: *mut EnclaveStatus,
-        cmd: u32,
-        in_buf: *mut u8,
-        in_len: usize,
-        out_buf: *mut u8,
-        out_max: usize,
-        out_len: &mut usize,
-    ) -> sgx_status_t;
-}
+use sgx_types::sgx_enclave_id_t;
 
 pub struct EnclaveConnector {
     eid: sgx_enclave_id_t,
@@ -35,34 +23,92 @@ impl EnclaveConnector {
         E: Encode + EcallInput,
         D: Decode + EcallOutput,
     {
-        let input_payload = input.encode();
-        let result = self.inner_invoke_ecall(cmd, input_payload)?;
+        let mut input_payload = input.encode();
+        let result = backend::ecall(self.eid, cmd, &mut input_payload, self.output_max_len)?;
         let response = D::decode(&mut &result[..])?;
 
         Ok(response)
     }
+}
+
+/// Ecalls to the enclave loaded by `EnclaveDir::init_enclave`
+#[cfg(not(feature = "in-process"))]
+mod backend {
+    use crate::error::{FrameHostError, Result};
+    use codec::Decode;
+    use frame_common::EcallError;
+    use frame_types::{EnclaveStatus, FETCH_OUTPUT_CMD, OUTPUT_TICKET_SIZE};
+    use sgx_types::{sgx_enclave_id_t, sgx_status_t};
+    use std::{thread, time::Duration};
+
+    /// Ecalls can run concurrently up to `TCSNum` in the enclave's config, and the others are retried after this interval.
+    const OUT_OF_TCS_RETRY_INTERVAL: Duration = Duration::from_micros(100);
+
+    extern "C" {
+        fn ecall_entry_point(
+            eid: sgx_enclave_id_t,
+            retval: *mut EnclaveStatus,
+            cmd: u32,
+            in_buf: *mut u8,
+            in_len: usize,
+            out_buf: *mut u8,
+            out_max: usize,
+            out_len: &mut usize,
+        ) -> sgx_status_t;
+    }
 
-    fn inner_invoke_ecall(&self, cmd: u32, mut input: Vec<u8>) -> Result<Vec<u8>> {
+    /// `output_max` is the initial length of the output buffer.
+    /// If the output is larger, the enclave keeps it and it's fetched with a buffer of the required length.
+    pub(crate) fn ecall(
+        eid: sgx_enclave_id_t,
+        cmd: u32,
+        input: &mut [u8],
+        output_max: usize,
+    ) -> Result<Vec<u8>> {
+        let output_max = output_max.max(OUTPUT_TICKET_SIZE);
+        let (ret, output, output_len) = raw_ecall(eid, cmd, input, output_max)?;
+        if !ret.is_output_too_large() {
+            return check_output(ret, output, output_len, output_max, cmd);
+        }
+
+        let mut ticket = output;
+        ticket.truncate(OUTPUT_TICKET_SIZE);
+        let (ret, output, fetched_len) = raw_ecall(eid, FETCH_OUTPUT_CMD, &mut ticket, output_len)?;
+        check_output(ret, output, fetched_len, output_len, cmd)
+    }
+
+    fn raw_ecall(
+        eid: sgx_enclave_id_t,
+        cmd: u32,
+        input: &mut [u8],
+        output_max: usize,
+    ) -> Result<(EnclaveStatus, Vec<u8>, usize)> {
         let input_ptr = input.as_mut_ptr();
         let input_len = input.len();
-        let output_max = self.output_max_len;
-        let mut output_len = output_max;
-        let mut output_buf = Vec::with_capacity(output_max);
+        let mut output_len = 0;
+        let mut output_buf = vec![0u8; output_max];
         let output_ptr = output_buf.as_mut_ptr();
 
         let mut ret = EnclaveStatus::default();
 
-        let status = unsafe {
-            ecall_entry_point(
-                self.eid,
-                &mut ret,
-                cmd,
-                input_ptr,
-                input_len,
-                output_ptr,
-                output_max,
-                &mut output_len,
-            )
+        let status = loop {
+            let status = unsafe {
+                ecall_entry_point(
+                    eid,
+                    &mut ret,
+                    cmd,
+                    input_ptr,
+                    input_len,
+                    output_ptr,
+                    output_max,
+                    &mut output_len,
+                )
+            };
+            // All TCSs are used by the other threads' ecalls, so wait for one of them to return.
+            if status != sgx_status_t::SGX_ERROR_OUT_OF_TCS {
+                break status;
+            }
+            thread::sleep(OUT_OF_TCS_RETRY_INTERVAL);
         };
 
         if status != sgx_status_t::SGX_SUCCESS {
@@ -72,19 +118,38 @@ impl EnclaveConnector {
                 cmd,
             });
         }
-        if ret.is_err() {
-            return Err(FrameHostError::EnclaveError {
-                status: ret,
-                function: "ecall_entry_point",
+
+        Ok((ret, output_buf, output_len))
+    }
+
+    fn check_output(
+        ret: EnclaveStatus,
+        mut output: Vec<u8>,
+        output_len: usize,
+        output_max: usize,
+        cmd: u32,
+    ) -> Result<Vec<u8>> {
+        if output_len > output_max {
+            return Err(FrameHostError::OutputTooLarge {
+                output_len,
+                output_max,
                 cmd,
             });
         }
-        assert!(output_len < output_max);
 
-        unsafe {
-            output_buf.set_len(output_len);
+        output.truncate(output_len);
+        if ret.is_err() {
+            // The enclave returns the error through the output buffer unless it's too large.
+            return Err(match EcallError::decode(&mut &output[..]) {
+                Ok(error) if output_len != 0 => FrameHostError::Ecall { error, cmd },
+                _ => FrameHostError::EnclaveError {
+                    status: ret,
+                    function: "ecall_entry_point",
+                    cmd,
+                },
+            });
         }
 
-        Ok(output_buf)
+        Ok(output)
     }
 }
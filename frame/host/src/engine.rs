@@ -1,4 +1,5 @@
 use crate::ecalls::EnclaveConnector;
+use crate::error::Result;
 use codec::{Decode, Encode};
 use frame_common::{EcallInput, EcallOutput};
 
@@ -9,15 +10,19 @@ pub trait HostEngine {
     type EI: EcallInput + Encode;
     type EO: EcallOutput + Decode;
     type HO: HostOutput<EcallOutput = Self::EO>;
+    /// The initial length of the output buffer. Larger outputs are fetched by another ecall.
     const OUTPUT_MAX_LEN: usize;
     const CMD: u32;
 
-    fn exec(input: Self::HI, eid: sgx_enclave_id_t) -> anyhow::Result<Self::HO> {
+    /// Errors in the enclave are returned as `FrameHostError::Ecall`.
+    fn exec(input: Self::HI, eid: sgx_enclave_id_t) -> Result<Self::HO> {
         let (ecall_input, host_output) = input.apply()?;
         let ecall_output = EnclaveConnector::new(eid, Self::OUTPUT_MAX_LEN)
             .invoke_ecall::<Self::EI, Self::EO>(Self::CMD, ecall_input)?;
 
-        host_output.set_ecall_output(ecall_output)
+        host_output
+            .set_ecall_output(ecall_output)
+            .map_err(Into::into)
     }
 }
 
@@ -1,3 +1,4 @@
+use frame_common::EcallError;
 use frame_types::EnclaveStatus;
 use sgx_types::sgx_status_t;
 use thiserror::Error;
@@ -20,6 +21,14 @@ pub enum FrameHostError {
         function: &'static str,
         cmd: u32,
     },
+    #[error("Ecall failed: {error}, command: {cmd:?}")]
+    Ecall { error: EcallError, cmd: u32 },
+    #[error("Ecall output is larger than the buffer: output length: {output_len:?}, buffer length: {output_max:?}, command: {cmd:?}")]
+    OutputTooLarge {
+        output_len: usize,
+        output_max: usize,
+        cmd: u32,
+    },
     #[error("Codec error: {0}")]
     CodecError(#[from] codec::Error),
     #[error("IO error: {0}")]
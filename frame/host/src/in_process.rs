@@ -0,0 +1,238 @@
+//! An enclave running in the host process without SGX.
+//! Ecalls are dispatched directly to the handler generated by `register_ecall!` in an enclave crate built with `std`,
+//! so the driver and servers can run on platforms without the SGX SDK. It provides no confidentiality at all.
+
+use crate::error::{FrameHostError, Result};
+use frame_common::EcallError;
+use log::error;
+use sgx_types::sgx_enclave_id_t;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+/// The `ecall_handler` generated by `register_ecall!`
+pub type EcallHandler = fn(u32, &mut [u8]) -> anyhow::Result<Vec<u8>>;
+
+lazy_static! {
+    static ref ENCLAVES: RwLock<HashMap<sgx_enclave_id_t, EcallHandler>> =
+        RwLock::new(HashMap::new());
+}
+
+static NEXT_EID: AtomicU64 = AtomicU64::new(1);
+
+/// Stands in for `SgxEnclave`, and is unregistered when it's dropped.
+#[derive(Debug)]
+pub struct InProcessEnclave {
+    eid: sgx_enclave_id_t,
+}
+
+impl InProcessEnclave {
+    pub fn new(handler: EcallHandler) -> Self {
+        let eid = NEXT_EID.fetch_add(1, Ordering::SeqCst);
+        ENCLAVES.write().unwrap().insert(eid, handler);
+
+        InProcessEnclave { eid }
+    }
+
+    pub fn geteid(&self) -> sgx_enclave_id_t {
+        self.eid
+    }
+}
+
+impl Drop for InProcessEnclave {
+    fn drop(&mut self) {
+        ENCLAVES.write().unwrap().remove(&self.eid);
+    }
+}
+
+/// The output isn't copied through a buffer, so it's returned regardless of `_output_max`.
+pub(crate) fn ecall(
+    eid: sgx_enclave_id_t,
+    cmd: u32,
+    input: &mut [u8],
+    _output_max: usize,
+) -> Result<Vec<u8>> {
+    let handler = *ENCLAVES
+        .read()
+        .unwrap()
+        .get(&eid)
+        .ok_or_else(|| anyhow::anyhow!("No in-process enclave is registered: eid {}", eid))?;
+
+    handler(cmd, input).map_err(|e| {
+        error!(
+            "Error in enclave (in_process::ecall): command: {:?}, error: {:?}",
+            cmd, e
+        );
+        FrameHostError::Ecall {
+            error: EcallError::from_anyhow(&e),
+            cmd,
+        }
+    })
+}
+
+/// Quotes made from the enclave's report without the quoting enclave.
+/// They are deterministic and unsigned, so only the mock IAS server in `tests/mock-ias-server` accepts them.
+pub(crate) mod mock_quote {
+    use sgx_types::{sgx_quote_nonce_t, sgx_report_body_t, sgx_report_t};
+    use sha2::{Digest, Sha256};
+    use std::{mem, slice};
+
+    /// Offset of the report body in `sgx_quote_t`
+    const REPORT_BODY_OFFSET: usize = 48;
+    const SIGN_TYPE_OFFSET: usize = 2;
+    /// `sgx_quote_t` without the signature, followed by `signature_len` of zero
+    pub const MOCK_QUOTE_SIZE: usize = REPORT_BODY_OFFSET + mem::size_of::<sgx_report_body_t>() + 4;
+
+    pub fn new(report: &sgx_report_t, sign_type: u16) -> Vec<u8> {
+        let mut quote = vec![0u8; MOCK_QUOTE_SIZE];
+        // version 2 for EPID quotes
+        quote[0] = 2;
+        quote[SIGN_TYPE_OFFSET..SIGN_TYPE_OFFSET + 2].copy_from_slice(&sign_type.to_le_bytes());
+
+        let body = unsafe {
+            slice::from_raw_parts(
+                &report.body as *const sgx_report_body_t as *const u8,
+                mem::size_of::<sgx_report_body_t>(),
+            )
+        };
+        quote[REPORT_BODY_OFFSET..REPORT_BODY_OFFSET + body.len()].copy_from_slice(body);
+
+        quote
+    }
+
+    /// The quoting enclave's report binding the quote to the request,
+    /// whose REPORTDATA is SHA256(nonce || quote) followed by zeros.
+    pub fn qe_report(nonce: &sgx_quote_nonce_t, quote: &[u8]) -> sgx_report_t {
+        let mut hasher = Sha256::new();
+        hasher.input(&nonce.rand[..]);
+        hasher.input(quote);
+
+        let mut report = sgx_report_t::default();
+        report.body.report_data.d[..32].copy_from_slice(&hasher.result()[..]);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecalls::EnclaveConnector;
+    use codec::{Decode, Encode};
+    use frame_common::{
+        ecall_error::INTERNAL_ERROR_MESSAGE, EcallErrorKind, EcallInput, EcallOutput,
+    };
+    use sgx_types::{sgx_quote_nonce_t, sgx_report_t};
+    use sha2::{Digest, Sha256};
+
+    const INCREMENT_CMD: u32 = 1;
+
+    #[derive(Encode, Decode, Debug, PartialEq)]
+    struct Counter(u32);
+
+    impl EcallInput for Counter {}
+    impl EcallOutput for Counter {}
+
+    #[derive(Encode)]
+    struct Malformed;
+
+    impl EcallInput for Malformed {}
+
+    fn handler(cmd: u32, input: &mut [u8]) -> anyhow::Result<Vec<u8>> {
+        match cmd {
+            INCREMENT_CMD => {
+                let counter =
+                    Counter::decode(&mut &input[..]).map_err(|e| anyhow::anyhow!("{:?}", e))?;
+                Ok(Counter(counter.0 + 1).encode())
+            }
+            _ => Err(EcallError::new(EcallErrorKind::NotFound, "Not registered").into()),
+        }
+    }
+
+    #[test]
+    fn test_dispatch_ecall() {
+        let enclave = InProcessEnclave::new(handler);
+        let connector = EnclaveConnector::new(enclave.geteid(), 16);
+
+        let output: Counter = connector.invoke_ecall(INCREMENT_CMD, Counter(1)).unwrap();
+        assert_eq!(output, Counter(2));
+        // The output longer than the initial buffer is also returned.
+        let output: Counter = EnclaveConnector::new(enclave.geteid(), 1)
+            .invoke_ecall(INCREMENT_CMD, Counter(1))
+            .unwrap();
+        assert_eq!(output, Counter(2));
+    }
+
+    #[test]
+    fn test_ecall_error() {
+        let enclave = InProcessEnclave::new(handler);
+        let connector = EnclaveConnector::new(enclave.geteid(), 16);
+
+        match connector.invoke_ecall::<Counter, Counter>(INCREMENT_CMD + 1, Counter(1)) {
+            Err(FrameHostError::Ecall { error, cmd }) => {
+                assert_eq!(error.kind(), EcallErrorKind::NotFound);
+                assert_eq!(cmd, INCREMENT_CMD + 1);
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        // Errors not classified by the enclave are internal errors.
+        match connector.invoke_ecall::<Malformed, Counter>(INCREMENT_CMD, Malformed) {
+            Err(FrameHostError::Ecall { error, .. }) => {
+                assert_eq!(error.kind(), EcallErrorKind::Internal);
+                // The decoding error's details aren't returned.
+                assert_eq!(error.message(), INTERNAL_ERROR_MESSAGE);
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_dropped_enclave() {
+        let enclave = InProcessEnclave::new(handler);
+        let eid = enclave.geteid();
+        drop(enclave);
+
+        assert!(EnclaveConnector::new(eid, 16)
+            .invoke_ecall::<Counter, Counter>(INCREMENT_CMD, Counter(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_mock_quote() {
+        let mut report = sgx_report_t::default();
+        report.body.mr_enclave.m = [1u8; 32];
+        report.body.report_data.d = [2u8; 64];
+
+        let quote = mock_quote::new(&report, 1);
+        assert_eq!(quote.len(), mock_quote::MOCK_QUOTE_SIZE);
+        assert_eq!(quote, mock_quote::new(&report, 1));
+        // The offsets of MRENCLAVE and REPORTDATA read by the attestation services
+        assert_eq!(&quote[112..144], &[1u8; 32][..]);
+        assert_eq!(&quote[368..432], &[2u8; 64][..]);
+    }
+
+    #[test]
+    fn test_mock_qe_report() {
+        let quote = mock_quote::new(&sgx_report_t::default(), 1);
+        let mut nonce = sgx_quote_nonce_t::default();
+        nonce.rand = [3u8; 16];
+
+        let report_data = mock_quote::qe_report(&nonce, &quote).body.report_data.d;
+        let mut hasher = Sha256::new();
+        hasher.input(&nonce.rand[..]);
+        hasher.input(&quote);
+        assert_eq!(&report_data[..32], &hasher.result()[..]);
+        assert_eq!(&report_data[32..], &[0u8; 32][..]);
+        // Another nonce yields another report.
+        assert_ne!(
+            mock_quote::qe_report(&sgx_quote_nonce_t::default(), &quote)
+                .body
+                .report_data
+                .d,
+            report_data
+        );
+    }
+}
@@ -1,15 +1,29 @@
 #[macro_use]
 extern crate lazy_static;
 
+#[cfg(all(feature = "in-process", not(debug_assertions)))]
+compile_error!(
+    "The in-process enclave has no confidentiality, so it must not be built in release builds"
+);
+
+pub mod audit_log;
+#[cfg(not(feature = "in-process"))]
 mod config;
 pub mod ecalls;
 pub mod engine;
 mod error;
+#[cfg(feature = "in-process")]
+pub mod in_process;
+#[cfg(not(feature = "in-process"))]
 pub mod init_enclave;
-mod ocalls;
+pub mod ocalls;
 mod store_path_secrets;
 
+pub use audit_log::AuditLog;
 pub use error::FrameHostError as Error;
+#[cfg(feature = "in-process")]
+pub use in_process::InProcessEnclave;
+#[cfg(not(feature = "in-process"))]
 pub use init_enclave::EnclaveDir;
 use std::{env, path::PathBuf};
 pub use store_path_secrets::StorePathSecrets;
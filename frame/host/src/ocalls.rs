@@ -1,8 +1,15 @@
-use crate::StorePathSecrets;
+//! The untrusted functions called by the enclave through the edge routines.
+//! The in-process enclave calls them directly.
+
+use crate::{AuditLog, StorePathSecrets};
 use anyhow::Result;
-use codec::Encode;
+use codec::{Decode, Encode};
+use frame_common::{
+    audit_log::SignedAuditEntry,
+    enclave_log::{LogLevel, LogRecord},
+};
 use frame_types::UntrustedStatus;
-use log::debug;
+use log::{debug, Level};
 use sgx_types::*;
 use std::{
     net::{SocketAddr, TcpStream},
@@ -35,21 +42,86 @@ pub extern "C" fn ocall_import_path_secret(
     UntrustedStatus::success()
 }
 
+/// Forward the enclave's log record to the host's logger.
 #[no_mangle]
-pub extern "C" fn ocall_sgx_init_quote(
-    ret_ti: *mut sgx_target_info_t,
-    ret_gid: *mut sgx_epid_group_id_t,
-) -> UntrustedStatus {
-    let ret = unsafe { sgx_init_quote(ret_ti, ret_gid) };
+pub extern "C" fn ocall_log(record: *const u8, record_len: usize) -> UntrustedStatus {
+    let mut record = unsafe { slice::from_raw_parts(record, record_len) };
+    let record = match LogRecord::decode(&mut record) {
+        Ok(record) => record,
+        Err(e) => {
+            println!("Failed to decode the enclave's log record {:?}", e);
+            return UntrustedStatus::error();
+        }
+    };
+    let level = match record.level {
+        LogLevel::Error => Level::Error,
+        LogLevel::Warn => Level::Warn,
+        LogLevel::Info => Level::Info,
+        LogLevel::Debug => Level::Debug,
+        LogLevel::Trace => Level::Trace,
+    };
+
+    log::logger().log(
+        &log::Record::builder()
+            .level(level)
+            .target(&record.target)
+            .args(format_args!("{}", record))
+            .build(),
+    );
+
+    UntrustedStatus::success()
+}
 
-    if ret != sgx_status_t::SGX_SUCCESS {
-        println!("sgx_init_quote returned {}", ret);
+#[no_mangle]
+pub extern "C" fn ocall_append_audit_log(entry: *const u8, entry_len: usize) -> UntrustedStatus {
+    let mut entry = unsafe { slice::from_raw_parts(entry, entry_len) };
+    let result = SignedAuditEntry::decode(&mut entry)
+        .map_err(Into::into)
+        .and_then(|entry| AuditLog::new().append(&entry));
+
+    if let Err(e) = result {
+        println!("Failed to append the enclave's audit log entry {:?}", e);
         return UntrustedStatus::error();
     }
 
     UntrustedStatus::success()
 }
 
+/// Returns the last entry of the audit log, whose length is zero if the log is empty.
+#[no_mangle]
+pub extern "C" fn ocall_last_audit_log_entry(
+    entry: *mut u8,
+    max_len: usize,
+    entry_len: *mut usize,
+) -> UntrustedStatus {
+    let last = match AuditLog::new().last() {
+        Ok(last) => last.map(|entry| entry.encode()).unwrap_or_default(),
+        Err(e) => {
+            println!("Failed to read the enclave's audit log {:?}", e);
+            return UntrustedStatus::error();
+        }
+    };
+    if last.len() > max_len {
+        println!("The audit log entry buffer is too small: {}", max_len);
+        return UntrustedStatus::error();
+    }
+
+    unsafe {
+        ptr::copy_nonoverlapping(last.as_ptr(), entry, last.len());
+        *entry_len = last.len();
+    }
+
+    UntrustedStatus::success()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_sgx_init_quote(
+    ret_ti: *mut sgx_target_info_t,
+    ret_gid: *mut sgx_epid_group_id_t,
+) -> UntrustedStatus {
+    epid::init_quote(ret_ti, ret_gid)
+}
+
 #[no_mangle]
 pub extern "C" fn ocall_get_quote(
     p_sigrl: *const u8,
@@ -60,43 +132,107 @@ pub extern "C" fn ocall_get_quote(
     p_nonce: *const sgx_quote_nonce_t,
     p_qe_report: *mut sgx_report_t,
     p_quote: *mut u8,
-    _maxlen: u32,
+    maxlen: u32,
     p_quote_len: *mut u32,
 ) -> UntrustedStatus {
-    let mut real_quote_len: u32 = 0;
+    epid::get_quote(
+        p_sigrl,
+        sigrl_len,
+        p_report,
+        quote_type,
+        p_spid,
+        p_nonce,
+        p_qe_report,
+        p_quote,
+        maxlen,
+        p_quote_len,
+    )
+}
 
-    let ret = unsafe { sgx_calc_quote_size(p_sigrl, sigrl_len, &mut real_quote_len as *mut u32) };
+#[no_mangle]
+pub extern "C" fn ocall_qe_get_target_info(ret_ti: *mut sgx_target_info_t) -> UntrustedStatus {
+    dcap::qe_get_target_info(ret_ti)
+}
 
-    if ret != sgx_status_t::SGX_SUCCESS {
-        println!("sgx_calc_quote_size returned {}", ret);
-        return UntrustedStatus::error();
+#[no_mangle]
+pub extern "C" fn ocall_qe_get_quote(
+    p_report: *const sgx_report_t,
+    p_quote: *mut u8,
+    maxlen: u32,
+    p_quote_len: *mut u32,
+) -> UntrustedStatus {
+    dcap::qe_get_quote(p_report, p_quote, maxlen, p_quote_len)
+}
+
+/// DCAP's quote generation library is linked only if the host is built with `SGX_DCAP=1`.
+#[cfg(dcap)]
+mod dcap {
+    use super::*;
+
+    pub fn qe_get_target_info(ret_ti: *mut sgx_target_info_t) -> UntrustedStatus {
+        let ret = unsafe { sgx_qe_get_target_info(ret_ti) };
+
+        if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+            println!("sgx_qe_get_target_info returned {:?}", ret);
+            return UntrustedStatus::error();
+        }
+
+        UntrustedStatus::success()
     }
 
-    println!("quote size = {}", real_quote_len);
-    unsafe {
-        *p_quote_len = real_quote_len;
-    }
-
-    let ret = unsafe {
-        sgx_get_quote(
-            p_report,
-            quote_type,
-            p_spid,
-            p_nonce,
-            p_sigrl,
-            sigrl_len,
-            p_qe_report,
-            p_quote as *mut sgx_quote_t,
-            real_quote_len,
-        )
-    };
+    pub fn qe_get_quote(
+        p_report: *const sgx_report_t,
+        p_quote: *mut u8,
+        maxlen: u32,
+        p_quote_len: *mut u32,
+    ) -> UntrustedStatus {
+        let mut real_quote_len: u32 = 0;
 
-    if ret != sgx_status_t::SGX_SUCCESS {
-        println!("sgx_calc_quote_size returned {}", ret);
-        return UntrustedStatus::error();
+        let ret = unsafe { sgx_qe_get_quote_size(&mut real_quote_len as *mut u32) };
+
+        if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+            println!("sgx_qe_get_quote_size returned {:?}", ret);
+            return UntrustedStatus::error();
+        }
+
+        println!("quote size = {}", real_quote_len);
+        unsafe {
+            *p_quote_len = real_quote_len;
+        }
+        if real_quote_len > maxlen {
+            println!("The quote buffer is too small: {}", maxlen);
+            return UntrustedStatus::error();
+        }
+
+        let ret = unsafe { sgx_qe_get_quote(p_report, real_quote_len, p_quote) };
+
+        if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+            println!("sgx_qe_get_quote returned {:?}", ret);
+            return UntrustedStatus::error();
+        }
+
+        UntrustedStatus::success()
     }
+}
 
-    UntrustedStatus::success()
+#[cfg(not(dcap))]
+mod dcap {
+    use super::*;
+
+    pub fn qe_get_target_info(_ret_ti: *mut sgx_target_info_t) -> UntrustedStatus {
+        println!("DCAP isn't supported. Build the host with SGX_DCAP=1");
+        UntrustedStatus::error()
+    }
+
+    pub fn qe_get_quote(
+        _p_report: *const sgx_report_t,
+        _p_quote: *mut u8,
+        _maxlen: u32,
+        _p_quote_len: *mut u32,
+    ) -> UntrustedStatus {
+        println!("DCAP isn't supported. Build the host with SGX_DCAP=1");
+        UntrustedStatus::error()
+    }
 }
 
 #[no_mangle]
@@ -142,12 +278,157 @@ pub extern "C" fn ocall_get_update_info(
     enclave_trusted: i32,
     update_info: *mut sgx_update_info_bit_t,
 ) -> UntrustedStatus {
-    let ret = unsafe { sgx_report_attestation_status(platform_blob, enclave_trusted, update_info) };
+    epid::report_attestation_status(platform_blob, enclave_trusted, update_info)
+}
 
-    if ret != sgx_status_t::SGX_SUCCESS {
-        println!("sgx_report_attestation_status returned {}", ret);
-        return UntrustedStatus::error();
+/// EPID quotes generated by the quoting enclave through the AESM service
+#[cfg(not(feature = "in-process"))]
+mod epid {
+    use super::*;
+
+    pub fn init_quote(
+        ret_ti: *mut sgx_target_info_t,
+        ret_gid: *mut sgx_epid_group_id_t,
+    ) -> UntrustedStatus {
+        let ret = unsafe { sgx_init_quote(ret_ti, ret_gid) };
+
+        if ret != sgx_status_t::SGX_SUCCESS {
+            println!("sgx_init_quote returned {}", ret);
+            return UntrustedStatus::error();
+        }
+
+        UntrustedStatus::success()
     }
 
-    UntrustedStatus::success()
+    #[allow(clippy::too_many_arguments)]
+    pub fn get_quote(
+        p_sigrl: *const u8,
+        sigrl_len: u32,
+        p_report: *const sgx_report_t,
+        quote_type: sgx_quote_sign_type_t,
+        p_spid: *const sgx_spid_t,
+        p_nonce: *const sgx_quote_nonce_t,
+        p_qe_report: *mut sgx_report_t,
+        p_quote: *mut u8,
+        _maxlen: u32,
+        p_quote_len: *mut u32,
+    ) -> UntrustedStatus {
+        let mut real_quote_len: u32 = 0;
+
+        let ret =
+            unsafe { sgx_calc_quote_size(p_sigrl, sigrl_len, &mut real_quote_len as *mut u32) };
+
+        if ret != sgx_status_t::SGX_SUCCESS {
+            println!("sgx_calc_quote_size returned {}", ret);
+            return UntrustedStatus::error();
+        }
+
+        println!("quote size = {}", real_quote_len);
+        unsafe {
+            *p_quote_len = real_quote_len;
+        }
+
+        let ret = unsafe {
+            sgx_get_quote(
+                p_report,
+                quote_type,
+                p_spid,
+                p_nonce,
+                p_sigrl,
+                sigrl_len,
+                p_qe_report,
+                p_quote as *mut sgx_quote_t,
+                real_quote_len,
+            )
+        };
+
+        if ret != sgx_status_t::SGX_SUCCESS {
+            println!("sgx_calc_quote_size returned {}", ret);
+            return UntrustedStatus::error();
+        }
+
+        UntrustedStatus::success()
+    }
+
+    pub fn report_attestation_status(
+        platform_blob: *const sgx_platform_info_t,
+        enclave_trusted: i32,
+        update_info: *mut sgx_update_info_bit_t,
+    ) -> UntrustedStatus {
+        let ret =
+            unsafe { sgx_report_attestation_status(platform_blob, enclave_trusted, update_info) };
+
+        if ret != sgx_status_t::SGX_SUCCESS {
+            println!("sgx_report_attestation_status returned {}", ret);
+            return UntrustedStatus::error();
+        }
+
+        UntrustedStatus::success()
+    }
+}
+
+/// The in-process enclave is quoted without the quoting enclave, and its platform is never out of date.
+#[cfg(feature = "in-process")]
+mod epid {
+    use super::*;
+    use crate::in_process::mock_quote;
+
+    pub fn init_quote(
+        ret_ti: *mut sgx_target_info_t,
+        ret_gid: *mut sgx_epid_group_id_t,
+    ) -> UntrustedStatus {
+        unsafe {
+            *ret_ti = sgx_target_info_t::default();
+            *ret_gid = sgx_epid_group_id_t::default();
+        }
+
+        UntrustedStatus::success()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn get_quote(
+        _p_sigrl: *const u8,
+        _sigrl_len: u32,
+        p_report: *const sgx_report_t,
+        quote_type: sgx_quote_sign_type_t,
+        _p_spid: *const sgx_spid_t,
+        p_nonce: *const sgx_quote_nonce_t,
+        p_qe_report: *mut sgx_report_t,
+        p_quote: *mut u8,
+        maxlen: u32,
+        p_quote_len: *mut u32,
+    ) -> UntrustedStatus {
+        let quote = mock_quote::new(unsafe { &*p_report }, quote_type as u16);
+        if quote.len() > maxlen as usize {
+            println!("The quote buffer is too small: {}", maxlen);
+            return UntrustedStatus::error();
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(quote.as_ptr(), p_quote, quote.len());
+            *p_quote_len = quote.len() as u32;
+            if !p_qe_report.is_null() {
+                let nonce = if p_nonce.is_null() {
+                    sgx_quote_nonce_t::default()
+                } else {
+                    *p_nonce
+                };
+                *p_qe_report = mock_quote::qe_report(&nonce, &quote);
+            }
+        }
+
+        UntrustedStatus::success()
+    }
+
+    pub fn report_attestation_status(
+        _platform_blob: *const sgx_platform_info_t,
+        _enclave_trusted: i32,
+        update_info: *mut sgx_update_info_bit_t,
+    ) -> UntrustedStatus {
+        unsafe {
+            *update_info = sgx_update_info_bit_t::default();
+        }
+
+        UntrustedStatus::success()
+    }
 }
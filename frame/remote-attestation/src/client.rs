@@ -1,4 +1,8 @@
-use crate::IAS_REPORT_CA;
+use crate::{
+    dcap::{self, ATTESTATION_TYPE_DCAP},
+    policy::{parse_timestamp, AttestationPolicy, DEFAULT_MAX_REPORT_AGE},
+    TRUSTED_IAS_REPORT_CA,
+};
 use anyhow::{anyhow, bail, ensure, Result};
 use http_req::{
     request::{Method, Request},
@@ -6,6 +10,7 @@ use http_req::{
     uri::Uri,
 };
 use log::debug;
+use ring::rand::{SecureRandom, SystemRandom};
 use serde_json::Value;
 use std::{
     io::{BufReader, Write},
@@ -14,8 +19,18 @@ use std::{
     time::SystemTime,
 };
 
+/// Offsets in the `sgx_quote_t` structure.
+/// The report body starts at 48 bytes, and then
+/// mr_enclave starts at 64 bytes and report_data starts at 320 bytes in the report body.
+const MR_ENCLAVE_OFFSET: usize = 112;
+const MR_ENCLAVE_SIZE: usize = 32;
+const REPORT_DATA_OFFSET: usize = 368;
+const REPORT_DATA_SIZE: usize = 64;
+/// IAS accepts nonces up to 32 characters, so 16 random bytes are hex-encoded.
+const NONCE_SIZE: usize = 16;
+
 type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
-static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
+pub(crate) static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
     &webpki::ECDSA_P256_SHA256,
     &webpki::ECDSA_P256_SHA384,
     &webpki::ECDSA_P384_SHA256,
@@ -37,9 +52,28 @@ impl RAService {
         uri: &str,
         ias_api_key: &str,
         quote: &str,
+        policy: &AttestationPolicy,
     ) -> Result<(AttestationReport, ReportSig)> {
+        let (report, report_sig, _) =
+            Self::remote_attestation_with_cert(uri, ias_api_key, quote, policy)?;
+        Ok((report, report_sig))
+    }
+
+    /// Same as `remote_attestation`, but also returns the report signing certificate
+    /// so that other parties can verify the report by themselves.
+    pub fn remote_attestation_with_cert(
+        uri: &str,
+        ias_api_key: &str,
+        quote: &str,
+        policy: &AttestationPolicy,
+    ) -> Result<(AttestationReport, ReportSig, SigningCert)> {
         let uri: Uri = uri.parse().expect("Invalid uri");
-        let body = format!("{{\"isvEnclaveQuote\":\"{}\"}}\r\n", quote);
+        // IAS echoes the nonce back in the signed report, which prevents replaying old responses.
+        let nonce = generate_nonce()?;
+        let body = format!(
+            "{{\"isvEnclaveQuote\":\"{}\",\"nonce\":\"{}\"}}\r\n",
+            quote, nonce
+        );
         let mut writer = Vec::new();
 
         let response = RAClient::new(&uri)
@@ -47,12 +81,25 @@ impl RAService {
             .quote_body_mut(&body.as_bytes())
             .send(&mut writer)?;
 
-        let ra_resp = RAResponse::from_response(writer, response)?.verify_attestation_report()?;
+        let ra_resp = RAResponse::from_response(writer, response)?
+            .verify_attestation_report(policy, &nonce)?;
 
-        Ok((ra_resp.attestation_report, ra_resp.report_sig))
+        Ok((
+            ra_resp.attestation_report,
+            ra_resp.report_sig,
+            SigningCert::new(ra_resp.cert),
+        ))
     }
 }
 
+fn generate_nonce() -> Result<String> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("Failed to generate a nonce"))?;
+    Ok(nonce.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
 /// A client for remote attestation with IAS
 pub struct RAClient<'a> {
     request: Request<'a>,
@@ -131,93 +178,115 @@ impl RAResponse {
         })
     }
 
-    /// Verify that
-    /// 1. TLS server certificate
-    /// 2. report's signature
-    /// 3. report's timestamp
-    /// 4. quote status
-    fn verify_attestation_report(self) -> Result<Self> {
-        let now_func = webpki::Time::try_from(SystemTime::now())?;
-
-        let mut ca_reader = BufReader::new(IAS_REPORT_CA.as_bytes());
-        let mut root_store = rustls::RootCertStore::empty();
-        root_store
-            .add_pem_file(&mut ca_reader)
-            .expect("Failed to add CA");
-
-        let trust_anchors: Vec<webpki::TrustAnchor> = root_store
-            .roots
-            .iter()
-            .map(|cert| cert.to_trust_anchor())
-            .collect();
-
-        let ias_cert_dec = Self::decode_ias_report_ca()?;
-        let mut chain: Vec<&[u8]> = Vec::new();
-        chain.push(&ias_cert_dec);
-
-        let sig_cert = webpki::EndEntityCert::from(&self.cert)?;
-
-        sig_cert.verify_is_valid_tls_server_cert(
-            SUPPORTED_SIG_ALGS,
-            &webpki::TLSServerTrustAnchors(&trust_anchors),
-            &chain,
-            now_func,
-        )?;
-
-        sig_cert.verify_signature(
-            &webpki::RSA_PKCS1_2048_8192_SHA256,
-            &self.attestation_report.as_bytes(),
-            &self.report_sig.as_bytes(),
+    fn verify_attestation_report(self, policy: &AttestationPolicy, nonce: &str) -> Result<Self> {
+        self.attestation_report.verify(
+            &self.report_sig,
+            &SigningCert::new(self.cert.clone()),
+            policy,
         )?;
 
         let attn_report = self.attestation_report.as_json()?;
-        self.verify_timestamp(&attn_report)?;
-        self.verify_quote_status(&attn_report)?;
+        ensure!(
+            attn_report["nonce"].as_str() == Some(nonce),
+            "The nonce of the attestation report doesn't match the request's one"
+        );
 
         Ok(self)
     }
+}
 
-    /// Verify report's timestamp is within 24H (90day is recommended by Intel)
-    fn verify_timestamp(&self, attn_report: &Value) -> Result<()> {
-        if let Value::String(_time) = &attn_report["timestamp"] {
-            Ok(())
-        // TODO
-        // let time_fixed = time.clone() + "+0000";
-        // let ts = DateTime::parse_from_str(&time_fixed, "%Y-%m-%dT%H:%M:%S%.f%z").unwrap().timestamp();
-        // let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-        // ensure!(now - ts > 0, "")
-        } else {
-            bail!("Failed to fetch timestamp from attestation report");
-        }
+/// Verify that
+/// 1. TLS server certificate
+/// 2. report's signature
+/// 3. report's timestamp
+/// 4. quote status and advisories
+/// against the policy.
+fn verify_report(
+    report: &AttestationReport,
+    report_sig: &ReportSig,
+    cert: &[u8],
+    policy: &AttestationPolicy,
+) -> Result<()> {
+    let now_func = webpki::Time::try_from(SystemTime::now())?;
+
+    let mut ca_reader = BufReader::new(TRUSTED_IAS_REPORT_CA.as_bytes());
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store
+        .add_pem_file(&mut ca_reader)
+        .map_err(|_| anyhow!("Failed to add CA"))?;
+
+    let trust_anchors: Vec<webpki::TrustAnchor> = root_store
+        .roots
+        .iter()
+        .map(|cert| cert.to_trust_anchor())
+        .collect();
+
+    let ias_cert_dec = decode_ias_report_ca()?;
+    let mut chain: Vec<&[u8]> = Vec::new();
+    chain.push(&ias_cert_dec);
+
+    let sig_cert = webpki::EndEntityCert::from(cert)?;
+
+    sig_cert.verify_is_valid_tls_server_cert(
+        SUPPORTED_SIG_ALGS,
+        &webpki::TLSServerTrustAnchors(&trust_anchors),
+        &chain,
+        now_func,
+    )?;
+
+    sig_cert.verify_signature(
+        &webpki::RSA_PKCS1_2048_8192_SHA256,
+        report.as_bytes(),
+        report_sig.as_bytes(),
+    )?;
+
+    let attn_report = report.as_json()?;
+    verify_timestamp(&attn_report, policy)?;
+    verify_quote_status(&attn_report, policy)?;
+
+    Ok(())
+}
+
+/// Verify report's timestamp is within the policy's max age
+fn verify_timestamp(attn_report: &Value, policy: &AttestationPolicy) -> Result<()> {
+    if let Value::String(time) = &attn_report["timestamp"] {
+        policy.verify_timestamp(time)
+    } else {
+        bail!("Failed to fetch timestamp from attestation report");
     }
+}
 
-    /// Verify the quote status included the attestation report is OK
-    fn verify_quote_status(&self, attn_report: &Value) -> Result<()> {
-        if let Value::String(quote_status) = &attn_report["isvEnclaveQuoteStatus"] {
-            match quote_status.as_ref() {
-                "OK" => Ok(()),
-                "GROUP_OUT_OF_DATE" => {
-                    println!("Enclave Quote Status: GROUP_OUT_OF_DATE");
-                    Ok(())
-                }
-                _ => bail!("Invalid Enclave Quote Status: {}", quote_status),
-            }
-        } else {
-            bail!("Failed to fetch isvEnclaveQuoteStatus from attestation report");
-        }
+/// Verify the quote status and the advisories included the attestation report are accepted by the policy
+fn verify_quote_status(attn_report: &Value, policy: &AttestationPolicy) -> Result<()> {
+    if let Value::String(quote_status) = &attn_report["isvEnclaveQuoteStatus"] {
+        let advisory_ids = match &attn_report["advisoryIDs"] {
+            Value::Array(ids) => ids
+                .iter()
+                .map(|id| {
+                    id.as_str()
+                        .map(ToString::to_string)
+                        .ok_or_else(|| anyhow!("Invalid advisoryIDs: {}", id))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            Value::Null => vec![],
+            ids => bail!("Invalid advisoryIDs: {}", ids),
+        };
+        policy.verify_quote_status(quote_status, &advisory_ids)
+    } else {
+        bail!("Failed to fetch isvEnclaveQuoteStatus from attestation report");
     }
+}
 
-    fn decode_ias_report_ca() -> Result<Vec<u8>> {
-        let mut ias_ca_stripped = IAS_REPORT_CA.as_bytes().to_vec();
-        ias_ca_stripped.retain(|&x| x != 0x0d && x != 0x0a);
-        let head_len = "-----BEGIN CERTIFICATE-----".len();
-        let tail_len = "-----END CERTIFICATE-----".len();
+fn decode_ias_report_ca() -> Result<Vec<u8>> {
+    let mut ias_ca_stripped = TRUSTED_IAS_REPORT_CA.as_bytes().to_vec();
+    ias_ca_stripped.retain(|&x| x != 0x0d && x != 0x0a);
+    let head_len = "-----BEGIN CERTIFICATE-----".len();
+    let tail_len = "-----END CERTIFICATE-----".len();
 
-        let full_len = ias_ca_stripped.len();
-        let ias_ca_core: &[u8] = &ias_ca_stripped[head_len..full_len - tail_len];
-        let ias_cert_dec = base64::decode(ias_ca_core)?;
-        Ok(ias_cert_dec)
-    }
+    let full_len = ias_ca_stripped.len();
+    let ias_ca_core: &[u8] = &ias_ca_stripped[head_len..full_len - tail_len];
+    let ias_cert_dec = base64::decode(ias_ca_core)?;
+    Ok(ias_cert_dec)
 }
 
 /// A report returned from IAS
@@ -240,6 +309,91 @@ impl AttestationReport {
     pub fn as_json(&self) -> Result<Value> {
         serde_json::from_slice(&self.as_bytes()).map_err(Into::into)
     }
+
+    /// Verify the report is signed by IAS with the given certificate and is accepted by the policy.
+    /// DCAP's report is verified with the quote and the collateral included in itself instead.
+    pub fn verify(
+        &self,
+        report_sig: &ReportSig,
+        cert: &SigningCert,
+        policy: &AttestationPolicy,
+    ) -> Result<()> {
+        if self.is_dcap()? {
+            return dcap::verify_report(self, policy);
+        }
+        verify_report(self, report_sig, cert.as_bytes(), policy)
+    }
+
+    /// Seconds since the UNIX epoch when the report was issued
+    pub fn issued_at(&self) -> Result<u64> {
+        let attn_report = self.as_json()?;
+        if let Value::String(time) = &attn_report["timestamp"] {
+            parse_timestamp(time)
+        } else {
+            bail!("Failed to fetch timestamp from attestation report");
+        }
+    }
+
+    /// Seconds since the UNIX epoch when the report becomes too old for the policy.
+    /// If the policy accepts reports of any age, the report expires at the default max age anyway
+    /// so that the platform's latest status is attested periodically.
+    pub fn expires_at(&self, policy: &AttestationPolicy) -> Result<u64> {
+        let max_report_age = policy.max_report_age().unwrap_or(DEFAULT_MAX_REPORT_AGE);
+        Ok(self.issued_at()? + max_report_age.as_secs())
+    }
+
+    pub fn is_dcap(&self) -> Result<bool> {
+        let attn_report = self.as_json()?;
+        Ok(attn_report["attestationType"].as_str() == Some(ATTESTATION_TYPE_DCAP))
+    }
+
+    /// The decoded `sgx_quote_t` structure included in the report.
+    pub fn quote_body(&self) -> Result<Vec<u8>> {
+        let attn_report = self.as_json()?;
+        if let Value::String(quote_body) = &attn_report["isvEnclaveQuoteBody"] {
+            let quote_body = base64::decode(quote_body)?;
+            ensure!(
+                quote_body.len() >= REPORT_DATA_OFFSET + REPORT_DATA_SIZE,
+                "Invalid length of isvEnclaveQuoteBody: {}",
+                quote_body.len()
+            );
+            Ok(quote_body)
+        } else {
+            bail!("Failed to fetch isvEnclaveQuoteBody from attestation report");
+        }
+    }
+
+    pub fn mr_enclave(&self) -> Result<[u8; MR_ENCLAVE_SIZE]> {
+        let quote_body = self.quote_body()?;
+        let mut res = [0u8; MR_ENCLAVE_SIZE];
+        res.copy_from_slice(&quote_body[MR_ENCLAVE_OFFSET..MR_ENCLAVE_OFFSET + MR_ENCLAVE_SIZE]);
+        Ok(res)
+    }
+
+    pub fn report_data(&self) -> Result<[u8; REPORT_DATA_SIZE]> {
+        let quote_body = self.quote_body()?;
+        let mut res = [0u8; REPORT_DATA_SIZE];
+        res.copy_from_slice(&quote_body[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + REPORT_DATA_SIZE]);
+        Ok(res)
+    }
+}
+
+/// A certificate which is used by IAS to sign the attestation report
+#[derive(Debug, Clone, Default)]
+pub struct SigningCert(Vec<u8>);
+
+impl SigningCert {
+    pub fn new(cert: Vec<u8>) -> Self {
+        SigningCert(cert)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0[..]
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
 }
 
 /// Signature of the attestation report
@@ -265,7 +419,7 @@ impl ReportSig {
     }
 }
 
-fn percent_decode(orig: String) -> Result<Vec<u8>> {
+pub(crate) fn percent_decode(orig: String) -> Result<Vec<u8>> {
     let v: Vec<&str> = orig.split('%').collect();
     ensure!(!v.is_empty(), "Certificate is blank");
     let mut ret = String::new();
@@ -0,0 +1,1241 @@
+//! Remote attestation with DCAP ECDSA quotes.
+//! Instead of sending the quote to IAS, the quote is verified against the collateral,
+//! i.e. TCB info and QE identity, provided by Intel's PCS or a PCCS.
+//! The verified quote is packed into a report whose `isvEnclaveQuoteBody` has the same layout as IAS's one,
+//! so MRENCLAVE and REPORTDATA are read from the same offsets as EPID's attestation reports.
+//! The contract verifies the quote in the report with the PCK certificate chain instead of IAS's signature,
+//! but the TCB status and the revocation of the PCK certificate are only checked here.
+
+use crate::client::{
+    percent_decode, AttestationReport, ReportSig, SigningCert, SUPPORTED_SIG_ALGS,
+};
+use crate::{
+    policy::{format_timestamp, parse_timestamp, AttestationPolicy},
+    TRUSTED_DCAP_ROOT_CA,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+use http_req::{
+    request::{Method, Request},
+    response::Headers,
+    uri::Uri,
+};
+use ring::{
+    digest,
+    signature::{self, UnparsedPublicKey},
+};
+use serde_json::{json, Value};
+use std::{
+    io::BufReader,
+    prelude::v1::*,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const ATTESTATION_TYPE_DCAP: &str = "DCAP";
+
+const QUOTE_VERSION: u16 = 3;
+const ATT_KEY_TYPE_ECDSA_P256: u16 = 2;
+const QUOTE_HEADER_SIZE: usize = 48;
+const REPORT_BODY_SIZE: usize = 384;
+/// The header and the report body, which is the same layout as IAS's `isvEnclaveQuoteBody`
+const QUOTE_BODY_SIZE: usize = QUOTE_HEADER_SIZE + REPORT_BODY_SIZE;
+const ECDSA_SIG_SIZE: usize = 64;
+const ECDSA_PUBKEY_SIZE: usize = 64;
+const CERT_DATA_TYPE_PCK_CERT_CHAIN: u16 = 5;
+
+/// Offsets in `sgx_report_body_t`
+const MISC_SELECT_OFFSET: usize = 16;
+const ATTRIBUTES_OFFSET: usize = 48;
+const ATTRIBUTES_SIZE: usize = 16;
+const MR_SIGNER_OFFSET: usize = 128;
+const ISV_PROD_ID_OFFSET: usize = 256;
+const ISV_SVN_OFFSET: usize = 258;
+const REPORT_DATA_OFFSET: usize = 320;
+
+/// OID 1.2.840.113741.1.13.1 of the SGX extension in PCK certificates
+const SGX_EXTENSION_OID: [u8; 9] = [0x2A, 0x86, 0x48, 0x86, 0xF8, 0x4D, 0x01, 0x0D, 0x01];
+const TCB_OID_SUFFIX: u8 = 0x02;
+const FMSPC_OID_SUFFIX: u8 = 0x04;
+const TCB_COMPONENTS_NUM: usize = 16;
+const PCESVN_OID_SUFFIX: u8 = 17;
+const FMSPC_SIZE: usize = 6;
+/// The common name of the CA issuing PCK certificates for multi-package platforms
+const PLATFORM_CA_CN: &str = "Intel SGX PCK Platform CA";
+
+const DER_INTEGER: u8 = 0x02;
+const DER_BIT_STRING: u8 = 0x03;
+const DER_SEQUENCE: u8 = 0x30;
+const DER_UTC_TIME: u8 = 0x17;
+const DER_GENERALIZED_TIME: u8 = 0x18;
+/// The explicit tag of the certificate's version
+const DER_VERSION_TAG: u8 = 0xa0;
+
+/// The very high level service for remote attestations with DCAP
+pub struct DcapRAService;
+
+impl DcapRAService {
+    /// Verify the base64-encoded ECDSA quote with the collateral fetched from `pccs_url`,
+    /// e.g. `https://api.trustedservices.intel.com/sgx/certification/v3`.
+    /// The returned report contains the quote and the collateral, so that other parties can verify it by themselves.
+    /// DCAP's report has neither a separated signature nor a signing certificate, so both are empty,
+    /// and the host passes the quote's PCK certificate chain to the contract instead.
+    pub fn remote_attestation(
+        pccs_url: &str,
+        quote: &str,
+        policy: &AttestationPolicy,
+    ) -> Result<(AttestationReport, ReportSig, SigningCert)> {
+        let raw_quote = base64::decode(quote)?;
+        let (fmspc, pck_ca) = {
+            let quote = Quote::parse(&raw_quote)?;
+            let pck_chain = pem_to_der_certs(quote.pck_cert_chain)?;
+            ensure!(
+                pck_chain.len() >= 2,
+                "The PCK certificate chain must contain the PCK CA's certificate"
+            );
+            (
+                PckTcb::from_cert(&pck_chain[0])?.fmspc,
+                pck_ca_name(&pck_chain[1]),
+            )
+        };
+
+        let (tcb_info_body, tcb_info_issuer_chain) = fetch_collateral(
+            &format!("{}/tcb?fmspc={}", pccs_url, to_hex(&fmspc)),
+            "SGX-TCB-Info-Issuer-Chain",
+        )?;
+        let (qe_identity_body, qe_identity_issuer_chain) = fetch_collateral(
+            &format!("{}/qe/identity", pccs_url),
+            "SGX-Enclave-Identity-Issuer-Chain",
+        )?;
+
+        let tcb_info = extract_raw_object(&tcb_info_body, "tcbInfo")?;
+        let qe_identity = extract_raw_object(&qe_identity_body, "enclaveIdentity")
+            .or_else(|_| extract_raw_object(&qe_identity_body, "qeIdentity"))?;
+        let tcb_info_sig = signature_of(&tcb_info_body)?;
+        let qe_identity_sig = signature_of(&qe_identity_body)?;
+        let (pck_crl, _) = fetch(&format!("{}/pckcrl?ca={}", pccs_url, pck_ca))?;
+
+        let collateral = Collateral {
+            tcb_info: tcb_info.to_string(),
+            tcb_info_sig,
+            tcb_info_issuer_chain: String::from_utf8(tcb_info_issuer_chain)?,
+            qe_identity: qe_identity.to_string(),
+            qe_identity_sig,
+            qe_identity_issuer_chain: String::from_utf8(qe_identity_issuer_chain)?,
+            pck_crl: crl_to_der(pck_crl)?,
+        };
+        let verified = verify_quote(&raw_quote, &collateral, policy)?;
+
+        let report = json!({
+            "attestationType": ATTESTATION_TYPE_DCAP,
+            "isvEnclaveQuoteBody": base64::encode(&raw_quote[..QUOTE_BODY_SIZE]),
+            "isvEnclaveQuoteStatus": verified.status,
+            "advisoryIDs": verified.advisory_ids,
+            "timestamp": timestamp()?,
+            "quote": quote,
+            "tcbInfo": collateral.tcb_info,
+            "tcbInfoSignature": collateral.tcb_info_sig,
+            "tcbInfoIssuerChain": collateral.tcb_info_issuer_chain,
+            "qeIdentity": collateral.qe_identity,
+            "qeIdentitySignature": collateral.qe_identity_sig,
+            "qeIdentityIssuerChain": collateral.qe_identity_issuer_chain,
+            "pckCrl": base64::encode(&collateral.pck_crl),
+        });
+
+        Ok((
+            AttestationReport::new(serde_json::to_vec(&report)?),
+            ReportSig::default(),
+            SigningCert::default(),
+        ))
+    }
+}
+
+/// Verify the quote and the collateral included in DCAP's report against the policy,
+/// and that `isvEnclaveQuoteBody` is the verified quote's one.
+/// The report itself isn't signed, so the quote status written in it isn't trusted and the one derived from
+/// the signed collateral is used instead. The report's timestamp is only a hint of the freshness for the same reason,
+/// but the collateral must not be expired when the report is verified.
+pub fn verify_report(report: &AttestationReport, policy: &AttestationPolicy) -> Result<()> {
+    let attn_report = report.as_json()?;
+    policy.verify_timestamp(json_str(&attn_report, "timestamp")?)?;
+    let quote = base64::decode(json_str(&attn_report, "quote")?)?;
+    let collateral = Collateral {
+        tcb_info: json_str(&attn_report, "tcbInfo")?.to_string(),
+        tcb_info_sig: json_str(&attn_report, "tcbInfoSignature")?.to_string(),
+        tcb_info_issuer_chain: json_str(&attn_report, "tcbInfoIssuerChain")?.to_string(),
+        qe_identity: json_str(&attn_report, "qeIdentity")?.to_string(),
+        qe_identity_sig: json_str(&attn_report, "qeIdentitySignature")?.to_string(),
+        qe_identity_issuer_chain: json_str(&attn_report, "qeIdentityIssuerChain")?.to_string(),
+        pck_crl: base64::decode(json_str(&attn_report, "pckCrl")?)?,
+    };
+    verify_quote(&quote, &collateral, policy)?;
+
+    let quote_body = report.quote_body()?;
+    ensure!(
+        quote_body[..] == quote[..QUOTE_BODY_SIZE],
+        "isvEnclaveQuoteBody doesn't match the verified quote"
+    );
+
+    Ok(())
+}
+
+/// TCB info and QE identity signed by Intel's TCB signing key, and the CRL of the PCK certificate's issuer
+struct Collateral {
+    tcb_info: String,
+    /// Hex-encoded ECDSA signature over `tcb_info`
+    tcb_info_sig: String,
+    /// PEM-encoded certificate chain of the TCB signing key
+    tcb_info_issuer_chain: String,
+    qe_identity: String,
+    qe_identity_sig: String,
+    qe_identity_issuer_chain: String,
+    /// DER-encoded CRL signed by the PCK CA
+    pck_crl: Vec<u8>,
+}
+
+/// The TCB status of the platform and the advisories affecting it
+struct VerifiedStatus {
+    status: String,
+    advisory_ids: Vec<String>,
+}
+
+/// Verify the quote against the unexpired collateral, and that the platform's TCB status is accepted by the policy.
+fn verify_quote(
+    raw_quote: &[u8],
+    collateral: &Collateral,
+    policy: &AttestationPolicy,
+) -> Result<VerifiedStatus> {
+    let now = webpki::Time::try_from(SystemTime::now())?;
+    let quote = Quote::parse(raw_quote)?;
+
+    // The PCK certificate chain must be rooted in Intel SGX Root CA,
+    // and the PCK key signs the QE's report.
+    let pck_chain = pem_to_der_certs(quote.pck_cert_chain)?;
+    let pck_cert = verify_cert_chain(&pck_chain, now)?;
+    pck_cert.verify_signature(
+        &webpki::ECDSA_P256_SHA256,
+        quote.qe_report,
+        &ecdsa_sig_to_der(quote.qe_report_sig)?,
+    )?;
+    verify_pck_crl(&collateral.pck_crl, &pck_chain)?;
+
+    // The QE binds the attestation key to its report.
+    let mut binding = quote.attest_pubkey.to_vec();
+    binding.extend_from_slice(quote.qe_auth_data);
+    let expected = digest::digest(&digest::SHA256, &binding);
+    let qe_report_data = &quote.qe_report[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + 64];
+    ensure!(
+        &qe_report_data[..32] == expected.as_ref() && qe_report_data[32..].iter().all(|b| *b == 0),
+        "The attestation key isn't bound to the QE's report"
+    );
+
+    // The attestation key signs the header and the enclave's report body.
+    let mut attest_pubkey = vec![0x04];
+    attest_pubkey.extend_from_slice(quote.attest_pubkey);
+    UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, &attest_pubkey)
+        .verify(quote.body, quote.sig)
+        .map_err(|_| anyhow!("Invalid quote signature"))?;
+
+    let qe_identity = verify_signed_json(
+        &collateral.qe_identity,
+        &collateral.qe_identity_sig,
+        &collateral.qe_identity_issuer_chain,
+        now,
+    )?;
+    let qe_status = verify_qe_identity(&qe_identity, quote.qe_report)?;
+
+    let tcb_info = verify_signed_json(
+        &collateral.tcb_info,
+        &collateral.tcb_info_sig,
+        &collateral.tcb_info_issuer_chain,
+        now,
+    )?;
+    verify_next_update(&qe_identity)?;
+    verify_next_update(&tcb_info)?;
+    let pck_tcb = PckTcb::from_cert(&pck_chain[0])?;
+    let tcb_level = verify_tcb_info(&tcb_info, &pck_tcb)?;
+
+    let verified = if qe_status == "UpToDate" {
+        tcb_level
+    } else {
+        VerifiedStatus {
+            status: qe_status,
+            advisory_ids: tcb_level.advisory_ids,
+        }
+    };
+    policy.verify_quote_status(&verified.status, &verified.advisory_ids)?;
+
+    Ok(verified)
+}
+
+fn verify_qe_identity(qe_identity: &Value, qe_report: &[u8]) -> Result<String> {
+    let mr_signer = hex::decode(json_str(qe_identity, "mrsigner")?)?;
+    ensure!(
+        mr_signer[..] == qe_report[MR_SIGNER_OFFSET..MR_SIGNER_OFFSET + 32],
+        "QE's MRSIGNER doesn't match the QE identity"
+    );
+    let isv_prod_id = read_u16(qe_report, ISV_PROD_ID_OFFSET)?;
+    ensure!(
+        json_u64(qe_identity, "isvprodid")? == isv_prod_id as u64,
+        "QE's ISVPRODID doesn't match the QE identity"
+    );
+    ensure!(
+        masked_eq(
+            &qe_report[MISC_SELECT_OFFSET..MISC_SELECT_OFFSET + 4],
+            &hex::decode(json_str(qe_identity, "miscselect")?)?,
+            &hex::decode(json_str(qe_identity, "miscselectMask")?)?,
+            true,
+        ),
+        "QE's MISCSELECT doesn't match the QE identity"
+    );
+    ensure!(
+        masked_eq(
+            &qe_report[ATTRIBUTES_OFFSET..ATTRIBUTES_OFFSET + ATTRIBUTES_SIZE],
+            &hex::decode(json_str(qe_identity, "attributes")?)?,
+            &hex::decode(json_str(qe_identity, "attributesMask")?)?,
+            false,
+        ),
+        "QE's ATTRIBUTES doesn't match the QE identity"
+    );
+
+    let isv_svn = read_u16(qe_report, ISV_SVN_OFFSET)? as u64;
+    for level in json_array(qe_identity, "tcbLevels")? {
+        if isv_svn >= json_u64(&level["tcb"], "isvsvn")? {
+            return Ok(json_str(level, "tcbStatus")?.to_string());
+        }
+    }
+
+    Ok("Revoked".to_string())
+}
+
+/// Verify the signed collateral isn't expired.
+fn verify_next_update(collateral: &Value) -> Result<()> {
+    let next_update = parse_timestamp(json_str(collateral, "nextUpdate")?)?;
+    ensure!(now_secs()? < next_update, "The collateral is expired");
+    Ok(())
+}
+
+/// Verify the PCK certificate isn't revoked by the unexpired CRL of its issuer.
+/// The second certificate of the chain must be the issuer, because the CRL is verified with its key.
+fn verify_pck_crl(crl: &[u8], pck_chain: &[Vec<u8>]) -> Result<()> {
+    let pck_ca = pck_chain
+        .get(1)
+        .ok_or_else(|| anyhow!("Not found the PCK CA's certificate"))?;
+    let pck_ca = webpki::EndEntityCert::from(&pck_ca[..])?;
+    let pck_cert = SignedDer::parse(&pck_chain[0])?;
+    pck_ca
+        .verify_signature(&webpki::ECDSA_P256_SHA256, pck_cert.tbs, pck_cert.sig)
+        .map_err(|_| anyhow!("The PCK certificate isn't issued by the PCK CA"))?;
+    let crl = SignedDer::parse(crl)?;
+    pck_ca
+        .verify_signature(&webpki::ECDSA_P256_SHA256, crl.tbs, crl.sig)
+        .map_err(|_| anyhow!("The PCK CRL isn't issued by the PCK CA"))?;
+
+    let crl = Crl::parse(crl.tbs)?;
+    ensure!(now_secs()? < crl.next_update, "The PCK CRL is expired");
+    let serial = cert_serial(pck_cert.tbs)?;
+    ensure!(
+        !crl.revoked_serials.contains(&serial),
+        "The PCK certificate is revoked"
+    );
+
+    Ok(())
+}
+
+/// Find the TCB level of the platform, whose `advisoryIDs` is only included in TCB info v3.
+fn verify_tcb_info(tcb_info: &Value, pck_tcb: &PckTcb) -> Result<VerifiedStatus> {
+    let fmspc = hex::decode(json_str(tcb_info, "fmspc")?)?;
+    ensure!(
+        fmspc[..] == pck_tcb.fmspc[..],
+        "FMSPC of the PCK certificate doesn't match the TCB info"
+    );
+
+    for level in json_array(tcb_info, "tcbLevels")? {
+        let tcb = &level["tcb"];
+        let components = tcb_level_components(tcb)?;
+        let is_higher = pck_tcb
+            .components
+            .iter()
+            .zip(components.iter())
+            .all(|(pck, level)| *pck as u64 >= *level);
+        if is_higher && pck_tcb.pcesvn as u64 >= json_u64(tcb, "pcesvn")? {
+            let advisory_ids = match &level["advisoryIDs"] {
+                Value::Array(ids) => ids
+                    .iter()
+                    .filter_map(|id| id.as_str().map(ToString::to_string))
+                    .collect(),
+                _ => vec![],
+            };
+            return Ok(VerifiedStatus {
+                status: json_str(level, "tcbStatus")?.to_string(),
+                advisory_ids,
+            });
+        }
+    }
+
+    Ok(VerifiedStatus {
+        status: "Revoked".to_string(),
+        advisory_ids: vec![],
+    })
+}
+
+/// TCB info v2 has `sgxtcbcompNNsvn` fields and v3 has `sgxtcbcomponents` array.
+fn tcb_level_components(tcb: &Value) -> Result<Vec<u64>> {
+    if let Value::Array(components) = &tcb["sgxtcbcomponents"] {
+        return components.iter().map(|c| json_u64(c, "svn")).collect();
+    }
+
+    (1..=TCB_COMPONENTS_NUM)
+        .map(|i| json_u64(tcb, &format!("sgxtcbcomp{:02}svn", i)))
+        .collect()
+}
+
+/// Verify the signature over the raw JSON object, and then returns the parsed one.
+fn verify_signed_json(
+    raw: &str,
+    sig: &str,
+    issuer_chain: &str,
+    now: webpki::Time,
+) -> Result<Value> {
+    let issuer_chain = pem_to_der_certs(issuer_chain.as_bytes())?;
+    let signing_cert = verify_cert_chain(&issuer_chain, now)?;
+    signing_cert.verify_signature(
+        &webpki::ECDSA_P256_SHA256,
+        raw.as_bytes(),
+        &ecdsa_sig_to_der(&hex::decode(sig)?)?,
+    )?;
+
+    serde_json::from_str(raw).map_err(Into::into)
+}
+
+/// Verify the first certificate of the chain is issued by Intel SGX Root CA,
+/// or the test root CA with the `mock-ias` feature.
+fn verify_cert_chain<'a>(
+    chain: &'a [Vec<u8>],
+    now: webpki::Time,
+) -> Result<webpki::EndEntityCert<'a>> {
+    let mut ca_reader = BufReader::new(TRUSTED_DCAP_ROOT_CA.as_bytes());
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store
+        .add_pem_file(&mut ca_reader)
+        .map_err(|_| anyhow!("Failed to add CA"))?;
+    let trust_anchors: Vec<webpki::TrustAnchor> = root_store
+        .roots
+        .iter()
+        .map(|cert| cert.to_trust_anchor())
+        .collect();
+
+    let (leaf, intermediates) = chain
+        .split_first()
+        .ok_or_else(|| anyhow!("Certificate chain is empty"))?;
+    let intermediates: Vec<&[u8]> = intermediates.iter().map(|c| &c[..]).collect();
+    let cert = webpki::EndEntityCert::from(&leaf[..])?;
+    // Intel's certificates aren't issued for TLS, so neither EKU nor the DNS name are checked.
+    cert.verify_is_valid_tls_client_cert(
+        SUPPORTED_SIG_ALGS,
+        &webpki::TLSClientTrustAnchors(&trust_anchors),
+        &intermediates,
+        now,
+    )?;
+
+    Ok(cert)
+}
+
+/// A parsed `sgx_quote3_t` with `sgx_ql_ecdsa_sig_data_t`
+struct Quote<'a> {
+    body: &'a [u8],
+    sig: &'a [u8],
+    attest_pubkey: &'a [u8],
+    qe_report: &'a [u8],
+    qe_report_sig: &'a [u8],
+    qe_auth_data: &'a [u8],
+    pck_cert_chain: &'a [u8],
+}
+
+impl<'a> Quote<'a> {
+    fn parse(quote: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader(quote);
+        let body = reader.take(QUOTE_BODY_SIZE)?;
+        let version = read_u16(body, 0)?;
+        ensure!(
+            version == QUOTE_VERSION,
+            "Unsupported quote version: {}",
+            version
+        );
+        let att_key_type = read_u16(body, 2)?;
+        ensure!(
+            att_key_type == ATT_KEY_TYPE_ECDSA_P256,
+            "Unsupported attestation key type: {}",
+            att_key_type
+        );
+
+        let sig_data_len = reader.u32()? as usize;
+        let mut reader = Reader(reader.take(sig_data_len)?);
+        let sig = reader.take(ECDSA_SIG_SIZE)?;
+        let attest_pubkey = reader.take(ECDSA_PUBKEY_SIZE)?;
+        let qe_report = reader.take(REPORT_BODY_SIZE)?;
+        let qe_report_sig = reader.take(ECDSA_SIG_SIZE)?;
+        let qe_auth_data_len = reader.u16()? as usize;
+        let qe_auth_data = reader.take(qe_auth_data_len)?;
+        let cert_data_type = reader.u16()?;
+        ensure!(
+            cert_data_type == CERT_DATA_TYPE_PCK_CERT_CHAIN,
+            "Unsupported certification data type: {}",
+            cert_data_type
+        );
+        let cert_data_len = reader.u32()? as usize;
+        let pck_cert_chain = reader.take(cert_data_len)?;
+
+        Ok(Quote {
+            body,
+            sig,
+            attest_pubkey,
+            qe_report,
+            qe_report_sig,
+            qe_auth_data,
+            pck_cert_chain,
+        })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.0.len() >= len, "Unexpected end of the quote");
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        read_u16(self.take(2)?, 0)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+}
+
+/// The platform's TCB certified in the SGX extension of the PCK certificate
+struct PckTcb {
+    components: [u8; TCB_COMPONENTS_NUM],
+    pcesvn: u16,
+    fmspc: [u8; FMSPC_SIZE],
+}
+
+impl PckTcb {
+    fn from_cert(cert: &[u8]) -> Result<Self> {
+        let mut components = [0u8; TCB_COMPONENTS_NUM];
+        for (i, component) in components.iter_mut().enumerate() {
+            let value = find_sgx_extension(cert, &[TCB_OID_SUFFIX, i as u8 + 1])?;
+            *component = der_uint(value)? as u8;
+        }
+        let pcesvn = der_uint(find_sgx_extension(
+            cert,
+            &[TCB_OID_SUFFIX, PCESVN_OID_SUFFIX],
+        )?)?;
+
+        let value = find_sgx_extension(cert, &[FMSPC_OID_SUFFIX])?;
+        ensure!(value.len() == FMSPC_SIZE, "Invalid FMSPC length");
+        let mut fmspc = [0u8; FMSPC_SIZE];
+        fmspc.copy_from_slice(value);
+
+        Ok(PckTcb {
+            components,
+            pcesvn: pcesvn as u16,
+            fmspc,
+        })
+    }
+}
+
+/// A DER-encoded certificate or CRL, whose signed part is followed by the signature algorithm and the signature
+struct SignedDer<'a> {
+    /// `tbsCertificate` or `tbsCertList` including its header
+    tbs: &'a [u8],
+    /// DER-encoded ECDSA signature
+    sig: &'a [u8],
+}
+
+impl<'a> SignedDer<'a> {
+    fn parse(der: &'a [u8]) -> Result<Self> {
+        let (tag, body, _) = der_tlv(der)?;
+        ensure!(tag == DER_SEQUENCE, "Signed DER must be a SEQUENCE");
+        let (tag, _, rest) = der_tlv(body)?;
+        ensure!(tag == DER_SEQUENCE, "The signed part must be a SEQUENCE");
+        let tbs = &body[..body.len() - rest.len()];
+        let (_, _, rest) = der_tlv(rest)?; // signatureAlgorithm
+        let (tag, sig, _) = der_tlv(rest)?;
+        ensure!(
+            tag == DER_BIT_STRING && sig.first() == Some(&0),
+            "The signature must be a BIT STRING without unused bits"
+        );
+
+        Ok(SignedDer {
+            tbs,
+            sig: &sig[1..],
+        })
+    }
+}
+
+/// The fields of `tbsCertList` used to check the revocation
+struct Crl<'a> {
+    next_update: u64,
+    /// DER-encoded serial numbers of the revoked certificates
+    revoked_serials: Vec<&'a [u8]>,
+}
+
+impl<'a> Crl<'a> {
+    fn parse(tbs: &'a [u8]) -> Result<Self> {
+        let (_, mut fields, _) = der_tlv(tbs)?;
+        let (tag, _, rest) = der_tlv(fields)?;
+        if tag == DER_INTEGER {
+            fields = rest; // version
+        }
+        let (_, _, rest) = der_tlv(fields)?; // signature
+        let (_, _, rest) = der_tlv(rest)?; // issuer
+        let (_, _, rest) = der_tlv(rest)?; // thisUpdate
+        let (tag, next_update, rest) = der_tlv(rest)?;
+        let next_update =
+            der_time(tag, next_update).map_err(|_| anyhow!("The PCK CRL must have nextUpdate"))?;
+
+        let mut revoked_serials = vec![];
+        if !rest.is_empty() {
+            let (tag, mut entries, _) = der_tlv(rest)?;
+            // Otherwise, they are crlExtensions and no certificate is revoked.
+            if tag == DER_SEQUENCE {
+                while !entries.is_empty() {
+                    let (_, entry, next) = der_tlv(entries)?;
+                    let (tag, serial, _) = der_tlv(entry)?;
+                    ensure!(
+                        tag == DER_INTEGER,
+                        "The revoked serial number must be an INTEGER"
+                    );
+                    revoked_serials.push(serial);
+                    entries = next;
+                }
+            }
+        }
+
+        Ok(Crl {
+            next_update,
+            revoked_serials,
+        })
+    }
+}
+
+/// Returns the DER-encoded serial number in `tbsCertificate`.
+fn cert_serial(tbs: &[u8]) -> Result<&[u8]> {
+    let (_, fields, _) = der_tlv(tbs)?;
+    let (mut tag, mut serial, rest) = der_tlv(fields)?;
+    if tag == DER_VERSION_TAG {
+        let (t, s, _) = der_tlv(rest)?;
+        tag = t;
+        serial = s;
+    }
+    ensure!(tag == DER_INTEGER, "The serial number must be an INTEGER");
+    Ok(serial)
+}
+
+/// Convert UTCTime `YYMMDDhhmmssZ` or GeneralizedTime `YYYYMMDDhhmmssZ` into seconds since the UNIX epoch.
+fn der_time(tag: u8, value: &[u8]) -> Result<u64> {
+    let time = std::str::from_utf8(value)?;
+    let time = match tag {
+        // See RFC 5280 4.1.2.5.1 for the century of UTCTime.
+        DER_UTC_TIME if time.len() == 13 => match &time[..2] {
+            yy if yy >= "50" => format!("19{}", time),
+            _ => format!("20{}", time),
+        },
+        DER_GENERALIZED_TIME => time.to_string(),
+        _ => bail!("Invalid time in DER: {}", time),
+    };
+    ensure!(
+        time.len() == 15 && time.ends_with('Z') && time[..14].bytes().all(|b| b.is_ascii_digit()),
+        "Invalid time in DER: {}",
+        time
+    );
+
+    parse_timestamp(&format!(
+        "{}-{}-{}T{}:{}:{}",
+        &time[0..4],
+        &time[4..6],
+        &time[6..8],
+        &time[8..10],
+        &time[10..12],
+        &time[12..14]
+    ))
+}
+
+/// Returns the tag, the contents and the rest of the DER-encoded input.
+fn der_tlv(input: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+    ensure!(input.len() >= 2, "Unexpected end of DER");
+    let (len, header_len) = match input[1] {
+        len if len < 0x80 => (len as usize, 2),
+        len => {
+            let num = (len & 0x7f) as usize;
+            ensure!(
+                (1..=4).contains(&num) && input.len() >= 2 + num,
+                "Unsupported DER length"
+            );
+            let len = input[2..2 + num]
+                .iter()
+                .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+            (len, 2 + num)
+        }
+    };
+    ensure!(input.len() - header_len >= len, "Unexpected end of DER");
+
+    Ok((
+        input[0],
+        &input[header_len..header_len + len],
+        &input[header_len + len..],
+    ))
+}
+
+/// Find the value which follows the OID in the SGX extension.
+/// Each OID is encoded with its length, so an OID is never a prefix of another one.
+fn find_sgx_extension<'a>(cert: &'a [u8], oid_suffix: &[u8]) -> Result<&'a [u8]> {
+    let mut oid = vec![0x06, (SGX_EXTENSION_OID.len() + oid_suffix.len()) as u8];
+    oid.extend_from_slice(&SGX_EXTENSION_OID);
+    oid.extend_from_slice(oid_suffix);
+
+    let pos = cert
+        .windows(oid.len())
+        .position(|w| w == &oid[..])
+        .ok_or_else(|| anyhow!("Not found OID {:?} in the PCK certificate", oid_suffix))?;
+    let tlv = &cert[pos + oid.len()..];
+    ensure!(tlv.len() >= 2, "Unexpected end of the PCK certificate");
+    let (len, header_len) = match tlv[1] {
+        len if len < 0x80 => (len as usize, 2),
+        0x81 if tlv.len() >= 3 => (tlv[2] as usize, 3),
+        _ => bail!("Unsupported DER length in the PCK certificate"),
+    };
+    ensure!(
+        tlv.len() >= header_len + len,
+        "Unexpected end of the PCK certificate"
+    );
+
+    Ok(&tlv[header_len..header_len + len])
+}
+
+fn der_uint(value: &[u8]) -> Result<u32> {
+    ensure!(
+        !value.is_empty() && value.len() <= 5,
+        "Invalid DER integer length"
+    );
+    Ok(value.iter().fold(0u32, |acc, b| (acc << 8) | *b as u32))
+}
+
+/// Convert a raw `r || s` signature into DER, which webpki expects.
+fn ecdsa_sig_to_der(sig: &[u8]) -> Result<Vec<u8>> {
+    ensure!(
+        sig.len() == ECDSA_SIG_SIZE,
+        "ECDSA signature must be {} bytes",
+        ECDSA_SIG_SIZE
+    );
+    let r = der_integer(&sig[..32]);
+    let s = der_integer(&sig[32..]);
+
+    let mut der = vec![0x30, (r.len() + s.len()) as u8];
+    der.extend_from_slice(&r);
+    der.extend_from_slice(&s);
+    Ok(der)
+}
+
+fn der_integer(v: &[u8]) -> Vec<u8> {
+    let start = v.iter().position(|b| *b != 0).unwrap_or(v.len() - 1);
+    let v = &v[start..];
+    let mut res = vec![0x02];
+    if v[0] & 0x80 != 0 {
+        res.push(v.len() as u8 + 1);
+        res.push(0);
+    } else {
+        res.push(v.len() as u8);
+    }
+    res.extend_from_slice(v);
+    res
+}
+
+fn pem_to_der_certs(pem: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let certs = pem_to_der(pem, "CERTIFICATE")?;
+    ensure!(!certs.is_empty(), "Not found certificates in PEM");
+    Ok(certs)
+}
+
+/// A PCCS returns the PCK CRL in PEM, and Intel's PCS may return it in DER.
+fn crl_to_der(crl: Vec<u8>) -> Result<Vec<u8>> {
+    if crl.first() == Some(&DER_SEQUENCE) {
+        return Ok(crl);
+    }
+    pem_to_der(&crl, "X509 CRL")?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Not found the PCK CRL in PEM"))
+}
+
+fn pem_to_der(pem: &[u8], label: &str) -> Result<Vec<Vec<u8>>> {
+    let begin_line = format!("-----BEGIN {}-----", label);
+    let end_line = format!("-----END {}-----", label);
+
+    let pem = std::str::from_utf8(pem)?;
+    let mut ders = vec![];
+    let mut rest = pem;
+    while let Some(begin) = rest.find(&begin_line) {
+        let after_begin = &rest[begin + begin_line.len()..];
+        let end = after_begin
+            .find(&end_line)
+            .ok_or_else(|| anyhow!("Not found the end of {}", label))?;
+        let body: String = after_begin[..end]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        ders.push(base64::decode(&body)?);
+        rest = &after_begin[end + end_line.len()..];
+    }
+
+    Ok(ders)
+}
+
+/// PCK certificates are issued by either the processor CA or the platform CA, each of which has its own CRL.
+fn pck_ca_name(pck_ca: &[u8]) -> &'static str {
+    let is_platform_ca = pck_ca
+        .windows(PLATFORM_CA_CN.len())
+        .any(|w| w == PLATFORM_CA_CN.as_bytes());
+    if is_platform_ca {
+        "platform"
+    } else {
+        "processor"
+    }
+}
+
+fn fetch_collateral(url: &str, issuer_chain_header: &str) -> Result<(String, Vec<u8>)> {
+    let (body, headers) = fetch(url)?;
+    let issuer_chain = headers
+        .get(issuer_chain_header)
+        .ok_or_else(|| anyhow!("Not found {} header", issuer_chain_header))?
+        .clone();
+    let issuer_chain = percent_decode(issuer_chain)?;
+
+    Ok((String::from_utf8(body)?, issuer_chain))
+}
+
+fn fetch(url: &str) -> Result<(Vec<u8>, Headers)> {
+    let uri: Uri = url.parse().map_err(|e| anyhow!("Invalid uri: {:?}", e))?;
+    let host = uri
+        .host_header()
+        .ok_or_else(|| anyhow!("Not found host in the uri"))?;
+    let mut headers = Headers::new();
+    headers.insert("HOST", &host);
+    headers.insert("Connection", "close");
+
+    let mut writer = Vec::new();
+    let response = Request::new(&uri)
+        .method(Method::GET)
+        .headers(headers)
+        .send(&mut writer)
+        .map_err(|e| anyhow!("{:?}", e))?;
+    ensure!(
+        response.status_code().is_success(),
+        "Failed to fetch the collateral from {}: {}",
+        url,
+        response.status_code()
+    );
+
+    Ok((writer, response.headers().clone()))
+}
+
+/// Returns the raw JSON object of the key so that its signature can be verified over the exact bytes.
+fn extract_raw_object<'a>(body: &'a str, key: &str) -> Result<&'a str> {
+    let pattern = format!("\"{}\":", key);
+    let start = body
+        .find(&pattern)
+        .map(|pos| pos + pattern.len())
+        .ok_or_else(|| anyhow!("Not found {} in the collateral", key))?;
+    let object = body[start..].trim_start();
+    ensure!(object.starts_with('{'), "{} must be a JSON object", key);
+
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in object.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(&object[..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    bail!("{} isn't closed", key)
+}
+
+fn signature_of(body: &str) -> Result<String> {
+    let value: Value = serde_json::from_str(body)?;
+    Ok(json_str(&value, "signature")?.to_string())
+}
+
+fn now_secs() -> Result<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|e| anyhow!("{:?}", e))
+}
+
+/// The current time in the same format as IAS's timestamp
+fn timestamp() -> Result<String> {
+    Ok(format_timestamp(now_secs()?))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn masked_eq(actual: &[u8], expected: &[u8], mask: &[u8], little_endian: bool) -> bool {
+    if expected.len() != actual.len() || mask.len() != actual.len() {
+        return false;
+    }
+    // MISCSELECT is a little-endian u32 in the report, but a big-endian hex string in the QE identity.
+    let actual: Vec<u8> = if little_endian {
+        actual.iter().rev().cloned().collect()
+    } else {
+        actual.to_vec()
+    };
+
+    actual
+        .iter()
+        .zip(expected.iter())
+        .zip(mask.iter())
+        .all(|((a, e), m)| a & m == e & m)
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Result<u16> {
+    ensure!(buf.len() >= offset + 2, "Unexpected end of the buffer");
+    Ok(u16::from_le_bytes([buf[offset], buf[offset + 1]]))
+}
+
+fn json_str<'a>(value: &'a Value, key: &str) -> Result<&'a str> {
+    value[key]
+        .as_str()
+        .ok_or_else(|| anyhow!("Not found {} in JSON", key))
+}
+
+fn json_u64(value: &Value, key: &str) -> Result<u64> {
+    value[key]
+        .as_u64()
+        .ok_or_else(|| anyhow!("Not found {} in JSON", key))
+}
+
+fn json_array<'a>(value: &'a Value, key: &str) -> Result<&'a Vec<Value>> {
+    value[key]
+        .as_array()
+        .ok_or_else(|| anyhow!("Not found {} in JSON", key))
+}
+
+#[cfg(all(feature = "mock-ias", feature = "sgx"))]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    /// A PCK certificate with the SGX extension and its CA's certificate, which are issued by a test CA
+    const PCK_CERT_CHAIN: &str = include_str!("../certs/test-pck-cert-chain.pem");
+    /// CRLs of the test CA, which revoke other certificates, the PCK certificate, nothing but are expired,
+    /// and nothing but are signed by another key, respectively
+    const PCK_CRL: &str = include_str!("../certs/test-pck-crl.pem");
+    const PCK_CRL_REVOKED: &str = include_str!("../certs/test-pck-crl-revoked.pem");
+    const PCK_CRL_EXPIRED: &str = include_str!("../certs/test-pck-crl-expired.pem");
+    const PCK_CRL_FORGED: &str = include_str!("../certs/test-pck-crl-forged.pem");
+    /// DCAP's report of a quote signed with a test PCK certificate, and its collateral signed with a test TCB signing key,
+    /// both of which chain up to `TEST_DCAP_ROOT_CA`.
+    const DCAP_REPORT: &str = include_str!("../certs/test-dcap-report.json");
+    const QE_AUTH_DATA_LEN: usize = 32;
+    /// Offsets in the test quote
+    const SIG_DATA_LEN_OFFSET: usize = QUOTE_BODY_SIZE;
+    const QE_AUTH_DATA_LEN_OFFSET: usize = 1012;
+    const CERT_DATA_TYPE_OFFSET: usize = QE_AUTH_DATA_LEN_OFFSET + 2 + QE_AUTH_DATA_LEN;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_parse_quote,
+            test_reject_malformed_quote,
+            test_pck_tcb,
+            test_der_integers,
+            test_extract_raw_object,
+            test_der_time,
+            test_verify_pck_crl,
+            test_verify_signed_quote,
+            test_reject_tampered_signed_quote,
+        )
+    }
+
+    /// Each part of the quote is filled with its own byte, so that the parsed parts can be told apart.
+    fn quote() -> Vec<u8> {
+        let mut quote = vec![1u8; QUOTE_BODY_SIZE];
+        quote[0..2].copy_from_slice(&QUOTE_VERSION.to_le_bytes());
+        quote[2..4].copy_from_slice(&ATT_KEY_TYPE_ECDSA_P256.to_le_bytes());
+
+        let mut sig_data = vec![2u8; ECDSA_SIG_SIZE];
+        sig_data.extend_from_slice(&[3u8; ECDSA_PUBKEY_SIZE]);
+        sig_data.extend_from_slice(&[4u8; REPORT_BODY_SIZE]);
+        sig_data.extend_from_slice(&[5u8; ECDSA_SIG_SIZE]);
+        sig_data.extend_from_slice(&(QE_AUTH_DATA_LEN as u16).to_le_bytes());
+        sig_data.extend_from_slice(&[6u8; QE_AUTH_DATA_LEN]);
+        sig_data.extend_from_slice(&CERT_DATA_TYPE_PCK_CERT_CHAIN.to_le_bytes());
+        sig_data.extend_from_slice(&(PCK_CERT_CHAIN.len() as u32).to_le_bytes());
+        sig_data.extend_from_slice(PCK_CERT_CHAIN.as_bytes());
+
+        quote.extend_from_slice(&(sig_data.len() as u32).to_le_bytes());
+        quote.extend_from_slice(&sig_data);
+        quote
+    }
+
+    fn pck_chain() -> Vec<Vec<u8>> {
+        pem_to_der_certs(PCK_CERT_CHAIN.as_bytes()).unwrap()
+    }
+
+    fn signed_quote() -> (Vec<u8>, Collateral) {
+        let report: Value = serde_json::from_str(DCAP_REPORT).unwrap();
+        let field = |key: &str| json_str(&report, key).unwrap().to_string();
+        let collateral = Collateral {
+            tcb_info: field("tcbInfo"),
+            tcb_info_sig: field("tcbInfoSignature"),
+            tcb_info_issuer_chain: field("tcbInfoIssuerChain"),
+            qe_identity: field("qeIdentity"),
+            qe_identity_sig: field("qeIdentitySignature"),
+            qe_identity_issuer_chain: field("qeIdentityIssuerChain"),
+            pck_crl: base64::decode(&field("pckCrl")).unwrap(),
+        };
+
+        (base64::decode(&field("quote")).unwrap(), collateral)
+    }
+
+    fn test_parse_quote() {
+        let raw_quote = quote();
+        let quote = Quote::parse(&raw_quote).unwrap();
+
+        assert_eq!(quote.body, &raw_quote[..QUOTE_BODY_SIZE]);
+        assert_eq!(quote.sig, &[2u8; ECDSA_SIG_SIZE][..]);
+        assert_eq!(quote.attest_pubkey, &[3u8; ECDSA_PUBKEY_SIZE][..]);
+        assert_eq!(quote.qe_report, &[4u8; REPORT_BODY_SIZE][..]);
+        assert_eq!(quote.qe_report_sig, &[5u8; ECDSA_SIG_SIZE][..]);
+        assert_eq!(quote.qe_auth_data, &[6u8; QE_AUTH_DATA_LEN][..]);
+        assert_eq!(quote.pck_cert_chain, PCK_CERT_CHAIN.as_bytes());
+        assert_eq!(pem_to_der_certs(quote.pck_cert_chain).unwrap().len(), 2);
+    }
+
+    fn test_reject_malformed_quote() {
+        let valid = quote();
+        for len in &[
+            0,
+            QUOTE_BODY_SIZE - 1,
+            SIG_DATA_LEN_OFFSET + 3,
+            QE_AUTH_DATA_LEN_OFFSET + 1,
+            valid.len() - 1,
+        ] {
+            assert!(
+                Quote::parse(&valid[..*len]).is_err(),
+                "A quote truncated to {} bytes must be rejected",
+                len
+            );
+        }
+
+        let malformed = |offset: usize, value: &[u8]| {
+            let mut quote = valid.clone();
+            quote[offset..offset + value.len()].copy_from_slice(value);
+            Quote::parse(&quote).is_err()
+        };
+        // EPID's quote version
+        assert!(malformed(0, &2u16.to_le_bytes()));
+        // ECDSA P-384
+        assert!(malformed(2, &3u16.to_le_bytes()));
+        // The signature data exceeds the quote.
+        assert!(malformed(SIG_DATA_LEN_OFFSET, &u32::MAX.to_le_bytes()));
+        // The QE authentication data exceeds the signature data.
+        assert!(malformed(QE_AUTH_DATA_LEN_OFFSET, &u16::MAX.to_le_bytes()));
+        // Other than the PCK certificate chain
+        assert!(malformed(CERT_DATA_TYPE_OFFSET, &6u16.to_le_bytes()));
+        // The certification data exceeds the signature data.
+        assert!(malformed(
+            CERT_DATA_TYPE_OFFSET + 2,
+            &u32::MAX.to_le_bytes()
+        ));
+
+        assert!(pem_to_der_certs(b"").is_err());
+        assert!(pem_to_der_certs(b"-----BEGIN CERTIFICATE-----\nMIIB").is_err());
+        assert!(
+            pem_to_der_certs(b"-----BEGIN CERTIFICATE-----\n!!\n-----END CERTIFICATE-----")
+                .is_err()
+        );
+    }
+
+    fn test_pck_tcb() {
+        let pck_chain = pck_chain();
+        let pck_tcb = PckTcb::from_cert(&pck_chain[0]).unwrap();
+
+        let mut components = [0u8; TCB_COMPONENTS_NUM];
+        for (i, component) in components.iter_mut().enumerate() {
+            *component = i as u8 + 1;
+        }
+        // Encoded with the sign byte
+        components[0] = 200;
+        assert_eq!(pck_tcb.components, components);
+        assert_eq!(pck_tcb.pcesvn, 11);
+        assert_eq!(pck_tcb.fmspc, [0x00, 0x90, 0x6e, 0xa1, 0x00, 0x00]);
+
+        // The CA's certificate doesn't have the SGX extension.
+        assert!(PckTcb::from_cert(&pck_chain[1]).is_err());
+        // The extension is truncated in the middle of FMSPC.
+        let cert = &pck_chain[0];
+        let fmspc = find_sgx_extension(cert, &[FMSPC_OID_SUFFIX]).unwrap();
+        let fmspc_offset = fmspc.as_ptr() as usize - cert.as_ptr() as usize;
+        assert!(PckTcb::from_cert(&cert[..fmspc_offset + 2]).is_err());
+    }
+
+    fn test_der_integers() {
+        assert_eq!(der_uint(&[0x00, 0xc8]).unwrap(), 200);
+        assert_eq!(der_uint(&[0x0b]).unwrap(), 11);
+        assert!(der_uint(&[]).is_err());
+        assert!(der_uint(&[1u8; 6]).is_err());
+
+        // r needs the sign byte, and s has leading zeros.
+        let mut sig = vec![0x80u8; 32];
+        sig.extend_from_slice(&[0u8; 31]);
+        sig.push(0x01);
+        let der = ecdsa_sig_to_der(&sig).unwrap();
+        let mut expected = vec![0x30, 38, 0x02, 33, 0x00];
+        expected.extend_from_slice(&[0x80u8; 32]);
+        expected.extend_from_slice(&[0x02, 1, 0x01]);
+        assert_eq!(der, expected);
+        assert!(ecdsa_sig_to_der(&sig[..63]).is_err());
+
+        // Truncated or unsupported lengths
+        assert!(der_tlv(&[0x30]).is_err());
+        assert!(der_tlv(&[0x30, 0x03, 0x02, 0x01]).is_err());
+        assert!(der_tlv(&[0x30, 0x85, 0, 0, 0, 0, 1]).is_err());
+        assert!(der_tlv(&[0x30, 0x84, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert_eq!(
+            der_tlv(&[0x02, 0x81, 0x01, 0x07, 0xff]).unwrap(),
+            (DER_INTEGER, &[0x07u8][..], &[0xffu8][..])
+        );
+    }
+
+    fn test_extract_raw_object() {
+        let body = r#"{"tcbInfo":{"a":{"b":"}{\"}"},"c":1},"signature":"00"}"#;
+        assert_eq!(
+            extract_raw_object(body, "tcbInfo").unwrap(),
+            r#"{"a":{"b":"}{\"}"},"c":1}"#
+        );
+        assert!(extract_raw_object(body, "qeIdentity").is_err());
+        assert!(extract_raw_object(r#"{"tcbInfo":"{}"}"#, "tcbInfo").is_err());
+        assert!(extract_raw_object(r#"{"tcbInfo":{"a":{}"#, "tcbInfo").is_err());
+    }
+
+    fn test_der_time() {
+        assert_eq!(
+            der_time(DER_UTC_TIME, b"200101000000Z").unwrap(),
+            1_577_836_800
+        );
+        assert_eq!(
+            der_time(DER_UTC_TIME, b"491231235959Z").unwrap(),
+            2_524_607_999
+        );
+        assert_eq!(
+            der_time(DER_GENERALIZED_TIME, b"20990101000000Z").unwrap(),
+            4_070_908_800
+        );
+        assert!(der_time(DER_UTC_TIME, b"20990101000000Z").is_err());
+        assert!(der_time(DER_GENERALIZED_TIME, b"200101000000Z").is_err());
+        assert!(der_time(DER_GENERALIZED_TIME, b"2099010100000aZ").is_err());
+        assert!(der_time(DER_INTEGER, b"200101000000Z").is_err());
+    }
+
+    fn test_verify_pck_crl() {
+        let pck_chain = pck_chain();
+        let crl = |pem: &str| crl_to_der(pem.as_bytes().to_vec()).unwrap();
+
+        assert!(verify_pck_crl(&crl(PCK_CRL), &pck_chain).is_ok());
+        // DER-encoded CRLs are passed as they are.
+        assert_eq!(crl_to_der(crl(PCK_CRL)).unwrap(), crl(PCK_CRL));
+
+        assert!(verify_pck_crl(&crl(PCK_CRL_REVOKED), &pck_chain).is_err());
+        assert!(verify_pck_crl(&crl(PCK_CRL_EXPIRED), &pck_chain).is_err());
+        assert!(verify_pck_crl(&crl(PCK_CRL_FORGED), &pck_chain).is_err());
+        // The CRL must be verified with the PCK certificate's issuer.
+        let swapped = vec![pck_chain[1].clone(), pck_chain[0].clone()];
+        assert!(verify_pck_crl(&crl(PCK_CRL), &swapped).is_err());
+        assert!(verify_pck_crl(&crl(PCK_CRL), &pck_chain[..1]).is_err());
+
+        let revoked_crl = crl(PCK_CRL_REVOKED);
+        let revoked = Crl::parse(SignedDer::parse(&revoked_crl).unwrap().tbs).unwrap();
+        let serial = cert_serial(SignedDer::parse(&pck_chain[0]).unwrap().tbs).unwrap();
+        assert_eq!(revoked.revoked_serials.len(), 2);
+        assert_eq!(revoked.revoked_serials[1], serial);
+        assert_eq!(revoked.next_update, 4_070_908_800);
+        let expired_crl = crl(PCK_CRL_EXPIRED);
+        let expired = Crl::parse(SignedDer::parse(&expired_crl).unwrap().tbs).unwrap();
+        assert!(expired.revoked_serials.is_empty());
+        assert!(crl_to_der(b"not a CRL".to_vec()).is_err());
+    }
+
+    fn test_verify_signed_quote() {
+        let (quote, collateral) = signed_quote();
+        let verified = verify_quote(&quote, &collateral, &AttestationPolicy::default()).unwrap();
+        assert_eq!(verified.status, "UpToDate");
+        assert!(verified.advisory_ids.is_empty());
+
+        // The report was issued long ago, but the collateral in it isn't expired yet.
+        let report = AttestationReport::new(DCAP_REPORT.as_bytes().to_vec());
+        let policy = AttestationPolicy::default().without_max_age();
+        verify_report(&report, &policy).unwrap();
+        assert!(verify_report(&report, &AttestationPolicy::default()).is_err());
+    }
+
+    fn test_reject_tampered_signed_quote() {
+        let (quote, collateral) = signed_quote();
+        let policy = AttestationPolicy::default();
+        let tampered = |offset: usize| {
+            let mut quote = quote.clone();
+            quote[offset] ^= 1;
+            verify_quote(&quote, &collateral, &policy).is_err()
+        };
+        // MRENCLAVE of the enclave's report body
+        assert!(tampered(112));
+        // The attestation key
+        assert!(tampered(QUOTE_BODY_SIZE + 4 + ECDSA_SIG_SIZE));
+        // ISVSVN of the QE's report
+        assert!(tampered(
+            QUOTE_BODY_SIZE + 4 + ECDSA_SIG_SIZE + ECDSA_PUBKEY_SIZE + ISV_SVN_OFFSET
+        ));
+        // The QE authentication data
+        assert!(tampered(QE_AUTH_DATA_LEN_OFFSET + 2));
+
+        let mut modified = signed_quote().1;
+        modified.tcb_info = modified.tcb_info.replace("UpToDate", "OutOfDate");
+        assert!(verify_quote(&quote, &modified, &policy).is_err());
+        let mut modified = signed_quote().1;
+        modified.qe_identity_sig = collateral.tcb_info_sig.clone();
+        assert!(verify_quote(&quote, &modified, &policy).is_err());
+        // The PCK certificate's issuer doesn't issue this CRL.
+        let mut modified = signed_quote().1;
+        modified.pck_crl = crl_to_der(PCK_CRL.as_bytes().to_vec()).unwrap();
+        assert!(verify_quote(&quote, &modified, &policy).is_err());
+
+        // Only OK is accepted, which is IAS's status.
+        let policy = AttestationPolicy::new(None, vec!["OK".to_string()], vec![]);
+        assert!(verify_quote(&quote, &collateral, &policy).is_err());
+
+        // The report whose isvEnclaveQuoteBody is another one than the verified quote's
+        let mut report: Value = serde_json::from_str(DCAP_REPORT).unwrap();
+        let mut body = quote[..QUOTE_BODY_SIZE].to_vec();
+        body[112] ^= 1;
+        report["isvEnclaveQuoteBody"] = Value::String(base64::encode(&body));
+        let report = AttestationReport::new(serde_json::to_vec(&report).unwrap());
+        assert!(verify_report(&report, &AttestationPolicy::default().without_max_age()).is_err());
+    }
+}
@@ -1,10 +1,91 @@
-#![no_std]
+#![cfg_attr(feature = "sgx", no_std)]
+#[cfg(feature = "sgx")]
 #[macro_use]
 extern crate sgx_tstd as std;
+#[cfg(feature = "sgx")]
+extern crate anyhow_sgx as anyhow;
+#[cfg(feature = "std")]
+extern crate anyhow_std as anyhow;
+#[cfg(feature = "sgx")]
+extern crate base64_sgx as base64;
+#[cfg(feature = "std")]
+extern crate base64_std as base64;
+#[cfg(feature = "sgx")]
+extern crate http_req_sgx as http_req;
+#[cfg(feature = "std")]
+extern crate http_req_std as http_req;
+#[cfg(feature = "sgx")]
+extern crate log_sgx as log;
+#[cfg(feature = "std")]
+extern crate log_std as log;
+#[cfg(feature = "sgx")]
+extern crate ring_sgx as ring;
+#[cfg(feature = "std")]
+extern crate ring_std as ring;
+#[cfg(feature = "sgx")]
+extern crate rustls_sgx as rustls;
+#[cfg(feature = "std")]
+extern crate rustls_std as rustls;
+#[cfg(feature = "sgx")]
+extern crate serde_json_sgx as serde_json;
+#[cfg(feature = "std")]
+extern crate serde_json_std as serde_json;
+#[cfg(feature = "sgx")]
+extern crate webpki_sgx as webpki;
+#[cfg(feature = "std")]
+extern crate webpki_std as webpki;
+
+#[cfg(all(feature = "mock-ias", not(debug_assertions)))]
+compile_error!("The mock IAS's CA must not be trusted in release builds");
 
 mod client;
+mod dcap;
+mod policy;
+#[cfg(all(feature = "mock-ias", feature = "sgx"))]
+pub mod tests;
+
+pub use crate::client::{AttestationReport, RAService, ReportSig, SigningCert};
+pub use crate::dcap::DcapRAService;
+pub use crate::policy::{
+    AttestationPolicy, DEFAULT_ALLOWED_QUOTE_STATUSES, DEFAULT_MAX_REPORT_AGE,
+};
+use anyhow::Result;
+use std::string::String;
+
+/// Services verifying quotes, which is selected at runtime.
+#[derive(Debug, Clone)]
+pub enum AttestationService {
+    /// EPID quotes verified by IAS API v3
+    Ias { url: String, sub_key: String },
+    /// ECDSA quotes verified with the collateral provided by Intel's PCS or a PCCS
+    Dcap { pccs_url: String },
+}
 
-pub use crate::client::{AttestationReport, RAService, ReportSig};
+impl AttestationService {
+    pub fn is_dcap(&self) -> bool {
+        match self {
+            AttestationService::Dcap { .. } => true,
+            AttestationService::Ias { .. } => false,
+        }
+    }
+
+    /// Attest the base64-encoded quote, and returns the attestation report with its signature and the signing certificate.
+    /// The report is rejected unless it's accepted by the policy.
+    pub fn remote_attestation_with_cert(
+        &self,
+        quote: &str,
+        policy: &AttestationPolicy,
+    ) -> Result<(AttestationReport, ReportSig, SigningCert)> {
+        match self {
+            AttestationService::Ias { url, sub_key } => {
+                RAService::remote_attestation_with_cert(url, sub_key, quote, policy)
+            }
+            AttestationService::Dcap { pccs_url } => {
+                DcapRAService::remote_attestation(pccs_url, quote, policy)
+            }
+        }
+    }
+}
 
 pub const IAS_REPORT_CA: &str = "-----BEGIN CERTIFICATE-----
 MIIFSzCCA7OgAwIBAgIJANEHdl0yo7CUMA0GCSqGSIb3DQEBCwUAMH4xCzAJBgNV
@@ -37,3 +118,65 @@ Ud4APK0wZTGtfPXU7w+IBdG5Ez0kE1qzxGQaL4gINJ1zMyleDnbuS8UicjJijvqA
 DD+gT9sSpssq0ascmvH49MOgjt1yoysLtdCtJW/9FZpoOypaHx0R+mJTLwPXVMrv
 DaVzWh5aiEx+idkSGMnX
 -----END CERTIFICATE-----";
+
+/// The CA of the mock IAS server in `tests/mock-ias-server`, which is trusted instead of `IAS_REPORT_CA`
+/// with the `mock-ias` feature.
+pub const TEST_IAS_REPORT_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIEmTCCAwGgAwIBAgIURyh+Xn/dIkgodKbXGmqo2RiBDaIwDQYJKoZIhvcNAQEL
+BQAwVDELMAkGA1UEBhMCSlAxEDAOBgNVBAoMB0Fub25pZnkxMzAxBgNVBAMMKkFu
+b25pZnkgVGVzdCBBdHRlc3RhdGlvbiBSZXBvcnQgU2lnbmluZyBDQTAeFw0yMDAx
+MDEwMDAwMDBaFw00OTEyMzEyMzU5NTlaMFQxCzAJBgNVBAYTAkpQMRAwDgYDVQQK
+DAdBbm9uaWZ5MTMwMQYDVQQDDCpBbm9uaWZ5IFRlc3QgQXR0ZXN0YXRpb24gUmVw
+b3J0IFNpZ25pbmcgQ0EwggGiMA0GCSqGSIb3DQEBAQUAA4IBjwAwggGKAoIBgQCb
+6ITGQ1jVqm+2Ui/kbTA4lPzEX83x57nUbwJOUcIThICNCI9KQjFViPD3VXRUb8Fh
+db7+iqbVvT5W7Nt6P0CVzKh+tbkLwklwWqe/qe0iP0uHf9l0AYmYSO4fdhe2LdL9
+2NFYUMXpqvfqy9jqWfws1a8QwQmVNhaHDAaH5dIOXVsXpKNuEXHLZfZNXzpX/aJJ
+VZOwNF3S1riJ54Y2kYkkj9Uy02x3uz73Fs/LstAjpzgePUzcuSugL8ecgr7OMEla
+yZuH4IA02BA2YptaHQb3jhpiC1mGgx0h9DQ+NiEFoVMpEm/M7VVsMvPtjgDbVxeA
+5gFanRpzVWw2ulAACX1xVwAOhK0e9iYSoBS/U/IdxWCvyxz00e6RvaoWM/yl4CY+
+6Q++0l/kZ6/ku3KC/AJGOuFf/JkFPReWXw535M8zbEI1jJ/TQ53kpKT4mW7WWaHm
+wsPeREG4H50HaAmYpXgAD5yYog6CloQzGlNHbIr2XAqPcQb6geFuSz2VOx/FnjkC
+AwEAAaNjMGEwDwYDVR0TAQH/BAUwAwEB/zAOBgNVHQ8BAf8EBAMCAQYwHQYDVR0O
+BBYEFHEDt2r4ZjgD4x2gffudg2nBWui8MB8GA1UdIwQYMBaAFHEDt2r4ZjgD4x2g
+ffudg2nBWui8MA0GCSqGSIb3DQEBCwUAA4IBgQBQ5zGNV+2pNP5yNM9ThOHxJKwj
+Ezr3dSXAaUC9epsq4eHYF6P1SEGZehJvg/AhK7TczHH2cbQw0vIkpVQ8XwpKGru3
+IgvTILr3/KBlQ8zKI33+fFoHc++bBVyWg8Z0fa1e2o3wnun+qFw1DUG8i6B70MIv
+wvIwBfKC4KJyxLQ71jd9aCzuNu+qmBKLhYG6tzJuDP8Yw6XR1xuOmk94yqHKCir1
+bU51zpXNT55/DK7e8JPJPDZgEcTxHhpKb7G9rO4kFr7Gp6gBlefBZl1GsZlu9azy
+npb+UHb9E7OBPnxYf/vJYlu4PPO4cQHEWg9GlQxLrEtmYBRPltFljVc6mqNpN0o+
+s5YQUG+UpIQWHBJ3djPjcrWuBfrKqDvHTZTgazyG7+4CUy89a1EYo9mr9053o6iM
+zROAEX5Ir6noUx0B35z9fpWUvuwH/nT11heHqSt7l0Quha5RTcg4LW+nfwTwEpHZ
+mh9EmyGiEEQkII46MjL3YTunGcQhtnmsXJOy0wo=
+-----END CERTIFICATE-----";
+
+#[cfg(not(feature = "mock-ias"))]
+pub(crate) const TRUSTED_IAS_REPORT_CA: &str = IAS_REPORT_CA;
+#[cfg(feature = "mock-ias")]
+pub(crate) const TRUSTED_IAS_REPORT_CA: &str = TEST_IAS_REPORT_CA;
+
+/// Intel SGX Root CA, which issues PCK certificates and the TCB signing certificate for DCAP.
+pub const DCAP_ROOT_CA: &str = "-----BEGIN CERTIFICATE-----
+MIICjzCCAjSgAwIBAgIUImUM1lqdNInzg7SVUr9QGzknBqwwCgYIKoZIzj0EAwIw
+aDEaMBgGA1UEAwwRSW50ZWwgU0dYIFJvb3QgQ0ExGjAYBgNVBAoMEUludGVsIENv
+cnBvcmF0aW9uMRQwEgYDVQQHDAtTYW50YSBDbGFyYTELMAkGA1UECAwCQ0ExCzAJ
+BgNVBAYTAlVTMB4XDTE4MDUyMTEwNDUxMFoXDTQ5MTIzMTIzNTk1OVowaDEaMBgG
+A1UEAwwRSW50ZWwgU0dYIFJvb3QgQ0ExGjAYBgNVBAoMEUludGVsIENvcnBvcmF0
+aW9uMRQwEgYDVQQHDAtTYW50YSBDbGFyYTELMAkGA1UECAwCQ0ExCzAJBgNVBAYT
+AlVTMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEC6nEwMDIYZOj/iPWsCzaEKi7
+1OiOSLRFhWGjbnBVJfVnkY4u3IjkDYYL0MxO4mqsyYjlBalTVYxFP2sJBK5zlKOB
+uzCBuDAfBgNVHSMEGDAWgBQiZQzWWp00ifODtJVSv1AbOScGrDBSBgNVHR8ESzBJ
+MEegRaBDhkFodHRwczovL2NlcnRpZmljYXRlcy50cnVzdGVkc2VydmljZXMuaW50
+ZWwuY29tL0ludGVsU0dYUm9vdENBLmRlcjAdBgNVHQ4EFgQUImUM1lqdNInzg7SV
+Ur9QGzknBqwwDgYDVR0PAQH/BAQDAgEGMBIGA1UdEwEB/wQIMAYBAf8CAQEwCgYI
+KoZIzj0EAwIDSQAwRgIhAOW/5QkR+S9CiSDcNoowLuPRLsWGf/Yi7GSX94BgwTwg
+AiEA4J0lrHoMs+Xo5o/sX6O9QWxHRAvZUGOdRQ7cvqRXaqI=
+-----END CERTIFICATE-----";
+
+/// The root CA of the test DCAP quote and collateral in `certs/test-dcap-report.json`,
+/// which is trusted instead of `DCAP_ROOT_CA` with the `mock-ias` feature.
+pub const TEST_DCAP_ROOT_CA: &str = include_str!("../certs/test-dcap-root-ca.pem");
+
+#[cfg(not(feature = "mock-ias"))]
+pub(crate) const TRUSTED_DCAP_ROOT_CA: &str = DCAP_ROOT_CA;
+#[cfg(feature = "mock-ias")]
+pub(crate) const TRUSTED_DCAP_ROOT_CA: &str = TEST_DCAP_ROOT_CA;
@@ -0,0 +1,352 @@
+//! A policy deciding which attestation reports are acceptable.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use std::{
+    prelude::v1::*,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Intel recommends accepting reports within 90 days, but it's shortened to 24 hours by default.
+pub const DEFAULT_MAX_REPORT_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// IAS's `isvEnclaveQuoteStatus` and DCAP's TCB status of platforms with no known issues.
+/// `GROUP_OUT_OF_DATE` isn't accepted by default any longer, because the platform lacks security updates.
+/// It's accepted only with its advisories, e.g. `INTEL-SA-00334`, which are explicitly allowed by the policy.
+pub const DEFAULT_ALLOWED_QUOTE_STATUSES: [&str; 2] = ["OK", "UpToDate"];
+/// Allowed clock skew between the attestation service and this platform
+const CLOCK_SKEW_SECS: u64 = 5 * 60;
+
+/// Which reports are acceptable in terms of freshness, quote status and security advisories.
+#[derive(Debug, Clone)]
+pub struct AttestationPolicy {
+    /// Reports older than this are rejected. `None` accepts reports of any age.
+    max_report_age: Option<Duration>,
+    /// `isvEnclaveQuoteStatus`es accepted, e.g. `OK` and `GROUP_OUT_OF_DATE` for IAS
+    /// or `UpToDate` and `SWHardeningNeeded` for DCAP.
+    allowed_quote_statuses: Vec<String>,
+    /// Intel security advisory IDs, e.g. `INTEL-SA-00334`, accepted when the quote status isn't up to date.
+    /// A report is rejected if any of its advisories isn't included.
+    allowed_advisory_ids: Vec<String>,
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        AttestationPolicy {
+            max_report_age: Some(DEFAULT_MAX_REPORT_AGE),
+            allowed_quote_statuses: DEFAULT_ALLOWED_QUOTE_STATUSES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            allowed_advisory_ids: vec![],
+        }
+    }
+}
+
+impl AttestationPolicy {
+    pub fn new(
+        max_report_age: Option<Duration>,
+        allowed_quote_statuses: Vec<String>,
+        allowed_advisory_ids: Vec<String>,
+    ) -> Self {
+        AttestationPolicy {
+            max_report_age,
+            allowed_quote_statuses,
+            allowed_advisory_ids,
+        }
+    }
+
+    /// The same policy except that reports of any age are accepted.
+    /// This is used for reports which are legitimately verified long after they were issued,
+    /// e.g. ones embedded in handshakes replayed from the contract's logs.
+    pub fn without_max_age(&self) -> Self {
+        AttestationPolicy {
+            max_report_age: None,
+            ..self.clone()
+        }
+    }
+
+    /// The same policy except that the max age is shortened to `max_report_age`.
+    /// The max age can't be lengthened, so that the policy is only tightened.
+    pub fn with_shorter_max_age(&self, max_report_age: Duration) -> Result<Self> {
+        ensure!(
+            max_report_age.as_secs() > 0,
+            "The max age of reports must be positive"
+        );
+        if let Some(current) = self.max_report_age {
+            ensure!(
+                max_report_age <= current,
+                "The max age of reports can't be lengthened from {} secs to {} secs",
+                current.as_secs(),
+                max_report_age.as_secs()
+            );
+        }
+
+        Ok(AttestationPolicy {
+            max_report_age: Some(max_report_age),
+            ..self.clone()
+        })
+    }
+
+    pub fn max_report_age(&self) -> Option<Duration> {
+        self.max_report_age
+    }
+
+    /// Verify the report was issued within the max age.
+    /// The timestamp is in IAS's format, e.g. `2020-05-21T10:45:10.123456`, which is UTC,
+    /// and a trailing `Z` is also accepted.
+    pub fn verify_timestamp(&self, timestamp: &str) -> Result<()> {
+        let max_report_age = match self.max_report_age {
+            Some(age) => age,
+            None => return Ok(()),
+        };
+
+        let issued_at = parse_timestamp(timestamp)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| anyhow!("{:?}", e))?
+            .as_secs();
+        ensure!(
+            issued_at <= now + CLOCK_SKEW_SECS,
+            "The attestation report is issued in the future: {}",
+            timestamp
+        );
+        ensure!(
+            now.saturating_sub(issued_at) <= max_report_age.as_secs(),
+            "The attestation report is too old: {}",
+            timestamp
+        );
+
+        Ok(())
+    }
+
+    /// Verify the quote status and all the advisories are accepted.
+    pub fn verify_quote_status(&self, quote_status: &str, advisory_ids: &[String]) -> Result<()> {
+        if !self
+            .allowed_quote_statuses
+            .iter()
+            .any(|s| s == quote_status)
+        {
+            bail!("Invalid Enclave Quote Status: {}", quote_status);
+        }
+        if let Some(id) = advisory_ids
+            .iter()
+            .find(|id| !self.allowed_advisory_ids.contains(id))
+        {
+            bail!(
+                "Advisory {} isn't accepted (Enclave Quote Status: {})",
+                id,
+                quote_status
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse the UTC timestamp formatted as `YYYY-MM-DDThh:mm:ss[.ffffff][Z]` into seconds since the UNIX epoch.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Result<u64> {
+    let invalid = || anyhow!("Invalid timestamp: {}", timestamp);
+    let timestamp = timestamp.trim_end_matches('Z');
+    let datetime = timestamp.split('.').next().ok_or_else(invalid)?;
+    let mut parts = datetime.split('T');
+    let (date, time) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(date), Some(time), None) => (date, time),
+        _ => return Err(invalid()),
+    };
+
+    let parse_fields = |s: &str, sep: char| -> Result<Vec<u64>> {
+        let fields = s
+            .split(sep)
+            .map(|f| f.parse::<u64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(fields.len() == 3, "Invalid timestamp: {}", timestamp);
+        Ok(fields)
+    };
+    let date = parse_fields(date, '-')?;
+    let time = parse_fields(time, ':')?;
+    let (year, month, day) = (date[0] as i64, date[1] as i64, date[2] as i64);
+    ensure!(
+        (1..=12).contains(&month) && (1..=31).contains(&day),
+        "Invalid timestamp: {}",
+        timestamp
+    );
+    ensure!(
+        time[0] < 24 && time[1] < 60 && time[2] < 61,
+        "Invalid timestamp: {}",
+        timestamp
+    );
+
+    // Convert the civil date into days since the epoch.
+    // See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    ensure!(days >= 0, "Invalid timestamp: {}", timestamp);
+
+    Ok(days as u64 * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+/// Format seconds since the UNIX epoch in the same format as IAS's timestamp, e.g. `2020-05-21T10:45:10.000000`.
+pub(crate) fn format_timestamp(secs: u64) -> String {
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch into the civil date.
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000000",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(all(feature = "mock-ias", feature = "sgx"))]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_parse_timestamp,
+            test_verify_timestamp,
+            test_shorten_max_age,
+            test_verify_quote_status,
+        )
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00").unwrap(), 0);
+        assert_eq!(
+            parse_timestamp("2020-05-21T10:45:10.123456").unwrap(),
+            1_590_057_910
+        );
+        assert_eq!(
+            parse_timestamp("2020-05-21T10:45:10Z").unwrap(),
+            1_590_057_910
+        );
+        // A leap day
+        assert_eq!(
+            parse_timestamp("2020-02-29T23:59:59").unwrap(),
+            1_583_020_799
+        );
+        for secs in &[0, 951_782_400, 1_590_057_910, 4_102_444_799] {
+            assert_eq!(parse_timestamp(&format_timestamp(*secs)).unwrap(), *secs);
+        }
+
+        for invalid in &[
+            "",
+            "2020-05-21",
+            "2020-05-21T10:45",
+            "2020-05-21T10:45:10T00:00:00",
+            "2020-13-01T00:00:00",
+            "2020-05-00T00:00:00",
+            "2020-05-21T24:00:00",
+            "2020-05-21T10:60:00",
+            "2020-05-21T10:45:xx",
+            "1969-12-31T23:59:59",
+        ] {
+            assert!(
+                parse_timestamp(invalid).is_err(),
+                "{} must be rejected",
+                invalid
+            );
+        }
+    }
+
+    fn test_verify_timestamp() {
+        let policy = AttestationPolicy::default()
+            .with_shorter_max_age(Duration::from_secs(60 * 60))
+            .unwrap();
+        let now = now();
+
+        assert!(policy.verify_timestamp(&format_timestamp(now)).is_ok());
+        assert!(policy
+            .verify_timestamp(&format_timestamp(now - 30 * 60))
+            .is_ok());
+        assert!(
+            policy
+                .verify_timestamp(&format_timestamp(now - 2 * 60 * 60))
+                .is_err(),
+            "A report older than the max age must be rejected"
+        );
+        // The clock skew is tolerated, but a report from the far future isn't.
+        assert!(policy.verify_timestamp(&format_timestamp(now + 60)).is_ok());
+        assert!(policy
+            .verify_timestamp(&format_timestamp(now + CLOCK_SKEW_SECS + 60))
+            .is_err());
+        assert!(policy.verify_timestamp("invalid").is_err());
+
+        // Reports of any age are accepted without the max age.
+        let policy = policy.without_max_age();
+        assert!(policy
+            .verify_timestamp(&format_timestamp(now - 365 * 24 * 60 * 60))
+            .is_ok());
+    }
+
+    fn test_shorten_max_age() {
+        let policy = AttestationPolicy::default();
+        let shortened = policy
+            .with_shorter_max_age(Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(shortened.max_report_age(), Some(Duration::from_secs(60)));
+        assert_eq!(
+            shortened.allowed_quote_statuses,
+            policy.allowed_quote_statuses
+        );
+
+        assert!(policy
+            .with_shorter_max_age(DEFAULT_MAX_REPORT_AGE + Duration::from_secs(1))
+            .is_err());
+        assert!(policy.with_shorter_max_age(Duration::from_secs(0)).is_err());
+        // Any age is shortened.
+        assert!(policy
+            .without_max_age()
+            .with_shorter_max_age(Duration::from_secs(60))
+            .is_ok());
+    }
+
+    fn test_verify_quote_status() {
+        let policy = AttestationPolicy::default();
+        assert!(policy.verify_quote_status("OK", &[]).is_ok());
+        assert!(policy.verify_quote_status("UpToDate", &[]).is_ok());
+        assert!(policy
+            .verify_quote_status("GROUP_OUT_OF_DATE", &[])
+            .is_err());
+
+        let advisories = vec!["INTEL-SA-00334".to_string()];
+        let policy = AttestationPolicy::new(
+            None,
+            vec!["OK".to_string(), "GROUP_OUT_OF_DATE".to_string()],
+            advisories.clone(),
+        );
+        assert!(policy
+            .verify_quote_status("GROUP_OUT_OF_DATE", &advisories)
+            .is_ok());
+        assert!(policy
+            .verify_quote_status("GROUP_OUT_OF_DATE", &["INTEL-SA-00161".to_string()])
+            .is_err());
+    }
+}
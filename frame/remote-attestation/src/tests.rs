@@ -0,0 +1,103 @@
+//! Tests against the mock IAS servers in `tests/mock-ias-server`, which are started by the host of the unit tests.
+//! Each server behaves as indicated by the name of the environment variable of its URL.
+
+use crate::{AttestationPolicy, AttestationReport, RAService, ReportSig, SigningCert};
+use std::{
+    env,
+    prelude::v1::*,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use test_utils::*;
+
+const MOCK_SUB_KEY: &str = "mock-sub-key";
+const QUOTE_BODY_SIZE: usize = 432;
+const REPORT_DATA_OFFSET: usize = 368;
+const REPORT_DATA: [u8; 64] = [7u8; 64];
+
+pub fn run_tests() -> bool {
+    check_all_passed!(
+        run_tests!(
+            test_attestation_ok,
+            test_tampered_report,
+            test_group_out_of_date,
+            test_signature_invalid,
+            test_corrupted_signature,
+        ),
+        crate::policy::tests::run_tests(),
+        crate::dcap::tests::run_tests(),
+    )
+}
+
+fn test_attestation_ok() {
+    let (report, report_sig, cert) = attest("MOCK_IAS_OK_URL", &AttestationPolicy::default())
+        .expect("The report of OK status must be accepted");
+
+    assert_eq!(report.report_data().unwrap()[..], REPORT_DATA[..]);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert!(report.expires_at(&AttestationPolicy::default()).unwrap() > now);
+    assert!(report
+        .verify(&report_sig, &cert, &AttestationPolicy::default())
+        .is_ok());
+}
+
+fn test_tampered_report() {
+    let (report, report_sig, cert) =
+        attest("MOCK_IAS_OK_URL", &AttestationPolicy::default()).unwrap();
+
+    let mut tampered = report.into_vec();
+    let last = tampered.len() - 2;
+    tampered[last] ^= 1;
+    assert!(AttestationReport::new(tampered)
+        .verify(&report_sig, &cert, &AttestationPolicy::default())
+        .is_err());
+}
+
+fn test_group_out_of_date() {
+    let url = "MOCK_IAS_GROUP_OUT_OF_DATE_URL";
+    assert!(attest(url, &AttestationPolicy::default()).is_err());
+
+    let allowed_statuses = vec!["OK".to_string(), "GROUP_OUT_OF_DATE".to_string()];
+    let policy = AttestationPolicy::new(None, allowed_statuses.clone(), vec![]);
+    assert!(
+        attest(url, &policy).is_err(),
+        "The report must be rejected unless its advisories are accepted"
+    );
+
+    let policy = AttestationPolicy::new(None, allowed_statuses, vec!["INTEL-SA-00334".to_string()]);
+    assert!(attest(url, &policy).is_ok());
+}
+
+fn test_signature_invalid() {
+    assert!(attest(
+        "MOCK_IAS_SIGNATURE_INVALID_URL",
+        &AttestationPolicy::default()
+    )
+    .is_err());
+}
+
+fn test_corrupted_signature() {
+    // The quote status is OK, but the report's signature doesn't match.
+    assert!(attest(
+        "MOCK_IAS_CORRUPTED_SIGNATURE_URL",
+        &AttestationPolicy::default()
+    )
+    .is_err());
+}
+
+fn attest(
+    url_var: &str,
+    policy: &AttestationPolicy,
+) -> anyhow::Result<(AttestationReport, ReportSig, SigningCert)> {
+    let url = env::var(url_var).unwrap_or_else(|_| panic!("{} must be set", url_var));
+    RAService::remote_attestation_with_cert(&url, MOCK_SUB_KEY, &fake_quote(), policy)
+}
+
+/// The mock server doesn't verify the quote, so only the REPORTDATA is filled.
+fn fake_quote() -> String {
+    let mut quote = vec![0u8; QUOTE_BODY_SIZE];
+    quote[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + REPORT_DATA.len()].copy_from_slice(&REPORT_DATA);
+    base64::encode(&quote)
+}
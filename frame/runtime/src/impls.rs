@@ -89,7 +89,6 @@ macro_rules! __impl_inner_runtime {
             }
         }
 
-        #[cfg(feature = "sgx")]
         #[derive(Debug, Clone, Encode, Decode)]
         pub enum CallKind {
             $(
@@ -98,7 +97,6 @@ macro_rules! __impl_inner_runtime {
             )*
         }
 
-        #[cfg(feature = "sgx")]
         impl<G: ContextOps<S=StateType>> CallKindExecutor<G> for CallKind {
             type R = Runtime<G>;
             type S = StateType;
@@ -110,6 +108,12 @@ macro_rules! __impl_inner_runtime {
                 }
             }
 
+            fn id(&self) -> u32 {
+                match self {
+                    $( CallKind::$fn_name(_) => $fn_id, )*
+                }
+            }
+
             fn execute(self, runtime: Self::R, my_account_id: AccountId) -> Result<ReturnState<Self::S>> {
                 match self {
                     $( CallKind::$fn_name($fn_name) => {
@@ -123,12 +127,10 @@ macro_rules! __impl_inner_runtime {
             }
         }
 
-        #[cfg(feature = "sgx")]
         pub struct Runtime<G: ContextOps<S=StateType>> {
             db: G,
         }
 
-        #[cfg(feature = "sgx")]
         impl<G: ContextOps<S=StateType>> RuntimeExecutor<G> for Runtime<G> {
             type C = CallKind;
             type S = StateType;
@@ -144,7 +146,6 @@ macro_rules! __impl_inner_runtime {
             }
         }
 
-        #[cfg(feature = "sgx")]
         impl<G: ContextOps<S=StateType>> Runtime<G> {
             pub fn get_map<S: State>(
                 &self,
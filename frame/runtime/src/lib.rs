@@ -19,8 +19,6 @@ use serde_std as serde;
 pub mod impls;
 pub mod prelude;
 pub mod primitives;
-#[cfg(feature = "sgx")]
 pub mod traits;
 
-#[cfg(feature = "sgx")]
 pub use crate::traits::*;
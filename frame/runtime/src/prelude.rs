@@ -2,7 +2,6 @@ pub use crate::local_anyhow::{anyhow, ensure, Result};
 pub use crate::localstd::marker::PhantomData;
 pub use crate::localstd::prelude::v1::*;
 pub use crate::primitives::*;
-#[cfg(feature = "sgx")]
 pub use crate::traits::*;
 pub use crate::{
     __impl_inner_memory, __impl_inner_runtime, get_state, impl_memory, impl_runtime, return_update,
@@ -10,7 +9,7 @@ pub use crate::{
 };
 pub use codec::{Decode, Encode};
 pub use frame_common::{
-    crypto::{AccountId, OWNER_ACCOUNT_ID},
+    crypto::{AccountId, GLOBAL_ACCOUNT_ID},
     state_types::*,
     traits::*,
 };
@@ -1,17 +1,18 @@
 use crate::local_anyhow::Result;
-use crate::localstd::{
-    fmt::Debug,
-    string::String,
-    sync::{SgxRwLockReadGuard, SgxRwLockWriteGuard},
-    vec::Vec,
+#[cfg(feature = "std")]
+use crate::localstd::sync::{RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "sgx")]
+use crate::localstd::sync::{
+    SgxRwLockReadGuard as RwLockReadGuard, SgxRwLockWriteGuard as RwLockWriteGuard,
 };
+use crate::localstd::{fmt::Debug, string::String, vec::Vec};
 use codec::{Decode, Encode};
 use frame_common::{
-    crypto::{AccountId, Ciphertext, ExportHandshake, ExportPathSecret},
+    crypto::{AccountId, AttestedReport, Ciphertext, ExportHandshake, ExportPathSecret},
     state_types::{MemId, ReturnState, UpdatedState},
     traits::*,
 };
-use frame_treekem::{handshake::HandshakeParams, DhPubKey, EciesCiphertext};
+use frame_treekem::{DhPubKey, EciesCiphertext, SenderKey};
 
 /// Execute state transition functions from runtime
 pub trait RuntimeExecutor<G: ContextOps>: Sized {
@@ -28,15 +29,15 @@ pub trait CallKindExecutor<G: ContextOps>: Sized + Encode + Decode + Debug + Clo
     type S: State;
 
     fn new(id: u32, state: &mut [u8]) -> Result<Self>;
+    /// The call id which this call kind is created from
+    fn id(&self) -> u32;
     fn execute(self, runtime: Self::R, my_account_id: AccountId) -> Result<ReturnState<Self::S>>;
 }
 
 pub trait ContextOps:
-    StateOps + GroupKeyGetter + NotificationOps + IdentityKeyOps + QuoteGetter
+    StateOps + GroupKeyGetter + NotificationOps + IdentityKeyOps + QuoteGetter + AuditLogOps
 {
     fn mrenclave_ver(&self) -> usize;
-    fn ias_url(&self) -> &str;
-    fn sub_key(&self) -> &str;
 }
 
 /// A getter of state stored in enclave memory.
@@ -69,9 +70,21 @@ pub trait StateOps {
 pub trait GroupKeyGetter {
     type GK: GroupKeyOps;
 
-    fn read_group_key(&self) -> SgxRwLockReadGuard<Self::GK>;
+    fn read_group_key(&self) -> RwLockReadGuard<Self::GK>;
 
-    fn write_group_key(&self) -> SgxRwLockWriteGuard<Self::GK>;
+    fn write_group_key(&self) -> RwLockWriteGuard<Self::GK>;
+}
+
+pub trait AuditLogOps {
+    /// Append a state transition to the enclave's hash-chained and signed audit log.
+    /// The entry's sequence number is consumed even if the host fails to store it,
+    /// so that the missing entry is detected.
+    fn append_audit_log(
+        &self,
+        ciphertext_hash: [u8; 32],
+        call_id: u32,
+        updated_states_hash: [u8; 32],
+    ) -> Result<()>;
 }
 
 pub trait NotificationOps {
@@ -91,22 +104,37 @@ pub trait IdentityKeyOps {
 pub trait GroupKeyOps: Sized {
     fn create_handshake(&self) -> Result<(ExportHandshake, ExportPathSecret)>;
 
-    fn process_handshake(&mut self, handshake: &HandshakeParams) -> Result<()>;
-
-    fn encrypt(&self, plaintext: Vec<u8>) -> Result<Ciphertext>;
-
-    fn decrypt(&self, app_msg: &Ciphertext) -> Result<Option<Vec<u8>>>;
-
-    /// Ratchet sender's keychain per a transaction
-    fn sender_ratchet(&mut self, roster_idx: usize) -> Result<()>;
-
-    /// Ratchet receiver's keychain per a transaction
-    fn receiver_ratchet(&mut self, roster_idx: usize) -> Result<()>;
-
-    /// Syncing the sender and receiver app keychains
-    fn sync_ratchet(&mut self, roster_idx: usize, msg_gen: u32) -> Result<()>;
-
-    fn my_roster_idx(&self) -> u32;
+    /// Create a handshake removing the member of `removed_roster_idx` from the group
+    fn create_remove_handshake(
+        &self,
+        removed_roster_idx: u32,
+    ) -> Result<(ExportHandshake, ExportPathSecret)>;
+
+    /// Process a handshake stored in the contract of `contract_address`
+    /// after verifying that it is signed by the sender's attested identity key
+    fn process_handshake(
+        &mut self,
+        handshake: &ExportHandshake,
+        contract_address: &[u8],
+    ) -> Result<()>;
+
+    /// Decrypt a received message, ratcheting the receiver's keychain up to the message's generation.
+    /// Messages can arrive out of order within a bounded number of skipped generations.
+    fn decrypt(&mut self, app_msg: &Ciphertext) -> Result<Option<Vec<u8>>>;
+
+    /// Ratchet sender's keychain per a transaction and reserve the new generation for it.
+    /// The reserved key encrypts the transaction's message after the group key's lock is released.
+    fn reserve_sender_key(&mut self) -> Result<SenderKey>;
+
+    /// Returns an error if the roster index hasn't been assigned yet.
+    fn my_roster_idx(&self) -> Result<u32>;
+
+    /// Assign the roster index allocated by the contract before joining the group
+    fn set_my_roster_idx(&mut self, roster_idx: u32) -> Result<()>;
+
+    /// Attach the attestation of this enclave's identity key to the created handshakes
+    /// until the group registers its verifying key
+    fn set_my_attestation(&mut self, report: Vec<u8>, report_sig: Vec<u8>, signing_cert: Vec<u8>);
 }
 
 pub trait QuoteGetter: Sized {
@@ -114,4 +142,14 @@ pub trait QuoteGetter: Sized {
     /// QUOTE will be sent to Attestation Service to verify SGX's status.
     /// For more information: https://api.trustedservices.intel.com/documents/sgx-attestation-api-spec.pdf
     fn quote(&self) -> Result<String>;
+
+    /// Attest this enclave with the attestation service selected at runtime, i.e. IAS for EPID quotes
+    /// or the collateral from a PCCS for DCAP quotes.
+    /// The verified report is cached, and this enclave is attested again once its refresh time has passed.
+    fn attested_report(&self) -> Result<AttestedReport>;
+
+    /// Returns the attestation report, its signature and the report signing certificate.
+    fn remote_attestation(&self) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        self.attested_report().map(AttestedReport::into_parts)
+    }
 }
@@ -3,67 +3,97 @@ use crate::crypto::{
     hkdf,
     hmac::HmacKey,
     secrets::{AppMemberSecret, AppSecret},
+    suite::{CipherSuite, DefaultCipherSuite},
     SHA256_OUTPUT_LEN,
 };
 use crate::group_state::GroupState;
 use crate::local_anyhow::{anyhow, ensure, Result};
 use crate::local_log::warn;
-use crate::local_ring::aead::{
-    Aad, BoundKey, Nonce, OpeningKey, SealingKey, UnboundKey, AES_256_GCM,
+use crate::local_ring::aead::{Aad, BoundKey, Nonce, OpeningKey, SealingKey, UnboundKey};
+use crate::localstd::{
+    collections::VecDeque, convert::TryFrom, marker::PhantomData, prelude::v1::*,
 };
-use crate::localstd::{convert::TryFrom, prelude::v1::*};
 use crate::ratchet_tree::RatchetTreeNode;
 use codec::Encode;
 use frame_common::crypto::Ciphertext;
 
+/// The maximum number of generations which a received message can skip.
+pub const MAX_SKIPPED_GENERATIONS: u32 = 100;
+/// The maximum number of skipped keys kept over all members. The oldest one is dropped first.
+pub const MAX_SKIPPED_KEYS: usize = 1000;
+
 /// Application Keychain manages each member's `AppMemberSecret' and generation.
 #[derive(Debug, Clone, Default)]
-pub struct AppKeyChain {
+pub struct AppKeyChain<CS: CipherSuite = DefaultCipherSuite> {
     member_secrets_and_gens: Vec<(AppMemberSecret, u32)>,
+    /// `AppMemberSecret`s of the generations skipped by out-of-order messages,
+    /// which are keyed by roster index and generation, and are deleted once used.
+    skipped_keys: VecDeque<(u32, u32, AppMemberSecret)>,
     epoch: u32,
+    cipher_suite: PhantomData<CS>,
 }
 
-impl AppKeyChain {
+impl<CS: CipherSuite> AppKeyChain<CS> {
     /// Encrypt message with current member's application secret.
     pub fn encrypt_msg(
         &self,
-        mut plaintext: Vec<u8>,
-        group_state: &GroupState,
+        plaintext: Vec<u8>,
+        group_state: &GroupState<CS>,
     ) -> Result<Ciphertext> {
-        let my_roster_idx = group_state.my_roster_idx();
+        self.sender_key(group_state)?.encrypt_msg(plaintext)
+    }
 
-        let (ub_key, nonce_seq, generation) = self.key_nonce_gen(my_roster_idx as usize)?;
-        let mut sealing_key = SealingKey::new(ub_key, nonce_seq);
-        sealing_key.seal_in_place_append_tag(Aad::empty(), &mut plaintext)?;
+    /// Ratchet current member's application secret and reserve the new generation for a message.
+    /// The returned key encrypts the message without the keychain, so concurrent senders
+    /// only need the keychain exclusively while reserving their generations.
+    pub fn reserve_sender_key(&mut self, group_state: &GroupState<CS>) -> Result<SenderKey<CS>> {
+        let my_roster_idx = group_state
+            .my_roster_idx()
+            .ok_or_else(|| anyhow!("Cannot encrypt messages before joining the group"))?;
+        self.ratchet(my_roster_idx as usize)?;
 
-        let ciphertext = plaintext;
-        Ok(Ciphertext::new(
+        self.sender_key(group_state)
+    }
+
+    fn sender_key(&self, group_state: &GroupState<CS>) -> Result<SenderKey<CS>> {
+        let my_roster_idx = group_state
+            .my_roster_idx()
+            .ok_or_else(|| anyhow!("Cannot encrypt messages before joining the group"))?;
+        let (member_secret, generation) = self
+            .member_secrets_and_gens
+            .get(my_roster_idx as usize)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "sender_key: Roster index is out of range of application key chain. roster_idx: {:?}",
+                    my_roster_idx
+                )
+            })?;
+
+        Ok(SenderKey {
+            member_secret,
             generation,
-            group_state.epoch(),
-            my_roster_idx,
-            ciphertext,
-        ))
+            epoch: group_state.epoch(),
+            roster_idx: my_roster_idx,
+            group_id: group_state.group_id().to_vec(),
+            cipher_suite: PhantomData,
+        })
     }
 
     /// Decrypt messag with current member's application secret.
     pub fn decrypt_msg(
         &self,
         app_msg: &Ciphertext,
-        group_state: &GroupState,
+        group_state: &GroupState<CS>,
     ) -> Result<Option<Vec<u8>>> {
-        match group_state.my_node()? {
-            // If current my node contains a DhKeypair, cannot decrypt message because you haven't join the group.
-            RatchetTreeNode::Blank => {
+        match group_state.my_node() {
+            // If current my node doesn't contain a DhKeypair, cannot decrypt message because you haven't join the group.
+            None | Some(RatchetTreeNode::Blank) => {
                 warn!("The received message is ignored because your enclave hasn't join the group yet");
                 Ok(None)
             }
-            _ => {
-                ensure!(
-                    app_msg.epoch() == self.epoch,
-                    "The received messages's epoch ({:?}) differs from the current key_chain's ({:?})",
-                    app_msg.epoch(),
-                    self.epoch
-                );
+            Some(_) => {
+                self.ensure_msg_context(app_msg)?;
 
                 let (ub_key, nonce_seq, generation) =
                     self.key_nonce_gen(app_msg.roster_idx() as usize)?;
@@ -72,16 +102,107 @@ impl AppKeyChain {
                     "The received messages's generation ({:?}) differs from the current AppMemberSecret's ({:?})", app_msg.generation(), generation
                 );
 
-                let mut ciphertext = app_msg.encrypted_state_ref().to_vec();
-                let mut opening_key = OpeningKey::new(ub_key, nonce_seq);
-                let plaintext = opening_key.open_in_place(Aad::empty(), &mut ciphertext)?;
+                open_msg(ub_key, nonce_seq, app_msg, group_state).map(Some)
+            }
+        }
+    }
 
-                Ok(Some(plaintext.to_vec()))
+    /// Decrypt a received message even if it arrives out of order.
+    /// If the message skips generations, the keychain ratchets forward to the message's generation
+    /// and keeps the keys of the skipped generations, so that the messages arriving later are decrypted once.
+    /// A message of the current or an earlier generation is accepted only with a still-unused skipped key,
+    /// so that no message is decrypted twice.
+    /// The keychain is updated only if the message is decrypted successfully.
+    pub fn receive_msg(
+        &mut self,
+        app_msg: &Ciphertext,
+        group_state: &GroupState<CS>,
+    ) -> Result<Option<Vec<u8>>> {
+        match group_state.my_node() {
+            None | Some(RatchetTreeNode::Blank) => {
+                warn!("The received message is ignored because your enclave hasn't join the group yet");
+                return Ok(None);
             }
+            Some(_) => self.ensure_msg_context(app_msg)?,
         }
+
+        let roster_idx = app_msg.roster_idx();
+        let msg_gen = app_msg.generation();
+        let (member_secret, current_gen) = self
+            .member_secrets_and_gens
+            .get(roster_idx as usize)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "receive_msg: Roster index is out of range of application key chain. roster_idx: {:?}",
+                    roster_idx
+                )
+            })?;
+
+        // The message of a skipped generation, or a replayed one
+        if msg_gen <= current_gen {
+            let pos = self
+                .skipped_keys
+                .iter()
+                .position(|(r, g, _)| *r == roster_idx && *g == msg_gen)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The key of the generation ({:?}) of roster index ({:?}) has already been used or dropped",
+                        msg_gen,
+                        roster_idx
+                    )
+                })?;
+            let (ub_key, nonce_seq) = derive_key_nonce::<CS>(&self.skipped_keys[pos].2)?;
+            let plaintext = open_msg(ub_key, nonce_seq, app_msg, group_state)?;
+            self.skipped_keys.remove(pos);
+
+            return Ok(Some(plaintext));
+        }
+
+        ensure!(
+            msg_gen - current_gen <= MAX_SKIPPED_GENERATIONS,
+            "The received message's generation ({:?}) skips too many generations from the current one ({:?})",
+            msg_gen,
+            current_gen
+        );
+        let mut member_secret = member_secret;
+        let mut skipped = Vec::with_capacity((msg_gen - current_gen) as usize);
+        for gen in (current_gen + 1)..=msg_gen {
+            member_secret = next_member_secret(&member_secret, roster_idx)?;
+            if gen != msg_gen {
+                skipped.push((roster_idx, gen, member_secret.clone()));
+            }
+        }
+
+        let (ub_key, nonce_seq) = derive_key_nonce::<CS>(&member_secret)?;
+        let plaintext = open_msg(ub_key, nonce_seq, app_msg, group_state)?;
+
+        self.member_secrets_and_gens[roster_idx as usize] = (member_secret, msg_gen);
+        self.skipped_keys.extend(skipped);
+        while self.skipped_keys.len() > MAX_SKIPPED_KEYS {
+            self.skipped_keys.pop_front();
+        }
+
+        Ok(Some(plaintext))
     }
 
-    pub(crate) fn from_app_secret(group_state: &GroupState, app_secret: AppSecret) -> Self {
+    fn ensure_msg_context(&self, app_msg: &Ciphertext) -> Result<()> {
+        ensure!(
+            app_msg.cipher_suite() == CS::ID,
+            "The received message's cipher suite ({:?}) differs from the group's one ({:?})",
+            app_msg.cipher_suite(),
+            CS::ID
+        );
+        ensure!(
+            app_msg.epoch() == self.epoch,
+            "The received messages's epoch ({:?}) differs from the current key_chain's ({:?})",
+            app_msg.epoch(),
+            self.epoch
+        );
+        Ok(())
+    }
+
+    pub(crate) fn from_app_secret(group_state: &GroupState<CS>, app_secret: AppSecret) -> Self {
         let roster_len = match group_state.epoch() {
             0 => 1, // At the very first epoch, roster length should not be considered empty.
             _ => {
@@ -106,7 +227,9 @@ impl AppKeyChain {
 
         AppKeyChain {
             member_secrets_and_gens,
+            skipped_keys: VecDeque::new(),
             epoch: group_state.epoch(),
+            cipher_suite: PhantomData,
         }
     }
 
@@ -118,15 +241,7 @@ impl AppKeyChain {
             .ok_or_else(|| {
                 anyhow!("ratchet: Roster index is out of range of application key chain")
             })?;
-        let current_secret = member_secret.clone();
-
-        let roster_idx = u32::try_from(roster_idx)?;
-        hkdf::expand_label(
-            &current_secret.into(),
-            b"app sender",
-            &roster_idx.encode(),
-            member_secret.as_mut_bytes(),
-        )?;
+        *member_secret = next_member_secret(member_secret, u32::try_from(roster_idx)?)?;
 
         *gen = gen
             .checked_add(1)
@@ -135,6 +250,10 @@ impl AppKeyChain {
         Ok(())
     }
 
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
     pub fn generation(&self, roster_idx: usize) -> Result<u32> {
         let (_, gen) =
             self.member_secrets_and_gens
@@ -154,33 +273,136 @@ impl AppKeyChain {
                     anyhow!("key_nonce_gen: Roster index is out of range of application key chain. roster_idx: {:?}, key chain length: {:?}", roster_idx, self.member_secrets_and_gens.len())
                 })?;
 
-        let prk = HmacKey::from(member_secret);
-        let mut key_buf = [0u8; AES_256_GCM_KEY_SIZE];
-        let nonce_buf = [0u8; AES_256_GCM_NONCE_SIZE];
-        hkdf::expand_label(&prk, b"key", b"", &mut key_buf)?;
-        hkdf::expand_label(&prk, b"nonce", b"", &mut key_buf)?;
-
-        let ub_key = UnboundKey::new(&AES_256_GCM, &key_buf)?;
-        let nonce = Nonce::assume_unique_for_key(nonce_buf);
-        let nonce_seq = OneNonceSequence::new(nonce);
+        let (ub_key, nonce_seq) = derive_key_nonce::<CS>(member_secret)?;
 
         Ok((ub_key, nonce_seq, *gen))
     }
 }
 
+/// The application secret of a generation reserved by `AppKeyChain::reserve_sender_key`.
+/// It's consumed by encrypting a message, so that the nonce of the generation is never reused.
+#[derive(Debug)]
+pub struct SenderKey<CS: CipherSuite = DefaultCipherSuite> {
+    member_secret: AppMemberSecret,
+    generation: u32,
+    epoch: u32,
+    roster_idx: u32,
+    group_id: Vec<u8>,
+    cipher_suite: PhantomData<CS>,
+}
+
+impl<CS: CipherSuite> SenderKey<CS> {
+    pub fn encrypt_msg(self, mut plaintext: Vec<u8>) -> Result<Ciphertext> {
+        let (ub_key, nonce_seq) = derive_key_nonce::<CS>(&self.member_secret)?;
+        let aad = msg_aad::<CS>(&self.group_id, self.generation, self.epoch, self.roster_idx);
+        let mut sealing_key = SealingKey::new(ub_key, nonce_seq);
+        sealing_key.seal_in_place_append_tag(Aad::from(aad), &mut plaintext)?;
+
+        let ciphertext = plaintext;
+        Ok(Ciphertext::new(
+            self.generation,
+            self.epoch,
+            self.roster_idx,
+            CS::ID,
+            ciphertext,
+        ))
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+fn next_member_secret(member_secret: &AppMemberSecret, roster_idx: u32) -> Result<AppMemberSecret> {
+    let mut next_secret = member_secret.clone();
+    hkdf::expand_label(
+        &member_secret.into(),
+        b"app sender",
+        &roster_idx.encode(),
+        next_secret.as_mut_bytes(),
+    )?;
+
+    Ok(next_secret)
+}
+
+fn derive_key_nonce<CS: CipherSuite>(
+    member_secret: &AppMemberSecret,
+) -> Result<(UnboundKey, OneNonceSequence)> {
+    let prk = HmacKey::from(member_secret);
+    let mut key_buf = [0u8; AES_256_GCM_KEY_SIZE];
+    let nonce_buf = [0u8; AES_256_GCM_NONCE_SIZE];
+    hkdf::expand_label(&prk, b"key", b"", &mut key_buf)?;
+    hkdf::expand_label(&prk, b"nonce", b"", &mut key_buf)?;
+
+    let ub_key = UnboundKey::new(CS::aead(), &key_buf)?;
+    let nonce = Nonce::assume_unique_for_key(nonce_buf);
+    let nonce_seq = OneNonceSequence::new(nonce);
+
+    Ok((ub_key, nonce_seq))
+}
+
+/// The header fields of a message and the group id are authenticated as associated data,
+/// so that the messages tampered with on-chain or replayed to another group are rejected.
+fn msg_aad<CS: CipherSuite>(
+    group_id: &[u8],
+    generation: u32,
+    epoch: u32,
+    roster_idx: u32,
+) -> Vec<u8> {
+    (group_id, generation, epoch, roster_idx, CS::ID).encode()
+}
+
+fn open_msg<CS: CipherSuite>(
+    ub_key: UnboundKey,
+    nonce_seq: OneNonceSequence,
+    app_msg: &Ciphertext,
+    group_state: &GroupState<CS>,
+) -> Result<Vec<u8>> {
+    let aad = msg_aad::<CS>(
+        group_state.group_id(),
+        app_msg.generation(),
+        app_msg.epoch(),
+        app_msg.roster_idx(),
+    );
+    let mut ciphertext = app_msg.encrypted_state_ref().to_vec();
+    let mut opening_key = OpeningKey::new(ub_key, nonce_seq);
+    let plaintext = opening_key.open_in_place(Aad::from(aad), &mut ciphertext)?;
+
+    Ok(plaintext.to_vec())
+}
+
+#[cfg(feature = "sgx")]
 #[cfg(debug_assertions)]
 pub(crate) mod tests {
     use super::*;
-    use crate::handshake::{PathSecretKVS, PathSecretSource};
+    use crate::crypto::{secrets::PathSecret, suite::X25519ChaCha20Poly1305Sha256};
+    use crate::handshake::{Handshake, PathSecretKVS, PathSecretSource};
     use crate::test_funcs;
-    #[cfg(feature = "sgx")]
     use test_utils::*;
 
     pub(crate) fn run_tests() -> bool {
-        run_tests!(test_app_msg_correctness,)
+        run_tests!(
+            test_app_msg_correctness,
+            test_app_msg_correctness_x25519,
+            test_remove_member,
+            test_group_grows_as_members_join,
+            test_out_of_order_msgs,
+            test_reject_replayed_msg,
+            test_reject_tampered_header,
+            test_reserved_sender_keys,
+            test_reject_mismatched_path_secret,
+        )
     }
 
     fn test_app_msg_correctness() {
+        app_msg_correctness::<DefaultCipherSuite>();
+    }
+
+    fn test_app_msg_correctness_x25519() {
+        app_msg_correctness::<X25519ChaCha20Poly1305Sha256>();
+    }
+
+    fn app_msg_correctness<CS: CipherSuite>() {
         crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
         let msg = b"app msg correctnesss test";
 
@@ -188,9 +410,9 @@ pub(crate) mod tests {
         test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
         let source = PathSecretSource::LocalTestKV(kvs);
 
-        let mut group_state1 = GroupState::new(0).unwrap();
-        let mut group_state2 = GroupState::new(1).unwrap();
-        let mut group_state3 = GroupState::new(2).unwrap();
+        let mut group_state1 = GroupState::<CS>::new(0).unwrap();
+        let mut group_state2 = GroupState::<CS>::new(1).unwrap();
+        let mut group_state3 = GroupState::<CS>::new(2).unwrap();
 
         // Add member1
         let (_key_chain1_epoch1, _key_chain2_epoch1, _key_chain3_epoch1) =
@@ -387,4 +609,404 @@ pub(crate) mod tests {
             &mut key_chain2_epoch6,
         );
     }
+
+    fn test_remove_member() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+        let msg = b"remove member test";
+
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        let mut group_state1: GroupState = GroupState::new(0).unwrap();
+        let mut group_state2: GroupState = GroupState::new(1).unwrap();
+        let mut group_state3: GroupState = GroupState::new(2).unwrap();
+
+        // Add member1, member2 and member3
+        let _ = test_funcs::do_handshake_three_party(
+            &mut group_state1,
+            &mut group_state2,
+            &mut group_state3,
+            &source,
+        );
+        let _ = test_funcs::do_handshake_three_party(
+            &mut group_state2,
+            &mut group_state1,
+            &mut group_state3,
+            &source,
+        );
+        let (_, _, mut key_chain3_epoch3) = test_funcs::do_handshake_three_party(
+            &mut group_state3,
+            &mut group_state1,
+            &mut group_state2,
+            &source,
+        );
+
+        // member1 removes member3
+        let (mut key_chain1_epoch4, mut key_chain2_epoch4) = test_funcs::do_remove_handshake(
+            &mut group_state1,
+            &mut group_state2,
+            &mut group_state3,
+            &source,
+        );
+        assert_eq!(group_state1.epoch(), group_state2.epoch());
+        assert_ne!(group_state1.epoch(), group_state3.epoch());
+
+        // 1 --> 2
+        let app_msg = key_chain1_epoch4
+            .encrypt_msg(msg.to_vec(), &group_state1)
+            .unwrap();
+        let plaintext1 = key_chain1_epoch4
+            .decrypt_msg(&app_msg, &group_state1)
+            .unwrap()
+            .unwrap();
+        let plaintext2 = key_chain2_epoch4
+            .decrypt_msg(&app_msg, &group_state2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(plaintext1.as_slice(), msg);
+        assert_eq!(plaintext2.as_slice(), msg);
+
+        // The removed member cannot decrypt messages in the new epoch.
+        assert!(key_chain3_epoch3
+            .decrypt_msg(&app_msg, &group_state3)
+            .is_err());
+
+        // The remaining members can keep updating the group.
+        let (handshake, _) = group_state2.create_handshake(&source).unwrap();
+        let dummy_fn = |_: &[u8]| Err(anyhow!("This is dummy_fn"));
+        group_state2
+            .process_handshake(&handshake, &source, dummy_fn)
+            .unwrap();
+        group_state1
+            .process_handshake(&handshake, &source, dummy_fn)
+            .unwrap();
+        assert!(group_state3
+            .process_handshake(&handshake, &source, dummy_fn)
+            .is_err());
+    }
+
+    fn test_group_grows_as_members_join() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+        let msg = b"group grows test";
+
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        let mut group_states: Vec<GroupState> =
+            (0..4).map(|_| GroupState::new_unassigned()).collect();
+
+        // Each member is assigned the next roster index just before joining,
+        // so the tree grows beyond its initial size.
+        let key_chains = (0..4)
+            .map(|roster_idx| {
+                group_states[roster_idx]
+                    .set_my_roster_idx(roster_idx as u32)
+                    .unwrap();
+                let key_chains =
+                    test_funcs::do_handshake_n_party(roster_idx, &mut group_states, &source);
+                assert!(group_states[roster_idx].is_member());
+                key_chains
+            })
+            .last()
+            .unwrap();
+        assert!(group_states[3].set_my_roster_idx(4).is_err());
+
+        // 4 --> 1
+        let app_msg = key_chains[3]
+            .encrypt_msg(msg.to_vec(), &group_states[3])
+            .unwrap();
+        let plaintext = key_chains[0]
+            .decrypt_msg(&app_msg, &group_states[0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(plaintext.as_slice(), msg);
+
+        // A member cannot skip the next leaf of the tree.
+        let group_state6: GroupState = GroupState::new(5).unwrap();
+        assert!(group_state6.create_handshake(&source).is_err());
+    }
+
+    fn test_out_of_order_msgs() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        let mut group_state1: GroupState = GroupState::new(0).unwrap();
+        let mut group_state2: GroupState = GroupState::new(1).unwrap();
+        let mut group_state3: GroupState = GroupState::new(2).unwrap();
+        let _ = test_funcs::do_handshake_three_party(
+            &mut group_state1,
+            &mut group_state2,
+            &mut group_state3,
+            &source,
+        );
+        let (mut key_chain1, mut key_chain2, _) = test_funcs::do_handshake_three_party(
+            &mut group_state2,
+            &mut group_state1,
+            &mut group_state3,
+            &source,
+        );
+
+        // member1 sends the messages of generation 1, 2 and 3.
+        let app_msgs: Vec<Ciphertext> = (1..=3u8)
+            .map(|i| {
+                key_chain1.ratchet(0).unwrap();
+                key_chain1.encrypt_msg(vec![i], &group_state1).unwrap()
+            })
+            .collect();
+
+        // member2 receives them in the order of 3, 1, 2.
+        for &i in &[2, 0, 1] {
+            let plaintext = key_chain2
+                .receive_msg(&app_msgs[i], &group_state2)
+                .unwrap()
+                .unwrap();
+            assert_eq!(plaintext, vec![i as u8 + 1]);
+        }
+        assert_eq!(key_chain2.generation(0).unwrap(), 3);
+        // A skipped key can be used only once.
+        assert!(key_chain2.receive_msg(&app_msgs[0], &group_state2).is_err());
+
+        // A tampered message doesn't move the keychain forward.
+        key_chain1.ratchet(0).unwrap();
+        let app_msg = key_chain1.encrypt_msg(vec![4], &group_state1).unwrap();
+        let tampered = Ciphertext::new(
+            app_msg.generation() + 1,
+            app_msg.epoch(),
+            app_msg.roster_idx(),
+            app_msg.cipher_suite(),
+            app_msg.encrypted_state_ref().to_vec(),
+        );
+        assert!(key_chain2.receive_msg(&tampered, &group_state2).is_err());
+        assert_eq!(key_chain2.generation(0).unwrap(), 3);
+        let plaintext = key_chain2
+            .receive_msg(&app_msg, &group_state2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(plaintext, vec![4]);
+
+        // A message cannot skip too many generations.
+        for _ in 0..=MAX_SKIPPED_GENERATIONS {
+            key_chain1.ratchet(0).unwrap();
+        }
+        let app_msg = key_chain1.encrypt_msg(vec![5], &group_state1).unwrap();
+        assert!(key_chain2.receive_msg(&app_msg, &group_state2).is_err());
+    }
+
+    fn test_reject_replayed_msg() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        let mut group_state1: GroupState = GroupState::new(0).unwrap();
+        let mut group_state2: GroupState = GroupState::new(1).unwrap();
+        let mut group_state3: GroupState = GroupState::new(2).unwrap();
+        let _ = test_funcs::do_handshake_three_party(
+            &mut group_state1,
+            &mut group_state2,
+            &mut group_state3,
+            &source,
+        );
+        let (mut key_chain1, mut key_chain2, _) = test_funcs::do_handshake_three_party(
+            &mut group_state2,
+            &mut group_state1,
+            &mut group_state3,
+            &source,
+        );
+
+        // No message is sent with the initial generation.
+        let initial_msg = key_chain1.encrypt_msg(vec![0], &group_state1).unwrap();
+        assert!(key_chain2.receive_msg(&initial_msg, &group_state2).is_err());
+
+        let app_msg = key_chain1
+            .reserve_sender_key(&group_state1)
+            .unwrap()
+            .encrypt_msg(vec![1])
+            .unwrap();
+        let plaintext = key_chain2
+            .receive_msg(&app_msg, &group_state2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(plaintext, vec![1]);
+
+        // The latest message is replayed.
+        assert!(key_chain2.receive_msg(&app_msg, &group_state2).is_err());
+        assert_eq!(key_chain2.generation(0).unwrap(), 1);
+    }
+
+    fn test_reject_tampered_header() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+        let msg = b"tampered header test";
+
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        // The same members form two groups in different contracts by processing the same handshakes,
+        // so their keychains have the same secrets and only their group ids differ.
+        let mut groups: Vec<GroupState> = (0..3).map(|i| GroupState::new(i).unwrap()).collect();
+        let mut other_groups = groups.clone();
+        for group in &mut groups {
+            group.set_contract_address(&[1u8; 20]).unwrap();
+        }
+        for group in &mut other_groups {
+            group.set_contract_address(&[2u8; 20]).unwrap();
+        }
+        let dummy_fn = |_: &[u8]| Err(anyhow!("This is dummy_fn"));
+        let mut key_chains = vec![];
+        let mut other_key_chains = vec![];
+        for sender in 0..2 {
+            let (handshake, _) = groups[sender].create_handshake(&source).unwrap();
+            key_chains = groups
+                .iter_mut()
+                .map(|group| {
+                    group
+                        .process_handshake(&handshake, &source, dummy_fn)
+                        .unwrap()
+                })
+                .collect();
+            other_key_chains = other_groups
+                .iter_mut()
+                .map(|group| {
+                    group
+                        .process_handshake(&handshake, &source, dummy_fn)
+                        .unwrap()
+                })
+                .collect();
+        }
+        assert!(!groups[0].group_id().is_empty());
+        assert_eq!(groups[0].group_id(), groups[1].group_id());
+        assert_ne!(groups[1].group_id(), other_groups[1].group_id());
+
+        let app_msg = key_chains[0]
+            .reserve_sender_key(&groups[0])
+            .unwrap()
+            .encrypt_msg(msg.to_vec())
+            .unwrap();
+
+        // The tampered header fields are rejected before the message is opened.
+        let tamper = |generation: u32, epoch: u32, roster_idx: u32, cipher_suite: u16| {
+            Ciphertext::new(
+                generation,
+                epoch,
+                roster_idx,
+                cipher_suite,
+                app_msg.encrypted_state_ref().to_vec(),
+            )
+        };
+        let tampered_msgs = vec![
+            tamper(2, app_msg.epoch(), 0, app_msg.cipher_suite()),
+            tamper(1, app_msg.epoch() + 1, 0, app_msg.cipher_suite()),
+            tamper(1, app_msg.epoch(), 1, app_msg.cipher_suite()),
+            tamper(1, app_msg.epoch(), 0, app_msg.cipher_suite() + 1),
+        ];
+        for tampered in &tampered_msgs {
+            assert!(key_chains[1].decrypt_msg(tampered, &groups[1]).is_err());
+        }
+
+        // The message passes all the checks of the header in the other group, and its key is the same,
+        // so it's only rejected by the group id in the associated data.
+        assert!(other_key_chains[1]
+            .receive_msg(&app_msg, &other_groups[1])
+            .is_err());
+        assert_eq!(other_key_chains[1].generation(0).unwrap(), 0);
+
+        let plaintext = key_chains[1]
+            .receive_msg(&app_msg, &groups[1])
+            .unwrap()
+            .unwrap();
+        assert_eq!(plaintext.as_slice(), msg);
+
+        // The same message sent in the other group is accepted there.
+        let other_app_msg = other_key_chains[0]
+            .reserve_sender_key(&other_groups[0])
+            .unwrap()
+            .encrypt_msg(msg.to_vec())
+            .unwrap();
+        let plaintext = other_key_chains[1]
+            .receive_msg(&other_app_msg, &other_groups[1])
+            .unwrap()
+            .unwrap();
+        assert_eq!(plaintext.as_slice(), msg);
+    }
+
+    fn test_reserved_sender_keys() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        let mut group_state1: GroupState = GroupState::new(0).unwrap();
+        let mut group_state2: GroupState = GroupState::new(1).unwrap();
+        let mut group_state3: GroupState = GroupState::new(2).unwrap();
+        let _ = test_funcs::do_handshake_three_party(
+            &mut group_state1,
+            &mut group_state2,
+            &mut group_state3,
+            &source,
+        );
+        let (mut key_chain1, mut key_chain2, _) = test_funcs::do_handshake_three_party(
+            &mut group_state2,
+            &mut group_state1,
+            &mut group_state3,
+            &source,
+        );
+
+        // Each reservation gets its own generation, and the keys are used in any order.
+        let sender_keys: Vec<SenderKey> = (0..3)
+            .map(|_| key_chain1.reserve_sender_key(&group_state1).unwrap())
+            .collect();
+        let generations: Vec<u32> = sender_keys.iter().map(|k| k.generation()).collect();
+        assert_eq!(generations, vec![1, 2, 3]);
+        assert_eq!(key_chain1.generation(0).unwrap(), 3);
+
+        let app_msgs: Vec<Ciphertext> = sender_keys
+            .into_iter()
+            .rev()
+            .zip((1..=3u8).rev())
+            .map(|(key, i)| key.encrypt_msg(vec![i]).unwrap())
+            .collect();
+        for app_msg in &app_msgs {
+            let plaintext = key_chain2
+                .receive_msg(app_msg, &group_state2)
+                .unwrap()
+                .unwrap();
+            assert_eq!(plaintext, vec![app_msg.generation() as u8]);
+        }
+        assert_eq!(key_chain2.generation(0).unwrap(), 3);
+    }
+
+    fn test_reject_mismatched_path_secret() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        let mut group_state: GroupState = GroupState::new(0).unwrap();
+        let (handshake, export_path_secret) = group_state.create_handshake(&source).unwrap();
+
+        // A path secret forged by the host or the key vault
+        let forged = PathSecret::new_from_random_sgx()
+            .try_into_exporting(0, handshake.hash().as_ref())
+            .unwrap();
+        let mut forged_group_state = group_state.clone();
+        assert!(forged_group_state
+            .process_handshake(&handshake, &PathSecretSource::Local, |_| Ok(forged))
+            .is_err());
+
+        group_state
+            .process_handshake(&handshake, &PathSecretSource::Local, |_| {
+                Ok(export_path_secret)
+            })
+            .unwrap();
+        assert_eq!(group_state.epoch(), 1);
+    }
 }
@@ -80,7 +80,8 @@ impl Encode for DhPubKey {
 impl Decode for DhPubKey {
     fn decode<I: Input>(value: &mut I) -> Result<Self, Error> {
         let buf = <[u8; 33]>::decode(value)?;
-        let pubkey = PublicKey::parse_compressed(&buf).unwrap();
+        let pubkey = PublicKey::parse_compressed(&buf)
+            .map_err(|_| Error::from("Invalid compressed public key"))?;
         Ok(DhPubKey(pubkey))
     }
 }
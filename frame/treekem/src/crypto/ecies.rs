@@ -1,11 +1,12 @@
 use super::{
-    dh::{decapsulate, encapsulate, DhPrivateKey, DhPubKey},
+    dh::{DhPrivateKey, DhPubKey},
     hkdf,
     hmac::HmacKey,
+    suite::{CipherSuite, DefaultCipherSuite},
 };
 use crate::local_anyhow::{anyhow, Result};
 use crate::local_ring::aead::{
-    Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey, AES_256_GCM,
+    Aad, Algorithm, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey,
 };
 use crate::localstd::vec::Vec;
 use codec::{Decode, Encode};
@@ -14,25 +15,55 @@ use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "std")]
 #[derive(Debug, Clone, Encode, Decode, Default, Serialize, Deserialize)]
-pub struct EciesCiphertext {
-    ephemeral_public_key: DhPubKey,
+#[serde(bound(
+    serialize = "CS::PubKey: Serialize",
+    deserialize = "CS::PubKey: Deserialize<'de>"
+))]
+pub struct EciesCiphertext<CS: CipherSuite = DefaultCipherSuite> {
+    ephemeral_public_key: CS::PubKey,
     ciphertext: Vec<u8>,
 }
 
 #[cfg(feature = "sgx")]
 #[derive(Debug, Clone, Encode, Decode)]
-pub struct EciesCiphertext {
-    ephemeral_public_key: DhPubKey,
+pub struct EciesCiphertext<CS: CipherSuite = DefaultCipherSuite> {
+    ephemeral_public_key: CS::PubKey,
     ciphertext: Vec<u8>,
 }
 
 impl EciesCiphertext {
-    pub fn encrypt(others_pub_key: &DhPubKey, mut plaintext: Vec<u8>) -> Result<Self> {
-        let my_ephemeral_secret = DhPrivateKey::from_random()?;
-        let my_ephemeral_pub_key = DhPubKey::from_private_key(&my_ephemeral_secret);
+    /// Encrypt with the default cipher suite, which is the same as the enclave's identity key.
+    pub fn encrypt(others_pub_key: &DhPubKey, plaintext: Vec<u8>) -> Result<Self> {
+        Self::seal(others_pub_key, plaintext)
+    }
+
+    pub fn decrypt(self, my_priv_key: &DhPrivateKey) -> Result<Vec<u8>> {
+        self.open(my_priv_key)
+    }
+}
+
+impl<CS: CipherSuite> EciesCiphertext<CS> {
+    pub fn from_parts(ephemeral_public_key: CS::PubKey, ciphertext: Vec<u8>) -> Self {
+        EciesCiphertext {
+            ephemeral_public_key,
+            ciphertext,
+        }
+    }
+
+    pub fn ephemeral_public_key(&self) -> &CS::PubKey {
+        &self.ephemeral_public_key
+    }
 
-        let aes_key = encapsulate(&my_ephemeral_secret, &others_pub_key)?;
-        let (ub_key, nonce_seq) = derive_ecies_key_nonce(&aes_key)?;
+    pub fn ciphertext(&self) -> &[u8] {
+        &self.ciphertext
+    }
+
+    pub fn seal(others_pub_key: &CS::PubKey, mut plaintext: Vec<u8>) -> Result<Self> {
+        let my_ephemeral_secret = CS::private_key_from_random()?;
+        let my_ephemeral_pub_key = CS::public_key(&my_ephemeral_secret);
+
+        let aead_key = CS::encapsulate(&my_ephemeral_secret, &others_pub_key)?;
+        let (ub_key, nonce_seq) = derive_ecies_key_nonce(&aead_key, CS::aead())?;
         let mut sealing_key = SealingKey::new(ub_key, nonce_seq);
         sealing_key
             .seal_in_place_append_tag(Aad::empty(), &mut plaintext)
@@ -46,9 +77,9 @@ impl EciesCiphertext {
         })
     }
 
-    pub fn decrypt(self, my_priv_key: &DhPrivateKey) -> Result<Vec<u8>> {
-        let aes_key = decapsulate(&my_priv_key, &self.ephemeral_public_key)?;
-        let (ub_key, nonce_seq) = derive_ecies_key_nonce(&aes_key)?;
+    pub fn open(self, my_priv_key: &CS::PrivKey) -> Result<Vec<u8>> {
+        let aead_key = CS::decapsulate(&my_priv_key, &self.ephemeral_public_key)?;
+        let (ub_key, nonce_seq) = derive_ecies_key_nonce(&aead_key, CS::aead())?;
         let mut opening_key = OpeningKey::new(ub_key, nonce_seq);
 
         let mut ciphertext = self.ciphertext;
@@ -75,7 +106,10 @@ impl EciesLabel {
     }
 }
 
-fn derive_ecies_key_nonce(shared_secret_bytes: &[u8]) -> Result<(UnboundKey, OneNonceSequence)> {
+fn derive_ecies_key_nonce(
+    shared_secret_bytes: &[u8],
+    algorithm: &'static Algorithm,
+) -> Result<(UnboundKey, OneNonceSequence)> {
     let key_label = EciesLabel::new(b"key", AES_256_GCM_KEY_SIZE as u16);
     let nonce_label = EciesLabel::new(b"nonce", AES_256_GCM_NONCE_SIZE as u16);
 
@@ -86,13 +120,14 @@ fn derive_ecies_key_nonce(shared_secret_bytes: &[u8]) -> Result<(UnboundKey, One
     hkdf::expand(&prk, &key_label, &mut key_buf[..], hkdf::Aes256GcmKey)?;
     hkdf::expand(&prk, &nonce_label, &mut nonce_buf[..], hkdf::Aes256GcmNonce)?;
 
-    let ub_key = UnboundKey::new(&AES_256_GCM, &key_buf).map_err(|e| anyhow!("{:?}", e))?;
+    let ub_key = UnboundKey::new(algorithm, &key_buf).map_err(|e| anyhow!("{:?}", e))?;
     let nonce = Nonce::assume_unique_for_key(nonce_buf);
     let nonce_seq = OneNonceSequence::new(nonce);
 
     Ok((ub_key, nonce_seq))
 }
 
+/// Both AES-256-GCM and ChaCha20-Poly1305 take a 256-bit key and a 96-bit nonce.
 pub const AES_256_GCM_KEY_SIZE: usize = 256 / 8;
 pub const AES_256_GCM_NONCE_SIZE: usize = 96 / 8;
 
@@ -118,11 +153,12 @@ impl NonceSequence for OneNonceSequence {
 #[cfg(debug_assertions)]
 pub(crate) mod tests {
     use super::*;
+    use crate::crypto::suite::X25519ChaCha20Poly1305Sha256;
     use crate::localstd::string::String;
     use test_utils::*;
 
     pub(crate) fn run_tests() -> bool {
-        run_tests!(test_ecies_correctness,)
+        run_tests!(test_ecies_correctness, test_ecies_correctness_x25519,)
     }
 
     fn test_ecies_correctness() {
@@ -135,4 +171,18 @@ pub(crate) mod tests {
 
         assert_eq!(recovered_plaintext, plaintext);
     }
+
+    fn test_ecies_correctness_x25519() {
+        type CS = X25519ChaCha20Poly1305Sha256;
+        let plaintext = b"ecies correctness test";
+        let priv_key = CS::private_key_from_random().unwrap();
+        let pub_key = CS::public_key(&priv_key);
+
+        let ciphertext = EciesCiphertext::<CS>::seal(&pub_key, plaintext.to_vec()).unwrap();
+        let recovered_plaintext = ciphertext.clone().open(&priv_key).unwrap();
+        assert_eq!(recovered_plaintext, plaintext);
+
+        let other_priv_key = CS::private_key_from_random().unwrap();
+        assert!(ciphertext.open(&other_priv_key).is_err());
+    }
 }
@@ -4,6 +4,8 @@ pub mod hash;
 pub mod hkdf;
 pub mod hmac;
 pub mod secrets;
+pub mod suite;
+pub mod x25519;
 
 pub const SHA256_OUTPUT_LEN: usize = 256 / 8;
 
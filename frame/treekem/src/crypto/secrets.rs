@@ -5,15 +5,9 @@
 //! -> app_secret
 //! -> app_keychain
 
-use super::{
-    dh::{DhPrivateKey, DhPubKey},
-    hkdf,
-    hmac::HmacKey,
-    CryptoRng, SHA256_OUTPUT_LEN,
-};
-#[cfg(feature = "sgx")]
+use super::{hkdf, hmac::HmacKey, suite::CipherSuite, CryptoRng, SHA256_OUTPUT_LEN};
 use crate::handshake::AccessKey;
-use crate::local_anyhow::{anyhow, Result};
+use crate::local_anyhow::{anyhow, ensure, Result};
 use crate::localstd::{fmt, vec::Vec};
 use codec::{Decode, Encode, Input};
 use frame_common::crypto::rand_assign;
@@ -152,7 +146,9 @@ impl From<&[u8]> for PathSecret {
 
 impl PathSecret {
     /// See sec 5.4.
-    pub fn derive_node_values(self) -> Result<(DhPubKey, DhPrivateKey, NodeSecret, PathSecret)> {
+    pub fn derive_node_values<CS: CipherSuite>(
+        self,
+    ) -> Result<(CS::PubKey, CS::PrivKey, NodeSecret, PathSecret)> {
         let prk = HmacKey::from(self);
         let mut node_secret_buf = vec![0u8; SHA256_OUTPUT_LEN];
         hkdf::expand_label(&prk, b"node", b"", &mut node_secret_buf)?;
@@ -161,8 +157,8 @@ impl PathSecret {
         hkdf::expand_label(&prk, b"path", b"", &mut path_secret_buf)?;
 
         // TODO: Consider whether node_secret_buf is supposed to be hashed or not.
-        let node_private_key = DhPrivateKey::from_bytes(&node_secret_buf)?;
-        let node_public_key = DhPubKey::from_private_key(&node_private_key);
+        let node_private_key = CS::private_key_from_bytes(&node_secret_buf)?;
+        let node_public_key = CS::public_key(&node_private_key);
 
         let node_secret = NodeSecret::from(node_secret_buf);
         let parent_path_secret = PathSecret::from(path_secret_buf);
@@ -186,7 +182,6 @@ impl PathSecret {
         PathSecret(key)
     }
 
-    #[cfg(feature = "sgx")]
     pub fn derive_next(self, access_key: AccessKey) -> Result<PathSecret> {
         let prk = HmacKey::from(self);
         let mut path_secret_buf = vec![0u8; SHA256_OUTPUT_LEN];
@@ -203,7 +198,6 @@ impl PathSecret {
         self.as_bytes().len()
     }
 
-    #[cfg(feature = "sgx")]
     pub fn try_into_exporting(self, epoch: u32, id: &[u8]) -> Result<ExportPathSecret> {
         let encoded_sealed = UnsealedPathSecret::from(self).encoded_seal()?;
         let mut id_arr = [0u8; EXPORT_ID_SIZE];
@@ -212,7 +206,6 @@ impl PathSecret {
         Ok(ExportPathSecret::new(encoded_sealed, epoch, id_arr))
     }
 
-    #[cfg(feature = "sgx")]
     pub fn try_from_importing(imp_path_secret: ExportPathSecret) -> Result<Self> {
         let sealed_path_secret = SealedPathSecret::decode(&mut imp_path_secret.encoded_sealed())
             .map_err(|e| anyhow!("error: {:?}", e))?
@@ -234,12 +227,22 @@ impl UnsealedPathSecret {
 
         Ok(SealedPathSecret::new(sealed_data).encode())
     }
+
+    /// Without SGX, there is no key to seal it with, so it's exported in plaintext.
+    /// It's only for the in-process enclave, which cannot be built in release builds.
+    #[cfg(feature = "std")]
+    pub fn encoded_seal(self) -> Result<Vec<u8>> {
+        ensure!(
+            cfg!(debug_assertions),
+            "Path secrets cannot be exported in plaintext in release builds"
+        );
+        Ok(SealedPathSecret::new(self).encode())
+    }
 }
 
 #[cfg(feature = "sgx")]
 unsafe impl sgx_types::marker::ContiguousMemory for UnsealedPathSecret {}
 
-#[cfg(feature = "sgx")]
 impl From<PathSecret> for UnsealedPathSecret {
     fn from(ps: PathSecret) -> Self {
         assert_eq!(ps.len(), SHA256_OUTPUT_LEN);
@@ -316,6 +319,50 @@ impl fmt::Debug for SealedPathSecret<'_> {
     }
 }
 
+/// The path secret padded to the size of the sealed data, so that exported path secrets have the same format with SGX.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct SealedPathSecret(UnsealedPathSecret);
+
+#[cfg(feature = "std")]
+impl SealedPathSecret {
+    pub fn new(unsealed: UnsealedPathSecret) -> Self {
+        SealedPathSecret(unsealed)
+    }
+
+    pub fn unseal(&self) -> Result<UnsealedPathSecret> {
+        Ok(self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encode for SealedPathSecret {
+    fn encode(&self) -> Vec<u8> {
+        let mut res = vec![0u8; SEALED_DATA_SIZE];
+        res[..SHA256_OUTPUT_LEN].copy_from_slice(&(self.0).0);
+        res
+    }
+}
+
+#[cfg(feature = "std")]
+impl Decode for SealedPathSecret {
+    fn decode<I: Input>(value: &mut I) -> Result<Self, codec::Error> {
+        let mut buf = [0u8; SEALED_DATA_SIZE];
+        value.read(&mut buf)?;
+        let mut res = [0u8; SHA256_OUTPUT_LEN];
+        res.copy_from_slice(&buf[..SHA256_OUTPUT_LEN]);
+
+        Ok(SealedPathSecret(UnsealedPathSecret(res)))
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for SealedPathSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SealedPathSecret").finish()
+    }
+}
+
 #[cfg(feature = "sgx")]
 #[cfg(debug_assertions)]
 pub(crate) mod tests {
@@ -0,0 +1,115 @@
+//! Cipher suites of TreeKEM.
+//! A cipher suite determines the DH group of the ratchet tree nodes and ECIES,
+//! and the AEAD algorithm of ECIES and application messages.
+//! Every suite uses SHA-256 for hashing and HKDF.
+
+use super::{
+    dh::{self, DhPrivateKey, DhPubKey},
+    x25519::{self, X25519PrivateKey, X25519PubKey},
+};
+use crate::local_anyhow::Result;
+use crate::local_ring::aead::{self, Algorithm};
+use crate::localstd::fmt::Debug;
+use codec::{Decode, Encode};
+
+/// An identifier of the cipher suite embedded in handshakes and application messages.
+pub type CipherSuiteId = u16;
+
+pub const SECP256K1_AES256GCM_SHA256: CipherSuiteId = 0x0001;
+pub const X25519_CHACHA20POLY1305_SHA256: CipherSuiteId = 0x0002;
+
+pub trait CipherSuite:
+    Debug + Clone + Default + PartialEq + Encode + Decode + Send + Sync + 'static
+{
+    const ID: CipherSuiteId;
+
+    type PubKey: Debug + Clone + Default + PartialEq + Encode + Decode + Send + Sync;
+    type PrivKey: Debug + Clone + Default + PartialEq + Send + Sync;
+
+    /// Derive a private key deterministically from a node secret.
+    fn private_key_from_bytes(bytes: &[u8]) -> Result<Self::PrivKey>;
+
+    fn private_key_from_random() -> Result<Self::PrivKey>;
+
+    fn public_key(private_key: &Self::PrivKey) -> Self::PubKey;
+
+    /// Derive a shared secret with a fresh ephemeral key and the receiver's public key.
+    fn encapsulate(ephemeral_privkey: &Self::PrivKey, pubkey: &Self::PubKey) -> Result<[u8; 32]>;
+
+    /// Derive the same shared secret as `encapsulate` with the sender's ephemeral public key.
+    fn decapsulate(privkey: &Self::PrivKey, ephemeral_pubkey: &Self::PubKey) -> Result<[u8; 32]>;
+
+    /// The AEAD algorithm, which must take a 256-bit key and a 96-bit nonce.
+    fn aead() -> &'static Algorithm;
+}
+
+/// The default cipher suite, which is also used for the enclave's identity key.
+pub type DefaultCipherSuite = Secp256k1Aes256GcmSha256;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+pub struct Secp256k1Aes256GcmSha256;
+
+impl CipherSuite for Secp256k1Aes256GcmSha256 {
+    const ID: CipherSuiteId = SECP256K1_AES256GCM_SHA256;
+
+    type PubKey = DhPubKey;
+    type PrivKey = DhPrivateKey;
+
+    fn private_key_from_bytes(bytes: &[u8]) -> Result<Self::PrivKey> {
+        DhPrivateKey::from_bytes(bytes)
+    }
+
+    fn private_key_from_random() -> Result<Self::PrivKey> {
+        DhPrivateKey::from_random()
+    }
+
+    fn public_key(private_key: &Self::PrivKey) -> Self::PubKey {
+        DhPubKey::from_private_key(private_key)
+    }
+
+    fn encapsulate(ephemeral_privkey: &Self::PrivKey, pubkey: &Self::PubKey) -> Result<[u8; 32]> {
+        dh::encapsulate(ephemeral_privkey, pubkey)
+    }
+
+    fn decapsulate(privkey: &Self::PrivKey, ephemeral_pubkey: &Self::PubKey) -> Result<[u8; 32]> {
+        dh::decapsulate(privkey, ephemeral_pubkey)
+    }
+
+    fn aead() -> &'static Algorithm {
+        &aead::AES_256_GCM
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+pub struct X25519ChaCha20Poly1305Sha256;
+
+impl CipherSuite for X25519ChaCha20Poly1305Sha256 {
+    const ID: CipherSuiteId = X25519_CHACHA20POLY1305_SHA256;
+
+    type PubKey = X25519PubKey;
+    type PrivKey = X25519PrivateKey;
+
+    fn private_key_from_bytes(bytes: &[u8]) -> Result<Self::PrivKey> {
+        X25519PrivateKey::from_bytes(bytes)
+    }
+
+    fn private_key_from_random() -> Result<Self::PrivKey> {
+        X25519PrivateKey::from_random()
+    }
+
+    fn public_key(private_key: &Self::PrivKey) -> Self::PubKey {
+        X25519PubKey::from_private_key(private_key)
+    }
+
+    fn encapsulate(ephemeral_privkey: &Self::PrivKey, pubkey: &Self::PubKey) -> Result<[u8; 32]> {
+        x25519::encapsulate(ephemeral_privkey, pubkey)
+    }
+
+    fn decapsulate(privkey: &Self::PrivKey, ephemeral_pubkey: &Self::PubKey) -> Result<[u8; 32]> {
+        x25519::decapsulate(privkey, ephemeral_pubkey)
+    }
+
+    fn aead() -> &'static Algorithm {
+        &aead::CHACHA20_POLY1305
+    }
+}
@@ -0,0 +1,98 @@
+use super::{hkdf, hmac::HmacKey};
+use crate::local_anyhow::{anyhow, ensure, Result};
+use crate::localstd::vec::Vec;
+use codec::{Decode, Encode};
+use frame_common::crypto::rand_assign;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const X25519_KEY_SIZE: usize = 32;
+
+/// A X25519 private key. The scalar is clamped whenever it is used.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct X25519PrivateKey([u8; X25519_KEY_SIZE]);
+
+impl X25519PrivateKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == X25519_KEY_SIZE,
+            "X25519 private key must be {} bytes",
+            X25519_KEY_SIZE
+        );
+        let mut buf = [0u8; X25519_KEY_SIZE];
+        buf.copy_from_slice(bytes);
+
+        Ok(X25519PrivateKey(buf))
+    }
+
+    pub fn from_random() -> Result<Self> {
+        let mut buf = [0u8; X25519_KEY_SIZE];
+        rand_assign(&mut buf)?;
+
+        Ok(X25519PrivateKey(buf))
+    }
+
+    fn to_static_secret(&self) -> StaticSecret {
+        StaticSecret::from(self.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct X25519PubKey([u8; X25519_KEY_SIZE]);
+
+impl X25519PubKey {
+    pub fn from_private_key(private_key: &X25519PrivateKey) -> Self {
+        let pubkey = PublicKey::from(&private_key.to_static_secret());
+        X25519PubKey(*pubkey.as_bytes())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0[..]
+    }
+}
+
+pub fn encapsulate(
+    ephemeral_privkey: &X25519PrivateKey,
+    pubkey: &X25519PubKey,
+) -> Result<[u8; 32]> {
+    let shared_secret = diffie_hellman(ephemeral_privkey, pubkey)?;
+    let ephemeral_pubkey = X25519PubKey::from_private_key(ephemeral_privkey);
+
+    gen_out_buf(&ephemeral_pubkey, &shared_secret)
+}
+
+pub fn decapsulate(
+    privkey: &X25519PrivateKey,
+    ephemeral_pubkey: &X25519PubKey,
+) -> Result<[u8; 32]> {
+    let shared_secret = diffie_hellman(privkey, ephemeral_pubkey)?;
+
+    gen_out_buf(ephemeral_pubkey, &shared_secret)
+}
+
+fn diffie_hellman(privkey: &X25519PrivateKey, pubkey: &X25519PubKey) -> Result<[u8; 32]> {
+    let shared_secret = privkey
+        .to_static_secret()
+        .diffie_hellman(&PublicKey::from(pubkey.0));
+    let shared_secret = *shared_secret.as_bytes();
+    // A low-order public key results in the all-zero shared secret.
+    if shared_secret == [0u8; 32] {
+        return Err(anyhow!("X25519 shared secret must not be zero"));
+    }
+
+    Ok(shared_secret)
+}
+
+fn gen_out_buf(pubkey: &X25519PubKey, shared_secret: &[u8; 32]) -> Result<[u8; 32]> {
+    let mut master = Vec::with_capacity(X25519_KEY_SIZE * 2);
+    master.extend(pubkey.as_bytes().iter());
+    master.extend(shared_secret.iter());
+
+    let mut out_buf = [0u8; 32];
+    hkdf::expand(
+        &HmacKey::from(master),
+        b"dh",
+        &mut out_buf,
+        hkdf::Aes256GcmKey,
+    )?;
+    Ok(out_buf)
+}
@@ -1,126 +1,184 @@
 use crate::application::AppKeyChain;
-use crate::crypto::{hkdf, hmac::HmacKey, secrets::*};
+use crate::crypto::{
+    hash::hash_encodable,
+    hkdf,
+    hmac::HmacKey,
+    secrets::*,
+    suite::{CipherSuite, DefaultCipherSuite},
+};
 use crate::handshake::{AccessKey, Handshake, HandshakeParams, PathSecretSource};
 use crate::local_anyhow::{anyhow, ensure, Result};
+use crate::localstd::vec::Vec;
 use crate::ratchet_tree::{RatchetTree, RatchetTreeNode};
 use crate::tree_math;
 use codec::Encode;
 use frame_common::crypto::ExportPathSecret;
 
 #[derive(Clone, Debug, Encode)]
-pub struct GroupState {
+pub struct GroupState<CS: CipherSuite = DefaultCipherSuite> {
     /// The current version of the group key
     epoch: u32,
-    /// Only if a member has a leaf node contained DhPrivKey, this indicates the roster index.
-    /// Otherwise, this field is None.
+    /// The roster index assigned to this member.
+    /// This field is None until the index is allocated before sending an add handshake.
     #[codec(skip)]
-    my_roster_idx: u32,
+    my_roster_idx: Option<u32>,
     /// RatchetTree contains blank nodes or filled nodes which consist of DhPubkey and DhPrivKey.
-    tree: RatchetTree,
+    tree: RatchetTree<CS>,
     /// The initial secret used to derive app_secret.
     /// It works as a salt of HKDF.
     #[codec(skip)]
     init_secret: HmacKey,
+    /// The identifier of the group, which is the hash of the contract's address and the first handshake creating the group.
+    /// It's bound to application messages as associated data.
+    #[codec(skip)]
+    group_id: Vec<u8>,
+    /// The address of the contract which the group's handshakes are stored in
+    #[codec(skip)]
+    contract_address: Vec<u8>,
 }
 
-impl Handshake for GroupState {
+impl<CS: CipherSuite> Handshake<CS> for GroupState<CS> {
     fn create_handshake(
         &self,
         source: &PathSecretSource,
-    ) -> Result<(HandshakeParams, ExportPathSecret)> {
-        let my_roster_idx = self.my_roster_idx;
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(my_roster_idx)?;
-
-        let path_secret = Self::request_new_path_secret(source, my_roster_idx, self.epoch)?;
-        let mut new_group_state = self.clone();
-
-        if my_tree_idx == self.tree.size() {
-            new_group_state.tree.add_leaf_node(RatchetTreeNode::Blank);
-            new_group_state.tree.propagate_blank(my_roster_idx as usize);
-        }
-
-        let _ = new_group_state.set_new_path_secret(path_secret.clone(), my_tree_idx)?;
-        let direct_path_msg = new_group_state
-            .tree
-            .encrypt_direct_path_secret(my_tree_idx, path_secret.clone())?;
+    ) -> Result<(HandshakeParams<CS>, ExportPathSecret)> {
+        self.create_handshake_inner(source, None)
+    }
 
-        let handshake = HandshakeParams::new(self.epoch, my_roster_idx, direct_path_msg);
-        let export_path_secret =
-            path_secret.try_into_exporting(self.epoch, handshake.hash().as_ref())?;
+    fn create_remove_handshake(
+        &self,
+        source: &PathSecretSource,
+        removed_roster_idx: u32,
+    ) -> Result<(HandshakeParams<CS>, ExportPathSecret)> {
+        ensure!(
+            Some(removed_roster_idx) != self.my_roster_idx,
+            "Cannot remove myself from the group"
+        );
+        ensure!(
+            self.is_member(),
+            "Only a member of the group can remove other members"
+        );
+        let removed_tree_idx = RatchetTree::<CS>::roster_idx_to_tree_idx(removed_roster_idx)?;
+        ensure!(
+            removed_tree_idx < self.tree.size(),
+            "The removed member ({:?}) is not in the group",
+            removed_roster_idx
+        );
 
-        Ok((handshake, export_path_secret))
+        self.create_handshake_inner(source, Some(removed_roster_idx))
     }
 
     fn process_handshake<F>(
         &mut self,
-        handshake: &HandshakeParams,
+        handshake: &HandshakeParams<CS>,
         source: &PathSecretSource,
-        max_roster_idx: u32,
         req_path_secret_fn: F,
-    ) -> Result<AppKeyChain>
+    ) -> Result<AppKeyChain<CS>>
     where
         F: FnOnce(&[u8]) -> Result<ExportPathSecret>,
     {
+        handshake.ensure_cipher_suite()?;
         ensure!(
             handshake.prior_epoch() == self.epoch,
             "Handshake's prior epoch ({:?}) isn't the current epoch ({:?}).",
             handshake.prior_epoch(),
             self.epoch
         );
-        let sender_tree_idx = RatchetTree::roster_idx_to_tree_idx(handshake.roster_idx())?;
-        ensure!(sender_tree_idx <= self.tree.size(), "Invalid tree index");
+        let sender_tree_idx = RatchetTree::<CS>::roster_idx_to_tree_idx(handshake.roster_idx())?;
+        // A new member can only be added to the next leaf of the tree.
+        ensure!(
+            sender_tree_idx <= self.tree.next_leaf_idx(),
+            "Invalid tree index"
+        );
 
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(self.my_roster_idx)?;
-        let max_tree_size = RatchetTree::roster_idx_to_tree_idx(max_roster_idx)?;
+        let my_tree_idx = self.my_tree_idx()?;
+
+        // If the handshake contains a remove operation, blank the removed leaf's direct path
+        // so that the sender's new path secrets aren't encrypted to the removed member.
+        if let Some(removed_roster_idx) = handshake.removed_roster_idx() {
+            ensure!(
+                removed_roster_idx != handshake.roster_idx(),
+                "The sender cannot remove itself"
+            );
+            ensure!(
+                Some(removed_roster_idx) != self.my_roster_idx,
+                "This member has been removed from the group"
+            );
+            let removed_tree_idx = RatchetTree::<CS>::roster_idx_to_tree_idx(removed_roster_idx)?;
+            ensure!(
+                removed_tree_idx < self.tree.size() && sender_tree_idx < self.tree.size(),
+                "Invalid tree index of the remove operation"
+            );
+            self.tree.propagate_blank(removed_tree_idx);
+        }
 
-        // If sender_tree_size equals to the current tree size, the handshake contains an add operation.
-        if sender_tree_idx == self.tree.size() {
-            // This is just special operation for the very first epoch.
-            if self.tree.is_empty() {
-                self.tree.add_leaf_node(RatchetTreeNode::Blank);
-            }
-            for _ in 0..=(max_tree_size - self.tree.size()) / 2 {
-                self.tree.add_leaf_node(RatchetTreeNode::Blank);
-                self.tree.propagate_blank(sender_tree_idx);
-            }
+        // If the sender's leaf is out of the current tree, the handshake contains an add operation,
+        // so the tree grows to contain the new leaf.
+        if sender_tree_idx >= self.tree.size() {
+            self.tree.extend_to_leaf(sender_tree_idx);
+            self.tree.propagate_blank(sender_tree_idx);
         }
 
         let mut my_path_secret: Option<PathSecret> = None;
-        if let Some(my_leaf) = self.tree.get_mut(my_tree_idx) {
-            // Only if the received handshake sent from my own,
-            // request path secret to external key vault and then update the leaf node.
-            if sender_tree_idx == my_tree_idx {
-                let path_secret = match source {
-                    PathSecretSource::Local => {
-                        let imported_path_secret = req_path_secret_fn(handshake.hash().as_ref())?;
-                        ensure!(
-                            imported_path_secret.epoch() == self.epoch,
-                            "imported_path_secret's epoch isn't the current epoch"
-                        );
-                        PathSecret::try_from_importing(imported_path_secret)?
-                    }
-                    PathSecretSource::LocalTestKV(_) => {
-                        Self::request_new_path_secret(source, self.my_roster_idx, self.epoch)?
-                    }
-                    _ => unimplemented!(),
-                };
-
-                let (node_pubkey, node_privkey, _, _) = path_secret.clone().derive_node_values()?;
-
-                my_leaf.update_pub_key(node_pubkey);
-                my_leaf.update_priv_key(node_privkey);
-                my_path_secret = Some(path_secret);
-            }
+        // Only if the received handshake sent from my own,
+        // request path secret to external key vault and then update the leaf node.
+        if my_tree_idx == Some(sender_tree_idx) {
+            let path_secret = match source {
+                // `req_path_secret_fn` imports it from the local filesystem or the remote key vault.
+                PathSecretSource::Local | PathSecretSource::Remote(_) => {
+                    let imported_path_secret = req_path_secret_fn(handshake.hash().as_ref())?;
+                    ensure!(
+                        imported_path_secret.epoch() == self.epoch,
+                        "imported_path_secret's epoch isn't the current epoch"
+                    );
+                    PathSecret::try_from_importing(imported_path_secret)?
+                }
+                PathSecretSource::LocalTestKV(_) => {
+                    Self::request_new_path_secret(source, handshake.roster_idx(), self.epoch)?
+                }
+                PathSecretSource::LocalTest(_) => {
+                    return Err(anyhow!(
+                        "LocalTest path secret source cannot process own handshakes"
+                    ))
+                }
+            };
+
+            let (node_pubkey, node_privkey, _, _) =
+                path_secret.clone().derive_node_values::<CS>()?;
+            // The path secret is imported from outside of the enclave,
+            // so it must be the one from which the handshake's leaf public key was derived.
+            let leaf_msg = handshake
+                .path()
+                .node_msgs
+                .first()
+                .ok_or_else(|| anyhow!("The handshake doesn't contain the leaf public key"))?;
+            ensure!(
+                leaf_msg.public_key == node_pubkey,
+                "The imported path secret doesn't match the handshake's leaf public key"
+            );
+
+            let my_leaf = self
+                .tree
+                .get_mut(sender_tree_idx)
+                .ok_or_else(|| anyhow!("Not found my node"))?;
+            my_leaf.update_pub_key(node_pubkey);
+            my_leaf.update_priv_key(node_privkey);
+            my_path_secret = Some(path_secret);
         }
 
         let (update_secret, common_ancestor) =
-            self.apply_handshake(handshake, sender_tree_idx, my_path_secret)?;
+            self.apply_handshake(handshake, sender_tree_idx, my_tree_idx, my_path_secret)?;
         let direct_path_pub_keys = handshake.path().node_msgs.iter().map(|m| &m.public_key);
         self.tree.set_public_keys(
             sender_tree_idx,
             common_ancestor,
             direct_path_pub_keys.clone(),
         )?;
+        if self.epoch == 0 {
+            self.group_id = hash_encodable(&(&self.contract_address, handshake.hash().as_ref()))
+                .as_ref()
+                .to_vec();
+        }
         self.increment_epoch()?;
 
         let app_secret = self.update_epoch_secret(&update_secret)?;
@@ -130,18 +188,102 @@ impl Handshake for GroupState {
     }
 }
 
-impl GroupState {
+impl<CS: CipherSuite> GroupState<CS> {
+    fn create_handshake_inner(
+        &self,
+        source: &PathSecretSource,
+        removed_roster_idx: Option<u32>,
+    ) -> Result<(HandshakeParams<CS>, ExportPathSecret)> {
+        let my_roster_idx = self
+            .my_roster_idx
+            .ok_or_else(|| anyhow!("The roster index of this member hasn't been assigned yet"))?;
+        let my_tree_idx = RatchetTree::<CS>::roster_idx_to_tree_idx(my_roster_idx)?;
+        ensure!(
+            my_tree_idx <= self.tree.next_leaf_idx(),
+            "The roster index ({:?}) must not exceed the next leaf of the tree",
+            my_roster_idx
+        );
+
+        let path_secret = Self::request_new_path_secret(source, my_roster_idx, self.epoch)?;
+        let mut new_group_state = self.clone();
+
+        // The add handshake extends the tree in the same way as the receivers do.
+        if my_tree_idx >= self.tree.size() {
+            new_group_state.tree.extend_to_leaf(my_tree_idx);
+            new_group_state.tree.propagate_blank(my_tree_idx);
+        }
+        if let Some(removed_roster_idx) = removed_roster_idx {
+            let removed_tree_idx = RatchetTree::<CS>::roster_idx_to_tree_idx(removed_roster_idx)?;
+            new_group_state.tree.propagate_blank(removed_tree_idx);
+        }
+
+        let _ = new_group_state.set_new_path_secret(path_secret.clone(), my_tree_idx)?;
+        let direct_path_msg = new_group_state
+            .tree
+            .encrypt_direct_path_secret(my_tree_idx, path_secret.clone())?;
+
+        let handshake = match removed_roster_idx {
+            Some(removed_roster_idx) => HandshakeParams::new_remove(
+                self.epoch,
+                my_roster_idx,
+                removed_roster_idx,
+                direct_path_msg,
+            ),
+            None => HandshakeParams::new(self.epoch, my_roster_idx, direct_path_msg),
+        };
+        let export_path_secret =
+            path_secret.try_into_exporting(self.epoch, handshake.hash().as_ref())?;
+
+        Ok((handshake, export_path_secret))
+    }
+
     pub fn new(my_roster_idx: usize) -> Result<Self> {
-        let epoch = 0;
-        let tree = RatchetTree::new_empty();
-        let init_secret = HmacKey::default();
-
-        Ok(GroupState {
-            epoch,
-            my_roster_idx: my_roster_idx as u32,
-            tree,
-            init_secret,
-        })
+        let mut group_state = Self::new_unassigned();
+        group_state.my_roster_idx = Some(my_roster_idx as u32);
+
+        Ok(group_state)
+    }
+
+    /// Create a group state whose roster index hasn't been assigned yet.
+    /// It only follows the public part of the tree until the roster index is set.
+    pub fn new_unassigned() -> Self {
+        GroupState {
+            epoch: 0,
+            my_roster_idx: None,
+            tree: RatchetTree::new_empty(),
+            init_secret: HmacKey::default(),
+            group_id: vec![],
+            contract_address: vec![],
+        }
+    }
+
+    /// Set the address of the contract before processing the first handshake, which is bound to the group id.
+    /// It cannot be changed once the group has been created.
+    pub fn set_contract_address(&mut self, contract_address: &[u8]) -> Result<()> {
+        if self.epoch != 0 {
+            ensure!(
+                self.contract_address == contract_address,
+                "The group has already been created in another contract"
+            );
+        }
+        self.contract_address = contract_address.to_vec();
+
+        Ok(())
+    }
+
+    /// Assign the roster index allocated to this member before sending its add handshake.
+    /// It cannot be changed once this member has joined the group.
+    pub fn set_my_roster_idx(&mut self, roster_idx: u32) -> Result<()> {
+        if let Some(current) = self.my_roster_idx {
+            ensure!(
+                current == roster_idx || !self.is_member(),
+                "This member has already joined the group as the roster index {:?}",
+                current
+            );
+        }
+        self.my_roster_idx = Some(roster_idx);
+
+        Ok(())
     }
 
     /// Request own new path secret to external key vault
@@ -151,7 +293,11 @@ impl GroupState {
         epoch: u32,
     ) -> Result<PathSecret> {
         match source {
-            PathSecretSource::Local => Ok(PathSecret::new_from_random_sgx()),
+            // A remote key vault only backs up the path secret generated in this enclave,
+            // so the new path secret is generated in the same way as the local one.
+            PathSecretSource::Local | PathSecretSource::Remote(_) => {
+                Ok(PathSecret::new_from_random_sgx())
+            }
             // just for test use to derive new path secret depending on current path secret.
             PathSecretSource::LocalTest(current_path_secret) => {
                 let access_key = AccessKey::new(roster_idx, epoch);
@@ -167,17 +313,22 @@ impl GroupState {
             )
                 })
             }
-            PathSecretSource::Remote(_url) => unimplemented!(),
         }
     }
 
     fn apply_handshake(
         &mut self,
-        handshake: &HandshakeParams,
+        handshake: &HandshakeParams<CS>,
         sender_tree_idx: usize,
+        my_tree_idx: Option<usize>,
         path_secret: Option<PathSecret>,
     ) -> Result<(UpdateSecret, usize)> {
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(self.my_roster_idx)?;
+        let my_tree_idx = match my_tree_idx {
+            Some(my_tree_idx) => my_tree_idx,
+            // The roster index isn't assigned yet, so just follow all the public keys on the sender's path.
+            // The tree size is never on the direct path, so `set_public_keys` doesn't stop on the way.
+            None => return Ok((UpdateSecret::default(), self.tree.size())),
+        };
 
         // If the received handshake sent from my own, set the requested path secret.
         if sender_tree_idx == my_tree_idx {
@@ -185,19 +336,20 @@ impl GroupState {
 
             Ok((update_secret, my_tree_idx))
         } else {
-            // What the node is still blank means the member hasn't join the group yet.
+            // What the node is still blank or out of the tree means the member hasn't join the group yet.
             // More precisely, the member hasn't send an add handshake yet.
             // Otherwise, the handshake is an update operation,
             // so decrypt direct path message using based on current group state.
-            match self.tree.get(my_tree_idx).unwrap() {
-                RatchetTreeNode::Blank => {
+            match self.tree.get(my_tree_idx) {
+                None => Ok((UpdateSecret::default(), self.tree.size())),
+                Some(RatchetTreeNode::Blank) => {
                     let num_leaves = tree_math::num_leaves_in_tree(self.tree.size());
                     let common_ancestor =
                         tree_math::common_ancestor(sender_tree_idx, my_tree_idx, num_leaves);
 
                     Ok((UpdateSecret::default(), common_ancestor))
                 }
-                _ => {
+                Some(_) => {
                     let (path_secret, common_ancestor) = self.tree.decrypt_direct_path_msg(
                         &handshake.path(),
                         sender_tree_idx,
@@ -242,11 +394,21 @@ impl GroupState {
         Ok(app_secret.into())
     }
 
-    pub(crate) fn my_node(&self) -> Result<&RatchetTreeNode> {
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(self.my_roster_idx)?;
-        self.tree
-            .get(my_tree_idx)
-            .ok_or_else(|| anyhow!("Not found my node"))
+    /// Returns None if the roster index isn't assigned or my leaf hasn't been added to the tree yet.
+    pub(crate) fn my_node(&self) -> Option<&RatchetTreeNode<CS>> {
+        let my_tree_idx = self.my_tree_idx().ok()??;
+        self.tree.get(my_tree_idx)
+    }
+
+    fn my_tree_idx(&self) -> Result<Option<usize>> {
+        self.my_roster_idx
+            .map(RatchetTree::<CS>::roster_idx_to_tree_idx)
+            .transpose()
+    }
+
+    /// Whether this member's leaf holds its private key, that is, this member has joined the group.
+    pub fn is_member(&self) -> bool {
+        self.my_node().and_then(|node| node.private_key()).is_some()
     }
 
     pub(crate) fn roster_len(&self) -> Result<usize> {
@@ -260,7 +422,11 @@ impl GroupState {
         self.epoch
     }
 
-    pub fn my_roster_idx(&self) -> u32 {
+    pub fn group_id(&self) -> &[u8] {
+        &self.group_id
+    }
+
+    pub fn my_roster_idx(&self) -> Option<u32> {
         self.my_roster_idx
     }
 }
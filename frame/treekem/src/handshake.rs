@@ -1,8 +1,12 @@
 use crate::application::AppKeyChain;
 use crate::crypto::{
-    dh::DhPubKey, ecies::EciesCiphertext, hash::hash_encodable, secrets::PathSecret, CryptoRng,
+    ecies::EciesCiphertext,
+    hash::hash_encodable,
+    secrets::PathSecret,
+    suite::{CipherSuite, CipherSuiteId, DefaultCipherSuite},
+    CryptoRng,
 };
-use crate::local_anyhow::{anyhow, Result};
+use crate::local_anyhow::{anyhow, ensure, Result};
 use crate::local_ring::digest::Digest;
 #[cfg(feature = "std")]
 use crate::localstd::sync::RwLock;
@@ -13,21 +17,29 @@ use codec::{Decode, Encode};
 use frame_common::crypto::{ExportHandshake, ExportPathSecret};
 
 /// A handshake operates sharing a group key to each member.
-pub trait Handshake: Sized {
+pub trait Handshake<CS: CipherSuite = DefaultCipherSuite>: Sized {
     /// Create a handshake to broadcast other members.
     fn create_handshake(
         &self,
         source: &PathSecretSource,
-    ) -> Result<(HandshakeParams, ExportPathSecret)>;
+    ) -> Result<(HandshakeParams<CS>, ExportPathSecret)>;
+
+    /// Create a handshake which evicts the member of `removed_roster_idx` from the group
+    /// and re-keys the group so that the removed member cannot derive the next group key.
+    fn create_remove_handshake(
+        &self,
+        source: &PathSecretSource,
+        removed_roster_idx: u32,
+    ) -> Result<(HandshakeParams<CS>, ExportPathSecret)>;
 
     /// Process a received handshake from other members.
+    /// If the sender's leaf isn't contained in the tree yet, the tree grows to add it.
     fn process_handshake<F>(
         &mut self,
-        handshake: &HandshakeParams,
+        handshake: &HandshakeParams<CS>,
         source: &PathSecretSource,
-        max_roster_idx: u32,
         req_path_secret_fn: F,
-    ) -> Result<AppKeyChain>
+    ) -> Result<AppKeyChain<CS>>
     where
         F: FnOnce(&[u8]) -> Result<ExportPathSecret>;
 }
@@ -35,19 +47,41 @@ pub trait Handshake: Sized {
 // TODO: Does need signature over the group's history?
 /// This `Handshake` is sent to global ledger.
 #[derive(Clone, Debug, Encode, Decode)]
-pub struct HandshakeParams {
+pub struct HandshakeParams<CS: CipherSuite = DefaultCipherSuite> {
     /// This is equal to the epoch of the current groupstate
     /// at the time of receicing and applying the handshake.
     prior_epoch: u32,
     roster_idx: u32,
-    path: DirectPathMsg,
+    /// The cipher suite of the direct path keys and ciphertexts.
+    cipher_suite: CipherSuiteId,
+    /// Only if the handshake is a remove operation, this indicates the removed member's roster index.
+    /// The removed leaf's direct path is blanked before applying the sender's direct path.
+    removed_roster_idx: Option<u32>,
+    path: DirectPathMsg<CS>,
 }
 
-impl HandshakeParams {
-    pub fn new(prior_epoch: u32, roster_idx: u32, path: DirectPathMsg) -> Self {
+impl<CS: CipherSuite> HandshakeParams<CS> {
+    pub fn new(prior_epoch: u32, roster_idx: u32, path: DirectPathMsg<CS>) -> Self {
+        HandshakeParams {
+            prior_epoch,
+            roster_idx,
+            cipher_suite: CS::ID,
+            removed_roster_idx: None,
+            path,
+        }
+    }
+
+    pub fn new_remove(
+        prior_epoch: u32,
+        roster_idx: u32,
+        removed_roster_idx: u32,
+        path: DirectPathMsg<CS>,
+    ) -> Self {
         HandshakeParams {
             prior_epoch,
             roster_idx,
+            cipher_suite: CS::ID,
+            removed_roster_idx: Some(removed_roster_idx),
             path,
         }
     }
@@ -61,7 +95,26 @@ impl HandshakeParams {
     }
 
     pub fn from_export(export: ExportHandshake) -> Result<Self> {
-        HandshakeParams::decode(&mut &export.handshake()[..]).map_err(|e| anyhow!("{:?}", e))
+        let handshake = HandshakeParams::<CS>::decode(&mut &export.handshake()[..])
+            .map_err(|e| anyhow!("{:?}", e))?;
+        handshake.ensure_cipher_suite()?;
+
+        Ok(handshake)
+    }
+
+    /// The direct path can only be processed with the same cipher suite as the group's one.
+    pub fn ensure_cipher_suite(&self) -> Result<()> {
+        ensure!(
+            self.cipher_suite == CS::ID,
+            "The handshake's cipher suite ({:?}) differs from the group's one ({:?})",
+            self.cipher_suite,
+            CS::ID
+        );
+        Ok(())
+    }
+
+    pub fn cipher_suite(&self) -> CipherSuiteId {
+        self.cipher_suite
     }
 
     pub fn prior_epoch(&self) -> u32 {
@@ -72,32 +125,36 @@ impl HandshakeParams {
         self.roster_idx
     }
 
-    pub fn path(&self) -> &DirectPathMsg {
+    pub fn removed_roster_idx(&self) -> Option<u32> {
+        self.removed_roster_idx
+    }
+
+    pub fn path(&self) -> &DirectPathMsg<CS> {
         &self.path
     }
 }
 
 /// Encrypted direct path
 #[derive(Debug, Clone, Encode, Decode)]
-pub struct DirectPathMsg {
-    pub node_msgs: Vec<DirectPathNodeMsg>,
+pub struct DirectPathMsg<CS: CipherSuite> {
+    pub node_msgs: Vec<DirectPathNodeMsg<CS>>,
 }
 
-impl DirectPathMsg {
-    pub fn new(node_msgs: Vec<DirectPathNodeMsg>) -> Self {
+impl<CS: CipherSuite> DirectPathMsg<CS> {
+    pub fn new(node_msgs: Vec<DirectPathNodeMsg<CS>>) -> Self {
         DirectPathMsg { node_msgs }
     }
 }
 
 /// Containes a direct path node's public key and encrypted secrets
 #[derive(Debug, Clone, Encode, Decode)]
-pub struct DirectPathNodeMsg {
-    pub public_key: DhPubKey,
-    pub node_secrets: Vec<EciesCiphertext>,
+pub struct DirectPathNodeMsg<CS: CipherSuite> {
+    pub public_key: CS::PubKey,
+    pub node_secrets: Vec<EciesCiphertext<CS>>,
 }
 
-impl DirectPathNodeMsg {
-    pub fn new(public_key: DhPubKey, node_secrets: Vec<EciesCiphertext>) -> Self {
+impl<CS: CipherSuite> DirectPathNodeMsg<CS> {
+    pub fn new(public_key: CS::PubKey, node_secrets: Vec<EciesCiphertext<CS>>) -> Self {
         DirectPathNodeMsg {
             public_key,
             node_secrets,
@@ -108,6 +165,7 @@ impl DirectPathNodeMsg {
 #[derive(Debug, Clone)]
 pub enum PathSecretSource {
     Local,
+    /// Back up and recover path secrets via the key vault listening on the address.
     Remote(String),
     /// just for test use to derive new path secret depending on current path secret.
     LocalTest(CurrentPathSecret),
@@ -35,33 +35,28 @@ use std_rand_core as local_rand_core;
 #[cfg(feature = "std")]
 use std_ring as local_ring;
 
-#[cfg(feature = "sgx")]
 mod application;
 mod crypto;
-#[cfg(feature = "sgx")]
 mod group_state;
-#[cfg(feature = "sgx")]
 pub mod handshake;
-#[cfg(feature = "sgx")]
+pub mod mls;
 mod ratchet_tree;
-#[cfg(feature = "sgx")]
 mod tree_math;
 // #[cfg(debug_assertions)]
-#[cfg(feature = "sgx")]
 mod test_funcs;
 
-#[cfg(feature = "sgx")]
-pub use crate::application::AppKeyChain;
+pub use crate::application::{AppKeyChain, SenderKey};
 pub use crate::crypto::dh::{DhPrivateKey, DhPubKey};
 pub use crate::crypto::ecies::EciesCiphertext;
-#[cfg(feature = "sgx")]
-pub use crate::crypto::secrets::SealedPathSecret;
-pub use crate::crypto::secrets::{PathSecret, UnsealedPathSecret};
-#[cfg(feature = "sgx")]
+pub use crate::crypto::secrets::{PathSecret, SealedPathSecret, UnsealedPathSecret};
+pub use crate::crypto::suite::{
+    CipherSuite, CipherSuiteId, DefaultCipherSuite, Secp256k1Aes256GcmSha256,
+    X25519ChaCha20Poly1305Sha256, SECP256K1_AES256GCM_SHA256, X25519_CHACHA20POLY1305_SHA256,
+};
+pub use crate::crypto::x25519::{X25519PrivateKey, X25519PubKey};
 pub use crate::group_state::GroupState;
-#[cfg(feature = "sgx")]
 pub use crate::handshake::Handshake;
-#[cfg(feature = "sgx")]
+pub use crate::mls::{MlsDecode, MlsEncode};
 pub use crate::test_funcs::init_path_secret_kvs;
 
 #[cfg(feature = "sgx")]
@@ -76,6 +71,7 @@ pub mod tests {
             application::tests::run_tests(),
             crypto::ecies::tests::run_tests(),
             crypto::secrets::tests::run_tests(),
+            mls::tests::run_tests(),
         )
     }
 }
@@ -0,0 +1,545 @@
+//! An optional encoding of handshakes and application messages in the MLS (RFC 9420) wire format,
+//! which is an alternative to the SCALE encoding so that messages can be inspected by standard MLS tooling.
+//!
+//! Only the structures whose semantics line up are mapped:
+//! * `HandshakeParams` is a `PublicMessage` of a `Commit`. The direct path is the commit's `UpdatePath`,
+//!   and the removed member, if any, is a `Remove` proposal by value.
+//! * `Ciphertext` is a `PrivateMessage` of an application message.
+//! * There is no counterpart of `Welcome` because new members derive the group key from the direct path.
+//!
+//! Some fields cannot be filled in because anonify doesn't have them:
+//! * `group_id` is always empty since a contract has exactly one group.
+//! * Signatures, confirmation tags and membership tags are empty. Handshakes are authenticated by the enclave.
+//! * `SenderData` isn't encrypted, so `encrypted_sender_data` is the plaintext `SenderData`.
+//! * The cipher suite is carried in `authenticated_data` as a `uint16`.
+//!   The identifiers are anonify's own ones, not ones of the MLS cipher suite registry.
+
+use crate::crypto::{ecies::EciesCiphertext, suite::CipherSuite};
+use crate::handshake::{DirectPathMsg, DirectPathNodeMsg, HandshakeParams};
+use crate::local_anyhow::{anyhow, ensure, Result};
+use crate::localstd::vec::Vec;
+use codec::{Decode, Encode};
+use frame_common::crypto::Ciphertext;
+
+pub const MLS_VERSION_10: u16 = 1;
+pub const WIRE_FORMAT_PUBLIC_MESSAGE: u16 = 1;
+pub const WIRE_FORMAT_PRIVATE_MESSAGE: u16 = 2;
+
+const CONTENT_TYPE_APPLICATION: u8 = 1;
+const CONTENT_TYPE_COMMIT: u8 = 3;
+const SENDER_TYPE_MEMBER: u8 = 1;
+const PROPOSAL_OR_REF_PROPOSAL: u8 = 1;
+const PROPOSAL_TYPE_REMOVE: u16 = 3;
+const CREDENTIAL_TYPE_BASIC: u16 = 1;
+const LEAF_NODE_SOURCE_COMMIT: u8 = 3;
+const REUSE_GUARD_SIZE: usize = 4;
+/// Variable-length vectors can be at most 2^30 - 1 bytes.
+const MAX_VARINT: usize = (1 << 30) - 1;
+
+/// Encode a value in the MLS wire format.
+pub trait MlsEncode {
+    fn mls_encode(&self, buf: &mut Vec<u8>) -> Result<()>;
+
+    fn mls_encode_to_vec(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.mls_encode(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Decode a value from the MLS wire format.
+pub trait MlsDecode: Sized {
+    fn mls_decode(input: &mut &[u8]) -> Result<Self>;
+
+    /// Decode a value which must consume all of the bytes.
+    fn mls_decode_all(mut bytes: &[u8]) -> Result<Self> {
+        let res = Self::mls_decode(&mut bytes)?;
+        ensure!(bytes.is_empty(), "{} trailing bytes remain", bytes.len());
+        Ok(res)
+    }
+}
+
+impl<CS: CipherSuite> MlsEncode for HandshakeParams<CS> {
+    fn mls_encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&MLS_VERSION_10.to_be_bytes());
+        buf.extend_from_slice(&WIRE_FORMAT_PUBLIC_MESSAGE.to_be_bytes());
+
+        // FramedContent
+        encode_opaque(&[], buf)?; // group_id
+        buf.extend_from_slice(&(self.prior_epoch() as u64).to_be_bytes());
+        buf.push(SENDER_TYPE_MEMBER);
+        buf.extend_from_slice(&self.roster_idx().to_be_bytes());
+        encode_opaque(&self.cipher_suite().to_be_bytes(), buf)?; // authenticated_data
+        buf.push(CONTENT_TYPE_COMMIT);
+
+        // Commit
+        encode_vector(buf, |proposals| {
+            if let Some(removed_roster_idx) = self.removed_roster_idx() {
+                proposals.push(PROPOSAL_OR_REF_PROPOSAL);
+                proposals.extend_from_slice(&PROPOSAL_TYPE_REMOVE.to_be_bytes());
+                proposals.extend_from_slice(&removed_roster_idx.to_be_bytes());
+            }
+            Ok(())
+        })?;
+        buf.push(1); // optional<UpdatePath> is present
+        encode_update_path(self.path(), self.cipher_suite(), buf)?;
+
+        // FramedContentAuthData
+        encode_opaque(&[], buf)?; // signature
+        encode_opaque(&[], buf)?; // confirmation_tag
+        encode_opaque(&[], buf)?; // membership_tag
+
+        Ok(())
+    }
+}
+
+impl<CS: CipherSuite> MlsDecode for HandshakeParams<CS> {
+    fn mls_decode(input: &mut &[u8]) -> Result<Self> {
+        ensure_header(input, WIRE_FORMAT_PUBLIC_MESSAGE)?;
+
+        let _group_id = decode_opaque(input)?;
+        let epoch = decode_epoch(input)?;
+        ensure!(
+            read_u8(input)? == SENDER_TYPE_MEMBER,
+            "The sender of a handshake must be a member"
+        );
+        let roster_idx = read_u32(input)?;
+        decode_cipher_suite::<CS>(input)?;
+        ensure!(
+            read_u8(input)? == CONTENT_TYPE_COMMIT,
+            "A handshake must be a commit"
+        );
+
+        let removed_roster_idxs = decode_vector(input, |proposal| {
+            ensure!(
+                read_u8(proposal)? == PROPOSAL_OR_REF_PROPOSAL,
+                "Proposals must be sent by value"
+            );
+            ensure!(
+                read_u16(proposal)? == PROPOSAL_TYPE_REMOVE,
+                "Only remove proposals are supported"
+            );
+            read_u32(proposal)
+        })?;
+        ensure!(
+            removed_roster_idxs.len() <= 1,
+            "A handshake can remove at most one member"
+        );
+        ensure!(
+            read_u8(input)? == 1,
+            "A handshake must contain an update path"
+        );
+        let path = decode_update_path::<CS>(input)?;
+
+        let _signature = decode_opaque(input)?;
+        let _confirmation_tag = decode_opaque(input)?;
+        let _membership_tag = decode_opaque(input)?;
+
+        match removed_roster_idxs.first() {
+            Some(&removed_roster_idx) => Ok(HandshakeParams::new_remove(
+                epoch,
+                roster_idx,
+                removed_roster_idx,
+                path,
+            )),
+            None => Ok(HandshakeParams::new(epoch, roster_idx, path)),
+        }
+    }
+}
+
+impl MlsEncode for Ciphertext {
+    fn mls_encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&MLS_VERSION_10.to_be_bytes());
+        buf.extend_from_slice(&WIRE_FORMAT_PRIVATE_MESSAGE.to_be_bytes());
+
+        encode_opaque(&[], buf)?; // group_id
+        buf.extend_from_slice(&(self.epoch() as u64).to_be_bytes());
+        buf.push(CONTENT_TYPE_APPLICATION);
+        encode_opaque(&self.cipher_suite().to_be_bytes(), buf)?; // authenticated_data
+        encode_vector(buf, |sender_data| {
+            sender_data.extend_from_slice(&self.roster_idx().to_be_bytes());
+            sender_data.extend_from_slice(&self.generation().to_be_bytes());
+            sender_data.extend_from_slice(&[0u8; REUSE_GUARD_SIZE]);
+            Ok(())
+        })?;
+        encode_opaque(self.encrypted_state_ref(), buf)
+    }
+}
+
+impl MlsDecode for Ciphertext {
+    fn mls_decode(input: &mut &[u8]) -> Result<Self> {
+        ensure_header(input, WIRE_FORMAT_PRIVATE_MESSAGE)?;
+
+        let _group_id = decode_opaque(input)?;
+        let epoch = decode_epoch(input)?;
+        ensure!(
+            read_u8(input)? == CONTENT_TYPE_APPLICATION,
+            "A ciphertext must be an application message"
+        );
+        let aad = decode_opaque(input)?;
+        ensure!(
+            aad.len() == 2,
+            "The authenticated data must be a cipher suite"
+        );
+        let cipher_suite = u16::from_be_bytes([aad[0], aad[1]]);
+
+        let sender_data = decode_opaque(input)?;
+        let sender_data = &mut &sender_data[..];
+        let roster_idx = read_u32(sender_data)?;
+        let generation = read_u32(sender_data)?;
+        let _reuse_guard = read_bytes(sender_data, REUSE_GUARD_SIZE)?;
+        ensure!(sender_data.is_empty(), "Invalid sender data");
+
+        let encrypted_state = decode_opaque(input)?;
+
+        Ok(Ciphertext::new(
+            generation,
+            epoch,
+            roster_idx,
+            cipher_suite,
+            encrypted_state,
+        ))
+    }
+}
+
+/// The first node message is the sender's leaf, and the rest are its direct path nodes.
+fn encode_update_path<CS: CipherSuite>(
+    path: &DirectPathMsg<CS>,
+    cipher_suite: u16,
+    buf: &mut Vec<u8>,
+) -> Result<()> {
+    let (leaf, nodes) = path
+        .node_msgs
+        .split_first()
+        .ok_or_else(|| anyhow!("The direct path must contain the sender's leaf"))?;
+
+    // LeafNode
+    encode_opaque(&leaf.public_key.encode(), buf)?; // encryption_key
+    encode_opaque(&[], buf)?; // signature_key
+    buf.extend_from_slice(&CREDENTIAL_TYPE_BASIC.to_be_bytes());
+    encode_opaque(&[], buf)?; // identity
+                              // Capabilities
+    encode_vector(buf, |versions| {
+        versions.extend_from_slice(&MLS_VERSION_10.to_be_bytes());
+        Ok(())
+    })?;
+    encode_vector(buf, |cipher_suites| {
+        cipher_suites.extend_from_slice(&cipher_suite.to_be_bytes());
+        Ok(())
+    })?;
+    encode_opaque(&[], buf)?; // extensions
+    encode_opaque(&[], buf)?; // proposals
+    encode_vector(buf, |credentials| {
+        credentials.extend_from_slice(&CREDENTIAL_TYPE_BASIC.to_be_bytes());
+        Ok(())
+    })?;
+    buf.push(LEAF_NODE_SOURCE_COMMIT);
+    encode_opaque(&[], buf)?; // parent_hash
+    encode_opaque(&[], buf)?; // extensions
+    encode_opaque(&[], buf)?; // signature
+
+    // UpdatePathNode
+    encode_vector(buf, |update_path_nodes| {
+        for node in nodes {
+            encode_opaque(&node.public_key.encode(), update_path_nodes)?;
+            encode_vector(update_path_nodes, |hpke_ciphertexts| {
+                for ciphertext in &node.node_secrets {
+                    encode_opaque(
+                        &ciphertext.ephemeral_public_key().encode(),
+                        hpke_ciphertexts,
+                    )?;
+                    encode_opaque(ciphertext.ciphertext(), hpke_ciphertexts)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    })
+}
+
+fn decode_update_path<CS: CipherSuite>(input: &mut &[u8]) -> Result<DirectPathMsg<CS>> {
+    let leaf_public_key = decode_public_key::<CS>(input)?;
+    let _signature_key = decode_opaque(input)?;
+    ensure!(
+        read_u16(input)? == CREDENTIAL_TYPE_BASIC,
+        "Only basic credentials are supported"
+    );
+    let _identity = decode_opaque(input)?;
+    for _ in 0..5 {
+        let _capabilities = decode_opaque(input)?;
+    }
+    ensure!(
+        read_u8(input)? == LEAF_NODE_SOURCE_COMMIT,
+        "The leaf node must be sent by a commit"
+    );
+    let _parent_hash = decode_opaque(input)?;
+    let _extensions = decode_opaque(input)?;
+    let _signature = decode_opaque(input)?;
+
+    let mut node_msgs = vec![DirectPathNodeMsg::new(leaf_public_key, vec![])];
+    let nodes = decode_vector(input, |update_path_node| {
+        let public_key = decode_public_key::<CS>(update_path_node)?;
+        let node_secrets = decode_vector(update_path_node, |hpke_ciphertext| {
+            let kem_output = decode_public_key::<CS>(hpke_ciphertext)?;
+            let ciphertext = decode_opaque(hpke_ciphertext)?;
+            Ok(EciesCiphertext::<CS>::from_parts(kem_output, ciphertext))
+        })?;
+        Ok(DirectPathNodeMsg::new(public_key, node_secrets))
+    })?;
+    node_msgs.extend(nodes);
+
+    Ok(DirectPathMsg::new(node_msgs))
+}
+
+fn ensure_header(input: &mut &[u8], wire_format: u16) -> Result<()> {
+    ensure!(
+        read_u16(input)? == MLS_VERSION_10,
+        "Unsupported protocol version"
+    );
+    ensure!(read_u16(input)? == wire_format, "Unexpected wire format");
+    Ok(())
+}
+
+fn decode_epoch(input: &mut &[u8]) -> Result<u32> {
+    let epoch = read_u64(input)?;
+    ensure!(epoch <= u32::max_value() as u64, "Epoch is out of range");
+    Ok(epoch as u32)
+}
+
+fn decode_cipher_suite<CS: CipherSuite>(input: &mut &[u8]) -> Result<()> {
+    let aad = decode_opaque(input)?;
+    ensure!(
+        aad == CS::ID.to_be_bytes(),
+        "The message's cipher suite differs from the group's one"
+    );
+    Ok(())
+}
+
+fn decode_public_key<CS: CipherSuite>(input: &mut &[u8]) -> Result<CS::PubKey> {
+    let bytes = decode_opaque(input)?;
+    CS::PubKey::decode(&mut &bytes[..]).map_err(|e| anyhow!("Invalid public key: {:?}", e))
+}
+
+/// Encode a length with the variable-length integer encoding of RFC 9420 Section 2.1.2.
+fn encode_varint(len: usize, buf: &mut Vec<u8>) -> Result<()> {
+    if len < 1 << 6 {
+        buf.push(len as u8);
+    } else if len < 1 << 14 {
+        buf.extend_from_slice(&(len as u16 | 0x4000).to_be_bytes());
+    } else if len <= MAX_VARINT {
+        buf.extend_from_slice(&(len as u32 | 0x8000_0000).to_be_bytes());
+    } else {
+        return Err(anyhow!("Vector length {} is too large", len));
+    }
+    Ok(())
+}
+
+/// Decode a variable-length integer, which must be encoded in the minimum number of bytes.
+fn decode_varint(input: &mut &[u8]) -> Result<usize> {
+    let first = read_u8(input)?;
+    let (len, min) = match first >> 6 {
+        0 => return Ok(first as usize),
+        1 => (
+            u16::from_be_bytes([first & 0x3f, read_u8(input)?]) as usize,
+            1 << 6,
+        ),
+        2 => {
+            let rest = read_bytes(input, 3)?;
+            (
+                u32::from_be_bytes([first & 0x3f, rest[0], rest[1], rest[2]]) as usize,
+                1 << 14,
+            )
+        }
+        _ => return Err(anyhow!("Invalid variable-length integer prefix")),
+    };
+    ensure!(
+        len >= min,
+        "Variable-length integer is not minimally encoded"
+    );
+    Ok(len)
+}
+
+fn encode_opaque(bytes: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    encode_varint(bytes.len(), buf)?;
+    buf.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn decode_opaque(input: &mut &[u8]) -> Result<Vec<u8>> {
+    let len = decode_varint(input)?;
+    read_bytes(input, len).map(|e| e.to_vec())
+}
+
+/// Encode the elements written by `f` as a variable-length vector.
+fn encode_vector<F>(buf: &mut Vec<u8>, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Vec<u8>) -> Result<()>,
+{
+    let mut elements = vec![];
+    f(&mut elements)?;
+    encode_opaque(&elements, buf)
+}
+
+/// Decode a variable-length vector whose elements are decoded by `f` until it is consumed.
+fn decode_vector<T, F>(input: &mut &[u8], mut f: F) -> Result<Vec<T>>
+where
+    F: FnMut(&mut &[u8]) -> Result<T>,
+{
+    let bytes = decode_opaque(input)?;
+    let elements = &mut &bytes[..];
+    let mut res = vec![];
+    while !elements.is_empty() {
+        res.push(f(elements)?);
+    }
+    Ok(res)
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    ensure!(input.len() >= len, "Unexpected end of input");
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_u8(input: &mut &[u8]) -> Result<u8> {
+    Ok(read_bytes(input, 1)?[0])
+}
+
+fn read_u16(input: &mut &[u8]) -> Result<u16> {
+    let bytes = read_bytes(input, 2)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(read_bytes(input, 4)?);
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64(input: &mut &[u8]) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(read_bytes(input, 8)?);
+    Ok(u64::from_be_bytes(buf))
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::crypto::suite::{DefaultCipherSuite, X25519ChaCha20Poly1305Sha256};
+    use crate::group_state::GroupState;
+    use crate::handshake::{Handshake, PathSecretKVS, PathSecretSource};
+    use crate::test_funcs;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_varint_vectors,
+            test_private_message_vector,
+            test_handshake_round_trip,
+            test_handshake_round_trip_x25519,
+        )
+    }
+
+    // The examples of RFC 9420 Section 2.1.2
+    fn test_varint_vectors() {
+        let vectors: Vec<(&[u8], usize)> = vec![
+            (&[0x25][..], 37),
+            (&[0x7b, 0xbd][..], 15293),
+            (&[0x9d, 0x7f, 0x3e, 0x7d][..], 494878333),
+        ];
+        for (bytes, value) in vectors {
+            assert_eq!(decode_varint(&mut &bytes[..]).unwrap(), value);
+            let mut buf = vec![];
+            encode_varint(value, &mut buf).unwrap();
+            assert_eq!(&buf[..], bytes);
+        }
+
+        // MLS requires the minimum encoding, unlike QUIC.
+        assert!(decode_varint(&mut &[0x40, 0x25][..]).is_err());
+        assert!(decode_varint(&mut &[0xc0, 0, 0, 0, 0, 0, 0, 0x25][..]).is_err());
+        assert!(encode_varint(MAX_VARINT + 1, &mut vec![]).is_err());
+    }
+
+    fn test_private_message_vector() {
+        let expected: &[u8] = &[
+            0x00, 0x01, // version: mls10
+            0x00, 0x02, // wire_format: mls_private_message
+            0x00, // group_id
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, // epoch
+            0x01, // content_type: application
+            0x02, 0x00, 0x01, // authenticated_data: cipher suite
+            0x0c, // encrypted_sender_data
+            0x00, 0x00, 0x00, 0x01, // leaf_index
+            0x00, 0x00, 0x00, 0x02, // generation
+            0x00, 0x00, 0x00, 0x00, // reuse_guard
+            0x02, 0xaa, 0xbb, // ciphertext
+        ];
+        let ciphertext = Ciphertext::new(2, 3, 1, 1, vec![0xaa, 0xbb]);
+
+        assert_eq!(&ciphertext.mls_encode_to_vec().unwrap()[..], expected);
+        let decoded = Ciphertext::mls_decode_all(expected).unwrap();
+        assert_eq!(decoded.generation(), 2);
+        assert_eq!(decoded.epoch(), 3);
+        assert_eq!(decoded.roster_idx(), 1);
+        assert_eq!(decoded.cipher_suite(), 1);
+        assert_eq!(decoded.encrypted_state_ref(), &[0xaa, 0xbb]);
+
+        assert!(Ciphertext::mls_decode_all(&expected[..expected.len() - 1]).is_err());
+    }
+
+    fn test_handshake_round_trip() {
+        handshake_round_trip::<DefaultCipherSuite>();
+    }
+
+    fn test_handshake_round_trip_x25519() {
+        handshake_round_trip::<X25519ChaCha20Poly1305Sha256>();
+    }
+
+    /// A handshake decoded from the MLS wire format must be processed as same as the original one.
+    fn handshake_round_trip<CS: CipherSuite>() {
+        crate::localstd::env::set_var("AUDITOR_ENDPOINT", "test");
+        let mut kvs = PathSecretKVS::new();
+        test_funcs::init_path_secret_kvs(&mut kvs, 10, 10);
+        let source = PathSecretSource::LocalTestKV(kvs);
+        let dummy_fn = |_: &[u8]| Err(anyhow!("This is dummy_fn"));
+
+        let mut group_state1 = GroupState::<CS>::new(0).unwrap();
+        let mut group_state2 = GroupState::<CS>::new(1).unwrap();
+        let mut group_state3 = GroupState::<CS>::new(2).unwrap();
+        test_funcs::do_handshake_three_party(
+            &mut group_state1,
+            &mut group_state2,
+            &mut group_state3,
+            &source,
+        );
+
+        let (handshake, _) = group_state2.create_handshake(&source).unwrap();
+        let encoded = handshake.mls_encode_to_vec().unwrap();
+        let decoded = HandshakeParams::<CS>::mls_decode_all(&encoded).unwrap();
+        assert_eq!(decoded.encode(), handshake.encode());
+        assert_eq!(decoded.mls_encode_to_vec().unwrap(), encoded);
+
+        let key_chain1 = group_state1
+            .process_handshake(&decoded, &source, dummy_fn)
+            .unwrap();
+        let key_chain2 = group_state2
+            .process_handshake(&handshake, &source, dummy_fn)
+            .unwrap();
+        let msg = b"mls round trip test";
+        let app_msg = key_chain2.encrypt_msg(msg.to_vec(), &group_state2).unwrap();
+        let app_msg = Ciphertext::mls_decode_all(&app_msg.mls_encode_to_vec().unwrap()).unwrap();
+        let plaintext = key_chain1
+            .decrypt_msg(&app_msg, &group_state1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(plaintext.as_slice(), msg);
+
+        let (remove_handshake, _) = group_state1.create_remove_handshake(&source, 1).unwrap();
+        let decoded =
+            HandshakeParams::<CS>::mls_decode_all(&remove_handshake.mls_encode_to_vec().unwrap())
+                .unwrap();
+        assert_eq!(decoded.removed_roster_idx(), Some(1));
+        assert_eq!(decoded.encode(), remove_handshake.encode());
+    }
+}
@@ -1,7 +1,7 @@
 use crate::crypto::{
-    dh::{DhPrivateKey, DhPubKey},
     ecies::EciesCiphertext,
     secrets::{NodeSecret, PathSecret},
+    suite::CipherSuite,
 };
 use crate::local_anyhow::{anyhow, ensure, Result};
 use crate::localstd::vec::Vec;
@@ -12,12 +12,12 @@ use crate::{
 use codec::Encode;
 
 #[derive(Clone, Debug, Encode)]
-pub struct RatchetTree {
-    nodes: Vec<RatchetTreeNode>,
+pub struct RatchetTree<CS: CipherSuite> {
+    nodes: Vec<RatchetTreeNode<CS>>,
 }
 
-impl RatchetTree {
-    pub fn new(nodes: Vec<RatchetTreeNode>) -> Self {
+impl<CS: CipherSuite> RatchetTree<CS> {
+    pub fn new(nodes: Vec<RatchetTreeNode<CS>>) -> Self {
         RatchetTree { nodes }
     }
 
@@ -28,7 +28,7 @@ impl RatchetTree {
     /// Set my leaf node derived from path secret to the provided tree index.
     #[allow(dead_code)]
     pub fn init_path_secret_idx(path_secret: PathSecret, my_tree_idx: usize) -> Result<Self> {
-        let (_, privkey, _, _) = path_secret.derive_node_values()?;
+        let (_, privkey, _, _) = path_secret.derive_node_values::<CS>()?;
         let my_leaf = RatchetTreeNode::from_private_key(privkey);
         let mut nodes = vec![RatchetTreeNode::Blank; my_tree_idx];
         nodes.push(my_leaf);
@@ -41,13 +41,14 @@ impl RatchetTree {
         &self,
         leaf_idx: usize,
         path_secret: PathSecret,
-    ) -> Result<DirectPathMsg> {
+    ) -> Result<DirectPathMsg<CS>> {
         ensure!(leaf_idx % 2 == 0, "index must be leaf's one.");
         let num_leaves = tree_math::num_leaves_in_tree(self.size());
         let direct_path = tree_math::node_direct_path(leaf_idx, num_leaves);
 
         let mut node_msgs = vec![];
-        let (leaf_public_key, _, _, mut parent_path_secret) = path_secret.derive_node_values()?;
+        let (leaf_public_key, _, _, mut parent_path_secret) =
+            path_secret.derive_node_values::<CS>()?;
         node_msgs.push(DirectPathNodeMsg::new(leaf_public_key, vec![]));
         if num_leaves == 1 {
             return Ok(DirectPathMsg::new(node_msgs));
@@ -55,7 +56,7 @@ impl RatchetTree {
 
         for path_node_idx in direct_path {
             let (parent_public_key, _, _, grandparent_path_secret) =
-                parent_path_secret.clone().derive_node_values()?;
+                parent_path_secret.clone().derive_node_values::<CS>()?;
 
             let mut encrypted_path_secrets = vec![];
             let copath_node_idx = tree_math::node_sibling(path_node_idx, num_leaves);
@@ -68,7 +69,7 @@ impl RatchetTree {
                     .public_key()
                     .ok_or_else(|| anyhow!("The resoluted node doesn't contain public key"))?;
 
-                let ciphertext = EciesCiphertext::encrypt(
+                let ciphertext = EciesCiphertext::<CS>::seal(
                     &others_pub_key,
                     parent_path_secret.as_bytes().to_vec(), // TODO:
                 )?;
@@ -87,7 +88,7 @@ impl RatchetTree {
 
     pub fn decrypt_direct_path_msg(
         &self,
-        direct_path_msg: &DirectPathMsg,
+        direct_path_msg: &DirectPathMsg<CS>,
         others_leaf_idx: usize,
         my_leaf_idx: usize,
     ) -> Result<(PathSecret, usize)> {
@@ -143,7 +144,7 @@ impl RatchetTree {
                     .get(pos)
                     .ok_or_else(|| anyhow!("Invalid direct path message"))?
                     .clone()
-                    .decrypt(&decryption_key)?;
+                    .open(&decryption_key)?;
                 let path_secret = PathSecret::from(plaintext);
 
                 return Ok((path_secret, common_ancestor_idx));
@@ -153,7 +154,7 @@ impl RatchetTree {
         Err(anyhow!("Cannot find node in the resolution."))
     }
 
-    pub fn add_leaf_node(&mut self, node: RatchetTreeNode) {
+    pub fn add_leaf_node(&mut self, node: RatchetTreeNode<CS>) {
         if self.nodes.is_empty() {
             self.nodes.push(node);
         } else {
@@ -162,6 +163,22 @@ impl RatchetTree {
         }
     }
 
+    /// Extend the tree with blank leaves until it contains the leaf of `leaf_idx`.
+    pub fn extend_to_leaf(&mut self, leaf_idx: usize) {
+        while self.size() <= leaf_idx {
+            self.add_leaf_node(RatchetTreeNode::Blank);
+        }
+    }
+
+    /// The tree index of the leaf which will be added next.
+    pub fn next_leaf_idx(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            self.size() + 1
+        }
+    }
+
     pub fn propagate_blank(&mut self, leaf_idx: usize) {
         let num_leaves = tree_math::num_leaves_in_tree(self.size());
         let direct_path = tree_math::node_extended_direct_path(leaf_idx, num_leaves);
@@ -183,7 +200,7 @@ impl RatchetTree {
         let root_node_secret = loop {
             let current_node = self.get_mut(current_node_idx).expect("Invalid node index.");
             let (node_pubkey, node_privkey, node_secret, parent_path_secret) =
-                path_secret.derive_node_values()?;
+                path_secret.derive_node_values::<CS>()?;
 
             current_node.update_pub_key(node_pubkey);
             current_node.update_priv_key(node_privkey);
@@ -204,7 +221,7 @@ impl RatchetTree {
     }
 
     #[allow(dead_code)]
-    pub fn set_single_public_key(&mut self, tree_idx: usize, pubkey: DhPubKey) -> Result<()> {
+    pub fn set_single_public_key(&mut self, tree_idx: usize, pubkey: CS::PubKey) -> Result<()> {
         let node = self.get_mut(tree_idx).ok_or_else(|| {
             anyhow!("Invalid tree index. Cannot set a public key to ratchet tree by add operation")
         })?;
@@ -221,7 +238,7 @@ impl RatchetTree {
         mut public_keys: I,
     ) -> Result<()>
     where
-        I: Iterator<Item = &'a DhPubKey>,
+        I: Iterator<Item = &'a CS::PubKey>,
     {
         let num_leaves = tree_math::num_leaves_in_tree(self.size());
         // direct path including a root
@@ -262,11 +279,11 @@ impl RatchetTree {
         self.size() == 0
     }
 
-    pub fn get(&self, idx: usize) -> Option<&RatchetTreeNode> {
+    pub fn get(&self, idx: usize) -> Option<&RatchetTreeNode<CS>> {
         self.nodes.get(idx)
     }
 
-    pub fn get_mut(&mut self, idx: usize) -> Option<&mut RatchetTreeNode> {
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut RatchetTreeNode<CS>> {
         self.nodes.get_mut(idx)
     }
 
@@ -275,7 +292,7 @@ impl RatchetTree {
     /// of the node.
     /// The ordering is ascending by node index.
     fn resolution(&self, idx: usize) -> Vec<usize> {
-        fn helper(tree: &RatchetTree, idx: usize, acc: &mut Vec<usize>) {
+        fn helper<CS: CipherSuite>(tree: &RatchetTree<CS>, idx: usize, acc: &mut Vec<usize>) {
             if let RatchetTreeNode::Blank = tree.nodes[idx] {
                 match tree_math::node_level(idx) {
                     0 => return,
@@ -299,26 +316,26 @@ impl RatchetTree {
 /// A node in RatchetTree. Every node must have a DH public key.
 /// It may also optionally contain the corresponding private key.
 #[derive(Debug, Clone, Encode)]
-pub enum RatchetTreeNode {
+pub enum RatchetTreeNode<CS: CipherSuite> {
     Blank,
     Filled {
-        public_key: DhPubKey,
+        public_key: CS::PubKey,
         #[codec(skip)]
-        private_key: Option<DhPrivateKey>,
+        private_key: Option<CS::PrivKey>,
     },
 }
 
-impl RatchetTreeNode {
+impl<CS: CipherSuite> RatchetTreeNode<CS> {
     #[allow(dead_code)]
-    pub fn from_private_key(private_key: DhPrivateKey) -> Self {
-        let public_key = DhPubKey::from_private_key(&private_key);
+    pub fn from_private_key(private_key: CS::PrivKey) -> Self {
+        let public_key = CS::public_key(&private_key);
         RatchetTreeNode::Filled {
             public_key,
             private_key: Some(private_key),
         }
     }
 
-    pub fn update_priv_key(&mut self, new_priv_key: DhPrivateKey) {
+    pub fn update_priv_key(&mut self, new_priv_key: CS::PrivKey) {
         match self {
             RatchetTreeNode::Blank => panic!("tried to update private key of blank node"),
             RatchetTreeNode::Filled {
@@ -330,7 +347,7 @@ impl RatchetTreeNode {
         }
     }
 
-    pub fn update_pub_key(&mut self, new_pub_key: DhPubKey) {
+    pub fn update_pub_key(&mut self, new_pub_key: CS::PubKey) {
         match self {
             RatchetTreeNode::Blank => {
                 *self = RatchetTreeNode::Filled {
@@ -344,7 +361,7 @@ impl RatchetTreeNode {
         }
     }
 
-    pub fn private_key(&self) -> Option<&DhPrivateKey> {
+    pub fn private_key(&self) -> Option<&CS::PrivKey> {
         match self {
             RatchetTreeNode::Blank => None,
             RatchetTreeNode::Filled {
@@ -353,7 +370,7 @@ impl RatchetTreeNode {
         }
     }
 
-    pub fn public_key(&self) -> Option<&DhPubKey> {
+    pub fn public_key(&self) -> Option<&CS::PubKey> {
         match self {
             RatchetTreeNode::Blank => None,
             RatchetTreeNode::Filled { ref public_key, .. } => Some(public_key),
@@ -1,4 +1,5 @@
 use crate::application::AppKeyChain;
+use crate::crypto::suite::CipherSuite;
 use crate::group_state::GroupState;
 use crate::handshake::{Handshake, PathSecretKVS, PathSecretSource};
 use crate::local_anyhow::anyhow;
@@ -14,37 +15,81 @@ pub fn init_path_secret_kvs(kvs: &mut PathSecretKVS, until_roster_idx: usize, un
     }
 }
 
-pub fn do_handshake_three_party(
-    my_group: &mut GroupState,
-    others_group1: &mut GroupState,
-    others_group2: &mut GroupState,
+pub fn do_handshake_three_party<CS: CipherSuite>(
+    my_group: &mut GroupState<CS>,
+    others_group1: &mut GroupState<CS>,
+    others_group2: &mut GroupState<CS>,
     source: &PathSecretSource,
-) -> (AppKeyChain, AppKeyChain, AppKeyChain) {
-    let max_roster_idx = 2;
+) -> (AppKeyChain<CS>, AppKeyChain<CS>, AppKeyChain<CS>) {
     let (handshake, _) = my_group.create_handshake(source).unwrap();
     let dummy_fn = |_: &[u8]| Err(anyhow!("This is dummy_fn"));
 
     let my_keychain = my_group
-        .process_handshake(&handshake, source, max_roster_idx, dummy_fn)
+        .process_handshake(&handshake, source, dummy_fn)
         .unwrap();
     let others_keychain1 = others_group1
-        .process_handshake(&handshake, source, max_roster_idx, dummy_fn)
+        .process_handshake(&handshake, source, dummy_fn)
         .unwrap();
     let others_keychain2 = others_group2
-        .process_handshake(&handshake, source, max_roster_idx, dummy_fn)
+        .process_handshake(&handshake, source, dummy_fn)
         .unwrap();
 
     (my_keychain, others_keychain1, others_keychain2)
 }
 
-pub fn encrypt_decrypt_helper(
+/// The member of `sender_idx` sends a handshake, and then all members in `groups` process it.
+pub fn do_handshake_n_party<CS: CipherSuite>(
+    sender_idx: usize,
+    groups: &mut [GroupState<CS>],
+    source: &PathSecretSource,
+) -> Vec<AppKeyChain<CS>> {
+    let (handshake, _) = groups[sender_idx].create_handshake(source).unwrap();
+    let dummy_fn = |_: &[u8]| Err(anyhow!("This is dummy_fn"));
+
+    groups
+        .iter_mut()
+        .map(|group| {
+            group
+                .process_handshake(&handshake, source, dummy_fn)
+                .unwrap()
+        })
+        .collect()
+}
+
+/// `my_group` removes `removed_group` from the group.
+/// The removed member must fail to process the handshake.
+pub fn do_remove_handshake<CS: CipherSuite>(
+    my_group: &mut GroupState<CS>,
+    others_group: &mut GroupState<CS>,
+    removed_group: &mut GroupState<CS>,
+    source: &PathSecretSource,
+) -> (AppKeyChain<CS>, AppKeyChain<CS>) {
+    let (handshake, _) = my_group
+        .create_remove_handshake(source, removed_group.my_roster_idx().unwrap())
+        .unwrap();
+    let dummy_fn = |_: &[u8]| Err(anyhow!("This is dummy_fn"));
+
+    let my_keychain = my_group
+        .process_handshake(&handshake, source, dummy_fn)
+        .unwrap();
+    let others_keychain = others_group
+        .process_handshake(&handshake, source, dummy_fn)
+        .unwrap();
+    assert!(removed_group
+        .process_handshake(&handshake, source, dummy_fn)
+        .is_err());
+
+    (my_keychain, others_keychain)
+}
+
+pub fn encrypt_decrypt_helper<CS: CipherSuite>(
     msg: &[u8],
-    group1: &GroupState,
-    app_key_chain1: &mut AppKeyChain,
-    group2: &GroupState,
-    app_key_chain2: &mut AppKeyChain,
-    group3: &GroupState,
-    app_key_chain3: &mut AppKeyChain,
+    group1: &GroupState<CS>,
+    app_key_chain1: &mut AppKeyChain<CS>,
+    group2: &GroupState<CS>,
+    app_key_chain2: &mut AppKeyChain<CS>,
+    group3: &GroupState<CS>,
+    app_key_chain3: &mut AppKeyChain<CS>,
 ) {
     let app_msg = app_key_chain1.encrypt_msg(msg.to_vec(), group1).unwrap();
 
@@ -15,6 +15,12 @@ pub type RawPubkey = [u8; PUBKEY_SIZE];
 pub type RawSig = [u8; SIG_SIZE];
 pub type RawChallenge = [u8; RANDOMNESS_SIZE];
 
+/// The command fetching an ecall output which didn't fit in the host's buffer.
+/// Its input is the ticket which the enclave wrote in the buffer along with `EnclaveStatus::output_too_large()`.
+pub const FETCH_OUTPUT_CMD: u32 = u32::MAX;
+/// Size of the random ticket of a pending ecall output
+pub const OUTPUT_TICKET_SIZE: usize = 8;
+
 /// Status for Ecall
 #[repr(C)]
 #[derive(Debug, PartialEq, Eq)]
@@ -35,6 +41,16 @@ impl EnclaveStatus {
         EnclaveStatus(1)
     }
 
+    /// The output is larger than the host's buffer.
+    /// Its length is set to `real_out_len` and it's kept in the enclave until fetched by `FETCH_OUTPUT_CMD`.
+    pub fn output_too_large() -> Self {
+        EnclaveStatus(2)
+    }
+
+    pub fn is_output_too_large(&self) -> bool {
+        self.0 == 2
+    }
+
     pub fn is_err(&self) -> bool {
         match self.0 {
             0 => false,
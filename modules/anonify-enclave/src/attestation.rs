@@ -0,0 +1,170 @@
+//! Remote attestation of this enclave's identity key.
+//! The verified report is cached until shortly before it expires, so that joining the group,
+//! registering the report and requests to the key vault don't need a round-trip to the attestation service every time.
+
+use crate::{error::Result, identity_key::EnclaveIdentityKey};
+use anonify_io_types::*;
+use anyhow::anyhow;
+use frame_common::{crypto::AttestedReport, state_types::StateType};
+use frame_enclave::EnclaveEngine;
+use frame_runtime::traits::*;
+use remote_attestation::{AttestationPolicy, AttestationService};
+use sgx_types::sgx_spid_t;
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+#[cfg(feature = "sgx")]
+use std::sync::SgxRwLock as RwLock;
+use std::{
+    prelude::v1::*,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The cached report is refreshed this long before it expires.
+/// It's capped at a half of the report's lifetime so that reports with short max ages are also reused.
+const REFRESH_MARGIN_SECS: u64 = 60 * 60;
+
+/// Serves the cached attestation report to peers and clients.
+#[derive(Debug, Clone)]
+pub struct ReportGetter;
+
+impl EnclaveEngine for ReportGetter {
+    type EI = input::GetReport;
+    type EO = output::ReturnReport;
+
+    fn handle<R, C>(
+        _ecall_input: Self::EI,
+        enclave_context: &C,
+        _max_mem_size: usize,
+    ) -> anyhow::Result<Self::EO>
+    where
+        R: RuntimeExecutor<C, S = StateType>,
+        C: ContextOps<S = StateType> + Clone,
+    {
+        let attested_report = enclave_context.attested_report()?;
+
+        Ok(output::ReturnReport::new(attested_report))
+    }
+}
+
+/// Attests this enclave's identity key and caches the verified report.
+#[derive(Clone)]
+pub struct RemoteAttestor {
+    identity_key: EnclaveIdentityKey,
+    spid: sgx_spid_t,
+    service: AttestationService,
+    policy: AttestationPolicy,
+    cache: Arc<RwLock<Option<AttestedReport>>>,
+}
+
+impl RemoteAttestor {
+    pub fn new(
+        identity_key: EnclaveIdentityKey,
+        spid: sgx_spid_t,
+        service: AttestationService,
+        policy: AttestationPolicy,
+    ) -> Self {
+        RemoteAttestor {
+            identity_key,
+            spid,
+            service,
+            policy,
+            cache: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Generate a QUOTE for the attestation service selected at runtime.
+    pub fn quote(&self) -> Result<String> {
+        if self.service.is_dcap() {
+            self.identity_key.dcap_quote()
+        } else {
+            self.identity_key.quote(&self.spid)
+        }
+    }
+
+    pub fn policy(&self) -> &AttestationPolicy {
+        &self.policy
+    }
+
+    /// Returns the cached report until its refresh time has passed, and then attests this enclave again.
+    pub fn attested_report(&self) -> Result<AttestedReport> {
+        let now = unix_time()?;
+        if let Some(report) = fresh_report(&*self.cache.read().unwrap(), now) {
+            return Ok(report);
+        }
+
+        // Hold the write lock while attesting so that concurrent callers don't attest twice.
+        let mut cache = self.cache.write().unwrap();
+        if let Some(report) = fresh_report(&*cache, now) {
+            return Ok(report);
+        }
+
+        let (report, report_sig, cert) =
+            self.identity_key
+                .remote_attestation(&self.spid, &self.service, &self.policy)?;
+        let issued_at = report.issued_at()?;
+        let expires_at = report.expires_at(&self.policy)?;
+        let attested_report = AttestedReport::new(
+            report.into_vec(),
+            report_sig.into_vec(),
+            cert.into_vec(),
+            refresh_time(issued_at, expires_at),
+            expires_at,
+        );
+
+        *cache = Some(attested_report.clone());
+        Ok(attested_report)
+    }
+
+    /// Cache the report attested without this attestor, e.g. for a mock quote in tests.
+    #[cfg(feature = "sgx")]
+    #[cfg(debug_assertions)]
+    pub(crate) fn set_attested_report(&self, attested_report: AttestedReport) {
+        *self.cache.write().unwrap() = Some(attested_report);
+    }
+}
+
+fn fresh_report(cache: &Option<AttestedReport>, now: u64) -> Option<AttestedReport> {
+    cache
+        .as_ref()
+        .filter(|report| now < report.refresh_at())
+        .cloned()
+}
+
+fn refresh_time(issued_at: u64, expires_at: u64) -> u64 {
+    let lifetime = expires_at.saturating_sub(issued_at);
+    expires_at - REFRESH_MARGIN_SECS.min(lifetime / 2)
+}
+
+fn unix_time() -> Result<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|e| anyhow!("{:?}", e).into())
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_refresh_time, test_fresh_report,)
+    }
+
+    fn test_refresh_time() {
+        const DAY: u64 = 24 * 60 * 60;
+        assert_eq!(refresh_time(0, DAY), DAY - REFRESH_MARGIN_SECS);
+        // The margin is capped at a half of the lifetime.
+        assert_eq!(refresh_time(1000, 1600), 1300);
+    }
+
+    fn test_fresh_report() {
+        let cache = Some(AttestedReport::new(vec![1], vec![2], vec![3], 100, 200));
+
+        assert!(fresh_report(&cache, 99).is_some());
+        assert!(fresh_report(&cache, 100).is_none());
+        assert!(fresh_report(&None, 0).is_none());
+    }
+}
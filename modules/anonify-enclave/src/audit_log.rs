@@ -0,0 +1,170 @@
+//! The enclave's audit log of state transitions, which is stored by the host.
+//! See `frame_common::audit_log` for its verification.
+//! Each launch starts a new chain anchored to the last entry stored by the host.
+//! The host chooses the anchor, so a malicious host can drop entries from the end of the previous chain
+//! and give an earlier entry as the last one. Such entries are only detected by comparing the log with
+//! the ciphertexts stored in the contract, each of which has an entry with its hash.
+
+use crate::{error::Result, identity_key::EnclaveIdentityKey};
+use anyhow::anyhow;
+use frame_common::audit_log::{AuditEntry, SignedAuditEntry, AUDIT_HASH_SIZE};
+use frame_enclave::{
+    log::warn,
+    ocalls::{append_audit_log, last_audit_log_entry},
+};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "sgx")]
+use std::sync::SgxMutex as Mutex;
+use std::{
+    prelude::v1::*,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone)]
+pub struct AuditLog {
+    identity_key: EnclaveIdentityKey,
+    /// Locked until the host stores the entry, so that the entries are stored in order.
+    head: Arc<Mutex<ChainHead>>,
+}
+
+struct ChainHead {
+    next_seq: u64,
+    prev_hash: [u8; AUDIT_HASH_SIZE],
+}
+
+impl AuditLog {
+    /// If the host fails to give the last entry, e.g. the log is corrupted, a new chain which isn't anchored is started,
+    /// so that the enclave can still be launched.
+    pub fn new(identity_key: EnclaveIdentityKey) -> Self {
+        let last_entry = last_audit_log_entry().unwrap_or_else(|e| {
+            warn!(
+                "Failed to get the last audit log entry, so the new chain isn't anchored: {:?}",
+                e
+            );
+            None
+        });
+        Self::anchored(identity_key, last_entry.as_ref())
+    }
+
+    /// Starts a new chain whose first entry has the hash of the previous chain's last entry.
+    fn anchored(identity_key: EnclaveIdentityKey, last_entry: Option<&SignedAuditEntry>) -> Self {
+        let head = ChainHead {
+            next_seq: 0,
+            prev_hash: last_entry.map_or([0u8; AUDIT_HASH_SIZE], |last| last.entry.hash()),
+        };
+
+        AuditLog {
+            identity_key,
+            head: Arc::new(Mutex::new(head)),
+        }
+    }
+
+    pub fn append(
+        &self,
+        ciphertext_hash: [u8; AUDIT_HASH_SIZE],
+        call_id: u32,
+        updated_states_hash: [u8; AUDIT_HASH_SIZE],
+    ) -> Result<()> {
+        let mut head = self.head.lock().unwrap();
+        let entry = AuditEntry {
+            seq: head.next_seq,
+            prev_hash: head.prev_hash,
+            timestamp: unix_time()?,
+            ciphertext_hash,
+            call_id,
+            updated_states_hash,
+        };
+        let signed = self.sign(entry)?;
+        head.next_seq += 1;
+        head.prev_hash = signed.entry.hash();
+
+        append_audit_log(&signed).map_err(Into::into)
+    }
+
+    fn sign(&self, entry: AuditEntry) -> Result<SignedAuditEntry> {
+        let (sig, recovery_id) = self.identity_key.sign_recoverable(&entry.hash())?;
+
+        Ok(SignedAuditEntry::new(
+            entry,
+            sig.serialize().to_vec(),
+            recovery_id.serialize(),
+        ))
+    }
+}
+
+fn unix_time() -> Result<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|e| anyhow!("{:?}", e).into())
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use frame_common::audit_log::verify_chain;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_verify_signed_chain,)
+    }
+
+    fn signed_chain(audit_log: &AuditLog, len: u64) -> Vec<SignedAuditEntry> {
+        let mut prev_hash = audit_log.head.lock().unwrap().prev_hash;
+        (0..len)
+            .map(|seq| {
+                let entry = AuditEntry {
+                    seq,
+                    prev_hash,
+                    timestamp: 0,
+                    ciphertext_hash: [seq as u8; AUDIT_HASH_SIZE],
+                    call_id: 1,
+                    updated_states_hash: [0u8; AUDIT_HASH_SIZE],
+                };
+                prev_hash = entry.hash();
+                audit_log.sign(entry).unwrap()
+            })
+            .collect()
+    }
+
+    fn test_verify_signed_chain() {
+        let identity_key = EnclaveIdentityKey::new().unwrap();
+        let audit_log = AuditLog::anchored(identity_key.clone(), None);
+        let entries = signed_chain(&audit_log, 3);
+
+        assert_eq!(
+            verify_chain(&entries).unwrap(),
+            vec![identity_key.verifying_address()]
+        );
+        // The chain starts over anchored to the last entry when the enclave is launched again.
+        let relaunched = AuditLog::anchored(EnclaveIdentityKey::new().unwrap(), entries.last());
+        let mut relaunched_entries = entries.clone();
+        relaunched_entries.extend(signed_chain(&relaunched, 2));
+        assert_eq!(verify_chain(&relaunched_entries).unwrap().len(), 2);
+        // The last entry of the previous chain is dropped.
+        let mut truncated = relaunched_entries.clone();
+        truncated.remove(2);
+        assert!(verify_chain(&truncated).is_err());
+        // A chain which isn't anchored
+        let unanchored = AuditLog::anchored(EnclaveIdentityKey::new().unwrap(), None);
+        let mut unanchored_entries = entries.clone();
+        unanchored_entries.extend(signed_chain(&unanchored, 2));
+        assert!(verify_chain(&unanchored_entries).is_err());
+
+        // A missing entry
+        let mut missing = entries.clone();
+        missing.remove(1);
+        assert!(verify_chain(&missing).is_err());
+        // A tampered entry
+        let mut tampered = entries.clone();
+        tampered[1].entry.call_id = 2;
+        assert!(verify_chain(&tampered).is_err());
+        // An entry signed by another enclave
+        let mut forged = entries;
+        forged[2] = relaunched.sign(forged[2].entry.clone()).unwrap();
+        assert!(verify_chain(&forged).is_err());
+    }
+}
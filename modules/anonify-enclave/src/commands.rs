@@ -8,8 +8,9 @@ use frame_common::{
     traits::Hash256,
     AccessPolicy,
 };
-use frame_enclave::EnclaveEngine;
+use frame_enclave::{log::warn, EnclaveEngine};
 use frame_runtime::traits::*;
+use frame_treekem::SenderKey;
 use std::{marker::PhantomData, vec::Vec};
 
 /// A message sender that encrypts commands
@@ -23,7 +24,10 @@ impl<AP: AccessPolicy> EnclaveEngine for MsgSender<AP> {
     type EO = output::Command;
 
     fn eval_policy(ecall_input: &Self::EI) -> anyhow::Result<()> {
-        ecall_input.access_policy().verify()
+        ecall_input.access_policy().verify()?;
+        ecall_input
+            .access_policy()
+            .verify_call_id(ecall_input.call_id)
     }
 
     fn handle<R, C>(
@@ -35,16 +39,16 @@ impl<AP: AccessPolicy> EnclaveEngine for MsgSender<AP> {
         R: RuntimeExecutor<C, S = StateType>,
         C: ContextOps<S = StateType> + Clone,
     {
-        let group_key = &mut *enclave_context.write_group_key();
-        let roster_idx = group_key.my_roster_idx() as usize;
-        // ratchet sender's app keychain per tx.
-        group_key.sender_ratchet(roster_idx)?;
-
         let account_id = ecall_input.access_policy().into_account_id();
         let mut command = enclave_context.decrypt(ecall_input.encrypted_command)?;
+        let commands = Commands::<R, C>::new(ecall_input.call_id, &mut command, account_id)?;
 
-        let ciphertext = Commands::<R, C>::new(ecall_input.call_id, &mut command, account_id)?
-            .encrypt(group_key, max_mem_size)?;
+        // ratchet sender's app keychain per tx.
+        // Only the reservation holds the group key's lock, so commands are encrypted concurrently.
+        // It's reserved after the command is validated, because the receivers skip up to `MAX_SKIPPED_GENERATIONS`
+        // and the generations reserved by rejected commands would never be sent.
+        let sender_key = enclave_context.write_group_key().reserve_sender_key()?;
+        let ciphertext = commands.encrypt(sender_key, max_mem_size)?;
 
         let msg = Sha256::hash(&ciphertext.encode());
         let enclave_sig = enclave_context.sign(msg.as_bytes())?;
@@ -74,33 +78,34 @@ impl EnclaveEngine for MsgReceiver {
         C: ContextOps<S = StateType> + Clone,
     {
         let group_key = &mut *enclave_context.write_group_key();
-        let roster_idx = ecall_input.ciphertext().roster_idx() as usize;
-        let msg_gen = ecall_input.ciphertext().generation();
-
-        // Since the sender's keychain has already ratcheted,
-        // even if an error occurs in the state transition, the receiver's keychain also ratchet.
-        // `receiver_ratchet` fails if
-        //   1. Roster index is out of range of the keychain
-        //   2. error occurs in HKDF
-        //   3. the generation is over u32::MAX
-        // In addition to these, `sync_ratchet` fails even if the receiver generation is larger than that of the sender
-        // So if you run `sync_ratchet` first,
-        // it will either succeed or both fail for the mutable `app_keychain`, so it will be atomic.
-        group_key.sync_ratchet(roster_idx, msg_gen)?;
-        group_key.receiver_ratchet(roster_idx)?;
 
+        // The receiver's keychain ratchets up to the message's generation only if the message is decrypted,
+        // so messages can be received out of order, and the keychain stays consistent on errors.
         // Even if an error occurs in the state transition logic here, there is no problem because the state of `app_keychain` is consistent.
-        let iter_op = Commands::<R, C>::state_transition(
+        let transition_op = Commands::<R, C>::state_transition(
             enclave_context.clone(),
             ecall_input.ciphertext(),
             group_key,
         )?;
         let mut output = output::ReturnUpdatedState::default();
 
-        if let Some(updated_state_iter) = iter_op {
-            if let Some(updated_state) = enclave_context.update_state(updated_state_iter) {
+        if let Some((call_id, updated_states)) = transition_op {
+            let ciphertext_hash = Sha256::hash(&ecall_input.ciphertext().encode()).as_array();
+            let updated_states_hash = Sha256::hash(&updated_states.encode()).as_array();
+
+            if let Some(updated_state) = enclave_context.update_state(updated_states.into_iter()) {
                 output.update(updated_state);
             }
+            // The state transition has been applied as in the other members' enclaves,
+            // so a failure of the audit log doesn't fail it. The missing entry is detected by the verifier.
+            if let Err(e) =
+                enclave_context.append_audit_log(ciphertext_hash, call_id, updated_states_hash)
+            {
+                warn!(
+                    "Failed to append the state transition to the audit log: {}",
+                    e
+                );
+            }
         }
 
         Ok(output)
@@ -126,7 +131,7 @@ impl<R: RuntimeExecutor<CTX, S = StateType>, CTX: ContextOps> Commands<R, CTX> {
         })
     }
 
-    pub fn encrypt<GK: GroupKeyOps>(&self, key: &GK, max_mem_size: usize) -> Result<Ciphertext> {
+    pub fn encrypt(&self, key: SenderKey, max_mem_size: usize) -> Result<Ciphertext> {
         // Add padding to fix the ciphertext size of all state types.
         // The padding works for fixing the ciphertext size so that
         // other people cannot distinguish what state is encrypted based on the size.
@@ -138,20 +143,22 @@ impl<R: RuntimeExecutor<CTX, S = StateType>, CTX: ContextOps> Commands<R, CTX> {
 
         let mut buf = self.encode();
         append_padding(&mut buf, max_mem_size);
-        key.encrypt(buf).map_err(Into::into)
+        key.encrypt_msg(buf).map_err(Into::into)
     }
 
     /// Only if the TEE belongs to the group, you can receive ciphertext and decrypt it,
     /// otherwise do nothing.
+    /// Returns the call id with the updated states.
     pub fn state_transition<GK: GroupKeyOps>(
         ctx: CTX,
         ciphertext: &Ciphertext,
         group_key: &mut GK,
-    ) -> Result<Option<impl Iterator<Item = UpdatedState<StateType>> + Clone>> {
+    ) -> Result<Option<(u32, Vec<UpdatedState<StateType>>)>> {
         if let Some(commands) = Commands::<R, CTX>::decrypt(ciphertext, group_key)? {
-            let state_iter = commands.stf_call(ctx)?.into_iter();
+            let call_id = commands.call_kind.id();
+            let updated_states = commands.stf_call(ctx)?;
 
-            return Ok(Some(state_iter));
+            return Ok(Some((call_id, updated_states)));
         }
 
         Ok(None)
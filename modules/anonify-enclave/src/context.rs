@@ -1,59 +1,59 @@
 use crate::{
-    error::Result, group_key::GroupKey, identity_key::EnclaveIdentityKey, kvs::EnclaveDB,
+    attestation::RemoteAttestor,
+    audit_log::AuditLog,
+    error::Result,
+    group_key::GroupKey,
+    identity_key::{self_mr_enclave, EnclaveIdentityKey},
+    key_vault::{KeyVaultClient, MR_ENCLAVE_SIZE},
+    kvs::EnclaveDB,
     notify::Notifier,
 };
 use anonify_io_types::*;
 use anyhow::anyhow;
 use frame_common::{
-    crypto::AccountId,
+    crypto::{AccountId, AttestedReport},
     state_types::{MemId, ReturnState, StateType, UpdatedState},
     AccessPolicy,
 };
-use frame_enclave::{
-    ocalls::{get_quote, sgx_init_quote},
-    EnclaveEngine,
-};
+use frame_enclave::EnclaveEngine;
 use frame_runtime::traits::*;
 use frame_treekem::{
     handshake::{PathSecretKVS, PathSecretSource},
     init_path_secret_kvs, DhPubKey, EciesCiphertext,
 };
-use remote_attestation::RAService;
+use remote_attestation::{
+    AttestationPolicy, AttestationService, DEFAULT_ALLOWED_QUOTE_STATUSES, DEFAULT_MAX_REPORT_AGE,
+};
 use sgx_types::*;
 use std::prelude::v1::*;
-use std::{
-    env,
-    marker::PhantomData,
-    sync::{Arc, SgxRwLock, SgxRwLockReadGuard, SgxRwLockWriteGuard},
+#[cfg(feature = "std")]
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "sgx")]
+use std::sync::{
+    SgxRwLock as RwLock, SgxRwLockReadGuard as RwLockReadGuard,
+    SgxRwLockWriteGuard as RwLockWriteGuard,
 };
+use std::{env, marker::PhantomData, sync::Arc, time::Duration};
 
 pub const MRENCLAVE_VERSION: usize = 0;
+/// Intel's provisioning certification service, which can be replaced with a caching service (PCCS)
+const DEFAULT_PCCS_URL: &str = "https://api.trustedservices.intel.com/sgx/certification/v3";
 
-/// spid: Service provider ID for the ISV.
 #[derive(Clone)]
 pub struct EnclaveContext {
     version: usize,
-    ias_url: String,
-    sub_key: String,
-    spid: sgx_spid_t,
+    attestor: RemoteAttestor,
     identity_key: EnclaveIdentityKey,
     db: EnclaveDB,
     notifier: Notifier,
-    group_key: Arc<SgxRwLock<GroupKey>>,
+    group_key: Arc<RwLock<GroupKey>>,
+    audit_log: AuditLog,
 }
 
 impl ContextOps for EnclaveContext {
     fn mrenclave_ver(&self) -> usize {
         self.version
     }
-
-    fn ias_url(&self) -> &str {
-        &self.ias_url
-    }
-
-    fn sub_key(&self) -> &str {
-        &self.sub_key
-    }
 }
 
 impl StateOps for EnclaveContext {
@@ -108,11 +108,11 @@ impl StateOps for EnclaveContext {
 impl GroupKeyGetter for EnclaveContext {
     type GK = GroupKey;
 
-    fn read_group_key(&self) -> SgxRwLockReadGuard<Self::GK> {
+    fn read_group_key(&self) -> RwLockReadGuard<Self::GK> {
         self.group_key.read().unwrap()
     }
 
-    fn write_group_key(&self) -> SgxRwLockWriteGuard<Self::GK> {
+    fn write_group_key(&self) -> RwLockWriteGuard<Self::GK> {
         self.group_key.write().unwrap()
     }
 }
@@ -144,16 +144,32 @@ impl IdentityKeyOps for EnclaveContext {
     }
 }
 
+impl AuditLogOps for EnclaveContext {
+    fn append_audit_log(
+        &self,
+        ciphertext_hash: [u8; 32],
+        call_id: u32,
+        updated_states_hash: [u8; 32],
+    ) -> anyhow::Result<()> {
+        self.audit_log
+            .append(ciphertext_hash, call_id, updated_states_hash)
+            .map_err(Into::into)
+    }
+}
+
 impl QuoteGetter for EnclaveContext {
     fn quote(&self) -> anyhow::Result<String> {
-        let target_info = self.init_quote()?;
-        let report = self.report(&target_info)?;
-        self.encoded_quote(report).map_err(Into::into)
+        self.attestor.quote().map_err(Into::into)
+    }
+
+    fn attested_report(&self) -> anyhow::Result<AttestedReport> {
+        self.attestor.attested_report().map_err(Into::into)
     }
 }
 
 // TODO: Consider SGX_ERROR_BUSY.
 impl EnclaveContext {
+    /// spid: Service provider ID for the ISV.
     pub fn new(spid: &str) -> Result<Self> {
         let spid_vec = hex::decode(spid)?;
         let mut id = [0; 16];
@@ -163,6 +179,13 @@ impl EnclaveContext {
         let identity_key = EnclaveIdentityKey::new()?;
         let db = EnclaveDB::new();
 
+        let attestor = RemoteAttestor::new(
+            identity_key.clone(),
+            spid,
+            attestation_service()?,
+            attestation_policy()?,
+        );
+
         let source = match env::var("AUDITOR_ENDPOINT") {
             Err(_) => PathSecretSource::Local,
             Ok(test) if test == "test" => {
@@ -174,61 +197,125 @@ impl EnclaveContext {
             }
             Ok(url) => PathSecretSource::Remote(url),
         };
+        let key_vault = match &source {
+            PathSecretSource::Remote(addr) => Some(KeyVaultClient::new(
+                addr.clone(),
+                identity_key.clone(),
+                attestor.clone(),
+                allowed_key_vault_mrenclaves()?,
+            )?),
+            _ => None,
+        };
 
-        let my_roster_idx: usize = env::var("MY_ROSTER_IDX")
-            .expect("MY_ROSTER_IDX is not set")
-            .parse()
-            .expect("Failed to parse MY_ROSTER_IDX to usize");
-        let max_roster_idx: usize = env::var("MAX_ROSTER_IDX")
-            .expect("MAX_ROSTER_IDX is not set")
-            .parse()
-            .expect("Failed to parse MAX_ROSTER_IDX to usize");
-
-        let group_key = Arc::new(SgxRwLock::new(GroupKey::new(
-            my_roster_idx,
-            max_roster_idx,
+        let group_key = Arc::new(RwLock::new(GroupKey::new(
             source,
+            key_vault,
+            identity_key.clone(),
+            attestor.policy().clone(),
+            allowed_group_mrenclaves()?,
         )?));
         let notifier = Notifier::new();
-
-        let ias_url = env::var("IAS_URL")?;
-        let sub_key = env::var("SUB_KEY")?;
+        let audit_log = AuditLog::new(identity_key.clone());
 
         Ok(EnclaveContext {
-            spid,
+            attestor,
             identity_key,
             db,
             notifier,
             group_key,
+            audit_log,
             version: MRENCLAVE_VERSION,
-            ias_url,
-            sub_key,
         })
     }
+}
 
-    pub(crate) fn init_quote(&self) -> Result<sgx_target_info_t> {
-        let target_info = sgx_init_quote()?;
-        Ok(target_info)
+/// The attestation service is selected by `ATTESTATION_TYPE`, which is either `EPID` (default) or `DCAP`.
+fn attestation_service() -> Result<AttestationService> {
+    let attestation_type = env::var("ATTESTATION_TYPE").unwrap_or_else(|_| "EPID".to_string());
+    match attestation_type.as_str() {
+        "EPID" => Ok(AttestationService::Ias {
+            url: env::var("IAS_URL")?,
+            sub_key: env::var("SUB_KEY")?,
+        }),
+        "DCAP" => Ok(AttestationService::Dcap {
+            pccs_url: env::var("PCCS_URL").unwrap_or_else(|_| DEFAULT_PCCS_URL.to_string()),
+        }),
+        t => Err(anyhow!("Unsupported attestation type: {}", t).into()),
     }
+}
 
-    /// Return Attestation report
-    fn report(&self, target_info: &sgx_target_info_t) -> Result<sgx_report_t> {
-        let mut report = sgx_report_t::default();
-        let report_data = &self.identity_key.report_data()?;
-
-        if let Ok(r) = sgx_tse::rsgx_create_report(&target_info, &report_data) {
-            report = r;
+/// The attestation policy accepts the quote statuses and the advisories set by `ALLOWED_QUOTE_STATUSES`
+/// (default: `OK,UpToDate`) and `ALLOWED_ADVISORY_IDS` (default: none) when building the enclave,
+/// so the host cannot loosen it. The host may only shorten the max age of reports (default: 24 hours)
+/// by `ATTESTATION_MAX_AGE_SECS`.
+fn attestation_policy() -> Result<AttestationPolicy> {
+    let allowed_quote_statuses = match option_env!("ALLOWED_QUOTE_STATUSES") {
+        Some(statuses) => comma_separated(statuses),
+        None => DEFAULT_ALLOWED_QUOTE_STATUSES
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    };
+    let allowed_advisory_ids = comma_separated(option_env!("ALLOWED_ADVISORY_IDS").unwrap_or(""));
+    let policy = AttestationPolicy::new(
+        Some(DEFAULT_MAX_REPORT_AGE),
+        allowed_quote_statuses,
+        allowed_advisory_ids,
+    );
+
+    match env::var("ATTESTATION_MAX_AGE_SECS") {
+        Ok(secs) => {
+            let secs = secs
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| anyhow!("Invalid ATTESTATION_MAX_AGE_SECS: {}", e))?;
+            policy
+                .with_shorter_max_age(Duration::from_secs(secs))
+                .map_err(Into::into)
         }
-
-        Ok(report)
+        Err(_) => Ok(policy),
     }
+}
 
-    fn encoded_quote(&self, report: sgx_report_t) -> Result<String> {
-        let quote = get_quote(report, &self.spid)?;
+fn comma_separated(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|e| e.trim())
+        .filter(|e| !e.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
 
-        // Use base64-encoded QUOTE structure to communicate via defined API.
-        Ok(base64::encode(&quote))
-    }
+/// Comma-separated hex-encoded MRENCLAVEs of the key vault enclaves.
+/// They're set by `KEY_VAULT_MRENCLAVES` when building the enclave, so the host cannot change them.
+/// At least one of them is required to use the remote key vault, e.g. `MOCK_KEY_VAULT_MRENCLAVE` of the mock server in debug builds.
+fn allowed_key_vault_mrenclaves() -> Result<Vec<[u8; MR_ENCLAVE_SIZE]>> {
+    parse_mrenclaves(option_env!("KEY_VAULT_MRENCLAVES").unwrap_or_default())
+}
+
+/// MRENCLAVEs of the enclaves which the group members may run.
+/// Besides this build, the previous builds are set by `GROUP_MRENCLAVES` in the same format as `KEY_VAULT_MRENCLAVES`,
+/// so that the handshakes of the members which joined before `updateMrenclave` are still accepted.
+fn allowed_group_mrenclaves() -> Result<Vec<[u8; MR_ENCLAVE_SIZE]>> {
+    let mut mrenclaves = parse_mrenclaves(option_env!("GROUP_MRENCLAVES").unwrap_or_default())?;
+    mrenclaves.push(self_mr_enclave());
+
+    Ok(mrenclaves)
+}
+
+fn parse_mrenclaves(mrenclaves: &str) -> Result<Vec<[u8; MR_ENCLAVE_SIZE]>> {
+    mrenclaves
+        .split(',')
+        .filter(|e| !e.trim().is_empty())
+        .map(|e| {
+            let v = hex::decode(e.trim())?;
+            if v.len() != MR_ENCLAVE_SIZE {
+                return Err(anyhow!("MRENCLAVE must be {} bytes", MR_ENCLAVE_SIZE).into());
+            }
+            let mut res = [0u8; MR_ENCLAVE_SIZE];
+            res.copy_from_slice(&v);
+            Ok(res)
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone)]
@@ -241,7 +328,10 @@ impl<AP: AccessPolicy> EnclaveEngine for GetState<AP> {
     type EO = output::ReturnState;
 
     fn eval_policy(ecall_input: &Self::EI) -> anyhow::Result<()> {
-        ecall_input.access_policy().verify()
+        ecall_input.access_policy().verify()?;
+        ecall_input
+            .access_policy()
+            .verify_call_id(ecall_input.call_id())
     }
 
     fn handle<R, C>(
@@ -273,7 +363,7 @@ impl EnclaveEngine for ReportRegistration {
     type EO = output::ReturnRegisterReport;
 
     fn handle<R, C>(
-        _ecall_input: Self::EI,
+        ecall_input: Self::EI,
         enclave_context: &C,
         _max_mem_size: usize,
     ) -> anyhow::Result<Self::EO>
@@ -281,16 +371,21 @@ impl EnclaveEngine for ReportRegistration {
         R: RuntimeExecutor<C, S = StateType>,
         C: ContextOps<S = StateType> + Clone,
     {
-        let quote = enclave_context.quote()?;
-        let ias_url = enclave_context.ias_url();
-        let sub_key = enclave_context.sub_key();
-        let (report, report_sig) = RAService::remote_attestation(ias_url, sub_key, &quote)?;
+        let (report, report_sig, cert) = enclave_context.remote_attestation()?;
         let mrenclave_ver = enclave_context.mrenclave_ver();
-        let my_roster_idx = enclave_context.read_group_key().my_roster_idx();
+
+        // The recovered enclave takes over the roster index allocated when it joined the group,
+        // so that it can store commands and handshakes with its new identity key.
+        // Its next handshake carries the attestation, so that the other members replace the verifying key
+        // registered for the roster index with the new one.
+        let my_roster_idx = ecall_input.roster_idx();
+        let group_key = &mut *enclave_context.write_group_key();
+        group_key.set_my_roster_idx(my_roster_idx)?;
+        group_key.set_my_attestation(report.clone(), report_sig.clone(), cert);
 
         Ok(output::ReturnRegisterReport::new(
-            report.into_vec(),
-            report_sig.into_vec(),
+            report,
+            report_sig,
             mrenclave_ver,
             my_roster_idx,
         ))
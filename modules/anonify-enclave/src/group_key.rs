@@ -1,28 +1,70 @@
-use anyhow::{anyhow, Result};
-use frame_common::crypto::{Ciphertext, ExportHandshake, ExportPathSecret};
+use crate::{
+    handshake::OWNER_ROSTER_IDX,
+    identity_key::{recover_verifying_address, EnclaveIdentityKey},
+    key_vault::{verify_attested_identity, KeyVaultClient, MR_ENCLAVE_SIZE},
+};
+use anonify_io_types::key_vault::{AttestedIdentity, VERIFYING_ADDRESS_SIZE};
+use anyhow::{anyhow, ensure, Result};
+use codec::{Decode, Encode};
+use frame_common::{
+    crypto::{Ciphertext, ExportHandshake, ExportPathSecret, Sha256},
+    traits::Hash256,
+};
 use frame_runtime::traits::*;
 use frame_treekem::{
     handshake::{HandshakeParams, PathSecretSource},
-    AppKeyChain, GroupState, Handshake,
+    AppKeyChain, GroupState, Handshake, PathSecret, SenderKey,
 };
-use std::vec::Vec;
+use remote_attestation::{AttestationPolicy, AttestationReport};
+use std::{collections::HashMap, vec::Vec};
+
+/// The number of messages in the current epoch received while the previous epoch's keychain is kept.
+/// Messages sent before a handshake can still be decrypted in this window.
+const PRIOR_KEYCHAIN_WINDOW: u32 = 100;
 
 #[derive(Clone, Debug)]
 pub struct GroupKey {
     group_state: GroupState,
     sender_keychain: AppKeyChain,
     receiver_keychain: AppKeyChain,
-    max_roster_idx: usize,
+    /// The previous epoch's receiver keychain to decrypt messages in flight while the handshake is processed
+    prior_receiver_keychain: Option<AppKeyChain>,
+    /// The remaining number of messages until the previous epoch's keychain is dropped
+    prior_keychain_window: u32,
     source: PathSecretSource,
+    /// Only if the path secret source is remote, path secrets are backed up to the key vault.
+    key_vault: Option<KeyVaultClient>,
+    /// Handshakes created by this enclave are signed with the identity key.
+    identity_key: EnclaveIdentityKey,
+    /// The encoded attestation of this enclave's identity key,
+    /// which is attached to the created handshakes until the group processes one of them.
+    my_attestation: Option<Vec<u8>>,
+    /// The attested verifying keys of members' identity keys by roster index
+    verifying_keys: HashMap<u32, RegisteredKey>,
+    /// The policy of the attestations embedded in handshakes
+    attestation_policy: AttestationPolicy,
+    /// MRENCLAVEs of the enclaves which the members may run, i.e. this build and the previous ones
+    allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>,
+}
+
+/// A member's verifying key, and when its attestation was issued
+#[derive(Clone, Copy, Debug)]
+struct RegisteredKey {
+    verifying_address: [u8; VERIFYING_ADDRESS_SIZE],
+    issued_at: u64,
 }
 
 impl GroupKey {
+    /// The roster index is assigned when this enclave joins the group,
+    /// so the group state only follows the public part of the tree until then.
     pub fn new(
-        my_roster_idx: usize,
-        max_roster_idx: usize,
         source: PathSecretSource,
+        key_vault: Option<KeyVaultClient>,
+        identity_key: EnclaveIdentityKey,
+        attestation_policy: AttestationPolicy,
+        allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>,
     ) -> Result<Self> {
-        let group_state = GroupState::new(my_roster_idx)?;
+        let group_state = GroupState::new_unassigned();
         let sender_keychain = AppKeyChain::default();
         let receiver_keychain = sender_keychain.clone();
 
@@ -30,113 +72,504 @@ impl GroupKey {
             group_state,
             sender_keychain,
             receiver_keychain,
-            max_roster_idx,
+            prior_receiver_keychain: None,
+            prior_keychain_window: 0,
             source,
+            key_vault,
+            identity_key,
+            my_attestation: None,
+            verifying_keys: HashMap::new(),
+            attestation_policy,
+            allowed_mrenclaves,
         })
     }
+
+    fn sign_handshake(&self, mut handshake: ExportHandshake) -> Result<ExportHandshake> {
+        let msg = Sha256::hash(&handshake.signing_message());
+        let (sig, recovery_id) = self.identity_key.sign_recoverable(msg.as_bytes())?;
+        let attestation = self.my_attestation.clone().unwrap_or_default();
+        handshake.set_sig(
+            sig.serialize().to_vec(),
+            recovery_id.serialize(),
+            attestation,
+        );
+
+        Ok(handshake)
+    }
+
+    /// Returns the verifying key of the handshake's sender.
+    /// If the handshake is signed by the verifying key registered for the sender's roster index, it's accepted.
+    /// Otherwise, the handshake must have an attestation, and the signer must be the attested identity key
+    /// running on an allowed enclave.
+    /// The identity key is generated every time the enclave is launched, so a restarted member's new key
+    /// replaces the registered one, but only if its attestation was issued later.
+    /// The contract only stores the handshakes signed by the key registered with `registerReport` for the roster index.
+    fn verify_handshake_sender(&self, handshake: &ExportHandshake) -> Result<RegisteredKey> {
+        let msg = Sha256::hash(&handshake.signing_message());
+        let signer =
+            recover_verifying_address(msg.as_bytes(), handshake.sig(), handshake.recovery_id())?;
+
+        let registered = self.verifying_keys.get(&handshake.roster_idx());
+        if let Some(registered) = registered {
+            if registered.verifying_address == signer {
+                return Ok(*registered);
+            }
+        }
+
+        ensure!(
+            !handshake.attestation().is_empty(),
+            "The handshake isn't signed by the registered verifying key of the roster index ({:?})",
+            handshake.roster_idx()
+        );
+        let identity = AttestedIdentity::decode(&mut &handshake.attestation()[..])
+            .map_err(|e| anyhow!("AttestedIdentity::decode Error: {:?}", e))?;
+        // Handshakes are replayed from the contract's logs on recovery,
+        // so the attestations in them are accepted regardless of their age.
+        verify_attested_identity(
+            &identity,
+            &self.allowed_mrenclaves,
+            &self.attestation_policy.without_max_age(),
+        )?;
+        ensure!(
+            identity.verifying_address == signer,
+            "The handshake isn't signed by the attested verifying key"
+        );
+        let issued_at = AttestationReport::new(identity.report).issued_at()?;
+        if let Some(registered) = registered {
+            ensure!(
+                issued_at > registered.issued_at,
+                "The attestation of the new verifying key isn't later than the registered one's"
+            );
+        }
+
+        Ok(RegisteredKey {
+            verifying_address: signer,
+            issued_at,
+        })
+    }
+
+    fn backup_path_secret(&self, exp_ps: &ExportPathSecret) -> Result<()> {
+        if let Some(key_vault) = &self.key_vault {
+            let path_secret = PathSecret::try_from_importing(exp_ps.clone())?;
+            key_vault.backup_path_secret(path_secret, exp_ps.epoch(), exp_ps.id_as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Syncing the sender keychain with the receiver's one.
+    /// The sender's generation must not fall behind the receiver's,
+    /// e.g. after the recovery phase, otherwise the sent messages reuse the generations already received.
+    fn sync_sender_keychain(&mut self, roster_idx: usize) -> Result<()> {
+        let receiver_gen = self.receiver_keychain.generation(roster_idx)?;
+        while self.sender_keychain.generation(roster_idx)? < receiver_gen {
+            self.sender_keychain.ratchet(roster_idx)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_prior_epoch(&self, msg_epoch: u32) -> bool {
+        match &self.prior_receiver_keychain {
+            Some(prior) => {
+                msg_epoch == prior.epoch() && msg_epoch != self.receiver_keychain.epoch()
+            }
+            None => false,
+        }
+    }
+
+    fn count_down_prior_keychain_window(&mut self) {
+        if self.prior_receiver_keychain.is_some() {
+            self.prior_keychain_window = self.prior_keychain_window.saturating_sub(1);
+            if self.prior_keychain_window == 0 {
+                self.prior_receiver_keychain = None;
+            }
+        }
+    }
 }
 
 impl GroupKeyOps for GroupKey {
     fn create_handshake(&self) -> Result<(ExportHandshake, ExportPathSecret)> {
         let (handshake, exp_ps) = self.group_state.create_handshake(&self.source)?;
-        Ok((handshake.into_export(), exp_ps))
+        self.backup_path_secret(&exp_ps)?;
+
+        Ok((self.sign_handshake(handshake.into_export())?, exp_ps))
     }
 
-    fn process_handshake(&mut self, handshake: &HandshakeParams) -> Result<()> {
-        let keychain = self.group_state.process_handshake(
-            handshake,
-            &self.source,
-            self.max_roster_idx as u32,
-            frame_enclave::ocalls::import_path_secret,
-        )?;
-        // TODO: If the handshake transaction is flying out the air, wait updating the sender_keychain until the all remaining messages are proccessed.
-        // The number of remaining messages are difference between sender_keychain's generation and receiver_keychain's one.
-        self.sender_keychain = keychain.clone();
-        self.receiver_keychain = keychain;
+    fn create_remove_handshake(
+        &self,
+        removed_roster_idx: u32,
+    ) -> Result<(ExportHandshake, ExportPathSecret)> {
+        let (handshake, exp_ps) = self
+            .group_state
+            .create_remove_handshake(&self.source, removed_roster_idx)?;
+        self.backup_path_secret(&exp_ps)?;
+
+        Ok((self.sign_handshake(handshake.into_export())?, exp_ps))
+    }
+
+    fn process_handshake(
+        &mut self,
+        export_handshake: &ExportHandshake,
+        contract_address: &[u8],
+    ) -> Result<()> {
+        let verifying_key = self.verify_handshake_sender(export_handshake)?;
+        self.group_state.set_contract_address(contract_address)?;
+        let handshake = HandshakeParams::from_export(export_handshake.clone())?;
+        ensure!(
+            handshake.prior_epoch() == export_handshake.prior_epoch()
+                && handshake.roster_idx() == export_handshake.roster_idx(),
+            "The signed header doesn't match the handshake"
+        );
+        ensure!(
+            handshake.removed_roster_idx().is_none() || handshake.roster_idx() == OWNER_ROSTER_IDX,
+            "Only the owner can remove members from the group"
+        );
+
+        let keychain = match &self.key_vault {
+            Some(key_vault) => {
+                self.group_state
+                    .process_handshake(&handshake, &self.source, |id| {
+                        key_vault.recover_path_secret(id).map_err(Into::into)
+                    })?
+            }
+            None => self.group_state.process_handshake(
+                &handshake,
+                &self.source,
+                frame_enclave::ocalls::import_path_secret,
+            )?,
+        };
+        // Messages encrypted with the previous epoch's keychain may still be in flight,
+        // so keep the receiver keychain for a bounded window to decrypt them.
+        let prior_receiver_keychain =
+            std::mem::replace(&mut self.receiver_keychain, keychain.clone());
+        self.prior_receiver_keychain = Some(prior_receiver_keychain);
+        self.prior_keychain_window = PRIOR_KEYCHAIN_WINDOW;
+        self.sender_keychain = keychain;
+
+        self.verifying_keys
+            .insert(handshake.roster_idx(), verifying_key);
+        if let Some(removed_roster_idx) = handshake.removed_roster_idx() {
+            self.verifying_keys.remove(&removed_roster_idx);
+        }
+        // Once this enclave's current attestation is processed, the other members have registered its verifying key.
+        // Handshakes attested with the identity key before recovery don't count.
+        if self.my_attestation.as_deref() == Some(export_handshake.attestation()) {
+            self.my_attestation = None;
+        }
 
         Ok(())
     }
 
-    fn encrypt(&self, plaintext: Vec<u8>) -> Result<Ciphertext> {
-        self.sender_keychain
-            .encrypt_msg(plaintext, &self.group_state)
+    fn decrypt(&mut self, app_msg: &Ciphertext) -> Result<Option<Vec<u8>>> {
+        // A message sent before the last handshake is decrypted with the previous epoch's keychain,
+        // which is ratcheted independently from the current ones.
+        if self.is_prior_epoch(app_msg.epoch()) {
+            let prior = self
+                .prior_receiver_keychain
+                .as_mut()
+                .ok_or_else(|| anyhow!("The previous epoch's keychain has already been dropped"))?;
+            return prior.receive_msg(app_msg, &self.group_state);
+        }
+
+        let plaintext = self
+            .receiver_keychain
+            .receive_msg(app_msg, &self.group_state)?;
+        if plaintext.is_some() {
+            self.sync_sender_keychain(app_msg.roster_idx() as usize)?;
+        }
+        self.count_down_prior_keychain_window();
+
+        Ok(plaintext)
     }
 
-    fn decrypt(&self, app_msg: &Ciphertext) -> Result<Option<Vec<u8>>> {
-        self.receiver_keychain
-            .decrypt_msg(&app_msg, &self.group_state)
+    fn reserve_sender_key(&mut self) -> Result<SenderKey> {
+        self.sender_keychain.reserve_sender_key(&self.group_state)
     }
 
-    /// Ratchet sender's keychain per a transaction
-    fn sender_ratchet(&mut self, roster_idx: usize) -> Result<()> {
-        self.sender_keychain.ratchet(roster_idx)
+    fn my_roster_idx(&self) -> Result<u32> {
+        self.group_state
+            .my_roster_idx()
+            .ok_or_else(|| anyhow!("The roster index hasn't been assigned yet"))
     }
 
-    /// Ratchet receiver's keychain per a transaction
-    fn receiver_ratchet(&mut self, roster_idx: usize) -> Result<()> {
-        self.receiver_keychain.ratchet(roster_idx)
+    fn set_my_roster_idx(&mut self, roster_idx: u32) -> Result<()> {
+        self.group_state.set_my_roster_idx(roster_idx)
     }
 
-    /// Syncing the sender and receiver app keychains
-    fn sync_ratchet(&mut self, roster_idx: usize, msg_gen: u32) -> Result<()> {
-        let sender_gen = self.sender_keychain.generation(roster_idx)?;
-        let receiver_gen = self.receiver_keychain.generation(roster_idx)?;
+    fn set_my_attestation(&mut self, report: Vec<u8>, report_sig: Vec<u8>, signing_cert: Vec<u8>) {
+        let identity = AttestedIdentity::new(
+            report,
+            report_sig,
+            signing_cert,
+            self.identity_key.verifying_address(),
+            self.identity_key.encrypting_key(),
+        );
+        self.my_attestation = Some(identity.encode());
+    }
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::identity_key::self_mr_enclave;
+    use frame_treekem::{handshake::PathSecretKVS, init_path_secret_kvs};
+    use remote_attestation::RAService;
+    use std::{env, prelude::v1::*};
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_decrypt_prior_epoch_msg,
+            test_prior_keychain_window_expires,
+            test_attested_handshake_registers_key,
+            test_restarted_member_replaces_key,
+            test_remove_only_by_owner,
+        )
+    }
 
-        match sender_gen.checked_sub(receiver_gen) {
-            // syncing the sender and receiver app keychains
-            // Used for:
-            // - receiving messages from other TEE nodes
-            // - the recovery phase
-            Some(0) => {
-                println!(
-                    "[debug] syncing the sender and receiver app keychains in the recovery phase. The current generation is {:?}",
-                    receiver_gen
-                );
-                self.sender_ratchet(roster_idx)
-            },
-            // It's okay if the sender generation is only one bigger than receiver's.
-            Some(1) => Ok(()),
-            // The case there are multiple messages before the events are synced
-            Some(_) => {
-                // Even if the generation of the message is tampered with,
-                // there is no problem with the legitimacy of the state.
-                match msg_gen.checked_sub(receiver_gen) {
-                    Some(1) => Ok(()),
-                    // If an error occurs after ratcheting the sender's keychain,
-                    // the generation of the received message will be discontinuous against that of the receiver keychain,
-                    // so ratchet the receiver's keychain by the difference in order to be consistent.
-                    Some(diff) => {
-                        println!(
-                            "[warn] the generation of the received message will be discontinuous, so ratchet the receiver's keychain by {:?} times",
-                            diff - 1
-                        );
-                        for _ in 0..(diff - 1) {
-                            self.receiver_ratchet(roster_idx)?;
-                        }
-                        Ok(())
-                    },
-                    None => {
-                        Err(anyhow!(
-                            "The generation of the receiver keychain ({:?}) must not be bigger than the that of the received message ({:?}).
-                            Your TEE instance may not be synced to the latest state yet.",
-                            receiver_gen,
-                            msg_gen
-                        ))
-                    }
-                }
+    const MOCK_SUB_KEY: &str = "mock-sub-key";
+    const QUOTE_BODY_SIZE: usize = 432;
+    const MR_ENCLAVE_OFFSET: usize = 112;
+    const REPORT_DATA_OFFSET: usize = 368;
+
+    const MSG: &[u8] = b"group key test";
+    const CONTRACT_ADDRESS: [u8; 20] = [1u8; 20];
+
+    /// Group keys of the members whose verifying keys have been registered with each other
+    fn registered_group_keys(members: u32) -> Vec<GroupKey> {
+        let mut kvs = PathSecretKVS::new();
+        init_path_secret_kvs(&mut kvs, 10, 30);
+        let source = PathSecretSource::LocalTestKV(kvs);
+
+        let mut group_keys: Vec<GroupKey> = (0..members)
+            .map(|roster_idx| {
+                let mut group_key = GroupKey::new(
+                    source.clone(),
+                    None,
+                    EnclaveIdentityKey::new().unwrap(),
+                    AttestationPolicy::default(),
+                    vec![self_mr_enclave()],
+                )
+                .unwrap();
+                group_key.set_my_roster_idx(roster_idx).unwrap();
+                group_key
+            })
+            .collect();
+        let addresses: Vec<_> = group_keys
+            .iter()
+            .map(|group_key| group_key.identity_key.verifying_address())
+            .collect();
+        for group_key in &mut group_keys {
+            for (roster_idx, address) in addresses.iter().enumerate() {
+                let registered = RegisteredKey {
+                    verifying_address: *address,
+                    issued_at: 0,
+                };
+                group_key
+                    .verifying_keys
+                    .insert(roster_idx as u32, registered);
             }
-            // It's an error case if the receiver generation is bigger than sender's
-            // Here is the case when you sent a transaction with an old sender keychain during recovery
-            None => Err(anyhow!(
-                "The generation of the receiver keychain ({:?}) must not be bigger than the that of the sender keychain ({:?}).
-                Your TEE instance may not be synced to the latest state yet.",
-                receiver_gen,
-                sender_gen
-            )),
         }
+
+        group_keys
     }
 
-    fn my_roster_idx(&self) -> u32 {
-        self.group_state.my_roster_idx()
+    /// The sender creates a handshake, and then all the members process it.
+    fn handshake(group_keys: &mut [GroupKey], sender: usize) {
+        let (handshake, _) = group_keys[sender].create_handshake().unwrap();
+        for group_key in group_keys.iter_mut() {
+            group_key
+                .process_handshake(&handshake, &CONTRACT_ADDRESS)
+                .unwrap();
+        }
+    }
+
+    /// Attests the member's identity key by the mock IAS server, which is started by the host of the unit tests.
+    /// The mock server doesn't verify the quote, so only MRENCLAVE and REPORTDATA are filled.
+    fn attest(group_key: &mut GroupKey) {
+        let mut quote = vec![0u8; QUOTE_BODY_SIZE];
+        let mr_enclave = self_mr_enclave();
+        quote[MR_ENCLAVE_OFFSET..MR_ENCLAVE_OFFSET + mr_enclave.len()].copy_from_slice(&mr_enclave);
+        quote[REPORT_DATA_OFFSET..]
+            .copy_from_slice(&group_key.identity_key.report_data().unwrap().d);
+        let url = env::var("MOCK_IAS_OK_URL").expect("MOCK_IAS_OK_URL must be set");
+        let (report, report_sig, cert) = RAService::remote_attestation_with_cert(
+            &url,
+            MOCK_SUB_KEY,
+            &base64::encode(&quote),
+            &AttestationPolicy::default(),
+        )
+        .unwrap();
+
+        group_key.set_my_attestation(report.into_vec(), report_sig.into_vec(), cert.into_vec());
+    }
+
+    fn encrypt(group_key: &mut GroupKey) -> Ciphertext {
+        group_key
+            .reserve_sender_key()
+            .unwrap()
+            .encrypt_msg(MSG.to_vec())
+            .unwrap()
+    }
+
+    fn test_decrypt_prior_epoch_msg() {
+        let mut group_keys = registered_group_keys(2);
+        handshake(&mut group_keys, 0);
+        handshake(&mut group_keys, 1);
+
+        // Sent before the handshake is processed
+        let prior_msg = encrypt(&mut group_keys[0]);
+        handshake(&mut group_keys, 0);
+        let current_msg = encrypt(&mut group_keys[0]);
+        assert_ne!(prior_msg.epoch(), current_msg.epoch());
+
+        let plaintext = group_keys[1].decrypt(&current_msg).unwrap().unwrap();
+        assert_eq!(plaintext.as_slice(), MSG);
+        let plaintext = group_keys[1].decrypt(&prior_msg).unwrap().unwrap();
+        assert_eq!(plaintext.as_slice(), MSG);
+    }
+
+    fn test_prior_keychain_window_expires() {
+        let mut group_keys = registered_group_keys(2);
+        handshake(&mut group_keys, 0);
+        handshake(&mut group_keys, 1);
+
+        let prior_msg1 = encrypt(&mut group_keys[0]);
+        let prior_msg2 = encrypt(&mut group_keys[0]);
+        handshake(&mut group_keys, 0);
+
+        for _ in 0..PRIOR_KEYCHAIN_WINDOW - 1 {
+            let msg = encrypt(&mut group_keys[0]);
+            group_keys[1].decrypt(&msg).unwrap().unwrap();
+        }
+        // Still in the window
+        let plaintext = group_keys[1].decrypt(&prior_msg1).unwrap().unwrap();
+        assert_eq!(plaintext.as_slice(), MSG);
+
+        let msg = encrypt(&mut group_keys[0]);
+        group_keys[1].decrypt(&msg).unwrap().unwrap();
+        // The window has expired, so the previous epoch's keychain has been dropped.
+        assert!(group_keys[1].prior_receiver_keychain.is_none());
+        if let Ok(Some(_)) = group_keys[1].decrypt(&prior_msg2) {
+            panic!("The message of the previous epoch must not be decrypted after the window");
+        }
+    }
+
+    fn test_attested_handshake_registers_key() {
+        let mut group_keys = registered_group_keys(2);
+        handshake(&mut group_keys, 0);
+        // The member of roster index 1 is joining the group.
+        group_keys[0].verifying_keys.remove(&1);
+
+        let (unattested, _) = group_keys[1].create_handshake().unwrap();
+        assert!(group_keys[0]
+            .process_handshake(&unattested, &CONTRACT_ADDRESS)
+            .is_err());
+
+        attest(&mut group_keys[1]);
+        let (attested, _) = group_keys[1].create_handshake().unwrap();
+        for group_key in group_keys.iter_mut() {
+            group_key
+                .process_handshake(&attested, &CONTRACT_ADDRESS)
+                .unwrap();
+        }
+        assert_eq!(
+            registered_address(&group_keys[0], 1),
+            Some(group_keys[1].identity_key.verifying_address())
+        );
+        assert!(group_keys[1].my_attestation.is_none());
+
+        // The following handshakes are verified with the registered verifying key.
+        handshake(&mut group_keys, 1);
+    }
+
+    fn test_restarted_member_replaces_key() {
+        let mut group_keys = registered_group_keys(2);
+        handshake(&mut group_keys, 0);
+        handshake(&mut group_keys, 1);
+
+        // The member of roster index 1 is restarted with a new identity key.
+        let previous_identity_key = std::mem::replace(
+            &mut group_keys[1].identity_key,
+            EnclaveIdentityKey::new().unwrap(),
+        );
+        let (unattested, _) = group_keys[1].create_handshake().unwrap();
+        assert!(group_keys[0]
+            .process_handshake(&unattested, &CONTRACT_ADDRESS)
+            .is_err());
+
+        // The attestation must be running on an allowed enclave.
+        attest(&mut group_keys[1]);
+        let (attested, _) = group_keys[1].create_handshake().unwrap();
+        let mut disallowed = group_keys[0].clone();
+        disallowed.allowed_mrenclaves = vec![[0u8; MR_ENCLAVE_SIZE]];
+        assert!(disallowed
+            .process_handshake(&attested, &CONTRACT_ADDRESS)
+            .is_err());
+
+        // The new key attested later replaces the registered one.
+        for group_key in group_keys.iter_mut() {
+            group_key
+                .process_handshake(&attested, &CONTRACT_ADDRESS)
+                .unwrap();
+        }
+        assert_eq!(
+            registered_address(&group_keys[0], 1),
+            Some(group_keys[1].identity_key.verifying_address())
+        );
+        handshake(&mut group_keys, 1);
+
+        // The previous key is no longer accepted.
+        let mut previous = group_keys[1].clone();
+        previous.identity_key = previous_identity_key;
+        let (revoked, _) = previous.create_handshake().unwrap();
+        assert!(group_keys[0]
+            .process_handshake(&revoked, &CONTRACT_ADDRESS)
+            .is_err());
+
+        // A key whose attestation isn't later than the registered one's doesn't replace it.
+        let registered = registered_address(&group_keys[0], 1);
+        group_keys[0].verifying_keys.get_mut(&1).unwrap().issued_at = u64::MAX;
+        let mut stale = group_keys[1].clone();
+        stale.identity_key = EnclaveIdentityKey::new().unwrap();
+        attest(&mut stale);
+        let (stale_handshake, _) = stale.create_handshake().unwrap();
+        assert!(group_keys[0]
+            .process_handshake(&stale_handshake, &CONTRACT_ADDRESS)
+            .is_err());
+        assert_eq!(registered_address(&group_keys[0], 1), registered);
+    }
+
+    fn registered_address(
+        group_key: &GroupKey,
+        roster_idx: u32,
+    ) -> Option<[u8; VERIFYING_ADDRESS_SIZE]> {
+        group_key
+            .verifying_keys
+            .get(&roster_idx)
+            .map(|registered| registered.verifying_address)
+    }
+
+    fn test_remove_only_by_owner() {
+        let mut group_keys = registered_group_keys(3);
+        handshake(&mut group_keys, 0);
+        handshake(&mut group_keys, 1);
+        handshake(&mut group_keys, 2);
+
+        // A member other than the owner can't remove the others.
+        let (remove, _) = group_keys[1].create_remove_handshake(2).unwrap();
+        assert!(group_keys[0]
+            .process_handshake(&remove, &CONTRACT_ADDRESS)
+            .is_err());
+
+        let (remove, _) = group_keys[0].create_remove_handshake(2).unwrap();
+        for group_key in &mut group_keys[..2] {
+            group_key
+                .process_handshake(&remove, &CONTRACT_ADDRESS)
+                .unwrap();
+        }
+        assert!(registered_address(&group_keys[1], 2).is_none());
     }
 }
@@ -1,11 +1,9 @@
 use anonify_io_types::*;
-use anyhow::{anyhow, Result};
-use codec::{Decode, Encode};
+use anyhow::{ensure, Result};
+use codec::Encode;
 use frame_common::{crypto::Sha256, state_types::StateType};
 use frame_enclave::EnclaveEngine;
 use frame_runtime::traits::*;
-use frame_treekem::handshake::HandshakeParams;
-use remote_attestation::RAService;
 
 /// A add handshake Sender
 #[derive(Debug, Clone)]
@@ -16,7 +14,7 @@ impl EnclaveEngine for JoinGroupSender {
     type EO = output::ReturnJoinGroup;
 
     fn handle<R, C>(
-        _ecall_input: Self::EI,
+        ecall_input: Self::EI,
         enclave_context: &C,
         _max_mem_size: usize,
     ) -> Result<Self::EO>
@@ -24,17 +22,18 @@ impl EnclaveEngine for JoinGroupSender {
         R: RuntimeExecutor<C, S = StateType>,
         C: ContextOps<S = StateType> + Clone,
     {
-        let quote = enclave_context.quote()?;
-        let ias_url = enclave_context.ias_url();
-        let sub_key = enclave_context.sub_key();
-        let (report, report_sig) = RAService::remote_attestation(ias_url, sub_key, &quote)?;
+        let (report, report_sig, cert) = enclave_context.remote_attestation()?;
         let mrenclave_ver = enclave_context.mrenclave_ver();
-        let group_key = &*enclave_context.read_group_key();
+        let group_key = &mut *enclave_context.write_group_key();
+        // The roster index is allocated by the contract, and the add handshake grows the tree to contain it.
+        group_key.set_my_roster_idx(ecall_input.roster_idx())?;
+        // The join handshake carries the attestation so that the other members can register this enclave's verifying key.
+        group_key.set_my_attestation(report.clone(), report_sig.clone(), cert);
         let (export_handshake, export_path_secret) = group_key.create_handshake()?;
 
         Ok(output::ReturnJoinGroup::new(
-            report.into_vec(),
-            report_sig.into_vec(),
+            report,
+            report_sig,
             export_handshake.encode(),
             mrenclave_ver,
             export_handshake.roster_idx(),
@@ -75,6 +74,51 @@ impl EnclaveEngine for HandshakeSender {
     }
 }
 
+/// The roster index of the owner who deployed the contract
+pub(crate) const OWNER_ROSTER_IDX: u32 = 0;
+
+/// A remove handshake sender. Only the owner's enclave can remove other members.
+#[derive(Debug, Clone)]
+pub struct RemoveMemberSender;
+
+impl EnclaveEngine for RemoveMemberSender {
+    type EI = input::CallRemoveMember;
+    type EO = output::ReturnHandshake;
+
+    fn handle<R, C>(
+        ecall_input: Self::EI,
+        enclave_context: &C,
+        _max_mem_size: usize,
+    ) -> Result<Self::EO>
+    where
+        R: RuntimeExecutor<C, S = StateType>,
+        C: ContextOps<S = StateType> + Clone,
+    {
+        let group_key = &*enclave_context.read_group_key();
+        ensure!(
+            group_key.my_roster_idx()? == OWNER_ROSTER_IDX,
+            "Only the owner can remove members from the group"
+        );
+
+        let removed_roster_idx = ecall_input.removed_roster_idx();
+        let (export_handshake, export_path_secret) =
+            group_key.create_remove_handshake(removed_roster_idx)?;
+        let roster_idx = export_handshake.roster_idx();
+        // The signature also covers the removed roster index so that the contract can trust it.
+        let mut msg = export_handshake.encode();
+        msg.extend_from_slice(&roster_idx.to_be_bytes());
+        let msg = Sha256::hash_with_u32(&msg, removed_roster_idx);
+        let enclave_sig = enclave_context.sign(msg.as_bytes())?;
+
+        Ok(output::ReturnHandshake::new(
+            export_handshake,
+            export_path_secret,
+            enclave_sig,
+            roster_idx,
+        ))
+    }
+}
+
 /// A handshake receiver
 #[derive(Debug, Clone)]
 pub struct HandshakeReceiver;
@@ -93,10 +137,7 @@ impl EnclaveEngine for HandshakeReceiver {
         C: ContextOps<S = StateType> + Clone,
     {
         let group_key = &mut *enclave_context.write_group_key();
-        let handshake = HandshakeParams::decode(&mut &ecall_input.handshake().handshake()[..])
-            .map_err(|_| anyhow!("HandshakeParams::decode Error"))?;
-
-        group_key.process_handshake(&handshake)?;
+        group_key.process_handshake(ecall_input.handshake(), ecall_input.contract_address())?;
 
         Ok(output::Empty::default())
     }
@@ -1,15 +1,25 @@
 //! This module contains enclave specific cryptographic logics.
 
-use crate::error::Result;
+use crate::{error::Result, key_vault::MR_ENCLAVE_SIZE};
 use anonify_io_types::*;
+use anyhow::anyhow;
 use codec::Encode;
 use frame_common::{crypto::rand_assign, state_types::StateType, traits::Keccak256};
-use frame_enclave::EnclaveEngine;
+use frame_enclave::{
+    ocalls::{get_quote, qe_get_quote, qe_get_target_info, sgx_init_quote},
+    report::{create_report, self_report},
+    EnclaveEngine,
+};
 use frame_runtime::traits::*;
 use frame_treekem::{DhPrivateKey, DhPubKey, EciesCiphertext};
-use secp256k1::{self, util::SECRET_KEY_SIZE, Message, PublicKey, SecretKey, Signature};
-use sgx_types::sgx_report_data_t;
-use std::prelude::v1::Vec;
+use remote_attestation::{
+    AttestationPolicy, AttestationReport, AttestationService, ReportSig, SigningCert,
+};
+use secp256k1::{
+    self, util::SECRET_KEY_SIZE, Message, PublicKey, RecoveryId, SecretKey, Signature,
+};
+use sgx_types::{sgx_report_data_t, sgx_report_t, sgx_spid_t};
+use std::prelude::v1::*;
 
 const HASHED_PUBKEY_SIZE: usize = 20;
 const ENCRYPTING_KEY_SIZE: usize = 33;
@@ -70,6 +80,13 @@ impl EnclaveIdentityKey {
         Ok(sig.0)
     }
 
+    /// Sign with a recovery id so that the verifier can recover the signer's address.
+    pub fn sign_recoverable(&self, msg: &[u8]) -> Result<(Signature, RecoveryId)> {
+        let msg = Message::parse_slice(msg)?;
+        let sig = secp256k1::sign(&msg, &self.signing_privkey)?;
+        Ok(sig)
+    }
+
     pub fn decrypt(&self, ciphertext: EciesCiphertext) -> Result<Vec<u8>> {
         ciphertext
             .decrypt(&self.decrypting_privkey)
@@ -101,13 +118,55 @@ impl EnclaveIdentityKey {
         Ok(sgx_report_data_t { d: report_data })
     }
 
+    /// Generate a base64-encoded QUOTE which includes this identity key's REPORTDATA.
+    pub fn quote(&self, spid: &sgx_spid_t) -> Result<String> {
+        let target_info = sgx_init_quote()?;
+        let mut report = sgx_report_t::default();
+        let report_data = &self.report_data()?;
+
+        if let Ok(r) = create_report(&target_info, &report_data) {
+            report = r;
+        }
+
+        let quote = get_quote(report, spid)?;
+        // Use base64-encoded QUOTE structure to communicate via defined API.
+        Ok(base64::encode(&quote))
+    }
+
+    /// Generate a base64-encoded DCAP ECDSA QUOTE which includes this identity key's REPORTDATA.
+    pub fn dcap_quote(&self) -> Result<String> {
+        let target_info = qe_get_target_info()?;
+        let report_data = &self.report_data()?;
+        let report = create_report(&target_info, &report_data)?;
+
+        let quote = qe_get_quote(report)?;
+        Ok(base64::encode(&quote))
+    }
+
+    /// Generate a QUOTE for the attestation service, and then attest it.
+    pub fn remote_attestation(
+        &self,
+        spid: &sgx_spid_t,
+        service: &AttestationService,
+        policy: &AttestationPolicy,
+    ) -> Result<(AttestationReport, ReportSig, SigningCert)> {
+        let quote = if service.is_dcap() {
+            self.dcap_quote()?
+        } else {
+            self.quote(spid)?
+        };
+
+        service
+            .remote_attestation_with_cert(&quote, policy)
+            .map_err(Into::into)
+    }
+
+    pub fn verifying_address(&self) -> [u8; HASHED_PUBKEY_SIZE] {
+        self.verifying_key_into_array()
+    }
+
     fn verifying_key_into_array(&self) -> [u8; HASHED_PUBKEY_SIZE] {
-        let pubkey = &self.verifying_key().serialize();
-        let account_id = &pubkey.keccak256()[12..];
-        assert_eq!(account_id.len(), HASHED_PUBKEY_SIZE);
-        let mut res = [0u8; HASHED_PUBKEY_SIZE];
-        res.copy_from_slice(account_id);
-        res
+        verifying_key_into_address(&self.verifying_key())
     }
 
     fn encrypting_key_into_vec(&self) -> Vec<u8> {
@@ -116,3 +175,37 @@ impl EnclaveIdentityKey {
         res
     }
 }
+
+/// Recover the address-formatted verifying key which signed the message.
+pub fn recover_verifying_address(
+    msg: &[u8],
+    sig: &[u8],
+    recovery_id: u8,
+) -> Result<[u8; HASHED_PUBKEY_SIZE]> {
+    if sig.len() != 64 {
+        return Err(anyhow!("Signature must be 64 bytes").into());
+    }
+    let mut sig_buf = [0u8; 64];
+    sig_buf.copy_from_slice(sig);
+
+    let msg = Message::parse_slice(msg)?;
+    let sig = Signature::parse(&sig_buf);
+    let recovery_id = RecoveryId::parse(recovery_id)?;
+    let pubkey = secp256k1::recover(&msg, &sig, &recovery_id)?;
+
+    Ok(verifying_key_into_address(&pubkey))
+}
+
+/// Returns MRENCLAVE of this enclave.
+pub fn self_mr_enclave() -> [u8; MR_ENCLAVE_SIZE] {
+    self_report().body.mr_enclave.m
+}
+
+fn verifying_key_into_address(pubkey: &PublicKey) -> [u8; HASHED_PUBKEY_SIZE] {
+    let pubkey = &pubkey.serialize();
+    let account_id = &pubkey.keccak256()[12..];
+    assert_eq!(account_id.len(), HASHED_PUBKEY_SIZE);
+    let mut res = [0u8; HASHED_PUBKEY_SIZE];
+    res.copy_from_slice(account_id);
+    res
+}
@@ -0,0 +1,299 @@
+//! A client of the remote key vault which backs up path secrets outside of this platform.
+//! Both of the enclave and the key vault are attested by IAS.
+//! Every request is encrypted to the vault's attested encrypting key and signed by this enclave's identity key,
+//! and every response is encrypted to this enclave's encrypting key and signed by the vault's attested identity key.
+
+use crate::{
+    attestation::RemoteAttestor,
+    error::Result,
+    identity_key::{recover_verifying_address, EnclaveIdentityKey},
+};
+use anonify_io_types::key_vault::*;
+use anyhow::anyhow;
+use codec::{Decode, Encode};
+use frame_common::{
+    crypto::{ExportPathSecret, Sha256, EXPORT_ID_SIZE},
+    traits::Hash256,
+};
+use frame_treekem::{EciesCiphertext, PathSecret};
+use remote_attestation::{AttestationPolicy, AttestationReport, ReportSig, SigningCert};
+use std::{fmt, net::TcpStream, prelude::v1::*};
+
+pub const MR_ENCLAVE_SIZE: usize = 32;
+
+#[derive(Clone)]
+pub struct KeyVaultClient {
+    addr: String,
+    identity_key: EnclaveIdentityKey,
+    /// Shares the cached attestation report with the enclave context.
+    attestor: RemoteAttestor,
+    /// MRENCLAVEs of the key vault enclaves allowed to store path secrets, which are compiled into this enclave.
+    allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>,
+}
+
+impl fmt::Debug for KeyVaultClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyVaultClient")
+            .field("addr", &self.addr)
+            .field("allowed_mrenclaves", &self.allowed_mrenclaves)
+            .finish()
+    }
+}
+
+impl KeyVaultClient {
+    pub fn new(
+        addr: String,
+        identity_key: EnclaveIdentityKey,
+        attestor: RemoteAttestor,
+        allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>,
+    ) -> Result<Self> {
+        if allowed_mrenclaves.is_empty() {
+            return Err(anyhow!("At least one key vault MRENCLAVE must be allowed").into());
+        }
+
+        Ok(KeyVaultClient {
+            addr,
+            identity_key,
+            attestor,
+            allowed_mrenclaves,
+        })
+    }
+
+    pub fn backup_path_secret(&self, path_secret: PathSecret, epoch: u32, id: &[u8]) -> Result<()> {
+        let req = KeyVaultRequest::Backup(BackupPathSecret::new(
+            path_secret.as_bytes().to_vec(),
+            epoch,
+            to_id_array(id)?,
+        ));
+
+        match self.request(req)? {
+            KeyVaultResponse::Stored => Ok(()),
+            resp => Err(anyhow!("Unexpected response from the key vault: {:?}", resp).into()),
+        }
+    }
+
+    /// Recover a path secret from the key vault, and then seal it to be imported to the group state.
+    pub fn recover_path_secret(&self, id: &[u8]) -> Result<ExportPathSecret> {
+        let req = KeyVaultRequest::Recover(RecoverPathSecret::new(to_id_array(id)?));
+
+        match self.request(req)? {
+            KeyVaultResponse::Recovered(backup) => {
+                if backup.id[..] != id[..] {
+                    return Err(anyhow!("Recovered path secret's id doesn't match").into());
+                }
+                PathSecret::from(backup.path_secret)
+                    .try_into_exporting(backup.epoch, &backup.id[..])
+                    .map_err(Into::into)
+            }
+            KeyVaultResponse::NotFound => {
+                Err(anyhow!("Not found the path secret in the key vault").into())
+            }
+            resp => Err(anyhow!("Unexpected response from the key vault: {:?}", resp).into()),
+        }
+    }
+
+    fn request(&self, req: KeyVaultRequest) -> Result<KeyVaultResponse> {
+        let mut stream = TcpStream::connect(&self.addr)?;
+
+        write_frame(&mut stream, &ClientMsg::GetIdentity)?;
+        let vault = match read_frame::<_, ServerMsg>(&mut stream)? {
+            ServerMsg::Identity(identity) => identity,
+            ServerMsg::Error(e) => return Err(server_error(e).into()),
+            _ => return Err(anyhow!("Unexpected message from the key vault").into()),
+        };
+        verify_attested_identity(&vault, &self.allowed_mrenclaves, self.attestor.policy())?;
+
+        let ciphertext = EciesCiphertext::encrypt(&vault.encrypting_key, req.encode())?;
+        let req_ciphertext = ciphertext.clone();
+        let msg = Sha256::hash(&ciphertext.encode());
+        let (sig, recovery_id) = self.identity_key.sign_recoverable(msg.as_bytes())?;
+        let signed_req = SignedRequest {
+            client: self.my_identity()?,
+            ciphertext,
+            sig: sig.serialize(),
+            recovery_id: recovery_id.serialize(),
+        };
+        write_frame(&mut stream, &ClientMsg::Request(signed_req))?;
+
+        match read_frame::<_, ServerMsg>(&mut stream)? {
+            ServerMsg::Response(signed_resp) => {
+                // The response must be signed by the vault verified above, not by the host.
+                let msg = SignedResponse::signing_msg(&req_ciphertext, &signed_resp.ciphertext);
+                let signer = recover_verifying_address(
+                    msg.as_bytes(),
+                    &signed_resp.sig[..],
+                    signed_resp.recovery_id,
+                )?;
+                if signer != vault.verifying_address {
+                    return Err(anyhow!(
+                        "The response isn't signed by the key vault's identity key"
+                    )
+                    .into());
+                }
+
+                let plaintext = self.identity_key.decrypt(signed_resp.ciphertext)?;
+                KeyVaultResponse::decode(&mut &plaintext[..]).map_err(Into::into)
+            }
+            ServerMsg::Error(e) => Err(server_error(e).into()),
+            _ => Err(anyhow!("Unexpected message from the key vault").into()),
+        }
+    }
+
+    /// This enclave's identity attested with the cached report, which is refreshed before the key vault rejects it.
+    fn my_identity(&self) -> Result<AttestedIdentity> {
+        let (report, report_sig, cert) = self.attestor.attested_report()?.into_parts();
+
+        Ok(AttestedIdentity::new(
+            report,
+            report_sig,
+            cert,
+            self.identity_key.verifying_address(),
+            self.identity_key.encrypting_key(),
+        ))
+    }
+}
+
+/// Verify that the identity is attested by IAS, is accepted by the policy, is running on an allowed enclave
+/// and the attested REPORTDATA binds its verifying address and encrypting key.
+pub fn verify_attested_identity(
+    identity: &AttestedIdentity,
+    allowed_mrenclaves: &[[u8; MR_ENCLAVE_SIZE]],
+    policy: &AttestationPolicy,
+) -> Result<()> {
+    let report = AttestationReport::new(identity.report.clone());
+    report.verify(
+        &ReportSig::new(identity.report_sig.clone()),
+        &SigningCert::new(identity.signing_cert.clone()),
+        policy,
+    )?;
+
+    let mr_enclave = report.mr_enclave()?;
+    if !allowed_mrenclaves.contains(&mr_enclave) {
+        return Err(anyhow!("MRENCLAVE {:?} is not allowed", mr_enclave).into());
+    }
+
+    let expected = identity.expected_report_data();
+    let report_data = report.report_data()?;
+    if report_data[..expected.len()] != expected[..] {
+        return Err(anyhow!("REPORTDATA doesn't match the attested identity").into());
+    }
+
+    Ok(())
+}
+
+fn to_id_array(id: &[u8]) -> Result<[u8; EXPORT_ID_SIZE]> {
+    if id.len() != EXPORT_ID_SIZE {
+        return Err(anyhow!("path secret's id must be {} bytes", EXPORT_ID_SIZE).into());
+    }
+    let mut res = [0u8; EXPORT_ID_SIZE];
+    res.copy_from_slice(id);
+    Ok(res)
+}
+
+fn server_error(msg: Vec<u8>) -> anyhow::Error {
+    anyhow!(
+        "The key vault returned an error: {}",
+        String::from_utf8_lossy(&msg)
+    )
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use frame_common::crypto::AttestedReport;
+    use remote_attestation::{AttestationService, RAService};
+    use sgx_types::sgx_spid_t;
+    use std::env;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_backup_and_recover_path_secret,
+            test_reject_unallowed_key_vault,
+        )
+    }
+
+    const MOCK_SUB_KEY: &str = "mock-sub-key";
+    const QUOTE_BODY_SIZE: usize = 432;
+    const MR_ENCLAVE_OFFSET: usize = 112;
+    const REPORT_DATA_OFFSET: usize = 368;
+    /// The same as `key_vault_server::MOCK_KEY_VAULT_MRENCLAVE`
+    const KEY_VAULT_MR_ENCLAVE: [u8; MR_ENCLAVE_SIZE] = [0x6b; MR_ENCLAVE_SIZE];
+    /// The client MRENCLAVE allowed by the mock key vault, which is started by the host of the unit tests.
+    const CLIENT_MR_ENCLAVE: [u8; MR_ENCLAVE_SIZE] = [0x63; MR_ENCLAVE_SIZE];
+
+    /// A client of the mock key vault, whose identity key is attested by the mock IAS server for `CLIENT_MR_ENCLAVE`.
+    /// The mock server doesn't verify the quote, so only MRENCLAVE and REPORTDATA are filled.
+    fn new_client(allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>) -> Result<KeyVaultClient> {
+        let addr = env::var("MOCK_KEY_VAULT_ADDRESS").expect("MOCK_KEY_VAULT_ADDRESS must be set");
+        let url = env::var("MOCK_IAS_OK_URL").expect("MOCK_IAS_OK_URL must be set");
+        let identity_key = EnclaveIdentityKey::new().unwrap();
+        let policy = AttestationPolicy::default();
+        let service = AttestationService::Ias {
+            url: url.clone(),
+            sub_key: MOCK_SUB_KEY.to_string(),
+        };
+        let attestor = RemoteAttestor::new(
+            identity_key.clone(),
+            sgx_spid_t::default(),
+            service,
+            policy.clone(),
+        );
+
+        let mut quote = vec![0u8; QUOTE_BODY_SIZE];
+        quote[MR_ENCLAVE_OFFSET..MR_ENCLAVE_OFFSET + MR_ENCLAVE_SIZE]
+            .copy_from_slice(&CLIENT_MR_ENCLAVE);
+        quote[REPORT_DATA_OFFSET..].copy_from_slice(&identity_key.report_data().unwrap().d);
+        let (report, report_sig, cert) = RAService::remote_attestation_with_cert(
+            &url,
+            MOCK_SUB_KEY,
+            &base64::encode(&quote),
+            &policy,
+        )
+        .unwrap();
+        let expires_at = report.expires_at(&policy).unwrap();
+        attestor.set_attested_report(AttestedReport::new(
+            report.into_vec(),
+            report_sig.into_vec(),
+            cert.into_vec(),
+            expires_at,
+            expires_at,
+        ));
+
+        KeyVaultClient::new(addr, identity_key, attestor, allowed_mrenclaves)
+    }
+
+    fn test_backup_and_recover_path_secret() {
+        let client = new_client(vec![KEY_VAULT_MR_ENCLAVE]).unwrap();
+        let id = [1u8; EXPORT_ID_SIZE];
+        let path_secret = PathSecret::new_from_random_sgx();
+        client
+            .backup_path_secret(path_secret.clone(), 3, &id)
+            .unwrap();
+
+        // The restarted enclave has a new identity key on the same MRENCLAVE.
+        let restarted = new_client(vec![KEY_VAULT_MR_ENCLAVE]).unwrap();
+        let recovered = restarted.recover_path_secret(&id).unwrap();
+        assert_eq!(recovered.epoch(), 3);
+        assert_eq!(recovered.id_as_ref(), &id[..]);
+        assert_eq!(
+            PathSecret::try_from_importing(recovered).unwrap(),
+            path_secret
+        );
+
+        assert!(restarted
+            .recover_path_secret(&[2u8; EXPORT_ID_SIZE])
+            .is_err());
+    }
+
+    fn test_reject_unallowed_key_vault() {
+        let client = new_client(vec![[0u8; MR_ENCLAVE_SIZE]]).unwrap();
+        let path_secret = PathSecret::new_from_random_sgx();
+        assert!(client
+            .backup_path_secret(path_secret, 0, &[3u8; EXPORT_ID_SIZE])
+            .is_err());
+
+        assert!(new_client(vec![]).is_err());
+    }
+}
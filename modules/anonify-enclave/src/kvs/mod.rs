@@ -2,11 +2,11 @@ use frame_common::{
     crypto::AccountId,
     state_types::{MemId, StateType, UpdatedState},
 };
-use std::{
-    collections::hash_map::HashMap,
-    prelude::v1::*,
-    sync::{Arc, SgxRwLock},
-};
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+#[cfg(feature = "sgx")]
+use std::sync::SgxRwLock as RwLock;
+use std::{collections::hash_map::HashMap, prelude::v1::*, sync::Arc};
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct DBKey((AccountId, MemId));
@@ -19,11 +19,11 @@ impl DBKey {
 }
 
 #[derive(Debug, Clone)]
-pub struct EnclaveDB(Arc<SgxRwLock<HashMap<DBKey, StateType>>>);
+pub struct EnclaveDB(Arc<RwLock<HashMap<DBKey, StateType>>>);
 
 impl EnclaveDB {
     pub fn new() -> Self {
-        EnclaveDB(Arc::new(SgxRwLock::new(HashMap::new())))
+        EnclaveDB(Arc::new(RwLock::new(HashMap::new())))
     }
 
     pub fn get(&self, account_id: AccountId, mem_id: MemId) -> StateType {
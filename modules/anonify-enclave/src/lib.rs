@@ -1,27 +1,48 @@
-#![cfg_attr(not(target_env = "sgx"), no_std)]
+#![cfg_attr(all(feature = "sgx", not(target_env = "sgx")), no_std)]
 #![cfg_attr(target_env = "sgx", feature(rustc_private))]
 
-#[cfg(not(target_env = "sgx"))]
+#[cfg(all(feature = "sgx", not(target_env = "sgx")))]
 #[macro_use]
 extern crate sgx_tstd as std;
+#[cfg(feature = "sgx")]
+extern crate anyhow_sgx as anyhow;
+#[cfg(feature = "std")]
+extern crate anyhow_std as anyhow;
+#[cfg(feature = "sgx")]
+extern crate base64_sgx as base64;
+#[cfg(feature = "std")]
+extern crate base64_std as base64;
+#[cfg(feature = "std")]
+extern crate sgx_types_std as sgx_types;
+#[cfg(feature = "sgx")]
+extern crate thiserror_sgx as thiserror;
+#[cfg(feature = "std")]
+extern crate thiserror_std as thiserror;
 
+mod attestation;
+mod audit_log;
 mod commands;
 pub mod context;
 mod error;
 mod group_key;
 mod handshake;
 mod identity_key;
+mod key_vault;
 mod kvs;
 mod notify;
 
 pub mod workflow {
+    pub use crate::attestation::ReportGetter;
     pub use crate::commands::{MsgReceiver, MsgSender};
     pub use crate::context::{GetState, ReportRegistration};
-    pub use crate::handshake::{HandshakeReceiver, HandshakeSender, JoinGroupSender};
+    pub use crate::handshake::{
+        HandshakeReceiver, HandshakeSender, JoinGroupSender, RemoveMemberSender,
+    };
     pub use crate::identity_key::EncryptingKeyGetter;
     pub use crate::notify::RegisterNotification;
 }
 
+#[cfg(feature = "sgx")]
 #[cfg(debug_assertions)]
 pub mod tests {
     use super::*;
@@ -29,6 +50,12 @@ pub mod tests {
     use test_utils::*;
 
     pub fn run_tests() -> bool {
-        check_all_passed!(notify::tests::run_tests(),)
+        check_all_passed!(
+            notify::tests::run_tests(),
+            attestation::tests::run_tests(),
+            audit_log::tests::run_tests(),
+            group_key::tests::run_tests(),
+            key_vault::tests::run_tests(),
+        )
     }
 }
@@ -3,21 +3,22 @@ use frame_common::{crypto::AccountId, state_types::StateType, AccessPolicy};
 use frame_enclave::EnclaveEngine;
 use frame_runtime::traits::*;
 use std::marker::PhantomData;
-use std::{
-    collections::HashSet,
-    sync::{Arc, SgxRwLock},
-};
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+#[cfg(feature = "sgx")]
+use std::sync::SgxRwLock as RwLock;
+use std::{collections::HashSet, sync::Arc};
 
 #[derive(Debug, Clone)]
 pub struct Notifier {
-    account_ids: Arc<SgxRwLock<HashSet<AccountId>>>,
+    account_ids: Arc<RwLock<HashSet<AccountId>>>,
 }
 
 impl Notifier {
     pub fn new() -> Self {
         let account_ids = HashSet::new();
         Notifier {
-            account_ids: Arc::new(SgxRwLock::new(account_ids)),
+            account_ids: Arc::new(RwLock::new(account_ids)),
         }
     }
 
@@ -60,6 +61,7 @@ impl<AP: AccessPolicy> EnclaveEngine for RegisterNotification<AP> {
     }
 }
 
+#[cfg(feature = "sgx")]
 #[cfg(debug_assertions)]
 pub(crate) mod tests {
     use super::*;
@@ -6,25 +6,42 @@ use crate::{
     utils::*,
     workflow::host_input,
 };
-use frame_common::{crypto::ExportPathSecret, state_types::UpdatedState, traits::*};
+use config::constants::OWNER_ROSTER_IDX;
+use frame_common::{
+    crypto::{AttestedReport, ExportPathSecret},
+    state_types::UpdatedState,
+    traits::*,
+};
 use frame_host::engine::HostEngine;
 use frame_treekem::{DhPubKey, EciesCiphertext};
+use futures_timer::Delay;
+use log::{error, info};
 use parking_lot::RwLock;
 use sgx_types::sgx_enclave_id_t;
-use std::{fmt::Debug, marker::Send, path::Path};
+use std::{
+    fmt::Debug,
+    marker::Send,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use web3::types::{Address, H256};
 
+/// Failed re-attestations are retried after this interval.
+const REATTESTATION_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
 /// This dispatcher communicates with a blockchain node.
+/// Ecalls don't take any lock of the dispatcher, so they run concurrently up to the enclave's `TCSNum`.
 #[derive(Debug)]
 pub struct Dispatcher<D: Deployer, S: Sender, W: Watcher> {
-    inner: RwLock<InnerDispatcher<D, S, W>>,
-}
-
-#[derive(Debug)]
-struct InnerDispatcher<D: Deployer, S: Sender, W: Watcher> {
+    enclave_id: sgx_enclave_id_t,
+    node_url: String,
+    /// It isn't locked, so that `get_account` isn't blocked while `deploy` waits for the confirmations.
     deployer: D,
-    sender: Option<S>,
-    watcher: Option<W>,
+    /// The sender and the watcher are replaced by `set_contract_addr`,
+    /// and the locks are only held while cloning them.
+    sender: RwLock<Option<Arc<S>>>,
+    watcher: RwLock<Option<Arc<W>>>,
     cache: EventCache,
 }
 
@@ -36,14 +53,15 @@ where
 {
     pub fn new(enclave_id: sgx_enclave_id_t, node_url: &str, cache: EventCache) -> Result<Self> {
         let deployer = D::new(enclave_id, node_url)?;
-        let inner = RwLock::new(InnerDispatcher {
+
+        Ok(Dispatcher {
+            enclave_id,
+            node_url: node_url.to_string(),
             deployer,
+            sender: RwLock::new(None),
+            watcher: RwLock::new(None),
             cache,
-            sender: None,
-            watcher: None,
-        });
-
-        Ok(Dispatcher { inner })
+        })
     }
 
     pub fn set_contract_addr<P: AsRef<Path> + Copy>(
@@ -51,20 +69,27 @@ where
         contract_addr: &str,
         abi_path: P,
     ) -> Result<()> {
-        let mut inner = self.inner.write();
-        let enclave_id = inner.deployer.get_enclave_id();
-        let node_url = inner.deployer.get_node_url();
-
         let contract_info = ContractInfo::new(abi_path, contract_addr);
-        let sender = S::new(enclave_id, node_url, contract_info)?;
-        let watcher = W::new(node_url, contract_info, inner.cache.clone())?;
+        let sender = S::new(self.enclave_id, &self.node_url, contract_info)?;
+        let watcher = W::new(&self.node_url, contract_info, self.cache.clone())?;
 
-        inner.sender = Some(sender);
-        inner.watcher = Some(watcher);
+        *self.sender.write() = Some(Arc::new(sender));
+        *self.watcher.write() = Some(Arc::new(watcher));
 
         Ok(())
     }
 
+    fn sender(&self) -> Result<Arc<S>> {
+        self.sender.read().clone().ok_or(HostError::AddressNotSet)
+    }
+
+    fn watcher(&self) -> Result<Arc<W>> {
+        self.watcher
+            .read()
+            .clone()
+            .ok_or(HostError::EventWatcherNotSet)
+    }
+
     pub async fn deploy<P: AsRef<Path> + Send>(
         &self,
         deploy_user: Address,
@@ -73,12 +98,11 @@ where
         bin_path: P,
         confirmations: usize,
     ) -> Result<(String, ExportPathSecret)> {
-        let mut inner = self.inner.write();
-        let eid = inner.deployer.get_enclave_id();
-        let input = host_input::JoinGroup::new(deploy_user, gas);
-        let host_output = JoinGroupWorkflow::exec(input, eid)?;
+        // The contract owner is always the first member of the group.
+        let input = host_input::JoinGroup::new(deploy_user, gas, OWNER_ROSTER_IDX);
+        let host_output = JoinGroupWorkflow::exec(input, self.enclave_id)?;
 
-        let contract_addr = inner
+        let contract_addr = self
             .deployer
             .deploy(host_output.clone(), abi_path, bin_path, confirmations)
             .await?;
@@ -109,22 +133,63 @@ where
         abi_path: P,
     ) -> Result<H256> {
         self.set_contract_addr(contract_addr, abi_path)?;
+        self.send_report(signer, gas).await
+    }
 
-        let inner = self.inner.read();
-        let eid = inner.deployer.get_enclave_id();
-        let input = host_input::RegisterReport::new(signer, gas);
-        let host_output = RegisterReportWorkflow::exec(input, eid)?;
+    /// Register this enclave's attestation report to the contract whose address is already set.
+    async fn send_report(&self, signer: Address, gas: u64) -> Result<H256> {
+        let sender = self.sender()?;
+        // A recovered node is registered again with the roster index allocated when it joined.
+        let roster_idx = sender.roster_idx(signer).await?;
+        let input = host_input::RegisterReport::new(signer, gas, roster_idx);
+        let host_output = RegisterReportWorkflow::exec(input, self.enclave_id)?;
 
-        let tx_hash = inner
-            .sender
-            .as_ref()
-            .ok_or(HostError::AddressNotSet)?
-            .register_report(host_output)
-            .await?;
+        let tx_hash = sender.register_report(host_output).await?;
 
         Ok(tx_hash)
     }
 
+    /// Keep the report registered in the contract fresh.
+    /// When the enclave's cached report is due for refresh, the enclave is attested again
+    /// and the new report is registered to the contract whose address is already set.
+    /// The keys in the report are the same, so the contract only refreshes the registered report.
+    /// This never returns, and failed attempts are retried after a while.
+    pub async fn reattest_periodically(&self, signer: Address, gas: u64) {
+        let mut delay = self.until_report_refresh();
+        loop {
+            Delay::new(delay).await;
+            delay = match self.send_report(signer, gas).await {
+                Ok(tx_hash) => {
+                    info!("Registered the refreshed attestation report: {:?}", tx_hash);
+                    self.until_report_refresh()
+                }
+                Err(err) => {
+                    error!(
+                        "Failed to register the refreshed attestation report: {:?}",
+                        err
+                    );
+                    REATTESTATION_RETRY_INTERVAL
+                }
+            };
+        }
+    }
+
+    fn until_report_refresh(&self) -> Duration {
+        let report = match self.get_report() {
+            Ok(report) => report,
+            Err(err) => {
+                error!("Failed to get the attestation report: {:?}", err);
+                return REATTESTATION_RETRY_INTERVAL;
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        Duration::from_secs(report.refresh_at().saturating_sub(now))
+    }
+
     pub async fn update_mrenclave<P: AsRef<Path> + Copy>(
         &self,
         signer: Address,
@@ -146,15 +211,15 @@ where
     ) -> Result<(H256, ExportPathSecret)> {
         self.set_contract_addr(contract_addr, abi_path)?;
 
-        let inner = self.inner.read();
-        let eid = inner.deployer.get_enclave_id();
-        let input = host_input::JoinGroup::new(signer, gas);
-        let host_output = JoinGroupWorkflow::exec(input, eid)?;
+        let sender = self.sender()?;
+        let roster_idx = match method {
+            "joinGroup" => sender.next_roster_idx().await?,
+            _ => OWNER_ROSTER_IDX,
+        };
+        let input = host_input::JoinGroup::new(signer, gas, roster_idx);
+        let host_output = JoinGroupWorkflow::exec(input, self.enclave_id)?;
 
-        let tx_hash = inner
-            .sender
-            .as_ref()
-            .ok_or(HostError::AddressNotSet)?
+        let tx_hash = sender
             .send_report_handshake(host_output.clone(), method)
             .await?;
 
@@ -178,7 +243,6 @@ where
         C: CallNameConverter,
         AP: AccessPolicy,
     {
-        let inner = self.inner.read();
         let input = host_input::Command::<C, AP>::new(
             encrypted_command,
             call_name.to_string(),
@@ -186,13 +250,9 @@ where
             signer,
             gas,
         );
-        let eid = inner.deployer.get_enclave_id();
-        let host_output = CommandWorkflow::exec(input, eid)?;
+        let host_output = CommandWorkflow::exec(input, self.enclave_id)?;
 
-        match &inner.sender {
-            Some(s) => s.send_command(host_output).await,
-            None => Err(HostError::AddressNotSet),
-        }
+        self.sender()?.send_command(host_output).await
     }
 
     pub fn get_state<ST, AP, C>(&self, access_policy: AP, call_name: &str) -> Result<ST>
@@ -202,10 +262,9 @@ where
         C: CallNameConverter,
     {
         let call_id = C::as_id(call_name);
-        let eid = self.inner.read().deployer.get_enclave_id();
         let input = host_input::GetState::new(access_policy, call_id);
 
-        let vec = GetStateWorkflow::exec(input, eid)?
+        let vec = GetStateWorkflow::exec(input, self.enclave_id)?
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?
             .into_vec(); // into Vec<u8> in StateType
@@ -214,17 +273,30 @@ where
     }
 
     pub async fn handshake(&self, signer: Address, gas: u64) -> Result<(H256, ExportPathSecret)> {
-        let inner = self.inner.read();
         let input = host_input::Handshake::new(signer, gas);
-        let eid = inner.deployer.get_enclave_id();
-        let host_output = HandshakeWorkflow::exec(input, eid)?;
-
-        let tx_hash = inner
-            .sender
-            .as_ref()
-            .ok_or(HostError::AddressNotSet)?
-            .handshake(host_output.clone())
-            .await?;
+        let host_output = HandshakeWorkflow::exec(input, self.enclave_id)?;
+
+        let tx_hash = self.sender()?.handshake(host_output.clone()).await?;
+        let export_path_secret = host_output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?
+            .export_path_secret();
+
+        Ok((tx_hash, export_path_secret))
+    }
+
+    /// Remove the member of `removed_roster_idx` from the group and re-key the group.
+    /// Only the owner's enclave can create the remove handshake and the contract also checks the signer is the owner.
+    pub async fn remove_member(
+        &self,
+        signer: Address,
+        gas: u64,
+        removed_roster_idx: u32,
+    ) -> Result<(H256, ExportPathSecret)> {
+        let input = host_input::RemoveMember::new(signer, gas, removed_roster_idx);
+        let host_output = RemoveMemberWorkflow::exec(input, self.enclave_id)?;
+
+        let tx_hash = self.sender()?.remove_member(host_output.clone()).await?;
         let export_path_secret = host_output
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?
@@ -237,28 +309,16 @@ where
     where
         St: State,
     {
-        let inner = self.inner.read();
-        let eid = inner.deployer.get_enclave_id();
-        inner
-            .watcher
-            .as_ref()
-            .ok_or(HostError::EventWatcherNotSet)?
-            .fetch_events(eid)
-            .await
+        self.watcher()?.fetch_events(self.enclave_id).await
     }
 
     pub async fn get_account(&self, index: usize, password: &str) -> Result<Address> {
-        self.inner
-            .read()
-            .deployer
-            .get_account(index, password)
-            .await
+        self.deployer.get_account(index, password).await
     }
 
     pub fn get_encrypting_key(&self) -> Result<DhPubKey> {
         let input = host_input::GetEncryptingKey::default();
-        let eid = self.inner.read().deployer.get_enclave_id();
-        let encrypting_key = GetEncryptingKeyWorkflow::exec(input, eid)?;
+        let encrypting_key = GetEncryptingKeyWorkflow::exec(input, self.enclave_id)?;
 
         Ok(encrypting_key
             .ecall_output
@@ -266,14 +326,23 @@ where
             .encrypting_key())
     }
 
+    /// Returns the enclave's cached attestation report, which is verifiable by peers and clients.
+    pub fn get_report(&self) -> Result<AttestedReport> {
+        let input = host_input::GetReport::default();
+        let report = GetReportWorkflow::exec(input, self.enclave_id)?;
+
+        Ok(report
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?
+            .attested_report())
+    }
+
     pub fn register_notification<AP>(&self, access_policy: AP) -> Result<()>
     where
         AP: AccessPolicy,
     {
-        let inner = self.inner.read();
         let input = host_input::RegisterNotification::new(access_policy);
-        let eid = inner.deployer.get_enclave_id();
-        let _host_output = RegisterNotificationWorkflow::exec(input, eid)?;
+        let _host_output = RegisterNotificationWorkflow::exec(input, self.enclave_id)?;
 
         Ok(())
     }
@@ -1,3 +1,4 @@
+use frame_common::{EcallError, EcallErrorKind};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, HostError>;
@@ -24,6 +25,28 @@ pub enum HostError {
     EthabiError(#[from] ethabi::Error),
     #[error("Codec error: {0}")]
     CodecError(#[from] codec::Error),
+    #[error("Invalid input to the enclave: {0}")]
+    InvalidInput(EcallError),
+    #[error("Rejected by the enclave's policy: {0}")]
+    PolicyViolation(EcallError),
+    #[error("Not found in the enclave: {0}")]
+    NotFound(EcallError),
+    #[error("Enclave error: {0}")]
+    EnclaveError(EcallError),
     #[error("Frame host error: {0}")]
-    FrameHostError(#[from] frame_host::Error),
+    FrameHostError(frame_host::Error),
+}
+
+impl From<frame_host::Error> for HostError {
+    fn from(err: frame_host::Error) -> Self {
+        match err {
+            frame_host::Error::Ecall { error, .. } => match error.kind() {
+                EcallErrorKind::InvalidInput => HostError::InvalidInput(error),
+                EcallErrorKind::PolicyViolation => HostError::PolicyViolation(error),
+                EcallErrorKind::NotFound => HostError::NotFound(error),
+                EcallErrorKind::Internal => HostError::EnclaveError(error),
+            },
+            err => HostError::FrameHostError(err),
+        }
+    }
 }
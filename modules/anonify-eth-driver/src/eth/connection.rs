@@ -1,4 +1,7 @@
-use super::event_watcher::{EthEvent, Web3Logs};
+use super::{
+    dcap::contract_report_inputs,
+    event_watcher::{EthEvent, Web3Logs},
+};
 use crate::{
     cache::EventCache,
     error::{HostError, Result},
@@ -50,8 +53,10 @@ impl Web3Contract {
         let ecall_output = output
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?;
-        let report = ecall_output.report().to_vec();
-        let report_sig = ecall_output.report_sig().to_vec();
+        let (report, report_sig) = contract_report_inputs(
+            ecall_output.report().to_vec(),
+            ecall_output.report_sig().to_vec(),
+        )?;
         let handshake = ecall_output.handshake().to_vec();
         let gas = output.gas;
 
@@ -76,8 +81,10 @@ impl Web3Contract {
         let ecall_output = output
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?;
-        let report = ecall_output.report().to_vec();
-        let report_sig = ecall_output.report_sig().to_vec();
+        let (report, report_sig) = contract_report_inputs(
+            ecall_output.report().to_vec(),
+            ecall_output.report_sig().to_vec(),
+        )?;
         let gas = output.gas;
 
         self.contract
@@ -134,6 +141,46 @@ impl Web3Contract {
             .map_err(Into::into)
     }
 
+    pub async fn remove_member(&self, output: host_output::RemoveMember) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let handshake = ecall_output.encode_handshake();
+        let enclave_sig = &ecall_output.encode_enclave_sig();
+        let gas = output.gas;
+
+        self.contract
+            .call(
+                "removeMember",
+                (
+                    handshake,
+                    enclave_sig.to_vec(),
+                    ecall_output.roster_idx(),
+                    output.removed_roster_idx,
+                ),
+                output.signer,
+                Options::with(|opt| opt.gas = Some(gas.into())),
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    /// The roster index which the contract will allocate to the next member joining the group.
+    pub async fn next_roster_idx(&self) -> Result<u32> {
+        self.contract
+            .query("nextRosterIdx", (), None, Options::default(), None)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// The roster index which the contract has allocated to the account.
+    pub async fn roster_idx(&self, account: Address) -> Result<u32> {
+        self.contract
+            .query("rosterIdxOf", account, None, Options::default(), None)
+            .await
+            .map_err(Into::into)
+    }
+
     pub async fn get_event(&self, cache: EventCache, key: Address) -> Result<Web3Logs> {
         let events = EthEvent::create_event();
         let ciphertext_sig = events.ciphertext_signature();
@@ -221,8 +268,10 @@ impl Web3Http {
         let ecall_output = output
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?;
-        let report = ecall_output.report().to_vec();
-        let report_sig = ecall_output.report_sig().to_vec();
+        let (report, report_sig) = contract_report_inputs(
+            ecall_output.report().to_vec(),
+            ecall_output.report_sig().to_vec(),
+        )?;
         let handshake = ecall_output.handshake().to_vec();
         let gas = output.gas;
 
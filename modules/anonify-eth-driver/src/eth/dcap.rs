@@ -0,0 +1,371 @@
+//! DCAP's reports aren't signed by the attestation service, so the contract verifies the quotes themselves.
+//! X.509 certificates are too costly to parse on-chain, so the PCK certificate chain embedded in the quote
+//! is split into the inputs of `DcapQuote.verifyPckChain` in the contract, which verifies them cryptographically.
+
+use anyhow::{anyhow, ensure, Result};
+use ethabi::Token;
+use serde_json::Value;
+
+const ATTESTATION_TYPE_DCAP: &str = "DCAP";
+/// The offset of the QE authentication data's length in `sgx_quote3_t`
+const QE_AUTH_DATA_LEN_OFFSET: usize = 1012;
+const CERT_DATA_TYPE_PCK_CERT_CHAIN: u16 = 5;
+const ECDSA_COORDINATE_SIZE: usize = 32;
+/// The DER-encoded SubjectPublicKeyInfo of a P-256 key up to its raw `x || y`
+const P256_SPKI_PREFIX: [u8; 27] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04,
+];
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_INTEGER: u8 = 0x02;
+const DER_BIT_STRING: u8 = 0x03;
+
+/// Returns the report and its signature to be passed to the contract.
+/// IAS's reports are passed as they are, and DCAP's ones are converted into the raw quote
+/// and the ABI-encoded PCK certificate chain.
+pub fn contract_report_inputs(report: Vec<u8>, report_sig: Vec<u8>) -> Result<(Vec<u8>, Vec<u8>)> {
+    let quote = match dcap_quote(&report)? {
+        Some(quote) => quote,
+        None => return Ok((report, report_sig)),
+    };
+    let pck_chain = pem_to_der_certs(pck_cert_chain(&quote)?)?;
+    ensure!(
+        pck_chain.len() >= 2,
+        "The PCK certificate chain must contain the PCK CA's certificate"
+    );
+    let pck_cert = SplitCert::parse(&pck_chain[0])?;
+    let ca_cert = SplitCert::parse(&pck_chain[1])?;
+
+    let encoded = ethabi::encode(&[
+        Token::Bytes(pck_cert.tbs),
+        Token::Bytes(pck_cert.sig),
+        Token::Uint((pck_cert.pubkey_offset as u64).into()),
+        Token::Bytes(ca_cert.tbs),
+        Token::Bytes(ca_cert.sig),
+        Token::Uint((ca_cert.pubkey_offset as u64).into()),
+    ]);
+
+    Ok((quote, encoded))
+}
+
+/// Returns the raw quote if the report is DCAP's one.
+fn dcap_quote(report: &[u8]) -> Result<Option<Vec<u8>>> {
+    let report: Value = match serde_json::from_slice(report) {
+        Ok(report) => report,
+        Err(_) => return Ok(None),
+    };
+    if report["attestationType"].as_str() != Some(ATTESTATION_TYPE_DCAP) {
+        return Ok(None);
+    }
+    let quote = report["quote"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Not found quote in DCAP's report"))?;
+
+    base64::decode(quote).map(Some).map_err(Into::into)
+}
+
+/// Returns the PEM-encoded PCK certificate chain in the quote's certification data.
+fn pck_cert_chain(quote: &[u8]) -> Result<&[u8]> {
+    let qe_auth_data_len = read_u16(quote, QE_AUTH_DATA_LEN_OFFSET)? as usize;
+    let cert_data_offset = QE_AUTH_DATA_LEN_OFFSET + 2 + qe_auth_data_len;
+    let cert_data_type = read_u16(quote, cert_data_offset)?;
+    ensure!(
+        cert_data_type == CERT_DATA_TYPE_PCK_CERT_CHAIN,
+        "Unsupported certification data type: {}",
+        cert_data_type
+    );
+    let len_offset = cert_data_offset + 2;
+    ensure!(quote.len() >= len_offset + 4, "Unexpected end of the quote");
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&quote[len_offset..len_offset + 4]);
+    let cert_data_len = u32::from_le_bytes(len) as usize;
+    ensure!(
+        quote.len() >= len_offset + 4 + cert_data_len,
+        "Unexpected end of the quote"
+    );
+
+    Ok(&quote[len_offset + 4..len_offset + 4 + cert_data_len])
+}
+
+/// A certificate split into the parts which the contract verifies
+#[derive(Debug, PartialEq)]
+struct SplitCert {
+    /// The DER-encoded `tbsCertificate`
+    tbs: Vec<u8>,
+    /// The raw `r || s` signature over `tbs`
+    sig: Vec<u8>,
+    /// The offset of the raw P-256 public key in `tbs`
+    pubkey_offset: usize,
+}
+
+impl SplitCert {
+    fn parse(cert: &[u8]) -> Result<Self> {
+        let (tag, cert_body, _) = read_tlv(cert)?;
+        ensure!(tag == DER_SEQUENCE, "A certificate must be a SEQUENCE");
+        let tbs_len = tlv_len(cert_body)?;
+        let tbs = &cert_body[..tbs_len];
+        let (_, _, rest) = read_tlv(&cert_body[tbs_len..])?; // signatureAlgorithm
+        let (tag, sig_bits, _) = read_tlv(rest)?;
+        ensure!(tag == DER_BIT_STRING, "signatureValue must be a BIT STRING");
+        ensure!(
+            sig_bits.first() == Some(&0),
+            "signatureValue must not have unused bits"
+        );
+
+        let pubkey_offset = tbs
+            .windows(P256_SPKI_PREFIX.len())
+            .position(|w| w == P256_SPKI_PREFIX)
+            .map(|pos| pos + P256_SPKI_PREFIX.len())
+            .ok_or_else(|| anyhow!("Not found a P-256 public key in the certificate"))?;
+        ensure!(
+            tbs.len() >= pubkey_offset + 2 * ECDSA_COORDINATE_SIZE,
+            "Unexpected end of the public key"
+        );
+
+        Ok(SplitCert {
+            tbs: tbs.to_vec(),
+            sig: ecdsa_sig_from_der(&sig_bits[1..])?,
+            pubkey_offset,
+        })
+    }
+}
+
+/// Convert a DER-encoded ECDSA signature into the raw `r || s`.
+fn ecdsa_sig_from_der(der: &[u8]) -> Result<Vec<u8>> {
+    let (tag, body, _) = read_tlv(der)?;
+    ensure!(tag == DER_SEQUENCE, "An ECDSA signature must be a SEQUENCE");
+    let (tag, r, rest) = read_tlv(body)?;
+    ensure!(tag == DER_INTEGER, "r must be an INTEGER");
+    let (tag, s, _) = read_tlv(rest)?;
+    ensure!(tag == DER_INTEGER, "s must be an INTEGER");
+
+    let mut sig = to_coordinate(r)?;
+    sig.extend_from_slice(&to_coordinate(s)?);
+    Ok(sig)
+}
+
+/// Strip the sign byte of a DER integer and pad it to the coordinate size.
+fn to_coordinate(int: &[u8]) -> Result<Vec<u8>> {
+    let start = int.iter().position(|b| *b != 0).unwrap_or(int.len());
+    let int = &int[start..];
+    ensure!(
+        int.len() <= ECDSA_COORDINATE_SIZE,
+        "An ECDSA integer exceeds {} bytes",
+        ECDSA_COORDINATE_SIZE
+    );
+
+    let mut res = vec![0u8; ECDSA_COORDINATE_SIZE - int.len()];
+    res.extend_from_slice(int);
+    Ok(res)
+}
+
+/// Returns the tag, the contents and the rest of the input.
+fn read_tlv(input: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+    let len = tlv_len(input)?;
+    let header_len = len - content_len(input)?;
+    Ok((input[0], &input[header_len..len], &input[len..]))
+}
+
+/// The length of the whole TLV at the head of the input
+fn tlv_len(input: &[u8]) -> Result<usize> {
+    let content_len = content_len(input)?;
+    let header_len = match input[1] {
+        len if len < 0x80 => 2,
+        len => 2 + (len & 0x7f) as usize,
+    };
+    ensure!(
+        input.len() >= header_len + content_len,
+        "Unexpected end of DER"
+    );
+    Ok(header_len + content_len)
+}
+
+fn content_len(input: &[u8]) -> Result<usize> {
+    ensure!(input.len() >= 2, "Unexpected end of DER");
+    match input[1] {
+        len if len < 0x80 => Ok(len as usize),
+        len => {
+            let num = (len & 0x7f) as usize;
+            ensure!(
+                (1..=4).contains(&num) && input.len() >= 2 + num,
+                "Unsupported DER length"
+            );
+            Ok(input[2..2 + num]
+                .iter()
+                .fold(0usize, |acc, b| (acc << 8) | *b as usize))
+        }
+    }
+}
+
+fn pem_to_der_certs(pem: &[u8]) -> Result<Vec<Vec<u8>>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let pem = std::str::from_utf8(pem)?;
+    let mut certs = vec![];
+    let mut rest = pem;
+    while let Some(begin) = rest.find(BEGIN) {
+        let after_begin = &rest[begin + BEGIN.len()..];
+        let end = after_begin
+            .find(END)
+            .ok_or_else(|| anyhow!("Not found the end of the certificate"))?;
+        let body: String = after_begin[..end]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        certs.push(base64::decode(&body)?);
+        rest = &after_begin[end + END.len()..];
+    }
+
+    Ok(certs)
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Result<u16> {
+    ensure!(buf.len() >= offset + 2, "Unexpected end of the quote");
+    Ok(u16::from_le_bytes([buf[offset], buf[offset + 1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethabi::ParamType;
+
+    /// A PCK certificate and its CA's one, which are issued by a test CA with P-256 keys
+    const PCK_CERT_CHAIN: &str = "-----BEGIN CERTIFICATE-----
+MIIBNzCB36ADAgECAgECMAoGCCqGSM49BAMCMBYxFDASBgNVBAMMC1Rlc3QgUENL
+IENBMCAXDTIwMDEwMTAwMDAwMFoYDzIxMTkxMjA4MDAwMDAwWjAfMR0wGwYDVQQD
+DBRUZXN0IFBDSyBDZXJ0aWZpY2F0ZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IA
+BDVL0gvg+o/fdnoaB+rcbRtwCv+rtzgIQqLOqSu+pbKpm9cXd10L7FNHf1Dt6ofC
+i078SaRkjUu+WCqOyU8tUbCjEzARMA8GCSqGSIb4TQENAQQCMAAwCgYIKoZIzj0E
+AwIDRwAwRAIgLAoq7vR5ktPoFcvleXg8wnmaUDgqdMBERyeo/HRqP2sCIAg9YLVy
+Ar07FoVY69GXalsblswDq0ScA3e/qNS0sNd0
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+MIIBMTCB2aADAgECAgEBMAoGCCqGSM49BAMCMBYxFDASBgNVBAMMC1Rlc3QgUENL
+IENBMCAXDTIwMDEwMTAwMDAwMFoYDzIxMTkxMjA4MDAwMDAwWjAWMRQwEgYDVQQD
+DAtUZXN0IFBDSyBDQTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABF8SvQWN/gaZ
+2V7BtD9a9Ld52RIJSJImTJIxGT4VMH7MkE9d946LkK77YneaKSbwYx+dYeNkZ+Ti
+cFnN1yySO6GjFjAUMBIGA1UdEwEB/wQIMAYBAf8CAQAwCgYIKoZIzj0EAwIDRwAw
+RAIgTUPest6QGRJZQ0qB7Ep3ApMsbJS/HnNhJFez4QO31TsCICIVV8uweDVa2vf2
+wkGxL7wzKEiyGsYVUgNTcJ20w1UE
+-----END CERTIFICATE-----
+";
+    const PCK_PUBKEY: &str = "354bd20be0fa8fdf767a1a07eadc6d1b700affabb7380842a2cea92bbea5b2a99bd717775d0bec53477f50edea87c28b4efc49a4648d4bbe582a8ec94f2d51b0";
+    const PCK_CA_PUBKEY: &str = "5f12bd058dfe0699d95ec1b43f5af4b779d912094892264c9231193e15307ecc904f5df78e8b90aefb62779a2926f0631f9d61e36467e4e27059cdd72c923ba1";
+    const QE_AUTH_DATA_LEN: usize = 32;
+
+    fn quote(cert_data_type: u16, cert_data: &[u8]) -> Vec<u8> {
+        let mut quote = vec![0u8; QE_AUTH_DATA_LEN_OFFSET];
+        quote[0..2].copy_from_slice(&3u16.to_le_bytes());
+        quote[2..4].copy_from_slice(&2u16.to_le_bytes());
+        quote.extend_from_slice(&(QE_AUTH_DATA_LEN as u16).to_le_bytes());
+        quote.extend_from_slice(&[1u8; QE_AUTH_DATA_LEN]);
+        quote.extend_from_slice(&cert_data_type.to_le_bytes());
+        quote.extend_from_slice(&(cert_data.len() as u32).to_le_bytes());
+        quote.extend_from_slice(cert_data);
+        quote
+    }
+
+    fn dcap_report(quote: &[u8]) -> Vec<u8> {
+        serde_json::json!({
+            "attestationType": ATTESTATION_TYPE_DCAP,
+            "quote": base64::encode(quote),
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_pass_through_ias_report() {
+        let report = br#"{"id":"1","isvEnclaveQuoteStatus":"OK"}"#.to_vec();
+        let report_sig = b"signature".to_vec();
+        assert_eq!(
+            contract_report_inputs(report.clone(), report_sig.clone()).unwrap(),
+            (report, report_sig)
+        );
+        assert_eq!(
+            contract_report_inputs(vec![], vec![]).unwrap(),
+            (vec![], vec![])
+        );
+    }
+
+    #[test]
+    fn test_split_pck_cert_chain() {
+        let quote = quote(CERT_DATA_TYPE_PCK_CERT_CHAIN, PCK_CERT_CHAIN.as_bytes());
+        let (report, pck_chain) = contract_report_inputs(dcap_report(&quote), vec![]).unwrap();
+        assert_eq!(report, quote);
+
+        let tokens = ethabi::decode(
+            &[
+                ParamType::Bytes,
+                ParamType::Bytes,
+                ParamType::Uint(256),
+                ParamType::Bytes,
+                ParamType::Bytes,
+                ParamType::Uint(256),
+            ],
+            &pck_chain,
+        )
+        .unwrap();
+        let certs = pem_to_der_certs(PCK_CERT_CHAIN.as_bytes()).unwrap();
+        for (i, pubkey) in [PCK_PUBKEY, PCK_CA_PUBKEY].iter().enumerate() {
+            let tbs = tokens[3 * i].clone().to_bytes().unwrap();
+            let sig = tokens[3 * i + 1].clone().to_bytes().unwrap();
+            let offset = tokens[3 * i + 2].clone().to_uint().unwrap().as_usize();
+
+            // The certificate's header is followed by `tbsCertificate`.
+            assert_eq!(&certs[i][4..4 + tbs.len()], &tbs[..]);
+            assert_eq!(sig.len(), 2 * ECDSA_COORDINATE_SIZE);
+            assert_eq!(
+                &tbs[offset - P256_SPKI_PREFIX.len()..offset],
+                &P256_SPKI_PREFIX[..]
+            );
+            assert_eq!(hex::encode(&tbs[offset..offset + 64]), *pubkey);
+        }
+    }
+
+    #[test]
+    fn test_reject_malformed_quote() {
+        let chain = PCK_CERT_CHAIN.as_bytes();
+        let valid = quote(CERT_DATA_TYPE_PCK_CERT_CHAIN, chain);
+
+        // The certification data is truncated.
+        let truncated = &valid[..valid.len() - 10];
+        assert!(contract_report_inputs(dcap_report(truncated), vec![]).is_err());
+        // The quote ends before the certification data.
+        let truncated = &valid[..QE_AUTH_DATA_LEN_OFFSET + 1];
+        assert!(contract_report_inputs(dcap_report(truncated), vec![]).is_err());
+        // Other than the PCK certificate chain
+        let other_type = quote(CERT_DATA_TYPE_PCK_CERT_CHAIN - 1, chain);
+        assert!(contract_report_inputs(dcap_report(&other_type), vec![]).is_err());
+        // Only the PCK certificate
+        let end = PCK_CERT_CHAIN.rfind("-----BEGIN").unwrap();
+        let no_ca = quote(CERT_DATA_TYPE_PCK_CERT_CHAIN, &chain[..end]);
+        assert!(contract_report_inputs(dcap_report(&no_ca), vec![]).is_err());
+        // A corrupted certificate
+        let corrupted = PCK_CERT_CHAIN.replacen("MIIBNzCB", "MIIBOzCB", 1);
+        let corrupted = quote(CERT_DATA_TYPE_PCK_CERT_CHAIN, corrupted.as_bytes());
+        assert!(contract_report_inputs(dcap_report(&corrupted), vec![]).is_err());
+        // The quote isn't base64-encoded.
+        let report = br#"{"attestationType":"DCAP","quote":"not base64!"}"#.to_vec();
+        assert!(contract_report_inputs(report, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_contract_test_root_key() {
+        const CONTRACT: &str = include_str!("../../../../contracts/test/AnonifyWithMockIas.sol");
+        const ROOT_CA: &str =
+            include_str!("../../../../frame/remote-attestation/certs/test-dcap-root-ca.pem");
+        const PREFIX: &str = "MOCK_DCAP_ROOT_KEY = hex\"";
+
+        let start = CONTRACT.find(PREFIX).unwrap() + PREFIX.len();
+        let end = start + CONTRACT[start..].find('"').unwrap();
+        let root = SplitCert::parse(&pem_to_der_certs(ROOT_CA.as_bytes()).unwrap()[0]).unwrap();
+        let offset = root.pubkey_offset;
+        assert_eq!(
+            &CONTRACT[start..end],
+            hex::encode(&root.tbs[offset..offset + 2 * ECDSA_COORDINATE_SIZE])
+        );
+    }
+}
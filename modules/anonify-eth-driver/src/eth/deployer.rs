@@ -1,6 +1,7 @@
 use super::connection::{Web3Contract, Web3Http};
 use crate::{error::Result, traits::*, utils::*, workflow::*};
 use async_trait::async_trait;
+use parking_lot::Mutex;
 use sgx_types::sgx_enclave_id_t;
 use std::{marker::Send, path::Path};
 use web3::types::Address;
@@ -10,7 +11,7 @@ use web3::types::Address;
 pub struct EthDeployer {
     enclave_id: sgx_enclave_id_t,
     web3_conn: Web3Http,
-    address: Option<Address>, // contract address
+    address: Mutex<Option<Address>>, // contract address
 }
 
 #[async_trait]
@@ -21,7 +22,7 @@ impl Deployer for EthDeployer {
         Ok(EthDeployer {
             enclave_id,
             web3_conn,
-            address: None,
+            address: Mutex::new(None),
         })
     }
 
@@ -30,7 +31,7 @@ impl Deployer for EthDeployer {
     }
 
     async fn deploy<P: AsRef<Path> + Send>(
-        &mut self,
+        &self,
         host_output: host_output::JoinGroup,
         abi_path: P,
         bin_path: P,
@@ -40,7 +41,7 @@ impl Deployer for EthDeployer {
             .web3_conn
             .deploy(host_output, abi_path, bin_path, confirmations)
             .await?;
-        self.address = Some(contract_addr);
+        *self.address.lock() = Some(contract_addr);
 
         Ok(hex::encode(contract_addr.as_bytes()))
     }
@@ -48,6 +49,7 @@ impl Deployer for EthDeployer {
     fn get_contract<P: AsRef<Path>>(self, abi_path: P) -> Result<ContractKind> {
         let addr = self
             .address
+            .into_inner()
             .expect("The contract hasn't be deployed yet.")
             .to_string();
         let contract_info = ContractInfo::new(abi_path, &addr);
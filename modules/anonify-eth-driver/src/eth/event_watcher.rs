@@ -162,10 +162,9 @@ impl Web3Logs {
         // Removes consecutive repeated message
         payloads.dedup();
         // Order guarantee
-        let immutable_payloads = payloads.clone();
         let payloads = {
             let mut mut_cache = self.cache.inner().write();
-            mut_cache.ensure_order_guarantee(payloads, immutable_payloads, MAX_TRIALS_NUM)
+            mut_cache.ensure_order_guarantee(payloads, MAX_TRIALS_NUM)
         };
 
         EnclaveLog {
@@ -304,7 +303,7 @@ impl InnerEnclaveLog {
                             handshake.prior_epoch(),
                         );
 
-                        if let Err(e) = Self::insert_handshake(eid, handshake) {
+                        if let Err(e) = Self::insert_handshake(eid, handshake, self.contract_addr) {
                             error!("Error in enclave (InsertHandshakeWorkflow::exec): {:?}", e);
                             continue;
                         }
@@ -320,8 +319,12 @@ impl InnerEnclaveLog {
         }
     }
 
-    fn insert_handshake(eid: sgx_enclave_id_t, handshake: ExportHandshake) -> Result<()> {
-        let input = host_input::InsertHandshake::new(handshake);
+    fn insert_handshake(
+        eid: sgx_enclave_id_t,
+        handshake: ExportHandshake,
+        contract_addr: [u8; 20],
+    ) -> Result<()> {
+        let input = host_input::InsertHandshake::new(handshake, contract_addr);
         InsertHandshakeWorkflow::exec(input, eid)?;
 
         Ok(())
@@ -373,22 +376,20 @@ impl PayloadType {
         }
     }
 
-    /// other is the next of self
-    pub fn is_next(&self, other: &Self) -> bool {
-        self.roster_idx == other.roster_idx
-            && ((self.epoch == other.epoch && self.generation + 1 == other.generation) ||
-            (self.epoch == other.epoch && other.generation == u32::MAX) || // TODO: order gurantee with handshake
-            (self.epoch + 1 == other.epoch && self.generation == u32::MAX && other.generation == 0))
-    }
-
     pub fn roster_idx(&self) -> u32 {
         self.roster_idx
     }
 
+    /// The epoch of a ciphertext, or the prior epoch of a handshake
     pub fn epoch(&self) -> u32 {
         self.epoch
     }
 
+    /// A handshake is the last of the generation
+    pub fn is_handshake(&self) -> bool {
+        self.generation == u32::MAX
+    }
+
     pub fn generation(&self) -> u32 {
         self.generation
     }
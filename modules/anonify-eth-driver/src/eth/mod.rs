@@ -1,8 +1,10 @@
 pub mod connection;
+mod dcap;
 pub mod deployer;
 pub mod event_watcher;
 pub mod sender;
 
+pub use self::dcap::contract_report_inputs;
 pub use self::deployer::EthDeployer;
 pub use self::event_watcher::EventWatcher;
 pub use self::sender::EthSender;
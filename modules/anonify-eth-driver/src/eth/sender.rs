@@ -42,6 +42,14 @@ impl Sender for EthSender {
         self.contract.get_account(index, password).await
     }
 
+    async fn next_roster_idx(&self) -> Result<u32> {
+        self.contract.next_roster_idx().await
+    }
+
+    async fn roster_idx(&self, account: Address) -> Result<u32> {
+        self.contract.roster_idx(account).await
+    }
+
     async fn send_report_handshake(
         &self,
         host_output: host_output::JoinGroup,
@@ -67,6 +75,11 @@ impl Sender for EthSender {
         self.contract.handshake(host_output).await
     }
 
+    async fn remove_member(&self, host_output: host_output::RemoveMember) -> Result<H256> {
+        debug!("Removing a member from the group: {:?}", host_output);
+        self.contract.remove_member(host_output).await
+    }
+
     fn get_contract(self) -> ContractKind {
         ContractKind::Web3Contract(self.contract)
     }
@@ -16,8 +16,9 @@ pub trait Deployer: Sized {
     async fn get_account(&self, index: usize, password: &str) -> Result<Address>;
 
     /// Deploying contract with attestation.
+    /// It takes `&self` so that other requests aren't blocked while waiting for the confirmations.
     async fn deploy<P: AsRef<Path> + Send>(
-        &mut self,
+        &self,
         host_output: host_output::JoinGroup,
         abi_path: P,
         bin_path: P,
@@ -44,6 +45,12 @@ pub trait Sender: Sized {
 
     async fn get_account(&self, index: usize, password: &str) -> Result<Address>;
 
+    /// The roster index which will be allocated to the next member joining the group.
+    async fn next_roster_idx(&self) -> Result<u32>;
+
+    /// The roster index which has been allocated to the account.
+    async fn roster_idx(&self, account: Address) -> Result<u32>;
+
     /// Send an encrypted command of state transition to blockchain nodes.
     async fn send_command(&self, host_output: host_output::Command) -> Result<H256>;
 
@@ -58,6 +65,9 @@ pub trait Sender: Sized {
 
     async fn handshake(&self, host_output: host_output::Handshake) -> Result<H256>;
 
+    /// Remove a member from the group. Only the owner of the contract can send it.
+    async fn remove_member(&self, host_output: host_output::RemoveMember) -> Result<H256>;
+
     fn get_contract(self) -> ContractKind;
 }
 
@@ -11,6 +11,9 @@ use std::marker::PhantomData;
 use web3::types::Address;
 
 pub const OUTPUT_MAX_LEN: usize = 2048;
+/// Reports with the signing certificate, or DCAP's collateral, are usually larger than `OUTPUT_MAX_LEN`,
+/// so the buffer is large enough to skip the second ecall fetching larger outputs.
+pub const REPORT_OUTPUT_MAX_LEN: usize = 32 * 1024;
 
 pub struct CommandWorkflow<C: CallNameConverter, AP: AccessPolicy> {
     c: PhantomData<C>,
@@ -59,6 +62,17 @@ impl HostEngine for HandshakeWorkflow {
     const CMD: u32 = CALL_HANDSHAKE_CMD;
 }
 
+pub struct RemoveMemberWorkflow;
+
+impl HostEngine for RemoveMemberWorkflow {
+    type HI = host_input::RemoveMember;
+    type EI = input::CallRemoveMember;
+    type EO = output::ReturnHandshake;
+    type HO = host_output::RemoveMember;
+    const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
+    const CMD: u32 = CALL_REMOVE_MEMBER_CMD;
+}
+
 pub struct RegisterNotificationWorkflow<AP: AccessPolicy> {
     ap: PhantomData<AP>,
 }
@@ -118,6 +132,17 @@ impl HostEngine for GetEncryptingKeyWorkflow {
     const CMD: u32 = GET_ENCRYPTING_KEY_CMD;
 }
 
+pub struct GetReportWorkflow;
+
+impl HostEngine for GetReportWorkflow {
+    type HI = host_input::GetReport;
+    type EI = input::GetReport;
+    type EO = output::ReturnReport;
+    type HO = host_output::ReturnReport;
+    const OUTPUT_MAX_LEN: usize = REPORT_OUTPUT_MAX_LEN;
+    const CMD: u32 = GET_REPORT_CMD;
+}
+
 pub mod host_input {
     use super::*;
 
@@ -165,11 +190,16 @@ pub mod host_input {
     pub struct JoinGroup {
         signer: Address,
         gas: u64,
+        roster_idx: u32,
     }
 
     impl JoinGroup {
-        pub fn new(signer: Address, gas: u64) -> Self {
-            JoinGroup { signer, gas }
+        pub fn new(signer: Address, gas: u64, roster_idx: u32) -> Self {
+            JoinGroup {
+                signer,
+                gas,
+                roster_idx,
+            }
         }
     }
 
@@ -178,20 +208,26 @@ pub mod host_input {
         type HostOutput = host_output::JoinGroup;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            let ecall_input = Self::EcallInput::new(self.roster_idx);
             let host_output = host_output::JoinGroup::new(self.signer, self.gas);
 
-            Ok((Self::EcallInput::default(), host_output))
+            Ok((ecall_input, host_output))
         }
     }
 
     pub struct RegisterReport {
         signer: Address,
         gas: u64,
+        roster_idx: u32,
     }
 
     impl RegisterReport {
-        pub fn new(signer: Address, gas: u64) -> Self {
-            RegisterReport { signer, gas }
+        pub fn new(signer: Address, gas: u64, roster_idx: u32) -> Self {
+            RegisterReport {
+                signer,
+                gas,
+                roster_idx,
+            }
         }
     }
 
@@ -200,9 +236,10 @@ pub mod host_input {
         type HostOutput = host_output::RegisterReport;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            let ecall_input = Self::EcallInput::new(self.roster_idx);
             let host_output = host_output::RegisterReport::new(self.signer, self.gas);
 
-            Ok((Self::EcallInput::default(), host_output))
+            Ok((ecall_input, host_output))
         }
     }
 
@@ -228,6 +265,35 @@ pub mod host_input {
         }
     }
 
+    pub struct RemoveMember {
+        signer: Address,
+        gas: u64,
+        removed_roster_idx: u32,
+    }
+
+    impl RemoveMember {
+        pub fn new(signer: Address, gas: u64, removed_roster_idx: u32) -> Self {
+            RemoveMember {
+                signer,
+                gas,
+                removed_roster_idx,
+            }
+        }
+    }
+
+    impl HostInput for RemoveMember {
+        type EcallInput = input::CallRemoveMember;
+        type HostOutput = host_output::RemoveMember;
+
+        fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            let ecall_input = Self::EcallInput::new(self.removed_roster_idx);
+            let host_output =
+                host_output::RemoveMember::new(self.signer, self.gas, self.removed_roster_idx);
+
+            Ok((ecall_input, host_output))
+        }
+    }
+
     pub struct RegisterNotification<AP: AccessPolicy> {
         access_policy: AP,
     }
@@ -297,11 +363,15 @@ pub mod host_input {
 
     pub struct InsertHandshake {
         handshake: ExportHandshake,
+        contract_address: [u8; 20],
     }
 
     impl InsertHandshake {
-        pub fn new(handshake: ExportHandshake) -> Self {
-            InsertHandshake { handshake }
+        pub fn new(handshake: ExportHandshake, contract_address: [u8; 20]) -> Self {
+            InsertHandshake {
+                handshake,
+                contract_address,
+            }
         }
     }
 
@@ -310,7 +380,7 @@ pub mod host_input {
         type HostOutput = host_output::InsertHandshake;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
-            let ecall_input = Self::EcallInput::new(self.handshake);
+            let ecall_input = Self::EcallInput::new(self.handshake, self.contract_address);
 
             Ok((ecall_input, Self::HostOutput::default()))
         }
@@ -327,6 +397,18 @@ pub mod host_input {
             Ok((Self::EcallInput::default(), Self::HostOutput::new()))
         }
     }
+
+    #[derive(Default)]
+    pub struct GetReport;
+
+    impl HostInput for GetReport {
+        type EcallInput = input::GetReport;
+        type HostOutput = host_output::ReturnReport;
+
+        fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            Ok((Self::EcallInput::default(), Self::HostOutput::new()))
+        }
+    }
 }
 
 pub mod host_output {
@@ -440,6 +522,35 @@ pub mod host_output {
         }
     }
 
+    #[derive(Debug, Clone)]
+    pub struct RemoveMember {
+        pub signer: Address,
+        pub gas: u64,
+        pub removed_roster_idx: u32,
+        pub ecall_output: Option<output::ReturnHandshake>,
+    }
+
+    impl HostOutput for RemoveMember {
+        type EcallOutput = output::ReturnHandshake;
+
+        fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
+            self.ecall_output = Some(output);
+
+            Ok(self)
+        }
+    }
+
+    impl RemoveMember {
+        pub fn new(signer: Address, gas: u64, removed_roster_idx: u32) -> Self {
+            RemoveMember {
+                signer,
+                gas,
+                removed_roster_idx,
+                ecall_output: None,
+            }
+        }
+    }
+
     #[derive(Default)]
     pub struct RegisterNotification;
 
@@ -513,4 +624,24 @@ pub mod host_output {
             ReturnEncryptingKey { ecall_output: None }
         }
     }
+
+    pub struct ReturnReport {
+        pub ecall_output: Option<output::ReturnReport>,
+    }
+
+    impl HostOutput for ReturnReport {
+        type EcallOutput = output::ReturnReport;
+
+        fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
+            self.ecall_output = Some(output);
+
+            Ok(self)
+        }
+    }
+
+    impl ReturnReport {
+        pub fn new() -> Self {
+            ReturnReport { ecall_output: None }
+        }
+    }
 }
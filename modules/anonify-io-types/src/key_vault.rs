@@ -0,0 +1,175 @@
+//! Messages between an enclave and the remote key vault which backs up path secrets.
+//!
+//! A connection carries exactly one request:
+//! 1. The client sends `ClientMsg::GetIdentity` and verifies the returned vault's `AttestedIdentity`.
+//! 2. The client sends `ClientMsg::Request` which is encrypted to the vault's encrypting key
+//!    and signed by the client's attested signing key.
+//!    The vault replies with `ServerMsg::Response` encrypted to the client's encrypting key
+//!    and signed by the vault's attested signing key, so the host cannot forge responses.
+use crate::localstd::{
+    io::{self, Read, Write},
+    vec::Vec,
+};
+use codec::{Decode, Encode};
+use frame_common::{
+    crypto::{Sha256, EXPORT_ID_SIZE},
+    traits::Hash256,
+};
+use frame_treekem::{DhPubKey, EciesCiphertext};
+
+pub const VERIFYING_ADDRESS_SIZE: usize = 20;
+/// Upper limit of a frame to avoid allocating unbounded memory for malformed inputs.
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// An identity of an enclave attested by IAS.
+/// The REPORTDATA of the attestation report must begin with `verifying_address` followed by `encrypting_key`.
+#[derive(Encode, Decode, Debug, Clone, Default)]
+pub struct AttestedIdentity {
+    pub report: Vec<u8>,
+    pub report_sig: Vec<u8>,
+    pub signing_cert: Vec<u8>,
+    pub verifying_address: [u8; VERIFYING_ADDRESS_SIZE],
+    pub encrypting_key: DhPubKey,
+}
+
+impl AttestedIdentity {
+    pub fn new(
+        report: Vec<u8>,
+        report_sig: Vec<u8>,
+        signing_cert: Vec<u8>,
+        verifying_address: [u8; VERIFYING_ADDRESS_SIZE],
+        encrypting_key: DhPubKey,
+    ) -> Self {
+        AttestedIdentity {
+            report,
+            report_sig,
+            signing_cert,
+            verifying_address,
+            encrypting_key,
+        }
+    }
+
+    /// The expected REPORTDATA prefix of the attestation report.
+    pub fn expected_report_data(&self) -> Vec<u8> {
+        let mut acc = self.verifying_address.to_vec();
+        acc.extend_from_slice(&self.encrypting_key.encode());
+        acc
+    }
+}
+
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct BackupPathSecret {
+    pub path_secret: Vec<u8>,
+    pub epoch: u32,
+    pub id: [u8; EXPORT_ID_SIZE],
+}
+
+impl BackupPathSecret {
+    pub fn new(path_secret: Vec<u8>, epoch: u32, id: [u8; EXPORT_ID_SIZE]) -> Self {
+        BackupPathSecret {
+            path_secret,
+            epoch,
+            id,
+        }
+    }
+}
+
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct RecoverPathSecret {
+    pub id: [u8; EXPORT_ID_SIZE],
+}
+
+impl RecoverPathSecret {
+    pub fn new(id: [u8; EXPORT_ID_SIZE]) -> Self {
+        RecoverPathSecret { id }
+    }
+}
+
+/// A plaintext request to the key vault
+#[derive(Encode, Decode, Debug, Clone)]
+pub enum KeyVaultRequest {
+    Backup(BackupPathSecret),
+    Recover(RecoverPathSecret),
+}
+
+/// A plaintext response from the key vault
+#[derive(Encode, Decode, Debug, Clone)]
+pub enum KeyVaultResponse {
+    Stored,
+    Recovered(BackupPathSecret),
+    NotFound,
+}
+
+/// An encrypted request signed by the client enclave's identity key.
+/// The signature is over sha256 of the encoded `ciphertext`.
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct SignedRequest {
+    pub client: AttestedIdentity,
+    pub ciphertext: EciesCiphertext,
+    pub sig: [u8; 64],
+    pub recovery_id: u8,
+}
+
+/// An encrypted response signed by the vault enclave's identity key.
+/// The signature is over `SignedResponse::signing_msg`, which binds the response to its request.
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct SignedResponse {
+    pub ciphertext: EciesCiphertext,
+    pub sig: [u8; 64],
+    pub recovery_id: u8,
+}
+
+impl SignedResponse {
+    /// sha256 of the encoded request's ciphertext followed by the encoded response's ciphertext
+    pub fn signing_msg(request: &EciesCiphertext, response: &EciesCiphertext) -> Sha256 {
+        let mut msg = request.encode();
+        msg.extend_from_slice(&response.encode());
+        Sha256::hash(&msg)
+    }
+}
+
+#[derive(Encode, Decode, Debug, Clone)]
+pub enum ClientMsg {
+    GetIdentity,
+    Request(SignedRequest),
+}
+
+#[derive(Encode, Decode, Debug, Clone)]
+pub enum ServerMsg {
+    Identity(AttestedIdentity),
+    Response(SignedResponse),
+    /// UTF-8 encoded error message
+    Error(Vec<u8>),
+}
+
+/// Write a length-prefixed frame.
+pub fn write_frame<W: Write, T: Encode>(writer: &mut W, msg: &T) -> io::Result<()> {
+    let buf = msg.encode();
+    if buf.len() > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "frame is too large",
+        ));
+    }
+    writer.write_all(&(buf.len() as u32).to_be_bytes())?;
+    writer.write_all(&buf)?;
+    writer.flush()
+}
+
+/// Read a length-prefixed frame.
+pub fn read_frame<R: Read, T: Decode>(reader: &mut R) -> io::Result<T> {
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf)?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame is too large",
+        ));
+    }
+
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    T::decode(&mut &buf[..])
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "failed to decode frame"))
+}
@@ -7,5 +7,6 @@ use std as localstd;
 #[cfg(all(not(feature = "std"), not(feature = "sgx")))]
 extern crate core as localstd;
 
+pub mod key_vault;
 pub mod types;
 pub use crate::types::*;
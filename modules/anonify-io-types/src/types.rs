@@ -1,7 +1,7 @@
 use crate::localstd::vec::Vec;
 use codec::{self, Decode, Encode, Input};
 use frame_common::{
-    crypto::{Ciphertext, ExportHandshake, ExportPathSecret},
+    crypto::{AttestedReport, Ciphertext, ExportHandshake, ExportPathSecret},
     state_types::{StateType, UpdatedState},
     traits::AccessPolicy,
     EcallInput, EcallOutput,
@@ -39,21 +39,69 @@ pub mod input {
 
     impl EcallInput for GetEncryptingKey {}
 
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct GetReport;
+
+    impl EcallInput for GetReport {}
+
     #[derive(Encode, Decode, Debug, Clone, Default)]
     pub struct CallHandshake;
 
     impl EcallInput for CallHandshake {}
 
     #[derive(Encode, Decode, Debug, Clone, Default)]
-    pub struct CallJoinGroup;
+    pub struct CallRemoveMember {
+        removed_roster_idx: u32,
+    }
+
+    impl EcallInput for CallRemoveMember {}
+
+    impl CallRemoveMember {
+        pub fn new(removed_roster_idx: u32) -> Self {
+            CallRemoveMember { removed_roster_idx }
+        }
+
+        pub fn removed_roster_idx(&self) -> u32 {
+            self.removed_roster_idx
+        }
+    }
+
+    /// `roster_idx` is the next index allocated by the contract's roster index counter.
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct CallJoinGroup {
+        roster_idx: u32,
+    }
 
     impl EcallInput for CallJoinGroup {}
 
+    impl CallJoinGroup {
+        pub fn new(roster_idx: u32) -> Self {
+            CallJoinGroup { roster_idx }
+        }
+
+        pub fn roster_idx(&self) -> u32 {
+            self.roster_idx
+        }
+    }
+
+    /// `roster_idx` is the index which the contract has already allocated to the sender.
     #[derive(Encode, Decode, Debug, Clone, Default)]
-    pub struct CallRegisterReport;
+    pub struct CallRegisterReport {
+        roster_idx: u32,
+    }
 
     impl EcallInput for CallRegisterReport {}
 
+    impl CallRegisterReport {
+        pub fn new(roster_idx: u32) -> Self {
+            CallRegisterReport { roster_idx }
+        }
+
+        pub fn roster_idx(&self) -> u32 {
+            self.roster_idx
+        }
+    }
+
     #[derive(Encode, Decode, Debug, Clone)]
     pub struct InsertCiphertext {
         ciphertext: Ciphertext,
@@ -74,18 +122,27 @@ pub mod input {
     #[derive(Encode, Decode, Debug, Clone)]
     pub struct InsertHandshake {
         handshake: ExportHandshake,
+        /// The address of the contract which the handshake is stored in
+        contract_address: [u8; 20],
     }
 
     impl EcallInput for InsertHandshake {}
 
     impl InsertHandshake {
-        pub fn new(handshake: ExportHandshake) -> Self {
-            InsertHandshake { handshake }
+        pub fn new(handshake: ExportHandshake, contract_address: [u8; 20]) -> Self {
+            InsertHandshake {
+                handshake,
+                contract_address,
+            }
         }
 
         pub fn handshake(&self) -> &ExportHandshake {
             &self.handshake
         }
+
+        pub fn contract_address(&self) -> &[u8] {
+            &self.contract_address
+        }
     }
 
     #[derive(Encode, Decode, Debug, Clone)]
@@ -236,6 +293,23 @@ pub mod output {
         }
     }
 
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct ReturnReport {
+        attested_report: AttestedReport,
+    }
+
+    impl EcallOutput for ReturnReport {}
+
+    impl ReturnReport {
+        pub fn new(attested_report: AttestedReport) -> Self {
+            ReturnReport { attested_report }
+        }
+
+        pub fn attested_report(self) -> AttestedReport {
+            self.attested_report
+        }
+    }
+
     #[derive(Encode, Decode, Debug, Clone, Default)]
     pub struct Empty;
 
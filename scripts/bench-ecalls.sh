@@ -0,0 +1,33 @@
+#!/bin/bash
+
+set -e
+
+source /root/.docker_bashrc
+export PATH=~/.cargo/bin:$PATH
+export SGX_MODE=HW
+export RUSTFLAGS=-Ctarget-feature=+aes,+sse2,+sse4.1,+ssse3
+
+# The enclave is signed with each of these TCSNums, and the ecalls are benchmarked with up to BENCH_MAX_THREADS threads.
+TCS_NUMS=${TCS_NUMS:-"1 2 4 8"}
+export BENCH_MAX_THREADS=${BENCH_MAX_THREADS:-8}
+
+dirpath=$(cd $(dirname $0) && pwd)
+cd "${dirpath}/.."
+solc -o contract-build --bin --abi --optimize --overwrite contracts/Anonify.sol
+
+for tcs_num in $TCS_NUMS; do
+    enclave_config="${dirpath}/../build/Enclave.tcs${tcs_num}.config.xml"
+    mkdir -p "$(dirname $enclave_config)"
+    sed "s|<TCSNum>.*</TCSNum>|<TCSNum>${tcs_num}</TCSNum>|" config/Enclave.config.xml > "$enclave_config"
+
+    cd scripts
+    # Sign the enclave again with the config
+    rm -f ../.anonify/enclave.signed.so
+    make ENCLAVE_DIR=example/erc20/enclave FEATURE_FLAGS="--features mock-ias" ENCLAVE_CONFIG="$enclave_config"
+    cd ..
+
+    echo "Benchmarking ecalls with TCSNum=${tcs_num}..."
+    cd tests/integration
+    BENCH_TCS_NUM=$tcs_num RUST_LOG=error cargo test --release bench_concurrent_ecalls -- --ignored --nocapture
+    cd ../..
+done
@@ -6,12 +6,21 @@ export ETH_URL=http://172.18.0.2:8545
 export ANONIFY_URL=172.18.0.3:8080 # depends on the container's ip in the docker network
 export ABI_PATH=../../../contract-build/Anonify.abi
 export BIN_PATH=../../../contract-build/Anonify.bin
-export MY_ROSTER_IDX=0
-export MAX_ROSTER_IDX=2
 export CONFIRMATIONS=1
 export ACCOUNT_INDEX=1
 export PASSWORD=anonify0101
 
 export SPID=
 export IAS_URL=https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report
-export SUB_KEY=
\ No newline at end of file
+export SUB_KEY=
+# EPID (IAS) or DCAP. DCAP requires the host to be built with SGX_DCAP=1.
+# The CLI can't verify DCAP reports, so it refuses to encrypt commands for nodes attested with DCAP.
+export ATTESTATION_TYPE=EPID
+export PCCS_URL=https://api.trustedservices.intel.com/sgx/certification/v3
+# Attestation reports older than this are rejected. It can only be shortened from the default of 24 hours.
+export ATTESTATION_MAX_AGE_SECS=86400
+# Compiled into the enclave, so they must be set when building it. e.g. GROUP_OUT_OF_DATE with INTEL-SA-00334
+export ALLOWED_QUOTE_STATUSES=OK,UpToDate
+export ALLOWED_ADVISORY_IDS=
+# The max level of the enclave's logs forwarded to the host's logger.
+export ENCLAVE_LOG_LEVEL=info
@@ -7,3 +7,9 @@ set -e
 
 # For non-docker
 export ANONIFY_URL=http://172.18.0.2:8080
+
+# Comma-separated hex-encoded MRENCLAVEs of the enclaves the CLI encrypts commands for.
+# Only nodes attested with EPID (IAS) are verifiable, so the CLI refuses nodes attested with DCAP.
+export ALLOWED_MRENCLAVES=
+# The report signing CA of the mock IAS server, used when the enclave is built with the `mock-ias` feature
+# export IAS_REPORT_CA=../../tests/mock-ias-server/certs/test-ca.pem
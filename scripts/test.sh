@@ -9,14 +9,17 @@ export RUSTFLAGS=-Ctarget-feature=+aes,+sse2,+sse4.1,+ssse3
 
 dirpath=$(cd $(dirname $0) && pwd)
 cd "${dirpath}/.."
-solc -o contract-build --bin --abi --optimize --overwrite contracts/Anonify.sol
+# The tests deploy `AnonifyWithMockIas`, which accepts the reports signed by the mock IAS server
+# and DCAP quotes chaining up to the test root CA in `frame/remote-attestation/certs`.
+solc -o contract-build --bin --abi --optimize --overwrite contracts/Anonify.sol contracts/test/AnonifyWithMockIas.sol
 
 cd frame/types
 cargo build
 
 cd ../../scripts
 # Generate a `enclave.signed.so` in `$HOME/.anonify`
-make DEBUG=1 ENCLAVE_DIR=example/erc20/enclave
+# The enclave trusts the mock IAS server started by the tests instead of Intel's one.
+make DEBUG=1 ENCLAVE_DIR=example/erc20/enclave FEATURE_FLAGS="--features mock-ias"
 
 # Testings
 
@@ -27,6 +30,8 @@ RUST_BACKTRACE=1 RUST_LOG=debug cargo test -- --nocapture
 cd ../../example/erc20/server
 RUST_BACKTRACE=1 RUST_LOG=debug cargo test test_deploy_post -- --nocapture
 sleep 1
+RUST_BACKTRACE=1 RUST_LOG=debug cargo test test_attestation_bundle -- --nocapture
+sleep 1
 RUST_BACKTRACE=1 RUST_LOG=debug cargo test test_multiple_messages -- --nocapture
 sleep 1
 RUST_BACKTRACE=1 RUST_LOG=debug cargo test test_skip_invalid_event -- --nocapture
@@ -39,7 +44,12 @@ echo "Unit testing..."
 cd ../../../scripts
 make DEBUG=1 TEST=1 ENCLAVE_DIR=tests/units/enclave
 cd ..
-RUST_BACKTRACE=1 RUST_LOG=debug cargo test -p unit-tests-host -p anonify-eth-driver -p frame-runtime -- --nocapture
+RUST_BACKTRACE=1 RUST_LOG=debug cargo test -p unit-tests-host -p anonify-eth-driver -p frame-runtime -p mock-ias-server -- --nocapture
+# The in-process enclave doesn't link the SGX libraries, so it is built separately.
+RUST_BACKTRACE=1 RUST_LOG=debug cargo test -p frame-host --features in-process -- --nocapture
+cd tests/in-process
+RUST_BACKTRACE=1 RUST_LOG=debug cargo test -- --nocapture
+cd ../..
 
 # Buildings
 
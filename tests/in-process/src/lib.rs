@@ -0,0 +1,95 @@
+//! End-to-end tests of the erc20 enclave built with `std` and run by `frame_host::InProcessEnclave`.
+//! The enclave's outputs are passed back to it directly instead of through a blockchain,
+//! and its reports are signed by the mock IAS server, so the tests run on any Linux box.
+
+use anonify_io_types::*;
+use codec::{Decode, Encode};
+use config::constants::*;
+use erc20_state_transition::{construct, CallName};
+use frame_common::{
+    crypto::{Ed25519ChallengeResponse, ExportHandshake},
+    traits::*,
+};
+use frame_host::{ecalls::EnclaveConnector, InProcessEnclave};
+use frame_runtime::primitives::U64;
+use frame_treekem::EciesCiphertext;
+use mock_ias_server::MockIasServer;
+use std::env;
+
+const OUTPUT_MAX_LEN: usize = 2048;
+/// The handshakes are passed back to the enclave as if they were stored in the contract of this address.
+const CONTRACT_ADDRESS: [u8; 20] = [1u8; 20];
+
+#[test]
+fn test_in_process_construct() {
+    set_env_vars();
+    let enclave = InProcessEnclave::new(anonifyenclave::ecall_handler);
+    let connector = EnclaveConnector::new(enclave.geteid(), OUTPUT_MAX_LEN);
+    let my_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+
+    // Join the group as its first member, and then receive the handshake as if it were emitted by the contract.
+    let join_group: output::ReturnJoinGroup = connector
+        .invoke_ecall(CALL_JOIN_GROUP_CMD, input::CallJoinGroup::new(0))
+        .unwrap();
+    let handshake = ExportHandshake::decode(&mut join_group.handshake()).unwrap();
+    let _: output::Empty = connector
+        .invoke_ecall(
+            INSERT_HANDSHAKE_CMD,
+            input::InsertHandshake::new(handshake, CONTRACT_ADDRESS),
+        )
+        .unwrap();
+
+    // Init state
+    let total_supply = U64::from_raw(100);
+    let encrypting_key: output::ReturnEncryptingKey = connector
+        .invoke_ecall(GET_ENCRYPTING_KEY_CMD, input::GetEncryptingKey::default())
+        .unwrap();
+    let init_cmd = construct { total_supply };
+    let encrypted_command =
+        EciesCiphertext::encrypt(&encrypting_key.encrypting_key(), init_cmd.encode()).unwrap();
+    let command: output::Command = connector
+        .invoke_ecall(
+            ENCRYPT_COMMAND_CMD,
+            input::Command::new(
+                my_access_policy.clone(),
+                encrypted_command,
+                CallName::as_id("construct"),
+            ),
+        )
+        .unwrap();
+
+    // Update state inside enclave with the ciphertext as if it were emitted by the contract.
+    let _: output::ReturnUpdatedState = connector
+        .invoke_ecall(
+            INSERT_CIPHERTEXT_CMD,
+            input::InsertCiphertext::new(command.ciphertext().clone()),
+        )
+        .unwrap();
+
+    // Get state from enclave
+    let my_balance: output::ReturnState = connector
+        .invoke_ecall(
+            GET_STATE_CMD,
+            input::GetState::new(my_access_policy, CallName::as_id("balance_of")),
+        )
+        .unwrap();
+    let my_balance = U64::decode_vec(my_balance.into_vec()).unwrap();
+
+    assert_eq!(my_balance, total_supply);
+}
+
+/// The enclave context reads them when it's initialized by the first ecall.
+fn set_env_vars() {
+    let mock_ias_url = MockIasServer::new()
+        .and_then(|server| server.spawn())
+        .expect("Failed to start the mock IAS server");
+    env::set_var("SPID", TEST_SPID);
+    env::set_var("IAS_URL", mock_ias_url);
+    env::set_var("SUB_KEY", "mock-sub-key");
+    env::set_var("AUDITOR_ENDPOINT", "test");
+    // The audit log is appended to `$PJ_ROOT_DIR/.anonify/audit.log`.
+    env::set_var(
+        "PJ_ROOT_DIR",
+        env::temp_dir().join("anonify-in-process-tests"),
+    );
+}
@@ -0,0 +1,134 @@
+//! Throughput of concurrent ecalls, which is run by `scripts/bench-ecalls.sh` for each `TCSNum`.
+//! Up to `TCSNum` threads run their ecalls in the enclave at the same time,
+//! so the throughput scales with the number of threads until it reaches `TCSNum`.
+//! The enclave's `TCSNum` is given by `BENCH_TCS_NUM`, and get state ecalls with more than one thread
+//! up to `TCSNum` must have higher throughput than a single thread.
+
+use super::*;
+use anonify_io_types::{input, output};
+use config::constants::{ENCRYPT_COMMAND_CMD, GET_STATE_CMD};
+use frame_host::ecalls::EnclaveConnector;
+use std::{iter, sync::Arc, thread, time::Instant};
+
+const DEFAULT_MAX_THREADS: usize = 8;
+const ECALLS_PER_THREAD: usize = 200;
+const OUTPUT_MAX_LEN: usize = 2048;
+
+#[actix_rt::test]
+#[ignore]
+async fn bench_concurrent_ecalls() {
+    set_env_vars();
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let eid = enclave.geteid();
+    let my_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+
+    let gas = 5_000_000;
+    let cache = EventCache::default();
+    let dispatcher =
+        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+
+    // The enclave joins the group to encrypt commands.
+    let deployer_addr = dispatcher
+        .get_account(ACCOUNT_INDEX, PASSWORD)
+        .await
+        .unwrap();
+    let (contract_addr, _) = dispatcher
+        .deploy(deployer_addr, gas, ABI_PATH, BIN_PATH, CONFIRMATIONS)
+        .await
+        .unwrap();
+    dispatcher
+        .set_contract_addr(&contract_addr, ABI_PATH)
+        .unwrap();
+    dispatcher.fetch_events::<U64>().await.unwrap();
+
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let init_cmd = construct {
+        total_supply: U64::from_raw(100),
+    };
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
+    let command = input::Command::new(
+        my_access_policy.clone(),
+        encrypted_command,
+        CallName::as_id("construct"),
+    );
+    let get_state = input::GetState::new(my_access_policy, CallName::as_id("balance_of"));
+
+    let max_threads = env::var("BENCH_MAX_THREADS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_MAX_THREADS);
+    let tcs_num: usize = env::var("BENCH_TCS_NUM")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1);
+    let mut get_state_results = vec![];
+    for threads in thread_counts(max_threads) {
+        let command = command.clone();
+        let encrypt_command = throughput(threads, move || {
+            let _: output::Command = EnclaveConnector::new(eid, OUTPUT_MAX_LEN)
+                .invoke_ecall(ENCRYPT_COMMAND_CMD, command.clone())
+                .unwrap();
+        });
+        let get_state = get_state.clone();
+        let get_state = throughput(threads, move || {
+            let _: output::ReturnState = EnclaveConnector::new(eid, OUTPUT_MAX_LEN)
+                .invoke_ecall(GET_STATE_CMD, get_state.clone())
+                .unwrap();
+        });
+
+        println!(
+            "threads: {:>2}, encrypt command: {:>8.1} ecalls/s, get state: {:>8.1} ecalls/s",
+            threads, encrypt_command, get_state
+        );
+        get_state_results.push((threads, get_state));
+    }
+
+    // Getting state only takes the read locks, so the ecalls run in parallel up to `TCSNum`.
+    let (_, single_thread) = get_state_results[0];
+    for (threads, get_state) in get_state_results
+        .into_iter()
+        .filter(|(threads, _)| *threads > 1 && *threads <= tcs_num)
+    {
+        assert!(
+            get_state > single_thread,
+            "get state with {} threads ({:.1} ecalls/s) doesn't beat a single thread ({:.1} ecalls/s) with TCSNum={}",
+            threads,
+            get_state,
+            single_thread,
+            tcs_num
+        );
+    }
+}
+
+/// 1, 2, 4, ... up to `max`
+fn thread_counts(max: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = iter::successors(Some(1), |n| Some(n * 2))
+        .take_while(|n| *n < max)
+        .collect();
+    counts.push(max);
+    counts
+}
+
+/// Returns the number of ecalls per second run by `threads` threads.
+fn throughput<F>(threads: usize, ecall: F) -> f64
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let ecall = Arc::new(ecall);
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let ecall = ecall.clone();
+            thread::spawn(move || {
+                for _ in 0..ECALLS_PER_THREAD {
+                    ecall();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    (threads * ECALLS_PER_THREAD) as f64 / start.elapsed().as_secs_f64()
+}
@@ -1,5 +1,8 @@
 #[macro_use]
 extern crate lazy_static;
+
+mod bench;
+
 use anonify_eth_driver::{dispatcher::*, eth::*, EventCache};
 use codec::{Decode, Encode};
 use erc20_state_transition::{
@@ -7,30 +10,39 @@ use erc20_state_transition::{
 };
 use ethabi::Contract as ContractABI;
 use frame_common::{
-    crypto::{AccountId, Ed25519ChallengeResponse, COMMON_ACCESS_POLICY},
+    crypto::{AccountId, Ed25519ChallengeResponse},
     traits::*,
 };
 use frame_host::EnclaveDir;
 use frame_runtime::primitives::{Approved, U64};
 use frame_treekem::{DhPubKey, EciesCiphertext};
+use mock_ias_server::MockIasServer;
 use sgx_types::*;
 use std::{collections::BTreeMap, env, fs::File, io::BufReader, str::FromStr};
 use web3::{
     contract::{Contract, Options},
     transports::Http,
-    types::Address,
+    types::{Address, H256},
     Web3,
 };
 
 const ETH_URL: &str = "http://172.28.0.2:8545";
 const ABI_PATH: &str = "../../contract-build/Anonify.abi";
-const BIN_PATH: &str = "../../contract-build/Anonify.bin";
+/// Accepts the reports signed by the mock IAS server instead of Intel's ones
+const BIN_PATH: &str = "../../contract-build/AnonifyWithMockIas.bin";
+/// DCAP's report of a quote chaining up to the test root CA trusted by `AnonifyWithMockIas`
+const DCAP_REPORT: &str =
+    include_str!("../../../frame/remote-attestation/certs/test-dcap-report.json");
 const CONFIRMATIONS: usize = 0;
 const ACCOUNT_INDEX: usize = 0;
 const PASSWORD: &str = "anonify0101";
 
 lazy_static! {
     pub static ref ENV_LOGGER_INIT: () = env_logger::init();
+    /// The enclave must be built with the `mock-ias` feature to trust the mock IAS server.
+    pub static ref MOCK_IAS_URL: String = MockIasServer::new()
+        .and_then(|server| server.spawn())
+        .expect("Failed to start the mock IAS server");
 }
 
 pub async fn get_encrypting_key(
@@ -124,13 +136,13 @@ async fn test_integration_eth_construct() {
 
     // Get state from enclave
     let owner_account_id = dispatcher
-        .get_state::<AccountId, _, CallName>(COMMON_ACCESS_POLICY.clone(), "owner")
+        .get_state::<AccountId, _, CallName>(my_access_policy.clone(), "owner")
         .unwrap();
     let my_balance = dispatcher
         .get_state::<U64, _, CallName>(my_access_policy.clone(), "balance_of")
         .unwrap();
     let actual_total_supply = dispatcher
-        .get_state::<U64, _, CallName>(COMMON_ACCESS_POLICY.clone(), "total_supply")
+        .get_state::<U64, _, CallName>(my_access_policy.clone(), "total_supply")
         .unwrap();
     assert_eq!(owner_account_id, my_access_policy.into_account_id());
     assert_eq!(my_balance, total_supply);
@@ -806,7 +818,7 @@ async fn test_integration_eth_mint() {
 
     // Check the final states
     let actual_total_supply = dispatcher
-        .get_state::<U64, _, CallName>(COMMON_ACCESS_POLICY.clone(), "total_supply")
+        .get_state::<U64, _, CallName>(my_access_policy.clone(), "total_supply")
         .unwrap();
     let owner_balance = dispatcher
         .get_state::<U64, _, CallName>(my_access_policy, "balance_of")
@@ -918,7 +930,7 @@ async fn test_integration_eth_burn() {
 
     // Check the final states
     let actual_total_supply = dispatcher
-        .get_state::<U64, _, CallName>(COMMON_ACCESS_POLICY.clone(), "total_supply")
+        .get_state::<U64, _, CallName>(my_access_policy.clone(), "total_supply")
         .unwrap();
     let owner_balance = dispatcher
         .get_state::<U64, _, CallName>(my_access_policy, "balance_of")
@@ -931,16 +943,251 @@ async fn test_integration_eth_burn() {
     assert_eq!(other_balance, U64::from_raw(10)); // 30 - 20(burn)
 }
 
+#[actix_rt::test]
+async fn test_join_group() {
+    set_env_vars();
+    let enclave1 = EnclaveDir::new().init_enclave(true).unwrap();
+    let enclave2 = EnclaveDir::new().init_enclave(true).unwrap();
+    let my_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+
+    let gas = 5_000_000;
+    let dispatcher1 = Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(
+        enclave1.geteid(),
+        ETH_URL,
+        EventCache::default(),
+    )
+    .unwrap();
+    let dispatcher2 = Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(
+        enclave2.geteid(),
+        ETH_URL,
+        EventCache::default(),
+    )
+    .unwrap();
+
+    // Deploy with the first member, whose roster index is 0
+    let deployer_addr = dispatcher1
+        .get_account(ACCOUNT_INDEX, PASSWORD)
+        .await
+        .unwrap();
+    let (contract_addr, _) = dispatcher1
+        .deploy(deployer_addr, gas, ABI_PATH, BIN_PATH, CONFIRMATIONS)
+        .await
+        .unwrap();
+    dispatcher1
+        .set_contract_addr(&contract_addr, ABI_PATH)
+        .unwrap();
+    dispatcher1.fetch_events::<U64>().await.unwrap();
+
+    // The second member joins the group with the roster index 1
+    let joiner_addr = dispatcher2
+        .get_account(ACCOUNT_INDEX + 1, PASSWORD)
+        .await
+        .unwrap();
+    dispatcher2
+        .set_contract_addr(&contract_addr, ABI_PATH)
+        .unwrap();
+    dispatcher2.fetch_events::<U64>().await.unwrap();
+    let (receipt, _) = dispatcher2
+        .join_group(joiner_addr, gas, &contract_addr, ABI_PATH)
+        .await
+        .unwrap();
+    println!("join group receipt: {:?}", receipt);
+    dispatcher1.fetch_events::<U64>().await.unwrap();
+    dispatcher2.fetch_events::<U64>().await.unwrap();
+
+    // The first command constructs the state, so only the deployer can store it.
+    let total_supply = U64::from_raw(100);
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher2).await;
+    let init_cmd = construct { total_supply };
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
+    let _ = dispatcher2
+        .send_command::<CallName, _>(
+            my_access_policy.clone(),
+            encrypted_command,
+            "construct",
+            joiner_addr,
+            gas,
+        )
+        .await;
+    dispatcher1.fetch_events::<U64>().await.unwrap();
+    dispatcher2.fetch_events::<U64>().await.unwrap();
+
+    for dispatcher in &[&dispatcher1, &dispatcher2] {
+        let balance = dispatcher
+            .get_state::<U64, _, CallName>(my_access_policy.clone(), "balance_of")
+            .unwrap();
+        assert_eq!(balance, U64::from_raw(0));
+    }
+
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
+    dispatcher1
+        .send_command::<CallName, _>(
+            my_access_policy.clone(),
+            encrypted_command,
+            "construct",
+            deployer_addr,
+            gas,
+        )
+        .await
+        .unwrap();
+    dispatcher1.fetch_events::<U64>().await.unwrap();
+    dispatcher2.fetch_events::<U64>().await.unwrap();
+
+    // A command sent by the second member is applied by both of the members.
+    let other_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+    let amount = U64::from_raw(30);
+    let recipient = other_access_policy.into_account_id();
+    let transfer_cmd = transfer { amount, recipient };
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, transfer_cmd.encode()).unwrap();
+    dispatcher2
+        .send_command::<CallName, _>(
+            my_access_policy.clone(),
+            encrypted_command,
+            "transfer",
+            joiner_addr,
+            gas,
+        )
+        .await
+        .unwrap();
+    dispatcher1.fetch_events::<U64>().await.unwrap();
+    dispatcher2.fetch_events::<U64>().await.unwrap();
+
+    for dispatcher in &[&dispatcher1, &dispatcher2] {
+        let balance = dispatcher
+            .get_state::<U64, _, CallName>(my_access_policy.clone(), "balance_of")
+            .unwrap();
+        assert_eq!(balance, U64::from_raw(70));
+        let other_balance = dispatcher
+            .get_state::<U64, _, CallName>(other_access_policy.clone(), "balance_of")
+            .unwrap();
+        assert_eq!(other_balance, amount);
+    }
+}
+
+#[actix_rt::test]
+async fn test_register_report_again() {
+    set_env_vars();
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let eid = enclave.geteid();
+    let my_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+
+    let gas = 5_000_000;
+    let cache = EventCache::default();
+    let dispatcher =
+        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+
+    // Deploy
+    let deployer_addr = dispatcher
+        .get_account(ACCOUNT_INDEX, PASSWORD)
+        .await
+        .unwrap();
+    let (contract_addr, _) = dispatcher
+        .deploy(deployer_addr, gas, ABI_PATH, BIN_PATH, CONFIRMATIONS)
+        .await
+        .unwrap();
+    dispatcher
+        .set_contract_addr(&contract_addr, ABI_PATH)
+        .unwrap();
+    dispatcher.fetch_events::<U64>().await.unwrap();
+
+    // The enclave is attested again with the same keys, so the registered report is just refreshed.
+    for _ in 0..2 {
+        let receipt = dispatcher
+            .register_report(deployer_addr, gas, &contract_addr, ABI_PATH)
+            .await
+            .unwrap();
+        println!("register report receipt: {:?}", receipt);
+    }
+
+    // The refreshed keys are still available to send commands.
+    let total_supply = U64::from_raw(100);
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let init_cmd = construct { total_supply };
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
+    dispatcher
+        .send_command::<CallName, _>(
+            my_access_policy.clone(),
+            encrypted_command,
+            "construct",
+            deployer_addr,
+            gas,
+        )
+        .await
+        .unwrap();
+    dispatcher.fetch_events::<U64>().await.unwrap();
+
+    let balance = dispatcher
+        .get_state::<U64, _, CallName>(my_access_policy, "balance_of")
+        .unwrap();
+    assert_eq!(balance, total_supply);
+}
+
+#[actix_rt::test]
+async fn test_deploy_with_dcap_quote() {
+    // Four P-256 signatures are verified in the contract, so it needs the larger gas limit of the test node.
+    let gas = 15_000_000u64;
+    let web3 = Web3::new(Http::new(ETH_URL).unwrap());
+    let deployer_addr = web3.eth().accounts().await.unwrap()[ACCOUNT_INDEX];
+    web3.personal()
+        .unlock_account(deployer_addr, PASSWORD, None)
+        .await
+        .unwrap();
+    let abi = std::fs::read(ABI_PATH).unwrap();
+    let bin = std::fs::read_to_string(BIN_PATH).unwrap();
+    let deploy = |report: Vec<u8>, pck_chain: Vec<u8>| {
+        // The owner's handshake only has to begin with the prior epoch and the roster index of zero.
+        let handshake = vec![0u8; 8];
+        Contract::deploy(web3.eth(), &abi)
+            .unwrap()
+            .options(Options::with(|opt| opt.gas = Some(gas.into())))
+            .confirmations(CONFIRMATIONS)
+            .execute(
+                bin.as_str(),
+                (report, pck_chain, handshake, 0u32),
+                deployer_addr,
+            )
+            .unwrap()
+    };
+
+    // The contract verifies the quote with the PCK certificate chain,
+    // and registers the enclave's keys in the quote's REPORTDATA.
+    let (quote, pck_chain) =
+        contract_report_inputs(DCAP_REPORT.as_bytes().to_vec(), vec![]).unwrap();
+    let contract = deploy(quote.clone(), pck_chain.clone()).await.unwrap();
+    let mr_enclave: H256 = contract
+        .query("mrEnclave", (), None, Options::default(), None)
+        .await
+        .unwrap();
+    assert_eq!(mr_enclave.as_bytes(), &quote[112..144]);
+    let encrypting_key = quote[388..421].to_vec();
+    let registered: Vec<u8> = contract
+        .query(
+            "getEncryptingKey",
+            encrypting_key.clone(),
+            None,
+            Options::default(),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(registered, encrypting_key);
+
+    // The enclave's report body isn't the one signed by the attestation key.
+    let mut tampered = quote.clone();
+    tampered[112] ^= 1;
+    assert!(deploy(tampered, pck_chain.clone()).await.is_err());
+    // The signature of the PCK CA's certificate is corrupted.
+    let mut tampered_chain = pck_chain;
+    let last = tampered_chain.len() - 40;
+    tampered_chain[last] ^= 1;
+    assert!(deploy(quote, tampered_chain).await.is_err());
+}
+
 pub fn set_env_vars() {
     *ENV_LOGGER_INIT;
     env::set_var("RUST_LOG", "DEBUG");
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     env::set_var("SPID", "2C149BFC94A61D306A96211AED155BE9");
-    env::set_var(
-        "IAS_URL",
-        "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report",
-    );
-    env::set_var("SUB_KEY", "77e2533de0624df28dc3be3a5b9e50d9");
+    env::set_var("IAS_URL", &*MOCK_IAS_URL);
+    env::set_var("SUB_KEY", "mock-sub-key");
     env::set_var("AUDITOR_ENDPOINT", "test");
 }
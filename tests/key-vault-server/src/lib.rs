@@ -0,0 +1,508 @@
+//! A mock of the remote key vault enclave for local development and tests.
+//! It attests like a key vault enclave running `MOCK_KEY_VAULT_MRENCLAVE`: its identity key is bound to
+//! an attestation report signed by the mock IAS server's key, so client enclaves built with the `mock-ias` feature
+//! accept it if `KEY_VAULT_MRENCLAVES` contains `MOCK_KEY_VAULT_MRENCLAVE`.
+//! In turn, it only serves the clients attested by the mock IAS server for one of the allowed MRENCLAVEs,
+//! and returns a path secret only to the MRENCLAVE which stored it.
+//! The owner is the MRENCLAVE rather than the identity key, since the group members running the same enclave
+//! share path secrets and an enclave's identity key is regenerated whenever it restarts.
+//! Path secrets are kept in memory.
+use anonify_io_types::key_vault::*;
+use anyhow::{anyhow, ensure, Result};
+use attestation_verifier::{AttestationBundle, EnclaveVerifier, MR_ENCLAVE_SIZE};
+use codec::{Decode, Encode};
+use frame_common::{
+    crypto::{Sha256, EXPORT_ID_SIZE},
+    traits::{Hash256, Keccak256},
+};
+use frame_treekem::{DhPrivateKey, DhPubKey, EciesCiphertext};
+use log::{debug, error};
+use mock_ias_server::{signing_cert_der, MockIasServer, TEST_CA_PEM};
+use std::{
+    collections::HashMap,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, RwLock},
+    thread,
+};
+
+/// The MRENCLAVE which this vault is attested for
+pub const MOCK_KEY_VAULT_MRENCLAVE: [u8; MR_ENCLAVE_SIZE] = [0x6b; MR_ENCLAVE_SIZE];
+
+/// The header and the report body of `sgx_quote_t`
+const QUOTE_BODY_SIZE: usize = 432;
+const MR_ENCLAVE_OFFSET: usize = 112;
+const REPORT_DATA_OFFSET: usize = 368;
+
+#[derive(Debug, Clone)]
+struct StoredPathSecret {
+    /// MRENCLAVE of the client which stored the path secret
+    owner: [u8; MR_ENCLAVE_SIZE],
+    backup: BackupPathSecret,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyVaultServer {
+    signing_privkey: secp256k1::SecretKey,
+    decrypting_privkey: DhPrivateKey,
+    /// Signs this vault's attestation reports in place of IAS
+    ias: MockIasServer,
+    /// Verifies the clients' attestation reports signed by the mock IAS server
+    verifier: EnclaveVerifier,
+    store: Arc<RwLock<HashMap<[u8; EXPORT_ID_SIZE], StoredPathSecret>>>,
+}
+
+impl KeyVaultServer {
+    /// A vault serving the client enclaves running one of the allowed MRENCLAVEs
+    pub fn new(allowed_mrenclaves: Vec<[u8; MR_ENCLAVE_SIZE]>) -> Result<Self> {
+        ensure!(
+            !allowed_mrenclaves.is_empty(),
+            "At least one client MRENCLAVE must be allowed"
+        );
+        let signing_privkey = loop {
+            if let Ok(key) = secp256k1::SecretKey::parse(&rand::random()) {
+                break key;
+            }
+        };
+
+        Ok(KeyVaultServer {
+            signing_privkey,
+            decrypting_privkey: DhPrivateKey::from_random()?,
+            ias: MockIasServer::new()?,
+            verifier: EnclaveVerifier::new(allowed_mrenclaves)?.with_trusted_ca(TEST_CA_PEM)?,
+            store: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    pub fn verifying_address(&self) -> [u8; VERIFYING_ADDRESS_SIZE] {
+        let pubkey = secp256k1::PublicKey::from_secret_key(&self.signing_privkey);
+        let mut address = [0u8; VERIFYING_ADDRESS_SIZE];
+        address.copy_from_slice(&pubkey.serialize().keccak256()[12..]);
+        address
+    }
+
+    pub fn encrypting_key(&self) -> DhPubKey {
+        DhPubKey::from_private_key(&self.decrypting_privkey)
+    }
+
+    /// Accept connections until the listener fails. Every connection is handled on its own thread.
+    pub fn run<A: ToSocketAddrs>(self, addr: A) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        self.serve(listener)
+    }
+
+    pub fn serve(self, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || {
+                if let Err(e) = server.handle_connection(stream) {
+                    error!("Failed to handle a key vault connection: {:?}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Start the server on a random local port in the background, and returns its address.
+    pub fn spawn(self) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?.to_string();
+        thread::spawn(move || {
+            if let Err(e) = self.serve(listener) {
+                error!("Key vault server stopped: {:?}", e);
+            }
+        });
+
+        Ok(addr)
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream) -> Result<()> {
+        match read_frame::<_, ClientMsg>(&mut stream)? {
+            ClientMsg::GetIdentity => {}
+            _ => return Err(anyhow!("The first message must be GetIdentity")),
+        }
+        write_frame(&mut stream, &ServerMsg::Identity(self.identity()?))?;
+
+        let signed_req = match read_frame::<_, ClientMsg>(&mut stream)? {
+            ClientMsg::Request(req) => req,
+            _ => return Err(anyhow!("The second message must be Request")),
+        };
+
+        let msg = match self.handle_request(signed_req) {
+            Ok(resp) => ServerMsg::Response(resp),
+            Err(e) => ServerMsg::Error(e.to_string().into_bytes()),
+        };
+        write_frame(&mut stream, &msg)?;
+
+        Ok(())
+    }
+
+    fn handle_request(&self, signed_req: SignedRequest) -> Result<SignedResponse> {
+        verify_request_sig(&signed_req)?;
+        let client = signed_req.client;
+        let owner = self.verify_client(&client)?;
+        let plaintext = signed_req
+            .ciphertext
+            .clone()
+            .decrypt(&self.decrypting_privkey)?;
+        let req = KeyVaultRequest::decode(&mut &plaintext[..])
+            .map_err(|e| anyhow!("Failed to decode a request: {:?}", e))?;
+
+        let resp = match req {
+            KeyVaultRequest::Backup(backup) => {
+                let mut store = self.store.write().unwrap();
+                if let Some(stored) = store.get(&backup.id) {
+                    ensure!(
+                        stored.owner == owner,
+                        "The path secret is stored by another enclave"
+                    );
+                }
+                debug!("Storing a path secret at epoch {}", backup.epoch);
+                store.insert(backup.id, StoredPathSecret { owner, backup });
+                KeyVaultResponse::Stored
+            }
+            // Path secrets stored by other enclaves are indistinguishable from missing ones.
+            KeyVaultRequest::Recover(recover) => {
+                match self.store.read().unwrap().get(&recover.id) {
+                    Some(stored) if stored.owner == owner => {
+                        KeyVaultResponse::Recovered(stored.backup.clone())
+                    }
+                    _ => KeyVaultResponse::NotFound,
+                }
+            }
+        };
+
+        let ciphertext = EciesCiphertext::encrypt(&client.encrypting_key, resp.encode())?;
+        let msg = SignedResponse::signing_msg(&signed_req.ciphertext, &ciphertext);
+        let msg = secp256k1::Message::parse_slice(msg.as_bytes())
+            .map_err(|e| anyhow!("Failed to parse message: {:?}", e))?;
+        let (sig, recovery_id) = secp256k1::sign(&msg, &self.signing_privkey)
+            .map_err(|e| anyhow!("Failed to sign the response: {:?}", e))?;
+
+        Ok(SignedResponse {
+            ciphertext,
+            sig: sig.serialize(),
+            recovery_id: recovery_id.serialize(),
+        })
+    }
+
+    /// Verify the client's attested identity, and returns its MRENCLAVE.
+    fn verify_client(&self, client: &AttestedIdentity) -> Result<[u8; MR_ENCLAVE_SIZE]> {
+        let verified = self.verifier.verify(&AttestationBundle {
+            report: client.report.clone(),
+            report_sig: client.report_sig.clone(),
+            signing_cert: client.signing_cert.clone(),
+            encrypting_key: client.encrypting_key.encode(),
+        })?;
+        ensure!(
+            verified.verifying_address == client.verifying_address,
+            "The verifying address isn't bound to the attestation report"
+        );
+
+        Ok(verified.mr_enclave)
+    }
+
+    /// This vault's identity attested by the mock IAS server, which is issued for every connection.
+    fn identity(&self) -> Result<AttestedIdentity> {
+        let mut identity = AttestedIdentity::new(
+            vec![],
+            vec![],
+            vec![],
+            self.verifying_address(),
+            self.encrypting_key(),
+        );
+        let quote = mock_quote(&MOCK_KEY_VAULT_MRENCLAVE, &identity);
+        let (report, report_sig) = self.ias.attest(&quote, None)?;
+        identity.report = report;
+        identity.report_sig = report_sig;
+        identity.signing_cert = signing_cert_der()?;
+
+        Ok(identity)
+    }
+}
+
+/// A quote of the enclave whose REPORTDATA binds the identity.
+/// The mock IAS server doesn't verify the quote, so only MRENCLAVE and REPORTDATA are filled.
+fn mock_quote(mr_enclave: &[u8; MR_ENCLAVE_SIZE], identity: &AttestedIdentity) -> Vec<u8> {
+    let mut quote = vec![0u8; QUOTE_BODY_SIZE];
+    quote[MR_ENCLAVE_OFFSET..MR_ENCLAVE_OFFSET + MR_ENCLAVE_SIZE].copy_from_slice(mr_enclave);
+    let report_data = identity.expected_report_data();
+    quote[REPORT_DATA_OFFSET..REPORT_DATA_OFFSET + report_data.len()].copy_from_slice(&report_data);
+    quote
+}
+
+/// Verify that the request is signed by the client's verifying key.
+pub fn verify_request_sig(signed_req: &SignedRequest) -> Result<()> {
+    let msg = Sha256::hash(&signed_req.ciphertext.encode());
+    let msg = secp256k1::Message::parse_slice(msg.as_bytes())
+        .map_err(|e| anyhow!("Failed to parse message: {:?}", e))?;
+    let sig = secp256k1::Signature::parse(&signed_req.sig);
+    let recovery_id = secp256k1::RecoveryId::parse(signed_req.recovery_id)
+        .map_err(|e| anyhow!("Failed to parse recovery id: {:?}", e))?;
+    let pubkey = secp256k1::recover(&msg, &sig, &recovery_id)
+        .map_err(|e| anyhow!("Failed to recover public key: {:?}", e))?;
+
+    let address = &pubkey.serialize().keccak256()[12..];
+    if address != &signed_req.client.verifying_address[..] {
+        return Err(anyhow!(
+            "The request isn't signed by the client's verifying key"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Verify that the response to the request is signed by the vault's verifying key.
+pub fn verify_response_sig(
+    request: &EciesCiphertext,
+    signed_resp: &SignedResponse,
+    vault_address: &[u8; VERIFYING_ADDRESS_SIZE],
+) -> Result<()> {
+    let msg = SignedResponse::signing_msg(request, &signed_resp.ciphertext);
+    let msg = secp256k1::Message::parse_slice(msg.as_bytes())
+        .map_err(|e| anyhow!("Failed to parse message: {:?}", e))?;
+    let sig = secp256k1::Signature::parse(&signed_resp.sig);
+    let recovery_id = secp256k1::RecoveryId::parse(signed_resp.recovery_id)
+        .map_err(|e| anyhow!("Failed to parse recovery id: {:?}", e))?;
+    let pubkey = secp256k1::recover(&msg, &sig, &recovery_id)
+        .map_err(|e| anyhow!("Failed to recover public key: {:?}", e))?;
+
+    if pubkey.serialize().keccak256()[12..] != vault_address[..] {
+        return Err(anyhow!(
+            "The response isn't signed by the vault's verifying key"
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_MRENCLAVE: [u8; MR_ENCLAVE_SIZE] = [1u8; MR_ENCLAVE_SIZE];
+    const OTHER_CLIENT_MRENCLAVE: [u8; MR_ENCLAVE_SIZE] = [2u8; MR_ENCLAVE_SIZE];
+
+    struct TestClient {
+        mr_enclave: [u8; MR_ENCLAVE_SIZE],
+        signing_privkey: secp256k1::SecretKey,
+        decrypting_privkey: DhPrivateKey,
+        /// Verifies the vault's attestation reports as client enclaves do
+        vault_verifier: EnclaveVerifier,
+    }
+
+    impl TestClient {
+        fn new(mr_enclave: [u8; MR_ENCLAVE_SIZE]) -> Self {
+            TestClient {
+                mr_enclave,
+                signing_privkey: secp256k1::SecretKey::parse(&rand::random()).unwrap(),
+                decrypting_privkey: DhPrivateKey::from_random().unwrap(),
+                vault_verifier: EnclaveVerifier::new(vec![MOCK_KEY_VAULT_MRENCLAVE])
+                    .unwrap()
+                    .with_trusted_ca(TEST_CA_PEM)
+                    .unwrap(),
+            }
+        }
+
+        fn unattested_identity(&self) -> AttestedIdentity {
+            let pubkey = secp256k1::PublicKey::from_secret_key(&self.signing_privkey);
+            let mut verifying_address = [0u8; VERIFYING_ADDRESS_SIZE];
+            verifying_address.copy_from_slice(&pubkey.serialize().keccak256()[12..]);
+            AttestedIdentity::new(
+                vec![],
+                vec![],
+                vec![],
+                verifying_address,
+                DhPubKey::from_private_key(&self.decrypting_privkey),
+            )
+        }
+
+        fn identity(&self) -> AttestedIdentity {
+            let mut identity = self.unattested_identity();
+            let quote = mock_quote(&self.mr_enclave, &identity);
+            let (report, report_sig) = MockIasServer::new().unwrap().attest(&quote, None).unwrap();
+            identity.report = report;
+            identity.report_sig = report_sig;
+            identity.signing_cert = signing_cert_der().unwrap();
+            identity
+        }
+
+        fn signed_request(
+            &self,
+            client: AttestedIdentity,
+            vault_encrypting_key: &DhPubKey,
+            req: &KeyVaultRequest,
+        ) -> SignedRequest {
+            let ciphertext = EciesCiphertext::encrypt(vault_encrypting_key, req.encode()).unwrap();
+            let msg = Sha256::hash(&ciphertext.encode());
+            let msg = secp256k1::Message::parse_slice(msg.as_bytes()).unwrap();
+            let (sig, recovery_id) = secp256k1::sign(&msg, &self.signing_privkey).unwrap();
+            SignedRequest {
+                client,
+                ciphertext,
+                sig: sig.serialize(),
+                recovery_id: recovery_id.serialize(),
+            }
+        }
+
+        fn request(&self, addr: &str, req: KeyVaultRequest) -> Result<KeyVaultResponse> {
+            let mut stream = TcpStream::connect(addr)?;
+            write_frame(&mut stream, &ClientMsg::GetIdentity)?;
+            let vault = match read_frame::<_, ServerMsg>(&mut stream)? {
+                ServerMsg::Identity(identity) => identity,
+                _ => return Err(anyhow!("Unexpected message")),
+            };
+            let verified = self.vault_verifier.verify(&AttestationBundle {
+                report: vault.report.clone(),
+                report_sig: vault.report_sig.clone(),
+                signing_cert: vault.signing_cert.clone(),
+                encrypting_key: vault.encrypting_key.encode(),
+            })?;
+            assert_eq!(verified.verifying_address, vault.verifying_address);
+
+            let signed_req = self.signed_request(self.identity(), &vault.encrypting_key, &req);
+            let ciphertext = signed_req.ciphertext.clone();
+            write_frame(&mut stream, &ClientMsg::Request(signed_req))?;
+
+            match read_frame::<_, ServerMsg>(&mut stream)? {
+                ServerMsg::Response(signed_resp) => {
+                    verify_response_sig(&ciphertext, &signed_resp, &vault.verifying_address)?;
+                    let plaintext = signed_resp.ciphertext.decrypt(&self.decrypting_privkey)?;
+                    Ok(KeyVaultResponse::decode(&mut &plaintext[..]).unwrap())
+                }
+                ServerMsg::Error(e) => Err(anyhow!(String::from_utf8_lossy(&e).to_string())),
+                _ => Err(anyhow!("Unexpected message")),
+            }
+        }
+    }
+
+    fn spawn_server() -> String {
+        KeyVaultServer::new(vec![CLIENT_MRENCLAVE, OTHER_CLIENT_MRENCLAVE])
+            .unwrap()
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backup_and_recover_path_secret() {
+        let addr = spawn_server();
+        let client = TestClient::new(CLIENT_MRENCLAVE);
+        let id = [1u8; EXPORT_ID_SIZE];
+        let req = KeyVaultRequest::Recover(RecoverPathSecret::new(id));
+        match client.request(&addr, req.clone()).unwrap() {
+            KeyVaultResponse::NotFound => {}
+            resp => panic!("Unexpected response: {:?}", resp),
+        }
+
+        let backup = BackupPathSecret::new(vec![2u8; 32], 3, id);
+        match client
+            .request(&addr, KeyVaultRequest::Backup(backup))
+            .unwrap()
+        {
+            KeyVaultResponse::Stored => {}
+            resp => panic!("Unexpected response: {:?}", resp),
+        }
+
+        // The restarted enclave has a new identity key on the same MRENCLAVE.
+        let restarted = TestClient::new(CLIENT_MRENCLAVE);
+        match restarted.request(&addr, req).unwrap() {
+            KeyVaultResponse::Recovered(recovered) => {
+                assert_eq!(recovered.path_secret, vec![2u8; 32]);
+                assert_eq!(recovered.epoch, 3);
+                assert_eq!(recovered.id, id);
+            }
+            resp => panic!("Unexpected response: {:?}", resp),
+        }
+    }
+
+    #[test]
+    fn test_recover_only_by_owner() {
+        let addr = spawn_server();
+        let owner = TestClient::new(CLIENT_MRENCLAVE);
+        let other = TestClient::new(OTHER_CLIENT_MRENCLAVE);
+        let id = [1u8; EXPORT_ID_SIZE];
+        let backup = BackupPathSecret::new(vec![2u8; 32], 3, id);
+        owner
+            .request(&addr, KeyVaultRequest::Backup(backup))
+            .unwrap();
+
+        let req = KeyVaultRequest::Recover(RecoverPathSecret::new(id));
+        match other.request(&addr, req).unwrap() {
+            KeyVaultResponse::NotFound => {}
+            resp => panic!("Unexpected response: {:?}", resp),
+        }
+        let overwrite = BackupPathSecret::new(vec![4u8; 32], 3, id);
+        assert!(other
+            .request(&addr, KeyVaultRequest::Backup(overwrite))
+            .is_err());
+    }
+
+    #[test]
+    fn test_reject_unattested_client() {
+        let server = KeyVaultServer::new(vec![CLIENT_MRENCLAVE]).unwrap();
+        let req = KeyVaultRequest::Recover(RecoverPathSecret::new([1u8; EXPORT_ID_SIZE]));
+
+        let client = TestClient::new(CLIENT_MRENCLAVE);
+        let signed_req = client.signed_request(client.identity(), &server.encrypting_key(), &req);
+        assert!(server.handle_request(signed_req).is_ok());
+
+        let signed_req =
+            client.signed_request(client.unattested_identity(), &server.encrypting_key(), &req);
+        assert!(server.handle_request(signed_req).is_err());
+
+        let disallowed = TestClient::new(OTHER_CLIENT_MRENCLAVE);
+        let signed_req =
+            disallowed.signed_request(disallowed.identity(), &server.encrypting_key(), &req);
+        assert!(server.handle_request(signed_req).is_err());
+
+        // The attested identity of another key
+        let signed_req =
+            disallowed.signed_request(client.identity(), &server.encrypting_key(), &req);
+        assert!(server.handle_request(signed_req).is_err());
+
+        assert!(KeyVaultServer::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_reject_forged_response() {
+        let server = KeyVaultServer::new(vec![CLIENT_MRENCLAVE]).unwrap();
+        let forger = KeyVaultServer::new(vec![CLIENT_MRENCLAVE]).unwrap();
+        let client = TestClient::new(CLIENT_MRENCLAVE);
+
+        let req = KeyVaultRequest::Recover(RecoverPathSecret::new([1u8; EXPORT_ID_SIZE]));
+        let signed_req = client.signed_request(client.identity(), &server.encrypting_key(), &req);
+        let ciphertext = signed_req.ciphertext.clone();
+
+        let signed_resp = server.handle_request(signed_req).unwrap();
+        verify_response_sig(&ciphertext, &signed_resp, &server.verifying_address()).unwrap();
+
+        // A response signed by another key, e.g. the host's
+        let backup = BackupPathSecret::new(vec![2u8; 32], 0, [1u8; EXPORT_ID_SIZE]);
+        let forged_ciphertext = EciesCiphertext::encrypt(
+            &client.unattested_identity().encrypting_key,
+            KeyVaultResponse::Recovered(backup).encode(),
+        )
+        .unwrap();
+        let forged_msg = SignedResponse::signing_msg(&ciphertext, &forged_ciphertext);
+        let forged_msg = secp256k1::Message::parse_slice(forged_msg.as_bytes()).unwrap();
+        let (forged_sig, forged_recovery_id) =
+            secp256k1::sign(&forged_msg, &forger.signing_privkey).unwrap();
+        let forged_resp = SignedResponse {
+            ciphertext: forged_ciphertext,
+            sig: forged_sig.serialize(),
+            recovery_id: forged_recovery_id.serialize(),
+        };
+        assert!(
+            verify_response_sig(&ciphertext, &forged_resp, &server.verifying_address()).is_err()
+        );
+
+        // A genuine response replayed for another request
+        let other_ciphertext =
+            EciesCiphertext::encrypt(&server.encrypting_key(), req.encode()).unwrap();
+        assert!(
+            verify_response_sig(&other_ciphertext, &signed_resp, &server.verifying_address())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,22 @@
+use attestation_verifier::parse_mrenclaves;
+use key_vault_server::{KeyVaultServer, MOCK_KEY_VAULT_MRENCLAVE};
+use std::env;
+
+fn main() {
+    env_logger::init();
+    let addr = env::var("KEY_VAULT_ADDRESS").unwrap_or_else(|_| "0.0.0.0:12345".to_string());
+    // Comma-separated hex-encoded MRENCLAVEs of the client enclaves
+    let client_mrenclaves =
+        env::var("KEY_VAULT_CLIENT_MRENCLAVES").expect("KEY_VAULT_CLIENT_MRENCLAVES must be set");
+    let client_mrenclaves =
+        parse_mrenclaves(&client_mrenclaves).expect("Failed to parse KEY_VAULT_CLIENT_MRENCLAVES");
+    let server =
+        KeyVaultServer::new(client_mrenclaves).expect("Failed to initialize the key vault server");
+
+    println!(
+        "Key vault server is listening on {} as MRENCLAVE {}",
+        addr,
+        hex::encode(MOCK_KEY_VAULT_MRENCLAVE)
+    );
+    server.run(addr).expect("Key vault server stopped");
+}
@@ -0,0 +1,504 @@
+//! A stand-in of Intel Attestation Service (API v3) for local development and tests.
+//! Quotes aren't verified at all, and every report is signed with the test key in `certs`,
+//! whose CA is trusted by enclaves built with the `mock-ias` feature of `remote-attestation`
+//! and whose modulus is trusted by the `AnonifyWithMockIas` contract.
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use log::{debug, error};
+use ring::{
+    rand::SystemRandom,
+    signature::{RsaKeyPair, RSA_PKCS1_SHA256},
+};
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+/// The CA which issues the report signing certificate, the same as `remote_attestation::TEST_IAS_REPORT_CA`
+pub const TEST_CA_PEM: &str = include_str!("../certs/test-ca.pem");
+/// The report signing certificate issued by `TEST_CA_PEM`
+pub const SIGNING_CERT_PEM: &str = include_str!("../certs/report-signing-cert.pem");
+const SIGNING_KEY_PKCS8: &[u8] = include_bytes!("../certs/report-signing-key.pk8");
+
+/// The header and the report body of `sgx_quote_t`
+const QUOTE_BODY_SIZE: usize = 432;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct MockIasServer {
+    quote_status: String,
+    advisory_ids: Vec<String>,
+    corrupt_signature: bool,
+    signing_key: Arc<RsaKeyPair>,
+    report_id: Arc<AtomicU64>,
+}
+
+impl MockIasServer {
+    /// A server returning `OK` for every quote
+    pub fn new() -> Result<Self> {
+        let signing_key = RsaKeyPair::from_pkcs8(SIGNING_KEY_PKCS8)
+            .map_err(|e| anyhow!("Failed to load the report signing key: {:?}", e))?;
+
+        Ok(MockIasServer {
+            quote_status: "OK".to_string(),
+            advisory_ids: vec![],
+            corrupt_signature: false,
+            signing_key: Arc::new(signing_key),
+            report_id: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Return the quote status, e.g. `GROUP_OUT_OF_DATE` or `SIGNATURE_INVALID`, with the advisories.
+    pub fn with_quote_status(mut self, quote_status: &str, advisory_ids: Vec<String>) -> Self {
+        self.quote_status = quote_status.to_string();
+        self.advisory_ids = advisory_ids;
+        self
+    }
+
+    /// Return reports whose signatures don't match, as if they were tampered on the way.
+    pub fn with_corrupted_signature(mut self) -> Self {
+        self.corrupt_signature = true;
+        self
+    }
+
+    /// Accept connections until the listener fails. Every connection is handled on its own thread.
+    pub fn run<A: ToSocketAddrs>(self, addr: A) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        self.serve(listener)
+    }
+
+    pub fn serve(self, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || {
+                if let Err(e) = server.handle_connection(stream) {
+                    error!("Failed to handle an attestation request: {:?}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Start the server on a random local port in the background, and returns the URL of the report API.
+    pub fn spawn(self) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!(
+            "http://{}/sgx/dev/attestation/v3/report",
+            listener.local_addr()?
+        );
+        thread::spawn(move || {
+            if let Err(e) = self.serve(listener) {
+                error!("Mock IAS server stopped: {:?}", e);
+            }
+        });
+
+        Ok(url)
+    }
+
+    /// Attest the decoded quote without HTTP, and returns the report and its signature.
+    /// Clients' verifiers are tested against the same reports as enclaves receive from the server.
+    pub fn attest(&self, quote: &[u8], nonce: Option<&str>) -> Result<(Vec<u8>, Vec<u8>)> {
+        let report = self.report(quote, nonce)?;
+        let sig = self.sign(&report)?;
+        Ok((report, sig))
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let resp = match HttpRequest::read(&mut reader) {
+            Ok(req) => self.handle_request(req),
+            Err(e) => HttpResponse::error(400, "Bad Request", &e.to_string()),
+        };
+        resp.write(stream)
+    }
+
+    fn handle_request(&self, req: HttpRequest) -> HttpResponse {
+        if req.method != "POST" {
+            return HttpResponse::error(405, "Method Not Allowed", "Only POST is supported");
+        }
+        match req.header("Ocp-Apim-Subscription-Key") {
+            Some(key) if !key.is_empty() => {}
+            _ => return HttpResponse::error(401, "Unauthorized", "Missing subscription key"),
+        }
+
+        match self.report_for_request(&req.body) {
+            Ok(report) => self.signed_report(report),
+            Err(e) => HttpResponse::error(400, "Bad Request", &e.to_string()),
+        }
+    }
+
+    fn report_for_request(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let req: Value = serde_json::from_slice(body)?;
+        let quote = req["isvEnclaveQuote"]
+            .as_str()
+            .ok_or_else(|| anyhow!("isvEnclaveQuote is required"))?;
+        let quote = base64::decode(quote)?;
+        self.report(&quote, req["nonce"].as_str())
+    }
+
+    /// Build the attestation verification report in the same format as IAS API v3.
+    fn report(&self, quote: &[u8], nonce: Option<&str>) -> Result<Vec<u8>> {
+        if quote.len() < QUOTE_BODY_SIZE {
+            return Err(anyhow!("Invalid quote length: {}", quote.len()));
+        }
+
+        let mut report = json!({
+            "id": self.report_id.fetch_add(1, Ordering::SeqCst).to_string(),
+            "timestamp": Utc::now().format("%Y-%m-%dT%H:%M:%S%.6f").to_string(),
+            "version": 3,
+            "isvEnclaveQuoteStatus": self.quote_status,
+            "isvEnclaveQuoteBody": base64::encode(&quote[..QUOTE_BODY_SIZE]),
+        });
+        if let Some(nonce) = nonce {
+            report["nonce"] = json!(nonce);
+        }
+        if !self.advisory_ids.is_empty() {
+            report["advisoryURL"] = json!("https://security-center.intel.com");
+            report["advisoryIDs"] = json!(self.advisory_ids);
+        }
+        debug!("Mock IAS report: {}", report);
+
+        serde_json::to_vec(&report).map_err(Into::into)
+    }
+
+    fn sign(&self, report: &[u8]) -> Result<Vec<u8>> {
+        let mut sig = vec![0u8; self.signing_key.public_modulus_len()];
+        self.signing_key
+            .sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), report, &mut sig)
+            .map_err(|e| anyhow!("Failed to sign the report: {:?}", e))?;
+        if self.corrupt_signature {
+            sig[0] ^= 0xff;
+        }
+        Ok(sig)
+    }
+
+    fn signed_report(&self, report: Vec<u8>) -> HttpResponse {
+        let sig = match self.sign(&report) {
+            Ok(sig) => sig,
+            Err(e) => return HttpResponse::error(500, "Internal Server Error", &e.to_string()),
+        };
+
+        // IAS returns the percent-encoded certificate chain from the signing certificate to the CA.
+        let cert_chain = format!("{}{}", SIGNING_CERT_PEM, TEST_CA_PEM);
+        HttpResponse {
+            status: 200,
+            reason: "OK",
+            headers: vec![
+                ("Content-Type", "application/json".to_string()),
+                ("X-IASReport-Signature", base64::encode(&sig)),
+                (
+                    "X-IASReport-Signing-Certificate",
+                    percent_encode(&cert_chain),
+                ),
+            ],
+            body: report,
+        }
+    }
+}
+
+/// The DER-encoded `SIGNING_CERT_PEM`, which verifies the signatures returned by `MockIasServer::attest`.
+pub fn signing_cert_der() -> Result<Vec<u8>> {
+    let b64: String = SIGNING_CERT_PEM
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.starts_with("-----"))
+        .collect();
+    base64::decode(&b64).map_err(Into::into)
+}
+
+struct HttpRequest {
+    method: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn read<R: BufRead>(reader: &mut R) -> Result<Self> {
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let method = request_line
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| anyhow!("Empty request"))?
+            .to_string();
+
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(anyhow!("Unexpected EOF in the headers"));
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let mut kv = line.splitn(2, ':');
+            let key = kv.next().unwrap_or_default().trim().to_string();
+            let value = kv.next().unwrap_or_default().trim().to_string();
+            headers.push((key, value));
+        }
+
+        let mut req = HttpRequest {
+            method,
+            headers,
+            body: vec![],
+        };
+        let len = match req.header("Content-Length") {
+            Some(len) => len.parse::<usize>()?,
+            None => 0,
+        };
+        if len > MAX_BODY_SIZE {
+            return Err(anyhow!("Too large body: {}", len));
+        }
+        req.body = vec![0u8; len];
+        reader.read_exact(&mut req.body)?;
+
+        Ok(req)
+    }
+
+    fn header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    reason: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn error(status: u16, reason: &'static str, msg: &str) -> Self {
+        HttpResponse {
+            status,
+            reason,
+            headers: vec![("Content-Type", "text/plain".to_string())],
+            body: msg.as_bytes().to_vec(),
+        }
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(writer, "HTTP/1.1 {} {}\r\n", self.status, self.reason)?;
+        for (key, value) in &self.headers {
+            write!(writer, "{}: {}\r\n", key, value)?;
+        }
+        write!(writer, "Content-Length: {}\r\n", self.body.len())?;
+        write!(writer, "Connection: close\r\n\r\n")?;
+        writer.write_all(&self.body)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::KeyPair;
+    use std::{io::Read, time::SystemTime};
+
+    type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
+    static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
+        &webpki::RSA_PKCS1_2048_8192_SHA256,
+        &webpki::RSA_PKCS1_3072_8192_SHA384,
+    ];
+
+    struct Response {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Response {
+        fn header(&self, key: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    fn request(url: &str, sub_key: Option<&str>, body: &str) -> Response {
+        let addr = url.trim_start_matches("http://").split('/').next().unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "POST /sgx/dev/attestation/v3/report HTTP/1.1\r\n").unwrap();
+        if let Some(key) = sub_key {
+            write!(stream, "Ocp-Apim-Subscription-Key: {}\r\n", key).unwrap();
+        }
+        write!(
+            stream,
+            "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+
+        let mut reader = BufReader::new(stream);
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line).unwrap();
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let mut kv = line.splitn(2, ':');
+            headers.push((
+                kv.next().unwrap().trim().to_string(),
+                kv.next().unwrap().trim().to_string(),
+            ));
+        }
+        let mut body = vec![];
+        reader.read_to_end(&mut body).unwrap();
+
+        Response {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    fn quote_request(nonce: &str) -> String {
+        let quote = base64::encode(&[1u8; QUOTE_BODY_SIZE][..]);
+        format!(
+            "{{\"isvEnclaveQuote\":\"{}\",\"nonce\":\"{}\"}}",
+            quote, nonce
+        )
+    }
+
+    fn pem_to_der(pem: &str) -> Vec<u8> {
+        let b64: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        base64::decode(&b64).unwrap()
+    }
+
+    /// Verify the report in the same way as `remote_attestation` with the test CA.
+    fn verify_signed_report(resp: &Response) -> Result<Value> {
+        let ca = pem_to_der(TEST_CA_PEM);
+        let trust_anchors = vec![webpki::trust_anchor_util::cert_der_as_trust_anchor(&ca)
+            .map_err(|e| anyhow!("{:?}", e))?];
+        let signing_cert = pem_to_der(SIGNING_CERT_PEM);
+        let cert = webpki::EndEntityCert::from(&signing_cert).map_err(|e| anyhow!("{:?}", e))?;
+        cert.verify_is_valid_tls_server_cert(
+            SUPPORTED_SIG_ALGS,
+            &webpki::TLSServerTrustAnchors(&trust_anchors),
+            &[&ca[..]],
+            webpki::Time::try_from(SystemTime::now()).map_err(|e| anyhow!("{:?}", e))?,
+        )
+        .map_err(|e| anyhow!("{:?}", e))?;
+
+        let sig = base64::decode(resp.header("X-IASReport-Signature").unwrap())?;
+        cert.verify_signature(&webpki::RSA_PKCS1_2048_8192_SHA256, &resp.body, &sig)
+            .map_err(|e| anyhow!("{:?}", e))?;
+
+        serde_json::from_slice(&resp.body).map_err(Into::into)
+    }
+
+    #[test]
+    fn test_signed_report() {
+        let url = MockIasServer::new().unwrap().spawn().unwrap();
+        let resp = request(&url, Some("mock-sub-key"), &quote_request("abc"));
+        assert_eq!(resp.status, 200);
+
+        let report = verify_signed_report(&resp).unwrap();
+        assert_eq!(report["isvEnclaveQuoteStatus"], "OK");
+        assert_eq!(report["nonce"], "abc");
+        assert_eq!(report["version"], 3);
+        assert!(report["advisoryIDs"].is_null());
+
+        let cert_chain = resp.header("X-IASReport-Signing-Certificate").unwrap();
+        assert!(cert_chain.starts_with("-----BEGIN%20CERTIFICATE-----"));
+    }
+
+    #[test]
+    fn test_contract_modulus() {
+        const CONTRACT: &str = include_str!("../../../contracts/test/AnonifyWithMockIas.sol");
+        const PREFIX: &str = "MOCK_RSA_MOD = hex\"";
+
+        let start = CONTRACT.find(PREFIX).unwrap() + PREFIX.len();
+        let end = start + CONTRACT[start..].find('"').unwrap();
+        let modulus: Vec<u8> = (start..end)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&CONTRACT[i..i + 2], 16).unwrap())
+            .collect();
+
+        // The DER-encoded `RSAPublicKey` contains the modulus as it is.
+        let signing_key = RsaKeyPair::from_pkcs8(SIGNING_KEY_PKCS8).unwrap();
+        assert_eq!(modulus.len(), signing_key.public_modulus_len());
+        assert!(signing_key
+            .public_key()
+            .as_ref()
+            .windows(modulus.len())
+            .any(|w| w == &modulus[..]));
+    }
+
+    #[test]
+    fn test_configured_quote_status() {
+        let url = MockIasServer::new()
+            .unwrap()
+            .with_quote_status("GROUP_OUT_OF_DATE", vec!["INTEL-SA-00334".to_string()])
+            .spawn()
+            .unwrap();
+        let resp = request(&url, Some("mock-sub-key"), &quote_request("abc"));
+
+        let report = verify_signed_report(&resp).unwrap();
+        assert_eq!(report["isvEnclaveQuoteStatus"], "GROUP_OUT_OF_DATE");
+        assert_eq!(report["advisoryIDs"], json!(["INTEL-SA-00334"]));
+    }
+
+    #[test]
+    fn test_corrupted_signature() {
+        let url = MockIasServer::new()
+            .unwrap()
+            .with_corrupted_signature()
+            .spawn()
+            .unwrap();
+        let resp = request(&url, Some("mock-sub-key"), &quote_request("abc"));
+
+        assert_eq!(resp.status, 200);
+        assert!(verify_signed_report(&resp).is_err());
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let url = MockIasServer::new().unwrap().spawn().unwrap();
+
+        let resp = request(&url, None, &quote_request("abc"));
+        assert_eq!(resp.status, 401);
+
+        let resp = request(&url, Some("mock-sub-key"), "{\"isvEnclaveQuote\":\"AAAA\"}");
+        assert_eq!(resp.status, 400);
+
+        let resp = request(&url, Some("mock-sub-key"), "not json");
+        assert_eq!(resp.status, 400);
+    }
+}
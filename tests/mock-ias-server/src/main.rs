@@ -0,0 +1,23 @@
+use mock_ias_server::MockIasServer;
+use std::env;
+
+fn main() {
+    env_logger::init();
+    let addr = env::var("MOCK_IAS_ADDRESS").unwrap_or_else(|_| "0.0.0.0:12346".to_string());
+    let quote_status = env::var("MOCK_IAS_QUOTE_STATUS").unwrap_or_else(|_| "OK".to_string());
+    let advisory_ids = env::var("MOCK_IAS_ADVISORY_IDS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    let server = MockIasServer::new()
+        .expect("Failed to initialize the mock IAS server")
+        .with_quote_status(&quote_status, advisory_ids);
+
+    println!(
+        "Mock IAS server is listening on {} (quote status: {})",
+        addr, quote_status
+    );
+    server.run(addr).expect("Mock IAS server stopped");
+}
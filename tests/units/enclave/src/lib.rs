@@ -10,8 +10,10 @@ use test_utils::*;
 #[no_mangle]
 pub fn ecall_run_tests() {
     let ret = check_all_passed!(
+        frame_enclave::tests::run_tests(),
         frame_treekem::tests::run_tests(),
         anonify_enclave::tests::run_tests(),
+        remote_attestation::tests::run_tests(),
     );
 
     assert!(ret);
@@ -1,5 +1,11 @@
 use frame_host::EnclaveDir;
+use key_vault_server::KeyVaultServer;
+use mock_ias_server::MockIasServer;
 use sgx_types::{sgx_enclave_id_t, sgx_status_t};
+use std::env;
+
+/// The client MRENCLAVE which the key vault tests in the enclave are attested for
+const KEY_VAULT_CLIENT_MRENCLAVE: [u8; 32] = [0x63; 32];
 
 extern "C" {
     pub fn ecall_run_tests(eid: sgx_enclave_id_t) -> sgx_status_t;
@@ -7,8 +13,44 @@ extern "C" {
 
 #[test]
 fn test_in_enclave() {
+    start_mock_ias_servers();
+    start_mock_key_vault();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let ret = unsafe { ecall_run_tests(enclave.geteid()) };
 
     assert_eq!(ret, sgx_status_t::SGX_SUCCESS);
 }
+
+/// The remote attestation tests in the enclave read the URLs of the mock IAS servers from the environment.
+fn start_mock_ias_servers() {
+    let spawn = |server: MockIasServer, var: &str| {
+        let url = server.spawn().expect("Failed to start a mock IAS server");
+        env::set_var(var, url);
+    };
+
+    spawn(MockIasServer::new().unwrap(), "MOCK_IAS_OK_URL");
+    spawn(
+        MockIasServer::new()
+            .unwrap()
+            .with_quote_status("GROUP_OUT_OF_DATE", vec!["INTEL-SA-00334".to_string()]),
+        "MOCK_IAS_GROUP_OUT_OF_DATE_URL",
+    );
+    spawn(
+        MockIasServer::new()
+            .unwrap()
+            .with_quote_status("SIGNATURE_INVALID", vec![]),
+        "MOCK_IAS_SIGNATURE_INVALID_URL",
+    );
+    spawn(
+        MockIasServer::new().unwrap().with_corrupted_signature(),
+        "MOCK_IAS_CORRUPTED_SIGNATURE_URL",
+    );
+}
+
+/// The key vault tests in the enclave read the address of the mock key vault from the environment.
+fn start_mock_key_vault() {
+    let addr = KeyVaultServer::new(vec![KEY_VAULT_CLIENT_MRENCLAVE])
+        .and_then(KeyVaultServer::spawn)
+        .expect("Failed to start the mock key vault");
+    env::set_var("MOCK_KEY_VAULT_ADDRESS", addr);
+}
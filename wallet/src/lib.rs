@@ -8,10 +8,12 @@ mod derive;
 mod disk;
 mod error;
 mod keyfile;
+mod multisig;
 
 pub use disk::{KeystoreDirectory, WalletDirectory};
 pub use error::WalletError as Error;
 pub use keyfile::KeyFile;
+pub use multisig::{PartialSignature, PartialSignatures};
 
 /// Operations in a wallet directory
 pub trait DirOperations {
@@ -0,0 +1,201 @@
+//! Collecting partial signatures from co-signers of a multisig account.
+use crate::{error::Result, SerdeBytes};
+use anyhow::anyhow;
+use ed25519_dalek::{Keypair, PublicKey, Signature};
+use frame_common::crypto::{AccountId, Ed25519ChallengeResponse, MultiSigChallengeResponse};
+use serde::{Deserialize, Serialize};
+
+/// A pending multisig request which is passed around co-signers.
+/// Each co-signer adds a signature over the same challenge until the threshold is met.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialSignatures {
+    pub threshold: u32,
+    pub pubkeys: Vec<SerdeBytes>,
+    pub challenge: SerdeBytes,
+    pub signatures: Vec<PartialSignature>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialSignature {
+    pub pubkey: SerdeBytes,
+    pub sig: SerdeBytes,
+}
+
+impl PartialSignatures {
+    pub fn new(threshold: u32, pubkeys: &[PublicKey], challenge: [u8; 32]) -> Result<Self> {
+        if threshold == 0 || threshold as usize > pubkeys.len() {
+            return Err(
+                anyhow!("Invalid threshold {} for {} keys", threshold, pubkeys.len()).into(),
+            );
+        }
+        // A co-signer listed more than once would count toward the threshold more than once.
+        for (i, pubkey) in pubkeys.iter().enumerate() {
+            if pubkeys[..i].contains(pubkey) {
+                return Err(anyhow!("Duplicated key in the key set: {:?}", pubkey).into());
+            }
+        }
+
+        Ok(PartialSignatures {
+            threshold,
+            pubkeys: pubkeys
+                .iter()
+                .map(|e| e.as_bytes().to_vec().into())
+                .collect(),
+            challenge: challenge.into(),
+            signatures: vec![],
+        })
+    }
+
+    /// Sign the challenge with a co-signer's key pair.
+    pub fn sign(&mut self, key_pair: &Keypair) -> Result<()> {
+        let sig = key_pair.sign(&self.challenge.0[..]);
+        self.add_signature(&key_pair.public, &sig)
+    }
+
+    /// Add a signature produced elsewhere. The signer must be in the key set and must not have signed yet.
+    pub fn add_signature(&mut self, pubkey: &PublicKey, sig: &Signature) -> Result<()> {
+        let pubkey_bytes: SerdeBytes = pubkey.as_bytes().to_vec().into();
+        if !self.pubkeys.contains(&pubkey_bytes) {
+            return Err(anyhow!("Signer is not in the key set").into());
+        }
+        if self.signatures.iter().any(|e| e.pubkey == pubkey_bytes) {
+            return Err(anyhow!("Signer has already signed").into());
+        }
+        pubkey
+            .verify(&self.challenge.0[..], sig)
+            .map_err(|e| anyhow!("{:?}", e))?;
+
+        self.signatures.push(PartialSignature {
+            pubkey: pubkey_bytes,
+            sig: sig.to_bytes().to_vec().into(),
+        });
+
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.signatures.len() >= self.threshold as usize
+    }
+
+    pub fn account_id(&self) -> Result<AccountId> {
+        let pubkeys = self.decode_pubkeys()?;
+        Ok(MultiSigChallengeResponse::account_id_from_keys(
+            self.threshold,
+            &pubkeys,
+        ))
+    }
+
+    /// Build the access policy once enough co-signers have signed.
+    pub fn finalize(&self) -> Result<MultiSigChallengeResponse> {
+        if !self.is_complete() {
+            return Err(anyhow!(
+                "Not enough signatures: {} of {}",
+                self.signatures.len(),
+                self.threshold
+            )
+            .into());
+        }
+
+        if self.challenge.0.len() != 32 {
+            return Err(anyhow!(
+                "Challenge must be 32 bytes, but got {} bytes",
+                self.challenge.0.len()
+            )
+            .into());
+        }
+        let mut challenge = [0u8; 32];
+        challenge.copy_from_slice(&self.challenge.0[..]);
+
+        let responses = self
+            .signatures
+            .iter()
+            .map(|e| -> Result<Ed25519ChallengeResponse> {
+                let pubkey =
+                    PublicKey::from_bytes(&e.pubkey.0[..]).map_err(|e| anyhow!("{:?}", e))?;
+                let sig = Signature::from_bytes(&e.sig.0[..]).map_err(|e| anyhow!("{:?}", e))?;
+                Ok(Ed25519ChallengeResponse::new(sig, pubkey, challenge))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let policy =
+            MultiSigChallengeResponse::new(self.threshold, self.decode_pubkeys()?, responses)?;
+        policy.verify_sigs()?;
+
+        Ok(policy)
+    }
+
+    fn decode_pubkeys(&self) -> Result<Vec<PublicKey>> {
+        self.pubkeys
+            .iter()
+            .map(|e| PublicKey::from_bytes(&e.0[..]).map_err(|e| anyhow!("{:?}", e).into()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_common::traits::AccessPolicy;
+    use rand::{rngs::OsRng, Rng};
+
+    fn gen_keypairs(n: usize) -> Vec<Keypair> {
+        let mut csprng = OsRng;
+        (0..n).map(|_| Keypair::generate(&mut csprng)).collect()
+    }
+
+    #[test]
+    fn test_collect_partial_signatures() {
+        let key_pairs = gen_keypairs(3);
+        let pubkeys: Vec<PublicKey> = key_pairs.iter().map(|e| e.public).collect();
+        let challenge = rand::thread_rng().gen::<[u8; 32]>();
+
+        let mut partial = PartialSignatures::new(2, &pubkeys, challenge).unwrap();
+        partial.sign(&key_pairs[2]).unwrap();
+        assert!(partial.finalize().is_err());
+        assert!(partial.sign(&key_pairs[2]).is_err());
+
+        partial.sign(&key_pairs[0]).unwrap();
+        let policy = partial.finalize().unwrap();
+        assert!(policy.verify().is_ok());
+
+        let mut reversed = pubkeys.clone();
+        reversed.reverse();
+        assert_eq!(
+            policy.into_account_id(),
+            MultiSigChallengeResponse::account_id_from_keys(2, &reversed)
+        );
+        assert_ne!(
+            policy.into_account_id(),
+            MultiSigChallengeResponse::account_id_from_keys(1, &pubkeys)
+        );
+    }
+
+    #[test]
+    fn test_reject_duplicated_keys() {
+        let key_pairs = gen_keypairs(2);
+        let pubkeys = vec![
+            key_pairs[0].public,
+            key_pairs[1].public,
+            key_pairs[0].public,
+        ];
+        let challenge = rand::thread_rng().gen::<[u8; 32]>();
+
+        assert!(PartialSignatures::new(2, &pubkeys, challenge).is_err());
+        assert!(PartialSignatures::new(2, &pubkeys[..2], challenge).is_ok());
+    }
+
+    #[test]
+    fn test_finalize_invalid_challenge_length() {
+        let key_pairs = gen_keypairs(2);
+        let pubkeys: Vec<PublicKey> = key_pairs.iter().map(|e| e.public).collect();
+        let challenge = rand::thread_rng().gen::<[u8; 32]>();
+
+        let mut partial = PartialSignatures::new(1, &pubkeys, challenge).unwrap();
+        partial.sign(&key_pairs[0]).unwrap();
+        // e.g. a request edited by a co-signer
+        partial.challenge = vec![0u8; 31].into();
+        assert!(partial.finalize().is_err());
+    }
+}